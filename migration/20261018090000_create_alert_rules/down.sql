@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS alert_rules_uuid_idx;
+DROP INDEX IF EXISTS alert_rules_stream_id_idx;
+
+DROP TABLE IF EXISTS alert_rules;
+DROP SEQUENCE IF EXISTS alert_rules_id_seq;
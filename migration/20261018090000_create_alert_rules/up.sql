@@ -0,0 +1,28 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE alert_rules_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE alert_rules (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('alert_rules_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  stream_id BIGINT REFERENCES streams (id) MATCH FULL NOT NULL,
+  name CHARACTER VARYING(64) NOT NULL,
+  level e_log_level NOT NULL DEFAULT 'error',
+  threshold INTEGER NOT NULL DEFAULT 1,
+  window_minutes INTEGER NOT NULL DEFAULT 5,
+  cooldown_minutes INTEGER NOT NULL DEFAULT 30,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE alert_rules_id_seq OWNED BY alert_rules.id;
+
+CREATE INDEX alert_rules_stream_id_idx ON alert_rules(stream_id);
+CREATE UNIQUE INDEX alert_rules_uuid_idx ON alert_rules(uuid);
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS alerts_uuid_idx;
+DROP INDEX IF EXISTS alerts_alert_rule_id_fired_at_idx;
+
+DROP TABLE IF EXISTS alerts;
+DROP SEQUENCE IF EXISTS alerts_id_seq;
+
+DROP TYPE IF EXISTS e_alert_state;
@@ -0,0 +1,34 @@
+DROP TYPE IF EXISTS e_alert_state;
+CREATE TYPE e_alert_state AS ENUM (
+  'firing',
+  'resolved'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE alerts_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE alerts (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('alerts_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  alert_rule_id BIGINT REFERENCES alert_rules (id) MATCH FULL NOT NULL,
+  state e_alert_state NOT NULL DEFAULT 'firing',
+  messages_count BIGINT NOT NULL DEFAULT 0,
+  fired_at TIMESTAMP WITHOUT TIME ZONE NOT NULL,
+  resolved_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE alerts_id_seq OWNED BY alerts.id;
+
+CREATE INDEX alerts_alert_rule_id_fired_at_idx ON alerts(
+  alert_rule_id, fired_at);
+CREATE UNIQUE INDEX alerts_uuid_idx ON alerts(uuid);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS webhooks_uuid_idx;
+DROP INDEX IF EXISTS webhooks_namespace_id_idx;
+
+DROP TABLE IF EXISTS webhooks;
+DROP SEQUENCE IF EXISTS webhooks_id_seq;
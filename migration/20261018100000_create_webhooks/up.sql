@@ -0,0 +1,26 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE webhooks_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE webhooks (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('webhooks_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  namespace_id BIGINT REFERENCES namespaces (id) MATCH FULL NOT NULL,
+  url CHARACTER VARYING(2048) NOT NULL,
+  secret CHARACTER VARYING(64) NOT NULL,
+  events TEXT[] NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE webhooks_id_seq OWNED BY webhooks.id;
+
+CREATE INDEX webhooks_namespace_id_idx ON webhooks(namespace_id);
+CREATE UNIQUE INDEX webhooks_uuid_idx ON webhooks(uuid);
@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS webhook_deliveries_uuid_idx;
+DROP INDEX IF EXISTS webhook_deliveries_state_next_attempt_at_idx;
+DROP INDEX IF EXISTS webhook_deliveries_webhook_id_created_at_idx;
+
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP SEQUENCE IF EXISTS webhook_deliveries_id_seq;
+
+DROP TYPE IF EXISTS e_webhook_delivery_state;
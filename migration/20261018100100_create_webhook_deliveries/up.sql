@@ -0,0 +1,41 @@
+DROP TYPE IF EXISTS e_webhook_delivery_state;
+CREATE TYPE e_webhook_delivery_state AS ENUM (
+  'pending',
+  'succeeded',
+  'failed'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE webhook_deliveries_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE webhook_deliveries (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('webhook_deliveries_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  webhook_id BIGINT REFERENCES webhooks (id) MATCH FULL NOT NULL,
+  event CHARACTER VARYING(64) NOT NULL,
+  payload TEXT NOT NULL,
+  state e_webhook_delivery_state NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  response_status INTEGER NULL,
+  error_message CHARACTER VARYING(256) NULL,
+  next_attempt_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  delivered_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE webhook_deliveries_id_seq OWNED BY webhook_deliveries.id;
+
+CREATE INDEX webhook_deliveries_webhook_id_created_at_idx
+  ON webhook_deliveries(webhook_id, created_at);
+CREATE INDEX webhook_deliveries_state_next_attempt_at_idx
+  ON webhook_deliveries(state, next_attempt_at);
+CREATE UNIQUE INDEX webhook_deliveries_uuid_idx ON webhook_deliveries(uuid);
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS digest_subscriptions_namespace_id_idx;
+DROP INDEX IF EXISTS digest_subscriptions_user_id_namespace_id_idx;
+
+DROP TABLE IF EXISTS digest_subscriptions;
+DROP SEQUENCE IF EXISTS digest_subscriptions_id_seq;
+
+DROP TYPE IF EXISTS e_digest_frequency;
@@ -0,0 +1,36 @@
+DROP TYPE IF EXISTS e_digest_frequency;
+CREATE TYPE e_digest_frequency AS ENUM (
+  'daily',
+  'weekly'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE digest_subscriptions_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE digest_subscriptions (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval(
+    'digest_subscriptions_id_seq'),
+  user_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  namespace_id BIGINT REFERENCES namespaces (id) MATCH FULL NOT NULL,
+  frequency e_digest_frequency NOT NULL DEFAULT 'weekly',
+  last_sent_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE digest_subscriptions_id_seq
+  OWNED BY digest_subscriptions.id;
+
+CREATE UNIQUE INDEX digest_subscriptions_user_id_namespace_id_idx
+  ON digest_subscriptions(user_id, namespace_id);
+CREATE INDEX digest_subscriptions_namespace_id_idx
+  ON digest_subscriptions(namespace_id);
@@ -0,0 +1 @@
+ALTER TABLE namespaces DROP COLUMN ingestion_rate_limit;
@@ -0,0 +1 @@
+ALTER TABLE namespaces ADD COLUMN ingestion_rate_limit INTEGER NULL;
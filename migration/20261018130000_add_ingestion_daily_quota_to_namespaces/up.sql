@@ -0,0 +1 @@
+ALTER TABLE namespaces ADD COLUMN ingestion_daily_quota BIGINT NULL;
@@ -0,0 +1,36 @@
+CREATE TYPE e_redaction_detector AS ENUM (
+  'email',
+  'credit_card',
+  'jwt',
+  'bearer_token',
+  'custom'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE redaction_rules_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE redaction_rules (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('redaction_rules_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  namespace_id BIGINT REFERENCES namespaces (id) MATCH FULL NOT NULL,
+  name CHARACTER VARYING(64) NOT NULL,
+  detector e_redaction_detector NOT NULL DEFAULT 'custom',
+  -- regular expression (only for custom)
+  pattern TEXT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE redaction_rules_id_seq OWNED BY redaction_rules.id;
+
+CREATE INDEX redaction_rules_namespace_id_idx
+  ON redaction_rules(namespace_id);
+CREATE UNIQUE INDEX redaction_rules_uuid_idx ON redaction_rules(uuid);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS namespaces_key_idx;
+
+ALTER TABLE namespaces DROP COLUMN key;
@@ -0,0 +1,18 @@
+ALTER TABLE namespaces ADD COLUMN key CHARACTER VARYING(64) NULL;
+
+-- generates keys from names (e.g. "alice's namespace" -> "alice-s-namespace")
+-- a suffix of id is appended on duplication
+UPDATE namespaces SET key = k.key FROM (
+  SELECT id, CASE
+    WHEN row_number() OVER (PARTITION BY slug ORDER BY id) > 1
+    THEN slug || '-' || id ELSE slug END AS key
+  FROM (
+    SELECT id, COALESCE(NULLIF(left(trim(BOTH '-' FROM regexp_replace(
+      lower(name), '[^a-z0-9]+', '-', 'g')), 48), ''), 'namespace') AS slug
+    FROM namespaces
+  ) AS s
+) AS k WHERE namespaces.id = k.id;
+
+ALTER TABLE namespaces ALTER COLUMN key SET NOT NULL;
+
+CREATE UNIQUE INDEX namespaces_key_idx ON namespaces(key);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS streams_namespace_id_slug_idx;
+
+ALTER TABLE streams DROP COLUMN slug;
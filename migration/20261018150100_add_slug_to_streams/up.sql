@@ -0,0 +1,20 @@
+ALTER TABLE streams ADD COLUMN slug CHARACTER VARYING(64) NULL;
+
+-- generates slugs from names (e.g. "alice's stream" -> "alice-s-stream")
+-- a suffix of id is appended on duplication in the namespace
+UPDATE streams SET slug = k.slug FROM (
+  SELECT id, CASE
+    WHEN row_number() OVER (PARTITION BY namespace_id, slug ORDER BY id) > 1
+    THEN slug || '-' || id ELSE slug END AS slug
+  FROM (
+    SELECT id, namespace_id, COALESCE(NULLIF(left(trim(BOTH '-' FROM
+      regexp_replace(lower(name), '[^a-z0-9]+', '-', 'g')), 48), ''),
+      'stream') AS slug
+    FROM streams
+  ) AS s
+) AS k WHERE streams.id = k.id;
+
+ALTER TABLE streams ALTER COLUMN slug SET NOT NULL;
+
+CREATE UNIQUE INDEX streams_namespace_id_slug_idx ON streams(
+  namespace_id, slug);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS namespace_key_redirects_key_idx;
+DROP INDEX IF EXISTS namespace_key_redirects_namespace_id_idx;
+
+DROP TABLE IF EXISTS namespace_key_redirects;
+DROP SEQUENCE IF EXISTS namespace_key_redirects_id_seq;
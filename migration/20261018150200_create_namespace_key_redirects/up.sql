@@ -0,0 +1,26 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE namespace_key_redirects_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- old keys of renamed namespaces
+CREATE TABLE namespace_key_redirects (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT
+    nextval('namespace_key_redirects_id_seq'),
+  namespace_id BIGINT REFERENCES namespaces (id) MATCH FULL NOT NULL,
+  key CHARACTER VARYING(64) NOT NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE namespace_key_redirects_id_seq
+  OWNED BY namespace_key_redirects.id;
+
+CREATE INDEX namespace_key_redirects_namespace_id_idx
+  ON namespace_key_redirects(namespace_id);
+CREATE UNIQUE INDEX namespace_key_redirects_key_idx
+  ON namespace_key_redirects(key);
@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS invitations_namespace_id_email_idx;
+DROP INDEX IF EXISTS invitations_token_idx;
+DROP INDEX IF EXISTS invitations_uuid_idx;
+DROP INDEX IF EXISTS invitations_namespace_id_idx;
+
+DROP TABLE IF EXISTS invitations;
+DROP SEQUENCE IF EXISTS invitations_id_seq;
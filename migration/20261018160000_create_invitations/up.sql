@@ -0,0 +1,34 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE invitations_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE invitations (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('invitations_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  namespace_id BIGINT REFERENCES namespaces (id) MATCH FULL NOT NULL,
+  inviter_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  email CHARACTER VARYING(128) NOT NULL,
+  role e_membership_role NOT NULL DEFAULT 'member',
+  token CHARACTER VARYING(256) NULL,
+  token_granted_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  token_expires_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  accepted_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE invitations_id_seq OWNED BY invitations.id;
+
+CREATE INDEX invitations_namespace_id_idx ON invitations(namespace_id);
+CREATE UNIQUE INDEX invitations_uuid_idx ON invitations(uuid);
+CREATE UNIQUE INDEX invitations_token_idx ON invitations(token);
+-- only one pending invitation for an email address in a namespace
+CREATE UNIQUE INDEX invitations_namespace_id_email_idx
+  ON invitations(namespace_id, email) WHERE accepted_at IS NULL;
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS memberships_namespace_id_primary_owner_idx;
@@ -0,0 +1,4 @@
+-- a namespace must have only one (active) primary owner
+CREATE UNIQUE INDEX memberships_namespace_id_primary_owner_idx
+  ON memberships (namespace_id)
+  WHERE role = 'primary_owner' AND revoked_at IS NULL;
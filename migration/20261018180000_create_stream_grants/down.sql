@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS stream_grants_stream_id_user_id_idx;
+DROP INDEX IF EXISTS stream_grants_user_id_idx;
+
+DROP TABLE IF EXISTS stream_grants;
+DROP SEQUENCE IF EXISTS stream_grants_id_seq;
+
+DROP TYPE IF EXISTS e_role;
@@ -0,0 +1,33 @@
+CREATE TYPE e_role AS ENUM (
+  'writer',
+  'member',
+  'maintainer',
+  'admin'
+);
+
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE stream_grants_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+CREATE TABLE stream_grants (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('stream_grants_id_seq'),
+  stream_id BIGINT REFERENCES streams (id) MATCH FULL NOT NULL,
+  user_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  role e_role NOT NULL DEFAULT 'writer',
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE stream_grants_id_seq OWNED BY stream_grants.id;
+
+CREATE INDEX stream_grants_user_id_idx ON stream_grants(user_id);
+-- a grant for a user on a stream
+CREATE UNIQUE INDEX stream_grants_stream_id_user_id_idx
+  ON stream_grants(stream_id, user_id);
@@ -0,0 +1,9 @@
+DROP TRIGGER IF EXISTS audit_events_deny_change ON audit_events;
+DROP FUNCTION IF EXISTS audit_events_deny_change();
+
+DROP INDEX IF EXISTS audit_events_namespace_id_created_at_idx;
+DROP INDEX IF EXISTS audit_events_actor_id_idx;
+DROP INDEX IF EXISTS audit_events_uuid_idx;
+
+DROP TABLE IF EXISTS audit_events;
+DROP SEQUENCE IF EXISTS audit_events_id_seq;
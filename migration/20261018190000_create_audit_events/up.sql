@@ -0,0 +1,43 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE audit_events_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- NOTE:
+-- actor_id and namespace_id have no foreign key, because events must stay
+-- even after the user or the namespace is deleted.
+CREATE TABLE audit_events (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('audit_events_id_seq'),
+  uuid UUID NOT NULL DEFAULT uuid_generate_v4(),
+  actor_id BIGINT NULL,
+  action CHARACTER VARYING(64) NOT NULL,
+  target CHARACTER VARYING(128) NULL,
+  namespace_id BIGINT NULL,
+  ip_address CHARACTER VARYING(64) NULL,
+  user_agent CHARACTER VARYING(256) NULL,
+  diff TEXT NOT NULL DEFAULT '{}',
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE audit_events_id_seq OWNED BY audit_events.id;
+
+CREATE UNIQUE INDEX audit_events_uuid_idx ON audit_events(uuid);
+CREATE INDEX audit_events_actor_id_idx ON audit_events(actor_id);
+CREATE INDEX audit_events_namespace_id_created_at_idx
+  ON audit_events(namespace_id, created_at);
+
+-- append-only
+CREATE OR REPLACE FUNCTION audit_events_deny_change() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_deny_change
+  BEFORE UPDATE OR DELETE ON audit_events
+  FOR EACH ROW EXECUTE PROCEDURE audit_events_deny_change();
@@ -0,0 +1,15 @@
+-- deleted users (not removed yet by the job) are put back into pending
+UPDATE users SET state = 'pending' WHERE state = 'deleted';
+
+ALTER TYPE e_user_state RENAME TO e_user_state_new;
+CREATE TYPE e_user_state AS ENUM (
+  'pending',
+  'active'
+);
+
+ALTER TABLE users ALTER COLUMN state DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN state TYPE e_user_state
+  USING state::text::e_user_state;
+ALTER TABLE users ALTER COLUMN state SET DEFAULT 'pending';
+
+DROP TYPE e_user_state_new;
@@ -0,0 +1,14 @@
+-- `ALTER TYPE ... ADD VALUE` can't run in a transaction block (PostgreSQL 11)
+ALTER TYPE e_user_state RENAME TO e_user_state_old;
+CREATE TYPE e_user_state AS ENUM (
+  'pending',
+  'active',
+  'deleted'
+);
+
+ALTER TABLE users ALTER COLUMN state DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN state TYPE e_user_state
+  USING state::text::e_user_state;
+ALTER TABLE users ALTER COLUMN state SET DEFAULT 'pending';
+
+DROP TYPE e_user_state_old;
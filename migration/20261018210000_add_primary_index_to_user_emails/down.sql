@@ -0,0 +1 @@
+DROP INDEX IF EXISTS user_emails_user_id_primary_idx;
@@ -0,0 +1,4 @@
+-- an user must have only one primary email address
+CREATE UNIQUE INDEX user_emails_user_id_primary_idx
+  ON user_emails (user_id)
+  WHERE role = 'primary';
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS user_two_factors_user_id_idx;
+
+DROP TABLE IF EXISTS user_two_factors;
+DROP SEQUENCE IF EXISTS user_two_factors_id_seq;
@@ -0,0 +1,29 @@
+-- equivalent to use of SERIAL or BIGSERIAL
+CREATE SEQUENCE user_two_factors_id_seq
+  START WITH 1
+  INCREMENT BY 1
+  NO MAXVALUE
+  NO MINVALUE
+  CACHE 1
+;
+
+-- NOTE:
+-- secret is encrypted, and recovery_codes are digests (SHA-256) of the codes.
+-- Two-factor authentication is enabled after enabled_at is set.
+CREATE TABLE user_two_factors (
+  id BIGINT NOT NULL PRIMARY KEY DEFAULT nextval('user_two_factors_id_seq'),
+  user_id BIGINT REFERENCES users (id) MATCH FULL NOT NULL,
+  secret CHARACTER VARYING(256) NOT NULL,
+  recovery_codes TEXT[] NOT NULL DEFAULT '{}',
+  last_used_step BIGINT NULL,
+  enabled_at TIMESTAMP WITHOUT TIME ZONE NULL,
+  created_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc'),
+  updated_at TIMESTAMP WITHOUT TIME ZONE NOT NULL
+    DEFAULT (now() AT TIME ZONE 'utc')
+);
+
+ALTER SEQUENCE user_two_factors_id_seq OWNED BY user_two_factors.id;
+
+CREATE UNIQUE INDEX user_two_factors_user_id_idx
+  ON user_two_factors(user_id);
@@ -10,6 +10,7 @@ use eloquentlog_console_api::db::init_pool_holder as init_db_pool_holder;
 use eloquentlog_console_api::mq::init_pool_holder as init_mq_pool_holder;
 use eloquentlog_console_api::ss::init_pool_holder as init_ss_pool_holder;
 use eloquentlog_console_api::config::Config;
+use eloquentlog_console_api::validation::password::PasswordPolicy;
 
 fn get_env() -> String {
     match env::var("ENV") {
@@ -26,6 +27,8 @@ fn main() {
     dotenv().ok();
     let config = Config::from(name.as_str()).expect("failed to get config");
     let logger = logger::get_logger(&config);
+    let password_policy =
+        PasswordPolicy::from(&config).expect("failed to load password policy");
 
     // connection pool holders
     let db_pool_holder = init_db_pool_holder(
@@ -47,5 +50,6 @@ fn main() {
         .manage(mq_pool_holder)
         .manage(ss_pool_holder)
         .manage(config)
+        .manage(password_policy)
         .launch();
 }
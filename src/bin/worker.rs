@@ -4,16 +4,21 @@
 extern crate slog;
 
 use std::env;
+use std::thread;
+use std::time::Duration;
 
+use chrono::Utc;
 use dotenv::dotenv;
 use fourche::queue::Queue;
 use proctitle::set_title;
-use redis::Client;
+use redis::{Client, Connection, RedisResult};
+use slog::Logger;
 
 use eloquentlog_console_api::config::Config;
 use eloquentlog_console_api::db::establish_connection;
-use eloquentlog_console_api::job::Job;
+use eloquentlog_console_api::job::{Job, JobKind};
 use eloquentlog_console_api::logger::get_logger;
+use eloquentlog_console_api::scheduler::{Scheduler, lock_key};
 
 fn get_env() -> String {
     match env::var("ENV") {
@@ -23,6 +28,53 @@ fn get_env() -> String {
     }
 }
 
+// Takes the lock of the job kind (SET NX EX), which expires a tick before its
+// next run. Only one of the workers can enqueue the job in the interval.
+fn lock(
+    conn: &mut Connection,
+    scheduler: &Scheduler,
+    kind: &JobKind,
+) -> RedisResult<bool> {
+    let ttl = scheduler
+        .interval(kind)
+        .map(|d| d.num_seconds() - Scheduler::TICK_INTERVAL as i64)
+        .unwrap_or(0)
+        .max(1);
+    let result: Option<String> = redis::cmd("SET")
+        .arg(lock_key(kind))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ttl)
+        .query(conn)?;
+    Ok(result.is_some())
+}
+
+// Enqueues periodic jobs using other connections.
+fn schedule(config: Config, logger: Logger) {
+    let client = Client::open(config.message_queue_url.as_str()).unwrap();
+    let mut mq_conn = client.get_connection().unwrap();
+    let mut lock_conn = client.get_connection().unwrap();
+
+    let mut scheduler = Scheduler::default();
+    let mut queue = Queue::new("default", &mut mq_conn);
+    loop {
+        for job in scheduler.tick(Utc::now().naive_utc()) {
+            match lock(&mut lock_conn, &scheduler, &job.kind) {
+                Ok(true) => {
+                    if let Err(e) = queue.enqueue::<Job<String>>(job) {
+                        error!(logger, "err: {}", e);
+                    }
+                },
+                // enqueued by another worker
+                Ok(false) => continue,
+                Err(e) => error!(logger, "err: {}", e),
+            }
+        }
+        thread::sleep(Duration::from_secs(Scheduler::TICK_INTERVAL));
+    }
+}
+
 fn main() {
     set_title("eloquentlog: worker");
     let name = get_env();
@@ -38,6 +90,10 @@ fn main() {
     let db_conn = establish_connection(&config);
 
     let logger = get_logger(&config);
+
+    let (c, l) = (config.clone(), logger.clone());
+    thread::spawn(move || schedule(c, l));
+
     let mut queue = Queue::new("default", &mut mq_conn);
     loop {
         match queue.dequeue::<Job<String>>() {
@@ -48,7 +104,11 @@ fn main() {
                     job.kind,
                     job.args.as_slice()
                 );
-                job.invoke(&db_conn, &config, &logger);
+                for j in job.invoke(&db_conn, &config, &logger) {
+                    if let Err(e) = queue.enqueue::<Job<String>>(j) {
+                        error!(logger, "err: {}", e);
+                    }
+                }
             },
             Err(e) => {
                 error!(logger, "err: {}", e);
@@ -1,21 +1,56 @@
 use std::convert::Into;
 use std::fmt;
 
+use chrono::NaiveDateTime;
 use diesel::PgConnection;
 use diesel::result::Error;
+use serde_json::json;
 use slog::Logger;
 
 use crate::config::Config;
-use crate::model::user::User;
+use crate::model::alert::Alert;
+use crate::model::alert_rule::AlertRule;
+use crate::model::digest::Digest;
+use crate::model::digest_subscription::DigestSubscription;
+use crate::model::invitation::Invitation;
+use crate::model::message::Message;
+use crate::model::namespace::Namespace;
+use crate::model::namespace_usage::NamespaceUsage;
+use crate::model::stream::Stream;
+use crate::model::user::{User, UserState};
 use crate::model::user_email::UserEmail;
+use crate::model::webhook::WebhookEvent;
+use crate::model::webhook_delivery::WebhookDelivery;
 use crate::mailer::user::UserMailer;
+use crate::service::alert_evaluator::AlertEvaluator;
+use crate::service::webhook_sender::{self, WebhookSender};
+use crate::util::{Clock, SystemClock};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum JobKind {
     SendUserActivationEmail,
     SendPasswordResetEmail,
+    EvaluateAlertRules,
+    SendAlertNotificationEmail,
+    DeliverWebhook,
+    RetryWebhookDeliveries,
+    SendDigestEmails,
+    SendDigestEmail,
+    SendQuotaWarningEmail,
+    DeleteNamespace,
+    SendInvitationEmail,
+    DeleteUser,
+    SendEmailVerificationEmail,
+    SendEmailChangeVerificationEmail,
+    SendEmailChangeNoticeEmail,
+    SendPasswordChangeNoticeEmail,
+    SendAccountUnlockEmail,
 }
 
+// a number of messages deleted (or anonymized) at once by DeleteNamespace and
+// DeleteUser
+const DELETE_BATCH_SIZE: i64 = 1000;
+
 impl fmt::Display for JobKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:?}", self)
@@ -31,12 +66,13 @@ pub struct Job<T> {
 impl<T> Job<T>
 where T: Clone + fmt::Debug + Into<String>
 {
+    /// Invokes the job and returns follow-up jobs to be enqueued (if any).
     pub fn invoke(
         &self,
         db_conn: &PgConnection,
         config: &Config,
         logger: &Logger,
-    ) {
+    ) -> Vec<Job<String>> {
         match self.kind {
             JobKind::SendUserActivationEmail => {
                 self.send_user_activation_email(db_conn, config, logger);
@@ -44,7 +80,55 @@ where T: Clone + fmt::Debug + Into<String>
             JobKind::SendPasswordResetEmail => {
                 self.send_password_reset_email(db_conn, config, logger);
             },
+            JobKind::EvaluateAlertRules => {
+                return self.evaluate_alert_rules(db_conn, logger);
+            },
+            JobKind::SendAlertNotificationEmail => {
+                self.send_alert_notification_email(db_conn, config, logger);
+            },
+            JobKind::DeliverWebhook => {
+                self.deliver_webhook(db_conn, config, logger);
+            },
+            JobKind::RetryWebhookDeliveries => {
+                return self.retry_webhook_deliveries(db_conn, logger);
+            },
+            JobKind::SendDigestEmails => {
+                return self.send_digest_emails(db_conn, logger);
+            },
+            JobKind::SendDigestEmail => {
+                self.send_digest_email(db_conn, config, logger);
+            },
+            JobKind::SendQuotaWarningEmail => {
+                self.send_quota_warning_email(db_conn, config, logger);
+            },
+            JobKind::DeleteNamespace => {
+                return self.delete_namespace(db_conn, logger);
+            },
+            JobKind::SendInvitationEmail => {
+                self.send_invitation_email(db_conn, config, logger);
+            },
+            JobKind::DeleteUser => {
+                return self.delete_user(db_conn, config, logger);
+            },
+            JobKind::SendEmailVerificationEmail => {
+                self.send_email_verification_email(db_conn, config, logger);
+            },
+            JobKind::SendEmailChangeVerificationEmail => {
+                self.send_email_change_verification_email(
+                    db_conn, config, logger,
+                );
+            },
+            JobKind::SendEmailChangeNoticeEmail => {
+                self.send_email_change_notice_email(db_conn, config, logger);
+            },
+            JobKind::SendPasswordChangeNoticeEmail => {
+                self.send_password_change_notice_email(db_conn, config, logger);
+            },
+            JobKind::SendAccountUnlockEmail => {
+                self.send_account_unlock_email(db_conn, config, logger);
+            },
         }
+        vec![]
     }
 
     fn send_user_activation_email(
@@ -147,4 +231,674 @@ where T: Clone + fmt::Debug + Into<String>
             }
         });
     }
+
+    fn evaluate_alert_rules(
+        &self,
+        db_conn: &PgConnection,
+        logger: &Logger,
+    ) -> Vec<Job<String>> {
+        let rules = AlertRule::find_all_in_visible_streams(db_conn, logger)
+            .unwrap_or_default();
+
+        let clock = SystemClock;
+        let evaluator = AlertEvaluator::new(db_conn, &clock, logger);
+
+        let mut jobs = vec![];
+        for rule in rules {
+            let result: Result<Option<Alert>, Error> = db_conn
+                .build_transaction()
+                .serializable()
+                .read_write()
+                .run::<_, diesel::result::Error, _>(|| {
+                    evaluator.evaluate(&rule).map_err(|e| {
+                        error!(logger, "err: {} {}", rule, e);
+                        Error::RollbackTransaction
+                    })
+                });
+
+            if let Ok(Some(alert)) = result {
+                let event = if alert.is_firing() {
+                    jobs.push(Job::<String> {
+                        kind: JobKind::SendAlertNotificationEmail,
+                        args: vec![alert.id.to_string()],
+                    });
+                    WebhookEvent::AlertFired
+                } else {
+                    WebhookEvent::AlertResolved
+                };
+
+                if let Some(stream) =
+                    Stream::find_by_id(rule.stream_id, db_conn, logger)
+                {
+                    let data = json!({
+                        "alert": alert,
+                        "alert_rule": rule,
+                        "stream": {
+                            "uuid": stream.uuid.to_string(),
+                            "name": stream.name,
+                        },
+                    });
+                    jobs.extend(webhook_sender::dispatch(
+                        stream.namespace_id,
+                        &event,
+                        data,
+                        db_conn,
+                        logger,
+                    ));
+                }
+            }
+        }
+        jobs
+    }
+
+    fn send_alert_notification_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.is_empty() {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let alert_id = args[0].clone().into().parse::<i64>().unwrap();
+
+        let alert = match Alert::find_by_id(alert_id, db_conn, logger) {
+            Some(a) => a,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+        // the rule or the stream may have been deleted in the meantime
+        let rule =
+            match AlertRule::find_by_id(alert.alert_rule_id, db_conn, logger) {
+                Some(r) => r,
+                None => {
+                    error!(logger, "not found :'(");
+                    return;
+                },
+            };
+        let stream = match Stream::find_by_id(rule.stream_id, db_conn, logger) {
+            Some(s) => s,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let users = User::find_all_by_namespace_id(
+            stream.namespace_id,
+            db_conn,
+            logger,
+        )
+        .unwrap_or_default();
+        for user in users {
+            let name = user.name.unwrap_or_else(|| "".to_string());
+
+            let mut mailer = UserMailer::new(config, logger);
+            // TODO: check result (should be Result instead of bool?)
+            mailer
+                .to((&user.email, &name))
+                .send_alert_notification_email(&stream, &rule, &alert);
+        }
+    }
+
+    fn deliver_webhook(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.is_empty() {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let delivery_id = args[0].clone().into().parse::<i64>().unwrap();
+
+        let delivery =
+            match WebhookDelivery::find_by_id(delivery_id, db_conn, logger) {
+                Some(d) => d,
+                None => {
+                    error!(logger, "not found :'(");
+                    return;
+                },
+            };
+
+        let clock = SystemClock;
+        let sender = WebhookSender::new(db_conn, config, &clock, logger);
+        if let Err(e) = sender.deliver(&delivery) {
+            error!(logger, "err: {} {}", delivery, e);
+        }
+    }
+
+    fn retry_webhook_deliveries(
+        &self,
+        db_conn: &PgConnection,
+        logger: &Logger,
+    ) -> Vec<Job<String>> {
+        let now = SystemClock.now();
+        WebhookDelivery::find_all_due(now, db_conn, logger)
+            .unwrap_or_default()
+            .iter()
+            .map(|d| {
+                Job::<String> {
+                    kind: JobKind::DeliverWebhook,
+                    args: vec![d.id.to_string()],
+                }
+            })
+            .collect()
+    }
+
+    // Marks due subscriptions as sent and returns a job for each of them.
+    fn send_digest_emails(
+        &self,
+        db_conn: &PgConnection,
+        logger: &Logger,
+    ) -> Vec<Job<String>> {
+        let now = SystemClock.now();
+        DigestSubscription::find_all_due(now, db_conn, logger)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|s| {
+                // the digest won't be sent twice even if the job fails
+                s.touch(now, db_conn, logger)
+                    .map_err(|e| error!(logger, "err: {} {}", s, e))
+                    .ok()
+            })
+            .map(|s| {
+                Job::<String> {
+                    kind: JobKind::SendDigestEmail,
+                    args: vec![s.id.to_string(), now.timestamp().to_string()],
+                }
+            })
+            .collect()
+    }
+
+    fn send_digest_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 2 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let subscription_id = args[0].clone().into().parse::<i64>().unwrap();
+        let until = NaiveDateTime::from_timestamp(
+            args[1].clone().into().parse::<i64>().unwrap(),
+            0,
+        );
+
+        let subscription = match DigestSubscription::find_by_id(
+            subscription_id,
+            db_conn,
+            logger,
+        ) {
+            Some(s) => s,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+        let user = match User::find_by_id(subscription.user_id, db_conn, logger)
+        {
+            Some(u) if u.state == UserState::Active => u,
+            _ => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+        // the user may have left the namespace
+        let namespace = match Namespace::find_all(&user, db_conn, logger)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|n| n.id == subscription.namespace_id)
+        {
+            Some(n) => n,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let digest = match Digest::build(
+            &namespace,
+            subscription.frequency,
+            until,
+            db_conn,
+            logger,
+        ) {
+            Some(d) => d,
+            None => return,
+        };
+
+        let name = user.name.unwrap_or_else(|| "".to_string());
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer
+            .to((&user.email, &name))
+            .send_digest_email(&namespace, &digest);
+    }
+
+    fn send_quota_warning_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 3 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let usage_id = args[0].clone().into().parse::<i64>().unwrap();
+        let percent = args[1].clone().into().parse::<i16>().unwrap();
+        let quota = args[2].clone().into().parse::<i64>().unwrap();
+
+        let usage = match NamespaceUsage::find_by_id(usage_id, db_conn, logger)
+        {
+            Some(u) => u,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+        let namespace =
+            match Namespace::find_by_id(usage.namespace_id, db_conn, logger) {
+                Some(n) => n,
+                None => {
+                    error!(logger, "not found :'(");
+                    return;
+                },
+            };
+
+        let users = User::find_all_by_namespace_id(
+            namespace.id,
+            db_conn,
+            logger,
+        )
+        .unwrap_or_default();
+        for user in users {
+            let name = user.name.unwrap_or_else(|| "".to_string());
+
+            let mut mailer = UserMailer::new(config, logger);
+            // TODO: check result (should be Result instead of bool?)
+            mailer
+                .to((&user.email, &name))
+                .send_quota_warning_email(&namespace, &usage, quota, percent);
+        }
+    }
+
+    // Deletes a batch of messages in the namespace, and returns the job
+    // itself until all of them are deleted. Then the namespace and the rest
+    // of records belong to it are deleted in a transaction.
+    fn delete_namespace(
+        &self,
+        db_conn: &PgConnection,
+        logger: &Logger,
+    ) -> Vec<Job<String>> {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.is_empty() {
+            return vec![];
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let namespace_id = args[0].clone().into().parse::<i64>().unwrap();
+
+        let namespace =
+            match Namespace::find_by_id(namespace_id, db_conn, logger) {
+                Some(n) => n,
+                None => {
+                    error!(logger, "not found :'(");
+                    return vec![];
+                },
+            };
+
+        match Message::delete_by_namespace_id(
+            namespace.id,
+            DELETE_BATCH_SIZE,
+            db_conn,
+            logger,
+        ) {
+            Err(e) => {
+                error!(logger, "err: {} {}", namespace, e);
+                return vec![];
+            },
+            Ok(n) if n > 0 => {
+                return vec![Job::<String> {
+                    kind: JobKind::DeleteNamespace,
+                    args: vec![namespace.id.to_string()],
+                }];
+            },
+            Ok(_) => {},
+        }
+
+        let _: Result<_, Error> = db_conn
+            .build_transaction()
+            .serializable()
+            .read_write()
+            .run::<_, diesel::result::Error, _>(|| {
+                namespace.delete(db_conn, logger).map_err(|e| {
+                    error!(logger, "err: {} {}", namespace, e);
+                    Error::RollbackTransaction
+                })
+            });
+        vec![]
+    }
+
+    fn send_invitation_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 2 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let invitation_id = args[0].clone().into().parse::<i64>().unwrap();
+        let token = args[1].clone().into();
+
+        let invitation =
+            match Invitation::find_by_id(invitation_id, db_conn, logger) {
+                Some(i) if i.accepted_at.is_none() => i,
+                _ => {
+                    error!(logger, "not found :'(");
+                    return;
+                },
+            };
+        let namespace = match Namespace::find_by_id(
+            invitation.namespace_id,
+            db_conn,
+            logger,
+        ) {
+            Some(n) => n,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+        let inviter =
+            match User::find_by_id(invitation.inviter_id, db_conn, logger) {
+                Some(u) => u,
+                None => {
+                    error!(logger, "not found :'(");
+                    return;
+                },
+            };
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer
+            .to((&invitation.email, ""))
+            .send_invitation_email(&namespace, &inviter, &token);
+    }
+
+    fn delete_user(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) -> Vec<Job<String>> {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.is_empty() {
+            return vec![];
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let user_id = args[0].clone().into().parse::<i64>().unwrap();
+
+        let user = match User::find_by_id(user_id, db_conn, logger) {
+            Some(u) if u.state == UserState::Deleted => u,
+            _ => {
+                error!(logger, "not found :'(");
+                return vec![];
+            },
+        };
+
+        match Message::anonymize_by_user_id(
+            user.id,
+            DELETE_BATCH_SIZE,
+            db_conn,
+            logger,
+        ) {
+            Err(e) => {
+                error!(logger, "err: {} {}", user, e);
+                return vec![];
+            },
+            Ok(n) if n > 0 => {
+                return vec![Job::<String> {
+                    kind: JobKind::DeleteUser,
+                    args: vec![user.id.to_string()],
+                }];
+            },
+            Ok(_) => {},
+        }
+
+        let result: Result<_, Error> = db_conn
+            .build_transaction()
+            .serializable()
+            .read_write()
+            .run::<_, diesel::result::Error, _>(|| {
+                user.delete(db_conn, logger).map_err(|e| {
+                    error!(logger, "err: {} {}", user, e);
+                    Error::RollbackTransaction
+                })
+            });
+        if result.is_err() {
+            return vec![];
+        }
+
+        let name = user.name.unwrap_or_else(|| "".to_string());
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer.to((&user.email, &name)).send_deregistration_email();
+        vec![]
+    }
+
+    fn send_email_verification_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 2 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let user_email_id = args[0].clone().into().parse::<i64>().unwrap();
+        let token = args[1].clone().into();
+
+        let email = match UserEmail::find_by_id(user_email_id, db_conn, logger)
+        {
+            Some(ue) if !ue.is_verified() => ue.email.unwrap_or_default(),
+            _ => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer.to((&email, "")).send_email_verification_email(&token);
+    }
+
+    fn send_email_change_verification_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 3 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let user_email_id = args[0].clone().into().parse::<i64>().unwrap();
+        let session_id = args[1].clone().into();
+        let token = args[2].clone().into();
+
+        let email = match UserEmail::find_by_id(user_email_id, db_conn, logger)
+        {
+            Some(ue) if !ue.is_verified() => ue.email.unwrap_or_default(),
+            _ => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer
+            .to((&email, ""))
+            .send_email_change_verification_email(&session_id, &token);
+    }
+
+    fn send_email_change_notice_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 3 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let user_email_id = args[0].clone().into().parse::<i64>().unwrap();
+        let session_id = args[1].clone().into();
+        let token = args[2].clone().into();
+
+        let user_email =
+            match UserEmail::find_by_id(user_email_id, db_conn, logger) {
+                Some(ue) if !ue.is_verified() => ue,
+                _ => {
+                    error!(logger, "not found :'(");
+                    return;
+                },
+            };
+        let user = match User::find_by_id(user_email.user_id, db_conn, logger)
+        {
+            Some(u) => u,
+            None => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let new_email = user_email.email.unwrap_or_default();
+        let name = user.name.unwrap_or_else(|| "".to_string());
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer.to((&user.email, &name)).send_email_change_notice_email(
+            &new_email,
+            &session_id,
+            &token,
+        );
+    }
+
+    fn send_password_change_notice_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.is_empty() {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let user_id = args[0].clone().into().parse::<i64>().unwrap();
+
+        let user = match User::find_by_id(user_id, db_conn, logger) {
+            Some(u) if u.state == UserState::Active => u,
+            _ => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let name = user.name.unwrap_or_else(|| "".to_string());
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer.to((&user.email, &name)).send_password_change_notice_email();
+    }
+
+    fn send_account_unlock_email(
+        &self,
+        db_conn: &PgConnection,
+        config: &Config,
+        logger: &Logger,
+    ) {
+        info!(logger, "args: {:#?}", self.args.as_slice());
+        let args = self.args.as_slice();
+        if args.len() < 3 {
+            return;
+        }
+
+        // FIXME:
+        // any good way for T? (see also worker.rs)
+        let user_id = args[0].clone().into().parse::<i64>().unwrap();
+
+        let session_id = args[1].clone().into();
+        let token = args[2].clone().into();
+
+        let user = match User::find_by_id(user_id, db_conn, logger) {
+            Some(u) if u.state == UserState::Active => u,
+            _ => {
+                error!(logger, "not found :'(");
+                return;
+            },
+        };
+
+        let name = user.name.unwrap_or_else(|| "".to_string());
+
+        let mut mailer = UserMailer::new(config, logger);
+        // TODO: check result (should be Result instead of bool?)
+        mailer
+            .to((&user.email, &name))
+            .send_account_unlock_email(&session_id, &token);
+    }
 }
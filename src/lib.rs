@@ -28,7 +28,6 @@ extern crate rusty_fork;
 use std::collections::HashMap;
 
 mod response;
-mod validation;
 mod service;
 mod schema;
 mod util;
@@ -44,6 +43,8 @@ pub mod mailer;
 pub mod model;
 pub mod request;
 pub mod route;
+pub mod scheduler;
+pub mod validation;
 
 // macros
 
@@ -78,10 +79,21 @@ pub fn routes() -> Vec<(&'static str, Vec<rocket::Route>)> {
                 route::activation::preflight::activate,
                 route::activation::activate,
                 route::authentication::preflight::login,
+                route::authentication::preflight::login_two_factor,
                 route::authentication::preflight::logout,
+                route::authentication::preflight::refresh,
                 route::authentication::preignition::login,
                 route::authentication::login,
+                route::authentication::login_two_factor,
                 route::authentication::logout,
+                route::authentication::refresh,
+                route::email_change::preflight::confirm_cancel,
+                route::email_change::preflight::request,
+                route::email_change::cancel,
+                route::email_change::confirm,
+                route::email_change::request,
+                route::password_change::preflight::change,
+                route::password_change::change,
                 route::password_reset::preflight::request,
                 route::password_reset::preflight::verify_update,
                 route::password_reset::preignition::request,
@@ -94,6 +106,16 @@ pub fn routes() -> Vec<(&'static str, Vec<rocket::Route>)> {
                 route::registration::preignition::register,
                 route::registration::deregister,
                 route::registration::register,
+                route::two_factor::preflight::disable,
+                route::two_factor::preflight::enable,
+                route::two_factor::preflight::recovery_codes,
+                route::two_factor::preflight::setup,
+                route::two_factor::disable,
+                route::two_factor::enable,
+                route::two_factor::recovery_codes,
+                route::two_factor::setup,
+                route::unlock::preflight::unlock,
+                route::unlock::unlock,
                 route::health::check,
             ],
         ),
@@ -110,16 +132,118 @@ pub fn routes() -> Vec<(&'static str, Vec<rocket::Route>)> {
                 route::access_token::hset_state,
                 route::access_token::append,
                 route::access_token::lrange,
+                route::alert_rule::preflight::del,
+                route::alert_rule::preflight::hgetall,
+                route::alert_rule::preflight::hset,
+                route::alert_rule::preflight::lrange,
+                route::alert_rule::del,
+                route::alert_rule::hgetall,
+                route::alert_rule::hset,
+                route::alert_rule::lrange,
+                route::audit_event::preflight::lrange,
+                route::audit_event::lrange,
+                route::digest::preflight::del,
+                route::digest::preflight::hgetall,
+                route::digest::preflight::hset,
+                route::digest::del,
+                route::digest::hgetall,
+                route::digest::hset,
+                route::invitation::preflight::accept,
+                route::invitation::preflight::del,
+                route::invitation::preflight::hgetall,
+                route::invitation::preflight::hset,
+                route::invitation::preflight::resend,
+                route::invitation::accept,
+                route::invitation::del,
+                route::invitation::hgetall,
+                route::invitation::hset,
+                route::invitation::resend,
+                route::membership::preflight::del,
+                route::membership::preflight::hgetall,
+                route::membership::preflight::hupdate,
+                route::membership::preflight::transfer,
+                route::membership::del,
+                route::membership::hgetall,
+                route::membership::hupdate,
+                route::membership::transfer,
                 route::message::preflight::append,
                 route::message::preflight::lrange,
                 route::message::append,
                 route::message::lrange,
+                route::namespace::preflight::archive,
+                route::namespace::preflight::archived,
+                route::namespace::preflight::del,
                 route::namespace::preflight::hget,
                 route::namespace::preflight::hgetall,
                 route::namespace::preflight::hset,
+                route::namespace::preflight::hupdate,
+                route::namespace::preflight::unarchive,
+                route::namespace::archive,
+                route::namespace::archived,
+                route::namespace::del,
                 route::namespace::hget,
                 route::namespace::hgetall,
                 route::namespace::hset,
+                route::namespace::hupdate,
+                route::namespace::unarchive,
+                route::redaction_rule::preflight::del,
+                route::redaction_rule::preflight::eval,
+                route::redaction_rule::preflight::hgetall,
+                route::redaction_rule::preflight::hset,
+                route::redaction_rule::del,
+                route::redaction_rule::eval,
+                route::redaction_rule::hgetall,
+                route::redaction_rule::hset,
+                route::stream::preflight::archive,
+                route::stream::preflight::archived,
+                route::stream::preflight::hget,
+                route::stream::preflight::hgetall,
+                route::stream::preflight::hset,
+                route::stream::preflight::hupdate,
+                route::stream::preflight::unarchive,
+                route::stream::archive,
+                route::stream::archived,
+                route::stream::hget,
+                route::stream::hgetall,
+                route::stream::hset,
+                route::stream::hupdate,
+                route::stream::unarchive,
+                route::stream_grant::preflight::del,
+                route::stream_grant::preflight::hgetall,
+                route::stream_grant::preflight::hset,
+                route::stream_grant::del,
+                route::stream_grant::hgetall,
+                route::stream_grant::hset,
+                route::usage::preflight::hget,
+                route::usage::preflight::lrange,
+                route::usage::hget,
+                route::usage::lrange,
+                route::user_email::preflight::append,
+                route::user_email::preflight::del,
+                route::user_email::preflight::hgetall,
+                route::user_email::preflight::primary,
+                route::user_email::preflight::verify,
+                route::user_email::append,
+                route::user_email::del,
+                route::user_email::hgetall,
+                route::user_email::primary,
+                route::user_email::verify,
+                route::user_session::preflight::clear,
+                route::user_session::preflight::del,
+                route::user_session::preflight::hgetall,
+                route::user_session::clear,
+                route::user_session::del,
+                route::user_session::hgetall,
+                route::webhook::preflight::del,
+                route::webhook::preflight::hgetall,
+                route::webhook::preflight::hset,
+                route::webhook::preflight::lrange,
+                route::webhook::preflight::ping,
+                route::webhook::del,
+                route::webhook::hgetall,
+                route::webhook::hset,
+                route::webhook::lrange,
+                route::webhook::ping,
                 route::health::check,
             ],
         ),
@@ -135,6 +259,7 @@ pub fn server() -> rocket::Rocket {
         .mount("/v1", r["/v1"].clone())
         .register(catchers![
             route::error::bad_request,
+            route::error::forbidden,
             route::error::internal_server_error,
             route::error::not_found,
             route::error::unauthorized,
@@ -6,6 +6,14 @@ use slog::Logger;
 
 use crate::config::Config;
 use crate::mailer::{Client, Header, Mailer};
+use crate::model::alert::Alert;
+use crate::model::alert_rule::AlertRule;
+use crate::model::digest::Digest;
+use crate::model::message::LogLevel;
+use crate::model::namespace::Namespace;
+use crate::model::namespace_usage::NamespaceUsage;
+use crate::model::stream::Stream;
+use crate::model::user::User;
 
 /// UserMailer is a wrapper handles email to user.
 ///
@@ -154,4 +162,486 @@ Eloquentlog
             .unwrap();
         self.mailer.send(email.into())
     }
+
+    /// Builds an unlock message of the account locked out by failed sign in
+    /// attempts and send it via actual mailer.
+    pub fn send_account_unlock_email(&mut self, s: &str, t: &str) -> bool {
+        let url = self.config.application_url.to_string();
+        let unlock_url = format!("{}/unlock?s={}&t={}", url, s, t);
+        let reset_url = format!("{}/password/reset", url);
+
+        let subject = "Your account has been locked";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+Your Eloquentlog account has been locked temporarily due to too many failed sign in attempts.
+To unlock it now, just follow the link below
+
+{}
+
+If you did not try to sign in, someone may be guessing your password. Consider to reset it via the link below
+
+{}
+
+--
+Eloquentlog
+{}
+"#,
+            unlock_url, reset_url, url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a notice message of the password change and send it via actual
+    /// mailer.
+    pub fn send_password_change_notice_email(&mut self) -> bool {
+        let url = self.config.application_url.to_string();
+        let reset_url = format!("{}/password/reset", url);
+
+        let subject = "Your password has been changed";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+The password of your Eloquentlog account has been changed.
+
+If you did not change it, reset your password immediately via the link below
+
+{}
+
+--
+Eloquentlog
+{}
+"#,
+            reset_url, url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a verification message for an additional email address and
+    /// send it via actual mailer.
+    pub fn send_email_verification_email(&mut self, t: &str) -> bool {
+        let url = self.config.application_url.to_string();
+        // TODO: build it with rocket::http::uri::Origin?
+        let verification_url = format!("{}/email/verify?t={}", url, t);
+
+        let subject = "Verify your email address";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+This address has been added to an Eloquentlog account.
+To verify it, just follow the link below (you need to be signed in)
+
+{}
+
+The link will expire in {} hours. If you did not add it, disregard this email.
+
+Happy logging !-)
+
+--
+Eloquentlog
+{}
+"#,
+            verification_url,
+            Config::USER_EMAIL_TOKEN_DURATION,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a verification message for the new address of the email change
+    /// and send it via actual mailer.
+    pub fn send_email_change_verification_email(
+        &mut self,
+        s: &str,
+        t: &str,
+    ) -> bool {
+        let url = self.config.application_url.to_string();
+        // TODO: build it with rocket::http::uri::Origin?
+        let verification_url = format!("{}/email/change?s={}&t={}", url, s, t);
+
+        let subject = "Confirm your new email address";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+Someone (hopefully you) has requested to change the email address of an Eloquentlog account to this address.
+To confirm it, just follow the link below
+
+{}
+
+The link will expire in {} hours. If you did not request it, disregard this email.
+
+Happy logging !-)
+
+--
+Eloquentlog
+{}
+"#,
+            verification_url,
+            Config::USER_EMAIL_TOKEN_DURATION,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a notice message of the email change for the current address
+    /// and send it via actual mailer.
+    pub fn send_email_change_notice_email(
+        &mut self,
+        new_email: &str,
+        s: &str,
+        t: &str,
+    ) -> bool {
+        let url = self.config.application_url.to_string();
+        // TODO: build it with rocket::http::uri::Origin?
+        let cancel_url =
+            format!("{}/email/change/cancel?s={}&t={}", url, s, t);
+
+        let subject = "Your email address is being changed";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+Someone (hopefully you) has requested to change the email address of your Eloquentlog account to {}.
+The address will be changed once it's confirmed via the link sent to it.
+
+If you did not request it, cancel it by following the link below, and change your password.
+
+{}
+
+--
+Eloquentlog
+{}
+"#,
+            new_email, cancel_url, url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a confirmation message of the account deletion and send it via
+    /// actual mailer.
+    pub fn send_deregistration_email(&mut self) -> bool {
+        let url = self.config.application_url.to_string();
+
+        let subject = "Your account has been deleted";
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+Your Eloquentlog account has been deleted as you requested.
+Namespaces you owned have been transferred to another owner, or deleted.
+Messages you sent remain in their streams, but without your name.
+
+Thank you for using Eloquentlog. Goodbye !-)
+
+--
+Eloquentlog
+{}
+"#,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a digest message of the namespace and send it via actual
+    /// mailer.
+    pub fn send_digest_email(
+        &mut self,
+        namespace: &Namespace,
+        digest: &Digest,
+    ) -> bool {
+        let url = self.config.application_url.to_string();
+        let namespace_url = format!("{}/namespace/{}", url, namespace.uuid);
+
+        let subject = format!(
+            "[Digest] {} ({})",
+            namespace.name,
+            digest.since.format("%Y-%m-%d")
+        );
+
+        let format_ranking = |ranking: &Vec<(String, i64)>| -> String {
+            if ranking.is_empty() {
+                return "  (none)".to_string();
+            }
+            ranking
+                .iter()
+                .map(|(k, n)| format!("  {:>8}  {}", n, k))
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let counts = LogLevel::iter()
+            .map(|level| {
+                let n = digest
+                    .counts
+                    .iter()
+                    .find(|(l, _)| l == level)
+                    .map_or(0, |(_, n)| *n);
+                format!("  {:>8}  {}", n, level)
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+Here is the {} digest of the namespace "{}".
+
+Period: {} - {} (UTC)
+
+Messages ({} in total):
+{}
+
+New issues:
+{}
+
+Noisy streams:
+{}
+
+Top codes:
+{}
+
+To see the namespace, just follow the link below
+
+{}
+
+--
+Eloquentlog
+{}
+"#,
+            digest.frequency,
+            namespace.name,
+            digest.since.format("%Y-%m-%d %H:%M:%S"),
+            digest.until.format("%Y-%m-%d %H:%M:%S"),
+            digest.total(),
+            counts,
+            format_ranking(&digest.new_issues),
+            format_ranking(&digest.noisy_streams),
+            format_ranking(&digest.top_codes),
+            namespace_url,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds an alert notification message and send it via actual mailer.
+    pub fn send_alert_notification_email(
+        &mut self,
+        stream: &Stream,
+        rule: &AlertRule,
+        alert: &Alert,
+    ) -> bool {
+        let url = self.config.application_url.to_string();
+        let stream_url = format!("{}/stream/{}", url, stream.uuid);
+
+        let subject = format!("[Alert] {} on {}", rule.name, stream.name);
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+An alert rule "{}" has fired on the stream "{}".
+There were {} messages at the level {} or above within the last {} minutes.
+
+Fired at: {} (UTC)
+
+To see the messages, just follow the link below
+
+{}
+
+You will not be notified again by this rule for {} minutes.
+
+--
+Eloquentlog
+{}
+"#,
+            rule.name,
+            stream.name,
+            alert.messages_count,
+            rule.level,
+            rule.window_minutes,
+            alert.fired_at.format("%Y-%m-%d %H:%M:%S"),
+            stream_url,
+            rule.cooldown_minutes,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds a warning message about the daily quota of the namespace and
+    /// send it via actual mailer.
+    pub fn send_quota_warning_email(
+        &mut self,
+        namespace: &Namespace,
+        usage: &NamespaceUsage,
+        quota: i64,
+        percent: i16,
+    ) -> bool {
+        let url = self.config.application_url.to_string();
+        let namespace_url = format!("{}/namespace/{}", url, namespace.uuid);
+
+        let subject = format!(
+            "[Quota] {} has used {}% of its daily quota",
+            namespace.name, percent
+        );
+        let notice = if percent >= 100 {
+            "Further messages will be rejected (or sampled) until the end of \
+             the day."
+        } else {
+            "Messages over the quota will be rejected (or sampled) until the \
+             end of the day."
+        };
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+The namespace "{}" has used {}% of its daily ingestion quota.
+
+Date: {} (UTC)
+Messages: {} / {}
+
+{}
+
+To see the usage, just follow the link below
+
+{}
+
+--
+Eloquentlog
+{}
+"#,
+            namespace.name,
+            percent,
+            usage.date.format("%Y-%m-%d"),
+            usage.messages_count,
+            quota,
+            notice,
+            namespace_url,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
+
+    /// Builds an invitation message to the namespace and send it via actual
+    /// mailer.
+    pub fn send_invitation_email(
+        &mut self,
+        namespace: &Namespace,
+        inviter: &User,
+        t: &str,
+    ) -> bool {
+        let url = self.config.application_url.to_string();
+        // TODO: build it with rocket::http::uri::Origin?
+        let acceptance_url = format!("{}/invitation/accept?t={}", url, t);
+
+        let subject =
+            format!("{} invited you to {}", inviter.username, namespace.name);
+        // TODO: use template file
+        let message = format!(
+            r#"
+Hi,
+
+{} has invited you to join the namespace "{}" on Eloquentlog.
+To accept the invitation, just follow the link below
+
+{}
+
+If you don't have an account yet, you can accept it after signing up.
+The link will expire in {} days.
+
+Happy logging !-)
+
+--
+Eloquentlog
+{}
+"#,
+            inviter.username,
+            namespace.name,
+            acceptance_url,
+            Config::INVITATION_TOKEN_DURATION,
+            url,
+        );
+        let email = Email::builder()
+            .to(self.header.to)
+            .from(self.header.from)
+            .subject(subject)
+            .text(message)
+            .build()
+            .unwrap();
+        self.mailer.send(email.into())
+    }
 }
@@ -282,6 +282,35 @@ impl AccessToken {
         }
     }
 
+    /// Revokes all the personal access tokens of the user at once, and
+    /// returns the number of revoked ones.
+    pub fn revoke_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let now = Utc::now().naive_utc();
+        let q = diesel::update(
+            access_tokens::table
+                .filter(Self::with_user(user))
+                .filter(Self::with_type(AgentType::Person))
+                .filter(Self::visible()),
+        )
+        .set((
+            access_tokens::state.eq(AccessTokenState::Disabled),
+            access_tokens::token.eq(None::<Vec<u8>>),
+            access_tokens::revoked_at.eq(now),
+            access_tokens::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to revoke access tokens"
+        })
+    }
+
     pub fn visible() -> Visible {
         access_tokens::revoked_at.is_null()
     }
@@ -504,4 +533,44 @@ mod test {
             assert_eq!(result.state, AccessTokenState::Disabled);
         })
     }
+
+    #[test]
+    fn test_revoke_all_by_user() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for agent_type in &[AgentType::Person, AgentType::Client] {
+                let _ = diesel::insert_into(access_tokens::table)
+                    .values((
+                        access_tokens::uuid.eq(Uuid::new_v4()),
+                        access_tokens::agent_id.eq(user.id),
+                        access_tokens::agent_type.eq(agent_type),
+                        access_tokens::name.eq("name"),
+                        access_tokens::token.eq(Some(b"token".to_vec())),
+                        access_tokens::state.eq(AccessTokenState::Enabled),
+                    ))
+                    .execute(conn)
+                    .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+            }
+
+            let result = AccessToken::revoke_all_by_user(&user, conn, logger);
+            assert_eq!(result, Ok(1));
+
+            let result = AccessToken::by_user(&user)
+                .filter(AccessToken::with_type(AgentType::Person))
+                .first::<AccessToken>(conn)
+                .expect("Failed to get a record");
+            assert!(result.token.is_none());
+            assert!(result.revoked_at.is_some());
+            assert_eq!(result.state, AccessTokenState::Disabled);
+
+            // already revoked
+            let result = AccessToken::revoke_all_by_user(&user, conn, logger);
+            assert_eq!(result, Ok(0));
+        })
+    }
 }
@@ -0,0 +1,332 @@
+//! # Alert
+//!
+//! Alert is a record of an AlertRule which has fired. It stays `firing`
+//! until the rule gets back under its threshold, then it will be `resolved`.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::model::alert_state::*;
+pub use crate::schema::alerts;
+
+use crate::logger::Logger;
+use crate::model::alert_rule::AlertRule;
+
+/// NewAlert
+#[derive(Debug)]
+pub struct NewAlert {
+    pub alert_rule_id: i64,
+    pub messages_count: i64,
+    pub fired_at: NaiveDateTime,
+}
+
+type AllColumns = (
+    alerts::id,
+    alerts::uuid,
+    alerts::alert_rule_id,
+    alerts::state,
+    alerts::messages_count,
+    alerts::fired_at,
+    alerts::resolved_at,
+    alerts::created_at,
+    alerts::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    alerts::id,
+    alerts::uuid,
+    alerts::alert_rule_id,
+    alerts::state,
+    alerts::messages_count,
+    alerts::fired_at,
+    alerts::resolved_at,
+    alerts::created_at,
+    alerts::updated_at,
+);
+
+/// Alert
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "alerts"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct Alert {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub alert_rule_id: i64,
+    pub state: AlertState,
+    pub messages_count: i64,
+    pub fired_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Alert {state}>", state = &self.state)
+    }
+}
+
+type All = dsl::Select<alerts::table, AllColumns>;
+type WithRule = dsl::Eq<alerts::alert_rule_id, i64>;
+type ByRule = dsl::Filter<All, WithRule>;
+
+impl Alert {
+    pub fn all() -> All {
+        alerts::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_rule(rule: &AlertRule) -> ByRule {
+        Self::all().filter(Self::with_rule(rule))
+    }
+
+    pub fn fetch_by_rule(
+        rule: &AlertRule,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if limit < 1 {
+            return None;
+        }
+
+        let q = Self::by_rule(rule)
+            .order(alerts::fired_at.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(alerts::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns the most recently fired alert for the rule.
+    pub fn latest_by_rule(
+        rule: &AlertRule,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_rule(rule)
+            .order(alerts::fired_at.desc())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(diesel::result::Error::NotFound) => None,
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn insert(
+        alert: &NewAlert,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let q = diesel::insert_into(alerts::table).values((
+            alerts::uuid.eq(uuid),
+            alerts::alert_rule_id.eq(alert.alert_rule_id),
+            alerts::state.eq(AlertState::Firing),
+            alerts::messages_count.eq(alert.messages_count),
+            alerts::fired_at.eq(alert.fired_at),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(a) => Some(a),
+        }
+    }
+
+    pub fn is_firing(&self) -> bool {
+        self.state == AlertState::Firing
+    }
+
+    pub fn resolve(
+        &self,
+        resolved_at: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            alerts::state.eq(AlertState::Resolved),
+            alerts::resolved_at.eq(Some(resolved_at)),
+            alerts::updated_at.eq(resolved_at),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to resolve alert")
+            },
+            Ok(alert) => Ok(alert),
+        }
+    }
+
+    pub fn with_rule(rule: &AlertRule) -> WithRule {
+        alerts::alert_rule_id.eq(rule.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{Duration, Utc};
+
+    use crate::model::alert_rule::alert_rules;
+    use crate::model::alert_rule::data::ALERT_RULES;
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::{Stream, streams};
+    use crate::model::stream::data::STREAMS;
+    use crate::model::test::run;
+
+    fn load_rule(conn: &PgConnection) -> AlertRule {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace.id;
+        let stream = diesel::insert_into(streams::table)
+            .values(&s)
+            .get_result::<Stream>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let mut r = ALERT_RULES.get("too many errors").unwrap().clone();
+        r.stream_id = stream.id;
+        diesel::insert_into(alert_rules::table)
+            .values(&r)
+            .get_result::<AlertRule>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_latest_by_rule() {
+        run(|conn, _, logger| {
+            let rule = load_rule(conn);
+            assert_eq!(Alert::latest_by_rule(&rule, conn, logger), None);
+
+            let now = Utc::now().naive_utc();
+            let _ = Alert::insert(
+                &NewAlert {
+                    alert_rule_id: rule.id,
+                    messages_count: 3,
+                    fired_at: now - Duration::hours(1),
+                },
+                conn,
+                logger,
+            )
+            .unwrap();
+            let alert = Alert::insert(
+                &NewAlert {
+                    alert_rule_id: rule.id,
+                    messages_count: 5,
+                    fired_at: now,
+                },
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            let result = Alert::latest_by_rule(&rule, conn, logger);
+            assert_eq!(result, Some(alert));
+        })
+    }
+
+    #[test]
+    fn test_resolve() {
+        run(|conn, _, logger| {
+            let rule = load_rule(conn);
+
+            let now = Utc::now().naive_utc();
+            let alert = Alert::insert(
+                &NewAlert {
+                    alert_rule_id: rule.id,
+                    messages_count: 3,
+                    fired_at: now,
+                },
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert!(alert.is_firing());
+
+            let result = alert.resolve(now, conn, logger);
+            assert!(result.is_ok());
+
+            let alert = result.unwrap();
+            assert_eq!(alert.state, AlertState::Resolved);
+            assert!(alert.resolved_at.is_some());
+        })
+    }
+}
@@ -0,0 +1,436 @@
+//! # AlertRule
+//!
+//! AlertRule describes a threshold on a stream. It fires an alert if the
+//! stream receives messages at the level (or above) more than `threshold`
+//! within the last `window_minutes`. Once fired, the rule never fires again
+//! within `cooldown_minutes`.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::model::log_level::*;
+pub use crate::schema::alert_rules;
+
+use crate::logger::Logger;
+use crate::model::stream::{Stream, streams};
+use crate::request::alert_rule::AlertRule as RequestData;
+
+/// NewAlertRule
+#[derive(Debug)]
+pub struct NewAlertRule {
+    pub stream_id: i64,
+    pub name: String,
+    pub level: LogLevel,
+    pub threshold: i32,
+    pub window_minutes: i32,
+    pub cooldown_minutes: i32,
+}
+
+impl fmt::Display for NewAlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewAlertRule {name}>", name = &self.name)
+    }
+}
+
+impl Default for NewAlertRule {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            stream_id: -1,
+            name: "".to_string(),
+            level: LogLevel::Error,
+            threshold: 1,
+            window_minutes: 5,
+            cooldown_minutes: 30,
+        }
+    }
+}
+
+impl From<RequestData> for NewAlertRule {
+    fn from(data: RequestData) -> Self {
+        let d = Self::default();
+        Self {
+            name: data.name.unwrap_or_else(|| "".to_string()),
+            level: data
+                .level
+                .map(LogLevel::from)
+                .unwrap_or_else(|| d.level.clone()),
+            threshold: data.threshold.unwrap_or(d.threshold),
+            window_minutes: data.window_minutes.unwrap_or(d.window_minutes),
+            cooldown_minutes: data
+                .cooldown_minutes
+                .unwrap_or(d.cooldown_minutes),
+
+            ..d
+        }
+    }
+}
+
+type AllColumns = (
+    alert_rules::id,
+    alert_rules::uuid,
+    alert_rules::stream_id,
+    alert_rules::name,
+    alert_rules::level,
+    alert_rules::threshold,
+    alert_rules::window_minutes,
+    alert_rules::cooldown_minutes,
+    alert_rules::created_at,
+    alert_rules::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    alert_rules::id,
+    alert_rules::uuid,
+    alert_rules::stream_id,
+    alert_rules::name,
+    alert_rules::level,
+    alert_rules::threshold,
+    alert_rules::window_minutes,
+    alert_rules::cooldown_minutes,
+    alert_rules::created_at,
+    alert_rules::updated_at,
+);
+
+/// AlertRule
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "alert_rules"]
+pub struct AlertRule {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub stream_id: i64,
+    pub name: String,
+    pub level: LogLevel,
+    pub threshold: i32,
+    pub window_minutes: i32,
+    pub cooldown_minutes: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<AlertRule {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+type All = dsl::Select<alert_rules::table, AllColumns>;
+type WithStream = dsl::Eq<alert_rules::stream_id, i64>;
+type WithUuid = dsl::Eq<alert_rules::uuid, Uuid>;
+type ByStream = dsl::Filter<All, WithStream>;
+
+impl AlertRule {
+    pub fn all() -> All {
+        alert_rules::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_stream(stream: &Stream) -> ByStream {
+        Self::all().filter(Self::with_stream(stream))
+    }
+
+    /// Returns all the rules on streams which are not archived.
+    pub fn find_all_in_visible_streams(
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::all()
+            .inner_join(streams::table)
+            .filter(Stream::visible())
+            .order(alert_rules::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_all_by_stream(
+        stream: &Stream,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_stream(stream).order(alert_rules::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(alert_rules::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_uuid_in(
+        stream: &Stream,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_stream(stream)
+            .filter(Self::with_uuid(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn insert(
+        rule: &NewAlertRule,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let q = diesel::insert_into(alert_rules::table).values((
+            alert_rules::uuid.eq(uuid),
+            alert_rules::stream_id.eq(rule.stream_id),
+            alert_rules::name.eq(&rule.name),
+            alert_rules::level.eq(&rule.level),
+            alert_rules::threshold.eq(rule.threshold),
+            alert_rules::window_minutes.eq(rule.window_minutes),
+            alert_rules::cooldown_minutes.eq(rule.cooldown_minutes),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(r) => Some(r),
+        }
+    }
+
+    /// Deletes the rule together with its alerts.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        use crate::model::alert::alerts;
+
+        let q = diesel::delete(
+            alerts::table.filter(alerts::alert_rule_id.eq(self.id)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        if let Err(e) = q.execute(conn) {
+            error!(logger, "err: {}", e);
+            return Err("failed to delete alerts");
+        }
+
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete alert rule")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn with_stream(stream: &Stream) -> WithStream {
+        alert_rules::stream_id.eq(stream.id)
+    }
+
+    pub fn with_uuid(s: &str) -> WithUuid {
+        let uuid = Uuid::parse_str(s).unwrap_or_else(|_| Uuid::nil());
+        alert_rules::uuid.eq(uuid)
+    }
+}
+
+#[cfg(test)]
+pub mod data {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+    use fnv::FnvHashMap;
+
+    use crate::fnvhashmap;
+    use crate::model::stream::data::STREAMS;
+
+    type AlertRuleFixture = FnvHashMap<&'static str, AlertRule>;
+
+    lazy_static! {
+        pub static ref ALERT_RULES: AlertRuleFixture = fnvhashmap! {
+            "too many errors" => AlertRule {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                stream_id: STREAMS.get("oswald's stream").unwrap().id,
+                name: "too many errors".to_string(),
+                level: LogLevel::Error,
+                threshold: 3,
+                window_minutes: 5,
+                cooldown_minutes: 30,
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::alert_rule::data::ALERT_RULES;
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+    use crate::model::test::run;
+
+    #[test]
+    fn test_new_alert_rule_default() {
+        let r = NewAlertRule {
+            ..Default::default()
+        };
+
+        assert_eq!(r.stream_id, -1);
+        assert_eq!(r.name, "".to_string());
+        assert_eq!(r.level, LogLevel::Error);
+        assert_eq!(r.threshold, 1);
+        assert_eq!(r.window_minutes, 5);
+        assert_eq!(r.cooldown_minutes, 30);
+    }
+
+    #[test]
+    fn test_alert_rule_format() {
+        let r = ALERT_RULES.get("too many errors").unwrap();
+        assert_eq!(format!("{}", r), format!("<AlertRule {}>", r.uuid));
+    }
+
+    #[test]
+    fn test_insert() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(&s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let r = NewAlertRule {
+                stream_id: stream.id,
+                name: "errors".to_string(),
+                level: LogLevel::Warning,
+
+                ..Default::default()
+            };
+            let result = AlertRule::insert(&r, conn, logger);
+            assert!(result.is_some());
+
+            let rule = result.unwrap();
+            assert_eq!(rule.stream_id, stream.id);
+            assert_eq!(rule.level, LogLevel::Warning);
+
+            let result = AlertRule::find_by_uuid_in(
+                &stream,
+                &rule.uuid.to_string(),
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(rule));
+        })
+    }
+
+    #[test]
+    fn test_find_all_in_visible_streams() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(&s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut r = ALERT_RULES.get("too many errors").unwrap().clone();
+            r.stream_id = stream.id;
+            let rule = diesel::insert_into(alert_rules::table)
+                .values(&r)
+                .get_result::<AlertRule>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = AlertRule::find_all_in_visible_streams(conn, logger);
+            assert_eq!(result, Some(vec![rule]));
+        })
+    }
+}
@@ -0,0 +1,105 @@
+//! # A type AlertState for Alert in alert.rs
+//!
+//! EAlertState represents SQL type value `e_alert_state` and AlertState is an
+//! Enum holds all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_alert_state")]
+pub struct EAlertState;
+
+#[derive(AsExpression, Clone, Debug, FromSqlRow, PartialEq, Serialize)]
+#[sql_type = "EAlertState"]
+pub enum AlertState {
+    Firing, // default
+    Resolved,
+}
+
+const ALERT_STATES: [AlertState; 2] = [AlertState::Firing, AlertState::Resolved];
+
+impl fmt::Display for AlertState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Firing => write!(f, "firing"),
+            Self::Resolved => write!(f, "resolved"),
+        }
+    }
+}
+
+impl ToSql<EAlertState, Pg> for AlertState {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            Self::Firing => out.write_all(b"firing")?,
+            Self::Resolved => out.write_all(b"resolved")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EAlertState, Pg> for AlertState {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"firing" => Ok(Self::Firing),
+            b"resolved" => Ok(Self::Resolved),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl From<String> for AlertState {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "firing" => Self::Firing,
+            "resolved" => Self::Resolved,
+            _ => Self::Firing,
+        }
+    }
+}
+
+impl AlertState {
+    pub fn iter() -> Iter<'static, AlertState> {
+        ALERT_STATES.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(AlertState::Firing, AlertState::from("firing".to_string()));
+        assert_eq!(
+            AlertState::Resolved,
+            AlertState::from("resolved".to_string())
+        );
+
+        // default
+        assert_eq!(AlertState::Firing, AlertState::from("unknown".to_string()));
+    }
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("firing", format!("{}", AlertState::Firing));
+        assert_eq!("resolved", format!("{}", AlertState::Resolved));
+    }
+
+    #[test]
+    fn test_as_vec() {
+        assert_eq!(
+            vec![AlertState::Firing, AlertState::Resolved],
+            AlertState::as_vec()
+        )
+    }
+}
@@ -0,0 +1,136 @@
+//! # A type AuditAction for AuditEvent in audit_event.rs
+//!
+//! AuditAction is an Enum holds all the actions recorded in the audit log.
+//! It's stored as text in `audit_events.action`.
+use std::fmt;
+use std::slice::Iter;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuditAction {
+    AccessTokenDump,
+    AccessTokenRevoke,
+    AccessTokenStateChange,
+    MembershipRevoke,
+    MembershipRoleChange,
+    MembershipTransfer,
+    NamespaceCreate,
+    NamespaceUpdate,
+    PasswordChange,
+    PasswordReset,
+    StreamArchive,
+    StreamGrantChange,
+    StreamGrantRevoke,
+    TwoFactorDisable,
+    TwoFactorEnable,
+    TwoFactorRecoveryCodesRenew,
+    UserActivate,
+    UserDeregister,
+    UserEmailChange,
+    UserEmailPromote,
+}
+
+const AUDIT_ACTIONS: [AuditAction; 20] = [
+    AuditAction::AccessTokenDump,
+    AuditAction::AccessTokenRevoke,
+    AuditAction::AccessTokenStateChange,
+    AuditAction::MembershipRevoke,
+    AuditAction::MembershipRoleChange,
+    AuditAction::MembershipTransfer,
+    AuditAction::NamespaceCreate,
+    AuditAction::NamespaceUpdate,
+    AuditAction::PasswordChange,
+    AuditAction::PasswordReset,
+    AuditAction::StreamArchive,
+    AuditAction::StreamGrantChange,
+    AuditAction::StreamGrantRevoke,
+    AuditAction::TwoFactorDisable,
+    AuditAction::TwoFactorEnable,
+    AuditAction::TwoFactorRecoveryCodesRenew,
+    AuditAction::UserActivate,
+    AuditAction::UserDeregister,
+    AuditAction::UserEmailChange,
+    AuditAction::UserEmailPromote,
+];
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::AccessTokenDump => write!(f, "access_token.dump"),
+            Self::AccessTokenRevoke => write!(f, "access_token.revoke"),
+            Self::AccessTokenStateChange => {
+                write!(f, "access_token.state_change")
+            },
+            Self::MembershipRevoke => write!(f, "membership.revoke"),
+            Self::MembershipRoleChange => write!(f, "membership.role_change"),
+            Self::MembershipTransfer => write!(f, "membership.transfer"),
+            Self::NamespaceCreate => write!(f, "namespace.create"),
+            Self::NamespaceUpdate => write!(f, "namespace.update"),
+            Self::PasswordChange => write!(f, "password.change"),
+            Self::PasswordReset => write!(f, "password.reset"),
+            Self::StreamArchive => write!(f, "stream.archive"),
+            Self::StreamGrantChange => write!(f, "stream_grant.change"),
+            Self::StreamGrantRevoke => write!(f, "stream_grant.revoke"),
+            Self::TwoFactorDisable => write!(f, "two_factor.disable"),
+            Self::TwoFactorEnable => write!(f, "two_factor.enable"),
+            Self::TwoFactorRecoveryCodesRenew => {
+                write!(f, "two_factor.recovery_codes_renew")
+            },
+            Self::UserActivate => write!(f, "user.activate"),
+            Self::UserDeregister => write!(f, "user.deregister"),
+            Self::UserEmailChange => write!(f, "user_email.change"),
+            Self::UserEmailPromote => write!(f, "user_email.promote"),
+        }
+    }
+}
+
+impl AuditAction {
+    pub fn iter() -> Iter<'static, AuditAction> {
+        AUDIT_ACTIONS.iter()
+    }
+
+    /// Returns the action for the name if it's known.
+    pub fn find(s: &str) -> Option<Self> {
+        Self::iter().find(|a| a.to_string() == s).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!(
+            "access_token.revoke",
+            format!("{}", AuditAction::AccessTokenRevoke)
+        );
+        assert_eq!(
+            "namespace.create",
+            format!("{}", AuditAction::NamespaceCreate)
+        );
+        assert_eq!("password.reset", format!("{}", AuditAction::PasswordReset));
+        assert_eq!(
+            "stream_grant.change",
+            format!("{}", AuditAction::StreamGrantChange)
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(
+            Some(AuditAction::StreamArchive),
+            AuditAction::find("stream.archive")
+        );
+        assert_eq!(
+            Some(AuditAction::UserActivate),
+            AuditAction::find("user.activate")
+        );
+        assert_eq!(
+            Some(AuditAction::MembershipRoleChange),
+            AuditAction::find("membership.role_change")
+        );
+
+        assert_eq!(None, AuditAction::find("Stream.Archive"));
+        assert_eq!(None, AuditAction::find("unknown"));
+    }
+}
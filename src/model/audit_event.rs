@@ -0,0 +1,279 @@
+//! # AuditEvent
+//!
+//! AuditEvent is an append-only record of a security- or configuration-
+//! relevant action. It keeps who did what to which target, from where, and
+//! the changes as a JSON object like `{"field": [old, new]}`.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+pub use crate::model::audit_action::*;
+pub use crate::schema::audit_events;
+
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+use crate::model::user::{User, users};
+use crate::request::client::ClientInfo;
+
+/// NewAuditEvent
+#[derive(Debug)]
+pub struct NewAuditEvent {
+    pub actor_id: Option<i64>,
+    pub action: AuditAction,
+    pub target: Option<String>,
+    pub namespace_id: Option<i64>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub diff: Value,
+}
+
+impl fmt::Display for NewAuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewAuditEvent {action}>", action = &self.action)
+    }
+}
+
+impl NewAuditEvent {
+    pub fn new(
+        action: AuditAction,
+        actor: Option<&User>,
+        client: &ClientInfo,
+    ) -> Self {
+        Self {
+            actor_id: actor.map(|u| u.id),
+            action,
+            target: None,
+            namespace_id: None,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            diff: json!({}),
+        }
+    }
+}
+
+/// AuditEvent
+#[derive(Clone, Debug, Identifiable, Queryable, Serialize)]
+#[table_name = "audit_events"]
+pub struct AuditEvent {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub actor_id: Option<i64>,
+    pub action: String,
+    pub target: Option<String>,
+    #[serde(skip)]
+    pub namespace_id: Option<i64>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    #[serde(with = "text_as_json")]
+    pub diff: String,
+    pub created_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+mod text_as_json {
+    use serde::{Serialize, Serializer};
+    use serde_json::Value;
+
+    pub fn serialize<S>(val: &str, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        serde_json::from_str::<Value>(val)
+            .unwrap_or(Value::Null)
+            .serialize(serializer)
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<AuditEvent {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+/// Conditions to narrow audit events down.
+#[derive(Debug, Default)]
+pub struct AuditEventFilter {
+    pub action: Option<AuditAction>,
+    pub actor_id: Option<i64>,
+}
+
+impl AuditEvent {
+    pub fn insert(
+        event: &NewAuditEvent,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let q = diesel::insert_into(audit_events::table).values((
+            audit_events::uuid.eq(uuid),
+            audit_events::actor_id.eq(event.actor_id),
+            audit_events::action.eq(event.action.to_string()),
+            audit_events::target.eq(&event.target),
+            audit_events::namespace_id.eq(event.namespace_id),
+            audit_events::ip_address.eq(&event.ip_address),
+            audit_events::user_agent.eq(&event.user_agent),
+            audit_events::diff.eq(event.diff.to_string()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(e) => Some(e),
+        }
+    }
+
+    /// Returns events in the namespace with their actors (if they still
+    /// exist), the newest first.
+    pub fn fetch_by_namespace(
+        namespace: &Namespace,
+        filter: &AuditEventFilter,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, Option<User>)>> {
+        if limit < 1 {
+            return None;
+        }
+
+        let mut q = audit_events::table
+            .left_join(users::table)
+            .filter(audit_events::namespace_id.eq(namespace.id))
+            .into_boxed();
+        if let Some(ref action) = filter.action {
+            q = q.filter(audit_events::action.eq(action.to_string()));
+        }
+        if let Some(actor_id) = filter.actor_id {
+            q = q.filter(audit_events::actor_id.eq(actor_id));
+        }
+        let q = q
+            .order((audit_events::created_at.desc(), audit_events::id.desc()))
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, Option<User>)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_new_audit_event() {
+        let client = ClientInfo {
+            ip_address: Some("127.0.0.1".to_string()),
+            user_agent: None,
+        };
+        let e = NewAuditEvent::new(AuditAction::PasswordReset, None, &client);
+
+        assert_eq!(e.actor_id, None);
+        assert_eq!(e.action, AuditAction::PasswordReset);
+        assert_eq!(e.ip_address, Some("127.0.0.1".to_string()));
+        assert_eq!(e.diff, json!({}));
+    }
+
+    #[test]
+    fn test_insert_and_fetch_by_namespace() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let client = ClientInfo::default();
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::NamespaceCreate,
+                Some(&user),
+                &client,
+            );
+            e.namespace_id = Some(namespace.id);
+            e.target = Some(namespace.uuid.to_string());
+            e.diff = json!({"name": [null, "piano"]});
+            let created = AuditEvent::insert(&e, conn, logger).unwrap();
+            assert_eq!(created.action, "namespace.create");
+            assert_eq!(created.diff, r#"{"name":[null,"piano"]}"#);
+
+            let mut e =
+                NewAuditEvent::new(AuditAction::StreamArchive, None, &client);
+            e.namespace_id = Some(namespace.id);
+            let archived = AuditEvent::insert(&e, conn, logger).unwrap();
+
+            // not in the namespace
+            let e =
+                NewAuditEvent::new(AuditAction::PasswordReset, None, &client);
+            let _ = AuditEvent::insert(&e, conn, logger).unwrap();
+
+            let filter = AuditEventFilter::default();
+            let result = AuditEvent::fetch_by_namespace(
+                &namespace, &filter, 0, 10, conn, logger,
+            )
+            .unwrap();
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0].0.id, archived.id);
+            assert!(result[0].1.is_none());
+            assert_eq!(result[1].0.id, created.id);
+            assert_eq!(result[1].1.as_ref().map(|u| u.id), Some(user.id));
+
+            let filter = AuditEventFilter {
+                action: Some(AuditAction::NamespaceCreate),
+                ..Default::default()
+            };
+            let result = AuditEvent::fetch_by_namespace(
+                &namespace, &filter, 0, 10, conn, logger,
+            )
+            .unwrap();
+            assert_eq!(result.len(), 1);
+
+            let filter = AuditEventFilter {
+                actor_id: Some(user.id),
+                ..Default::default()
+            };
+            let result = AuditEvent::fetch_by_namespace(
+                &namespace, &filter, 1, 10, conn, logger,
+            )
+            .unwrap();
+            assert!(result.is_empty());
+        })
+    }
+}
@@ -0,0 +1,187 @@
+//! Digest summarizes activities of a namespace for a period.
+//!
+//! It's built on demand from messages and it's not persisted.
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use serde::Serialize;
+
+use crate::logger::Logger;
+use crate::model::digest_subscription::DigestFrequency;
+use crate::model::message::{LogLevel, Message};
+use crate::model::namespace::Namespace;
+
+const RANKING_LIMIT: i64 = 5;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Digest {
+    pub frequency: DigestFrequency,
+
+    // the period [since, until)
+    pub since: NaiveDateTime,
+    pub until: NaiveDateTime,
+
+    pub counts: Vec<(LogLevel, i64)>,
+    pub new_issues: Vec<(String, i64)>,
+    pub noisy_streams: Vec<(String, i64)>,
+    pub top_codes: Vec<(String, i64)>,
+}
+
+impl Digest {
+    /// Builds a digest of the namespace for the period ending at `until`.
+    ///
+    /// New issues are the titles in error or critical level which appear
+    /// for the first time in the period.
+    pub fn build(
+        namespace: &Namespace,
+        frequency: DigestFrequency,
+        until: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let since = until - frequency.period();
+        let id = namespace.id;
+
+        let counts =
+            Message::count_by_level_in(id, since, until, conn, logger)?;
+
+        let titles = Message::rank_titles_in(
+            id,
+            vec![LogLevel::Error, LogLevel::Critical],
+            since,
+            until,
+            conn,
+            logger,
+        )?;
+        let known = Message::find_titles_before(
+            id,
+            titles.iter().map(|(t, _)| t.clone()).collect(),
+            since,
+            conn,
+            logger,
+        )?;
+        let new_issues = titles
+            .into_iter()
+            .filter(|(t, _)| !known.contains(t))
+            .take(RANKING_LIMIT as usize)
+            .collect();
+
+        let noisy_streams = Message::rank_streams_in(
+            id,
+            since,
+            until,
+            RANKING_LIMIT,
+            conn,
+            logger,
+        )?;
+        let top_codes = Message::rank_codes_in(
+            id,
+            since,
+            until,
+            RANKING_LIMIT,
+            conn,
+            logger,
+        )?;
+
+        Some(Self {
+            frequency,
+            since,
+            until,
+            counts,
+            new_issues,
+            noisy_streams,
+            top_codes,
+        })
+    }
+
+    /// Returns the total number of messages in the period.
+    pub fn total(&self) -> i64 {
+        self.counts.iter().map(|(_, n)| n).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+
+    use crate::model::message::{AgentType, LogFormat, messages};
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::{Stream, streams};
+    use crate::model::stream::data::STREAMS;
+    use crate::model::test::run;
+
+    #[test]
+    fn test_build() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(&s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let until = Utc::now().naive_utc();
+            let rows = vec![
+                // seen before the period
+                ("known", None, LogLevel::Error, Duration::days(2)),
+                ("known", Some("E1"), LogLevel::Error, Duration::hours(3)),
+                ("boom", Some("E1"), LogLevel::Critical, Duration::hours(2)),
+                ("boom", Some("E2"), LogLevel::Critical, Duration::hours(1)),
+                ("hello", None, LogLevel::Information, Duration::hours(1)),
+            ];
+            for (title, code, level, ago) in rows {
+                diesel::insert_into(messages::table)
+                    .values((
+                        messages::agent_id.eq(1),
+                        messages::agent_type.eq(AgentType::Person),
+                        messages::stream_id.eq(stream.id),
+                        messages::code.eq(code),
+                        messages::level.eq(level),
+                        messages::format.eq(LogFormat::TOML),
+                        messages::title.eq(title),
+                        messages::created_at.eq(until - ago),
+                    ))
+                    .execute(conn)
+                    .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+            }
+
+            let digest = Digest::build(
+                &namespace,
+                DigestFrequency::Daily,
+                until,
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            assert_eq!(digest.since, until - Duration::days(1));
+            assert_eq!(digest.total(), 4);
+            assert_eq!(
+                digest.counts,
+                vec![
+                    (LogLevel::Information, 1),
+                    (LogLevel::Error, 1),
+                    (LogLevel::Critical, 2),
+                ]
+            );
+            assert_eq!(digest.new_issues, vec![("boom".to_string(), 2)]);
+            assert_eq!(
+                digest.noisy_streams,
+                vec![("oswald's stream".to_string(), 4)]
+            );
+            assert_eq!(
+                digest.top_codes,
+                vec![("E1".to_string(), 2), ("E2".to_string(), 1)]
+            );
+        })
+    }
+}
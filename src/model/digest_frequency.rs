@@ -0,0 +1,127 @@
+//! # A type DigestFrequency for DigestSubscription in digest_subscription.rs
+//!
+//! EDigestFrequency represents SQL type value `e_digest_frequency` and
+//! DigestFrequency is an Enum holds all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use chrono::Duration;
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_digest_frequency")]
+pub struct EDigestFrequency;
+
+#[derive(AsExpression, Clone, Debug, FromSqlRow, PartialEq, Serialize)]
+#[sql_type = "EDigestFrequency"]
+pub enum DigestFrequency {
+    Daily,
+    Weekly, // default
+}
+
+const DIGEST_FREQUENCIES: [DigestFrequency; 2] =
+    [DigestFrequency::Daily, DigestFrequency::Weekly];
+
+impl fmt::Display for DigestFrequency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Daily => write!(f, "daily"),
+            Self::Weekly => write!(f, "weekly"),
+        }
+    }
+}
+
+impl ToSql<EDigestFrequency, Pg> for DigestFrequency {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            Self::Daily => out.write_all(b"daily")?,
+            Self::Weekly => out.write_all(b"weekly")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EDigestFrequency, Pg> for DigestFrequency {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"daily" => Ok(Self::Daily),
+            b"weekly" => Ok(Self::Weekly),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl From<String> for DigestFrequency {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "daily" => Self::Daily,
+            "weekly" => Self::Weekly,
+            _ => Self::Weekly,
+        }
+    }
+}
+
+impl DigestFrequency {
+    pub fn iter() -> Iter<'static, DigestFrequency> {
+        DIGEST_FREQUENCIES.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+
+    /// Returns the length of the period which a digest summarizes.
+    pub fn period(&self) -> Duration {
+        match *self {
+            Self::Daily => Duration::days(1),
+            Self::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(
+            DigestFrequency::Daily,
+            DigestFrequency::from("daily".to_string())
+        );
+        assert_eq!(
+            DigestFrequency::Weekly,
+            DigestFrequency::from("weekly".to_string())
+        );
+
+        // default
+        assert_eq!(
+            DigestFrequency::Weekly,
+            DigestFrequency::from("unknown".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("daily", format!("{}", DigestFrequency::Daily));
+        assert_eq!("weekly", format!("{}", DigestFrequency::Weekly));
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(Duration::hours(24), DigestFrequency::Daily.period());
+        assert_eq!(Duration::days(7), DigestFrequency::Weekly.period());
+    }
+
+    #[test]
+    fn test_as_vec() {
+        assert_eq!(
+            vec![DigestFrequency::Daily, DigestFrequency::Weekly],
+            DigestFrequency::as_vec()
+        )
+    }
+}
@@ -0,0 +1,413 @@
+//! # DigestSubscription
+//!
+//! DigestSubscription is an opt-in of a user for the digest email of a
+//! namespace. A user has at most one subscription per namespace.
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+
+pub use crate::model::digest_frequency::*;
+pub use crate::schema::digest_subscriptions;
+
+use crate::logger::Logger;
+use crate::model::user::User;
+
+type AllColumns = (
+    digest_subscriptions::id,
+    digest_subscriptions::user_id,
+    digest_subscriptions::namespace_id,
+    digest_subscriptions::frequency,
+    digest_subscriptions::last_sent_at,
+    digest_subscriptions::created_at,
+    digest_subscriptions::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    digest_subscriptions::id,
+    digest_subscriptions::user_id,
+    digest_subscriptions::namespace_id,
+    digest_subscriptions::frequency,
+    digest_subscriptions::last_sent_at,
+    digest_subscriptions::created_at,
+    digest_subscriptions::updated_at,
+);
+
+/// DigestSubscription
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "digest_subscriptions"]
+pub struct DigestSubscription {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(skip)]
+    pub user_id: i64,
+    #[serde(skip)]
+    pub namespace_id: i64,
+    pub frequency: DigestFrequency,
+    pub last_sent_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for DigestSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<DigestSubscription {frequency}>",
+            frequency = &self.frequency
+        )
+    }
+}
+
+type All = dsl::Select<digest_subscriptions::table, AllColumns>;
+type WithUser = dsl::Eq<digest_subscriptions::user_id, i64>;
+type ByUser = dsl::Filter<All, WithUser>;
+
+impl DigestSubscription {
+    pub fn all() -> All {
+        digest_subscriptions::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_user(user: &User) -> ByUser {
+        Self::all().filter(Self::with_user(user))
+    }
+
+    pub fn find_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_user(user).order(digest_subscriptions::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns subscriptions whose period has passed until the time.
+    pub fn find_all_due(
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let daily = DigestFrequency::Daily;
+        let weekly = DigestFrequency::Weekly;
+        let q = Self::all()
+            .filter(
+                digest_subscriptions::frequency
+                    .eq(&daily)
+                    .and(
+                        digest_subscriptions::last_sent_at
+                            .le(now - daily.period()),
+                    )
+                    .or(digest_subscriptions::frequency.eq(&weekly).and(
+                        digest_subscriptions::last_sent_at
+                            .le(now - weekly.period()),
+                    )),
+            )
+            .order(digest_subscriptions::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(digest_subscriptions::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_user_and_namespace_id(
+        user: &User,
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_user(user)
+            .filter(digest_subscriptions::namespace_id.eq(namespace_id))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Creates a subscription or updates the frequency of the existing one.
+    ///
+    /// The first digest is sent after a period from the subscription.
+    pub fn upsert(
+        user: &User,
+        namespace_id: i64,
+        frequency: DigestFrequency,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(digest_subscriptions::table)
+            .values((
+                digest_subscriptions::user_id.eq(user.id),
+                digest_subscriptions::namespace_id.eq(namespace_id),
+                digest_subscriptions::frequency.eq(&frequency),
+            ))
+            .on_conflict((
+                digest_subscriptions::user_id,
+                digest_subscriptions::namespace_id,
+            ))
+            .do_update()
+            .set((
+                digest_subscriptions::frequency.eq(&frequency),
+                digest_subscriptions::updated_at.eq(Utc::now().naive_utc()),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(s) => Some(s),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete digest subscription")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Marks the digest for the period until the time as sent.
+    pub fn touch(
+        &self,
+        sent_at: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            digest_subscriptions::last_sent_at.eq(sent_at),
+            digest_subscriptions::updated_at.eq(sent_at),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to update digest subscription")
+            },
+            Ok(s) => Ok(s),
+        }
+    }
+
+    pub fn with_user(user: &User) -> WithUser {
+        digest_subscriptions::user_id.eq(user.id)
+    }
+}
+
+#[cfg(test)]
+pub mod data {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+    use fnv::FnvHashMap;
+
+    use crate::fnvhashmap;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::user::data::USERS;
+
+    type DigestFixture = FnvHashMap<&'static str, DigestSubscription>;
+
+    lazy_static! {
+        pub static ref DIGEST_SUBSCRIPTIONS: DigestFixture = fnvhashmap! {
+            "oswald's weekly piano digest" => DigestSubscription {
+                id: 1,
+                user_id: USERS.get("oswald").unwrap().id,
+                namespace_id: NAMESPACES.get("piano").unwrap().id,
+                frequency: DigestFrequency::Weekly,
+                last_sent_at: Utc
+                    .ymd(2019, 7, 7)
+                    .and_hms(7, 20, 15)
+                    .naive_utc(),
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Duration;
+
+    use crate::model::digest_subscription::data::DIGEST_SUBSCRIPTIONS;
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::model::user::users;
+    use crate::model::user::data::USERS;
+
+    fn load_user_and_namespace(conn: &PgConnection) -> (User, Namespace) {
+        let u = USERS.get("oswald").unwrap();
+        let user = diesel::insert_into(users::table)
+            .values(u)
+            .get_result::<User>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        (user, namespace)
+    }
+
+    #[test]
+    fn test_digest_subscription_format() {
+        let s = DIGEST_SUBSCRIPTIONS
+            .get("oswald's weekly piano digest")
+            .unwrap();
+        assert_eq!(format!("{}", s), "<DigestSubscription weekly>");
+    }
+
+    #[test]
+    fn test_upsert() {
+        run(|conn, _, logger| {
+            let (user, namespace) = load_user_and_namespace(conn);
+
+            let result = DigestSubscription::upsert(
+                &user,
+                namespace.id,
+                DigestFrequency::Weekly,
+                conn,
+                logger,
+            );
+            assert!(result.is_some());
+            let subscription = result.unwrap();
+            assert_eq!(subscription.frequency, DigestFrequency::Weekly);
+
+            let result = DigestSubscription::upsert(
+                &user,
+                namespace.id,
+                DigestFrequency::Daily,
+                conn,
+                logger,
+            );
+            assert!(result.is_some());
+            let updated = result.unwrap();
+            assert_eq!(updated.id, subscription.id);
+            assert_eq!(updated.frequency, DigestFrequency::Daily);
+
+            let result =
+                DigestSubscription::find_all_by_user(&user, conn, logger);
+            assert_eq!(result, Some(vec![updated]));
+        })
+    }
+
+    #[test]
+    fn test_find_all_due_and_touch() {
+        run(|conn, _, logger| {
+            let (user, namespace) = load_user_and_namespace(conn);
+
+            let mut s = DIGEST_SUBSCRIPTIONS
+                .get("oswald's weekly piano digest")
+                .unwrap()
+                .clone();
+            s.user_id = user.id;
+            s.namespace_id = namespace.id;
+            s.last_sent_at = Utc::now().naive_utc() - Duration::days(2);
+            let subscription = diesel::insert_into(digest_subscriptions::table)
+                .values(&s)
+                .get_result::<DigestSubscription>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let now = Utc::now().naive_utc();
+
+            // weekly one is not due yet
+            let result = DigestSubscription::find_all_due(now, conn, logger);
+            assert_eq!(result, Some(vec![]));
+
+            let result = DigestSubscription::find_all_due(
+                now + Duration::days(5),
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(vec![subscription.clone()]));
+
+            let subscription = subscription
+                .touch(now + Duration::days(5), conn, logger)
+                .unwrap();
+            let result = DigestSubscription::find_all_due(
+                now + Duration::days(5),
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(vec![]));
+
+            assert!(subscription.delete(conn, logger).is_ok());
+            let result =
+                DigestSubscription::find_all_by_user(&user, conn, logger);
+            assert_eq!(result, Some(vec![]));
+        })
+    }
+}
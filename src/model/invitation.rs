@@ -0,0 +1,580 @@
+//! # Invitation
+//!
+//! Invitation is a pending membership of a namespace sent to an email
+//! address. It has a signed, expiring token (see `VerificationClaims`) and
+//! becomes a membership when someone accepts it with the token.
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::schema::invitations;
+
+use crate::logger::Logger;
+use crate::model::Verifiable;
+use crate::model::membership::{Membership, MembershipRole, NewMembership};
+use crate::model::namespace::Namespace;
+use crate::model::token::{Claims, VerificationClaims};
+use crate::model::user::{User, UserState};
+use crate::model::user_email::UserEmail;
+use crate::request::invitation::Invitation as RequestData;
+use crate::util::generate_random_hash;
+
+const TOKEN_LENGTH: i32 = 128;
+const TOKEN_SOURCE: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// NewInvitation
+#[derive(Debug)]
+pub struct NewInvitation {
+    pub namespace_id: i64,
+    pub inviter_id: i64,
+    pub email: String,
+    pub role: MembershipRole,
+}
+
+impl fmt::Display for NewInvitation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewInvitation {email}>", email = &self.email)
+    }
+}
+
+impl Default for NewInvitation {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            namespace_id: -1,
+            inviter_id: -1,
+            email: "".to_string(),
+            role: MembershipRole::Member,
+        }
+    }
+}
+
+impl From<RequestData> for NewInvitation {
+    fn from(data: RequestData) -> Self {
+        Self {
+            email: data.email.unwrap_or_else(|| "".to_string()),
+            role: data
+                .role
+                .map(MembershipRole::from)
+                .unwrap_or(MembershipRole::Member),
+
+            ..Default::default()
+        }
+    }
+}
+
+/// Invitation
+#[derive(
+    Associations,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[belongs_to(Namespace)]
+#[table_name = "invitations"]
+pub struct Invitation {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub namespace_id: i64,
+    #[serde(skip)]
+    pub inviter_id: i64,
+    pub email: String,
+    pub role: MembershipRole,
+    #[serde(skip)]
+    pub token: Option<String>,
+    #[serde(skip)]
+    pub token_granted_at: Option<NaiveDateTime>,
+    pub token_expires_at: Option<NaiveDateTime>,
+    pub accepted_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+impl fmt::Display for Invitation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Invitation {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+type Pending = dsl::IsNull<invitations::accepted_at>;
+type WithUuid = dsl::Eq<invitations::uuid, Uuid>;
+
+impl Invitation {
+    pub fn generate_token() -> String {
+        generate_random_hash(TOKEN_SOURCE, TOKEN_LENGTH)
+    }
+
+    /// Returns pending invitations in the namespace.
+    pub fn find_all_pending_by_namespace(
+        namespace: &Namespace,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = invitations::table
+            .filter(invitations::namespace_id.eq(namespace.id))
+            .filter(Self::pending())
+            .order(invitations::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = invitations::table.filter(invitations::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns a pending invitation for the email in the namespace.
+    pub fn find_pending_by_email_in(
+        namespace: &Namespace,
+        email: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = invitations::table
+            .filter(invitations::namespace_id.eq(namespace.id))
+            .filter(invitations::email.eq(email))
+            .filter(Self::pending())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns a pending invitation in the namespace.
+    pub fn find_pending_by_uuid_in(
+        namespace: &Namespace,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = invitations::table
+            .filter(invitations::namespace_id.eq(namespace.id))
+            .filter(Self::with_uuid(uuid))
+            .filter(Self::pending())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Saves a new invitation. The token is granted afterwards (see
+    /// `grant_token`).
+    pub fn insert(
+        invitation: &NewInvitation,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let q = diesel::insert_into(invitations::table).values((
+            invitations::uuid.eq(uuid),
+            invitations::namespace_id.eq(invitation.namespace_id),
+            invitations::inviter_id.eq(invitation.inviter_id),
+            invitations::email.eq(&invitation.email),
+            invitations::role.eq(&invitation.role),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(i) => Some(i),
+        }
+    }
+
+    /// Saves the subject of the token, and its timestamps. A previous token
+    /// (if any) won't be available anymore.
+    pub fn grant_token<T: Claims>(
+        &self,
+        token: &str,
+        issuer: &str,
+        secret: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let c = T::decode(token, issuer, secret).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "invalid token"
+        })?;
+
+        let q = diesel::update(self).set((
+            invitations::token.eq(c.get_subject()),
+            invitations::token_granted_at.eq(c.get_issued_at()),
+            invitations::token_expires_at.eq(c.get_expiration_time()),
+            invitations::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to grant a token"
+        })
+    }
+
+    /// Accepts the invitation, and adds the user to the namespace as a member
+    /// with the role (unless the user is already a member). This should be
+    /// called in a transaction.
+    ///
+    /// The user must own the invited email address. It must be verified,
+    /// except on registration (a pending user can't sign in until the address
+    /// is verified by the activation).
+    pub fn accept(
+        &self,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Membership, &'static str> {
+        let email = UserEmail::find_by_email_of(user, &self.email, conn, logger)
+            .ok_or("email doesn't match invitation")?;
+        if !email.is_verified() && user.state != UserState::Pending {
+            return Err("email isn't verified");
+        }
+
+        let q = diesel::update(self).set((
+            invitations::token.eq(None::<String>),
+            invitations::accepted_at.eq(Utc::now().naive_utc()),
+            invitations::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to accept invitation"
+        })?;
+
+        if let Some(m) = Membership::find_by_namespace_id_and_user(
+            self.namespace_id,
+            user,
+            conn,
+            logger,
+        ) {
+            return Ok(m);
+        }
+
+        let m = NewMembership {
+            namespace_id: self.namespace_id,
+            user_id: user.id,
+            role: self.role.clone(),
+        };
+        Membership::insert(&m, conn, logger).ok_or("failed to add membership")
+    }
+
+    /// Deletes (cancels) the invitation.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete invitation")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn pending() -> Pending {
+        invitations::accepted_at.is_null()
+    }
+
+    pub fn with_uuid(s: &str) -> WithUuid {
+        let uuid = Uuid::parse_str(s).unwrap_or_else(|_| Uuid::nil());
+        invitations::uuid.eq(uuid)
+    }
+}
+
+/// Verifiable with Invitation. It's for an acceptance action
+impl Verifiable<Invitation> for Invitation {
+    type TokenClaims = VerificationClaims;
+
+    fn extract_concrete_token(
+        token: &str,
+        issuer: &str,
+        secret: &str,
+    ) -> Result<String, &'static str> {
+        let claims = Self::TokenClaims::decode(token, issuer, secret)
+            .map_err(|_| "invalid token")?;
+        Ok(claims.get_subject())
+    }
+
+    fn load_by_concrete_token(
+        concrete_token: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let now = Utc::now().naive_utc();
+        let q = invitations::table
+            .filter(invitations::token.eq(concrete_token))
+            .filter(invitations::token_expires_at.gt(now))
+            .filter(Self::pending())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.first::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "not found"
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod data {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+    use fnv::FnvHashMap;
+
+    use crate::fnvhashmap;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::user::data::USERS;
+
+    type InvitationFixture = FnvHashMap<&'static str, Invitation>;
+
+    lazy_static! {
+        pub static ref INVITATIONS: InvitationFixture = fnvhashmap! {
+            "weenie to piano" => Invitation {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                namespace_id: NAMESPACES.get("piano").unwrap().id,
+                inviter_id: USERS.get("oswald").unwrap().id,
+                email: "weenie@example.org".to_string(),
+                role: MembershipRole::Member,
+                token: None,
+                token_granted_at: None,
+                token_expires_at: None,
+                accepted_at: None,
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Duration;
+
+    use crate::model::token::TokenData;
+    use crate::model::namespace::namespaces;
+    use crate::model::user::users;
+    use crate::model::user_email::{UserEmailIdentificationState, user_emails};
+
+    use crate::model::invitation::data::INVITATIONS;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::user::data::USERS;
+    use crate::model::user_email::data::USER_EMAILS;
+    use crate::model::test::run;
+
+    #[test]
+    fn test_new_invitation_default() {
+        let i = NewInvitation {
+            ..Default::default()
+        };
+
+        assert_eq!(i.namespace_id, -1);
+        assert_eq!(i.inviter_id, -1);
+        assert_eq!(i.email, "".to_string());
+        assert_eq!(i.role, MembershipRole::Member);
+    }
+
+    #[test]
+    fn test_invitation_format() {
+        let i = INVITATIONS.get("weenie to piano").unwrap();
+        assert_eq!(format!("{}", i), format!("<Invitation {}>", i.uuid));
+    }
+
+    #[test]
+    fn test_grant_token_and_accept() {
+        run(|conn, config, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("oswald").unwrap();
+            let inviter = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("weenie").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let i = NewInvitation {
+                namespace_id: namespace.id,
+                inviter_id: inviter.id,
+                email: user.email.to_string(),
+                role: MembershipRole::Owner,
+            };
+            let invitation = Invitation::insert(&i, conn, logger).unwrap();
+            assert!(invitation.token.is_none());
+
+            let result = Invitation::find_pending_by_email_in(
+                &namespace,
+                &user.email,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(invitation.clone()));
+
+            let now = Utc::now();
+            let data = TokenData {
+                value: Invitation::generate_token(),
+                granted_at: now.timestamp(),
+                expires_at: (now + Duration::days(7)).timestamp(),
+            };
+            let raw_token = VerificationClaims::encode(
+                data,
+                &config.verification_token_issuer,
+                &config.verification_token_key_id,
+                &config.verification_token_secret,
+            );
+            let invitation = invitation
+                .grant_token::<VerificationClaims>(
+                    &raw_token,
+                    &config.verification_token_issuer,
+                    &config.verification_token_secret,
+                    conn,
+                    logger,
+                )
+                .unwrap();
+            assert!(invitation.token.is_some());
+
+            let concrete_token = Invitation::extract_concrete_token(
+                &raw_token,
+                &config.verification_token_issuer,
+                &config.verification_token_secret,
+            )
+            .unwrap();
+            let result = Invitation::load_by_concrete_token(
+                &concrete_token,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Ok(invitation.clone()));
+
+            // the invited address isn't owned by the inviter
+            let result = invitation.accept(&inviter, conn, logger);
+            assert_eq!(result.err(), Some("email doesn't match invitation"));
+
+            let ue = USER_EMAILS.get("weenie's primary address").unwrap();
+            let user_email = diesel::insert_into(user_emails::table)
+                .values(ue)
+                .get_result::<UserEmail>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            // not verified yet
+            let _ = diesel::update(&user_email)
+                .set(
+                    user_emails::identification_state
+                        .eq(UserEmailIdentificationState::Pending),
+                )
+                .execute(conn)
+                .unwrap_or_else(|e| panic!("Error at updating: {}", e));
+            let result = invitation.accept(&user, conn, logger);
+            assert_eq!(result.err(), Some("email isn't verified"));
+
+            let _ = diesel::update(&user_email)
+                .set(
+                    user_emails::identification_state
+                        .eq(UserEmailIdentificationState::Done),
+                )
+                .execute(conn)
+                .unwrap_or_else(|e| panic!("Error at updating: {}", e));
+
+            let membership = invitation.accept(&user, conn, logger).unwrap();
+            assert_eq!(membership.namespace_id, namespace.id);
+            assert_eq!(membership.role, MembershipRole::Owner);
+
+            // can't be used twice
+            let result = Invitation::load_by_concrete_token(
+                &concrete_token,
+                conn,
+                logger,
+            );
+            assert!(result.is_err());
+            let result = Invitation::find_all_pending_by_namespace(
+                &namespace, conn, logger,
+            );
+            assert_eq!(result, Some(vec![]));
+        })
+    }
+}
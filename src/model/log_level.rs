@@ -11,7 +11,7 @@ use diesel::pg::Pg;
 use diesel::serialize::{self, IsNull, Output, ToSql};
 use serde::Serialize;
 
-#[derive(SqlType)]
+#[derive(QueryId, SqlType)]
 #[postgres(type_name = "e_log_level")]
 pub struct ELogLevel;
 
@@ -95,6 +95,14 @@ impl LogLevel {
     pub fn as_vec() -> Vec<LogLevel> {
         LogLevel::iter().cloned().collect()
     }
+
+    /// Returns levels equal to or more severe than itself.
+    pub fn and_above(&self) -> Vec<LogLevel> {
+        LogLevel::iter()
+            .skip_while(|l| *l != self)
+            .cloned()
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -169,4 +177,14 @@ mod test {
             LogLevel::as_vec()
         )
     }
+
+    #[test]
+    fn test_and_above() {
+        assert_eq!(LogLevel::as_vec(), LogLevel::Debug.and_above());
+        assert_eq!(
+            vec![LogLevel::Warning, LogLevel::Error, LogLevel::Critical],
+            LogLevel::Warning.and_above()
+        );
+        assert_eq!(vec![LogLevel::Critical], LogLevel::Critical.and_above());
+    }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
 use diesel::dsl;
 use diesel::pg::{Pg, PgConnection};
@@ -9,8 +9,9 @@ pub use crate::model::membership_role::*;
 pub use crate::schema::memberships;
 
 use crate::logger::Logger;
-use crate::model::user::User;
+use crate::model::user::{User, users};
 use crate::model::namespace::Namespace;
+use crate::model::stream_grant::StreamGrant;
 
 /// NewMembership
 #[derive(Debug)]
@@ -63,7 +64,10 @@ impl Clone for Membership {
     }
 }
 
-pub type WithUser = dsl::Eq<memberships::user_id, i64>;
+pub type WithUser = dsl::And<
+    dsl::Eq<memberships::user_id, i64>,
+    dsl::IsNull<memberships::revoked_at>,
+>;
 
 impl Membership {
     pub fn find_by_id(
@@ -85,6 +89,33 @@ impl Membership {
         }
     }
 
+    /// Returns the membership of the user in the namespace (if any).
+    pub fn find_by_namespace_id_and_user(
+        namespace_id: i64,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = memberships::table
+            .filter(memberships::namespace_id.eq(namespace_id))
+            .filter(Self::with_user(user))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Membership>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn insert(
         membership: &NewMembership,
         conn: &PgConnection,
@@ -107,8 +138,158 @@ impl Membership {
         }
     }
 
+    /// Returns memberships in the namespace with their users, ordered by
+    /// joined date.
+    pub fn find_all_by_namespace(
+        namespace: &Namespace,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, User)>> {
+        let q = memberships::table
+            .inner_join(users::table)
+            .filter(memberships::namespace_id.eq(namespace.id))
+            .filter(memberships::revoked_at.is_null())
+            .order(memberships::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, User)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns memberships of the user as the primary owner (including ones
+    /// in archived namespaces).
+    pub fn find_all_primary_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = memberships::table
+            .filter(Self::with_user(user))
+            .filter(memberships::role.eq(MembershipRole::PrimaryOwner))
+            .order(memberships::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Changes the role. The primary owner can be changed only via
+    /// `transfer_primary_ownership`.
+    pub fn change_role(
+        &self,
+        role: MembershipRole,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if self.role == MembershipRole::PrimaryOwner ||
+            role == MembershipRole::PrimaryOwner
+        {
+            return Err("primary owner can't be changed");
+        }
+
+        let q = diesel::update(self).set((
+            memberships::role.eq(role),
+            memberships::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to change role"
+        })
+    }
+
+    /// Revokes the membership. The primary owner can't be revoked.
+    ///
+    /// Stream grants of the member in the namespace are deleted together.
+    pub fn revoke(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if self.role == MembershipRole::PrimaryOwner {
+            return Err("primary owner can't be revoked");
+        }
+        StreamGrant::delete_by_user_in(
+            self.namespace_id,
+            self.user_id,
+            conn,
+            logger,
+        )?;
+
+        let now = Utc::now().naive_utc();
+        let q = diesel::update(self).set((
+            memberships::revoked_at.eq(now),
+            memberships::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to revoke membership"
+        })
+    }
+
+    /// Transfers the primary ownership to another member in the same
+    /// namespace. The current primary owner becomes an owner. This should be
+    /// called in a transaction.
+    pub fn transfer_primary_ownership(
+        &self,
+        to: &Self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if self.role != MembershipRole::PrimaryOwner ||
+            self.namespace_id != to.namespace_id ||
+            self.id == to.id ||
+            to.revoked_at.is_some()
+        {
+            return Err("primary ownership can't be transferred");
+        }
+
+        let now = Utc::now().naive_utc();
+
+        // the current one must be demoted first (see the unique index)
+        let q = diesel::update(self).set((
+            memberships::role.eq(MembershipRole::Owner),
+            memberships::updated_at.eq(now),
+        ));
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to demote primary owner"
+        })?;
+
+        let q = diesel::update(to).set((
+            memberships::role.eq(MembershipRole::PrimaryOwner),
+            memberships::updated_at.eq(now),
+        ));
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to promote primary owner"
+        })
+    }
+
+    /// Returns a condition for active (not revoked) membership of the user.
     pub fn with_user(user: &User) -> WithUser {
-        memberships::user_id.eq(user.id)
+        memberships::user_id
+            .eq(user.id)
+            .and(memberships::revoked_at.is_null())
     }
 }
 
@@ -155,3 +336,133 @@ pub mod data {
         };
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::namespaces;
+
+    use crate::model::membership::data::MEMBERSHIPS;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::user::data::USERS;
+    use crate::model::test::run;
+
+    // returns the namespace, and memberships of oswald (primary owner) and
+    // weenie (member) in it
+    fn load(
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> (Namespace, Membership, Membership) {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let mut memberships = vec![];
+        for (name, role) in &[
+            ("oswald", MembershipRole::PrimaryOwner),
+            ("weenie", MembershipRole::Member),
+        ] {
+            let u = USERS.get(name).unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let m = NewMembership {
+                namespace_id: namespace.id,
+                user_id: user.id,
+                role: role.clone(),
+            };
+            memberships.push(Membership::insert(&m, conn, logger).unwrap());
+        }
+        let member = memberships.pop().unwrap();
+        let primary_owner = memberships.pop().unwrap();
+        (namespace, primary_owner, member)
+    }
+
+    #[test]
+    fn test_membership_format() {
+        let m = MEMBERSHIPS.get("oswald as a primary owner").unwrap();
+        assert_eq!(format!("{}", m), "<Membership primary_owner>");
+    }
+
+    #[test]
+    fn test_find_all_primary_by_user() {
+        run(|conn, _, logger| {
+            let (_, primary_owner, member) = load(conn, logger);
+
+            let oswald = User::find_by_id(primary_owner.user_id, conn, logger)
+                .unwrap();
+            let result =
+                Membership::find_all_primary_by_user(&oswald, conn, logger)
+                    .unwrap();
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].id, primary_owner.id);
+
+            let weenie =
+                User::find_by_id(member.user_id, conn, logger).unwrap();
+            let result =
+                Membership::find_all_primary_by_user(&weenie, conn, logger)
+                    .unwrap();
+            assert!(result.is_empty());
+        })
+    }
+
+    #[test]
+    fn test_change_role() {
+        run(|conn, _, logger| {
+            let (_, primary_owner, member) = load(conn, logger);
+
+            let result =
+                primary_owner.change_role(MembershipRole::Member, conn, logger);
+            assert!(result.is_err());
+            let result =
+                member.change_role(MembershipRole::PrimaryOwner, conn, logger);
+            assert!(result.is_err());
+
+            let result =
+                member.change_role(MembershipRole::Owner, conn, logger);
+            assert_eq!(result.unwrap().role, MembershipRole::Owner);
+        })
+    }
+
+    #[test]
+    fn test_revoke() {
+        run(|conn, _, logger| {
+            let (namespace, primary_owner, member) = load(conn, logger);
+
+            let result = primary_owner.revoke(conn, logger);
+            assert!(result.is_err());
+
+            let result = member.revoke(conn, logger);
+            assert!(result.unwrap().revoked_at.is_some());
+
+            let result =
+                Membership::find_all_by_namespace(&namespace, conn, logger)
+                    .unwrap();
+            assert_eq!(result.len(), 1);
+            assert_eq!(result[0].0.id, primary_owner.id);
+        })
+    }
+
+    #[test]
+    fn test_transfer_primary_ownership() {
+        run(|conn, _, logger| {
+            let (_, primary_owner, member) = load(conn, logger);
+
+            let result =
+                member.transfer_primary_ownership(&primary_owner, conn, logger);
+            assert!(result.is_err());
+
+            let result =
+                primary_owner.transfer_primary_ownership(&member, conn, logger);
+            assert_eq!(result.unwrap().role, MembershipRole::PrimaryOwner);
+
+            let result = Membership::find_by_id(primary_owner.id, conn, logger);
+            assert_eq!(result.unwrap().role, MembershipRole::Owner);
+        })
+    }
+}
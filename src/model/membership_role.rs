@@ -76,6 +76,11 @@ impl From<String> for MembershipRole {
 }
 
 impl MembershipRole {
+    /// Returns true if the role is one of owners.
+    pub fn is_owner(&self) -> bool {
+        matches!(*self, Self::PrimaryOwner | Self::Owner)
+    }
+
     pub fn iter() -> Iter<'static, MembershipRole> {
         MEMBERSHIP_ROLES.iter()
     }
@@ -123,6 +128,13 @@ mod test {
         assert_eq!("member", format!("{}", MembershipRole::Member));
     }
 
+    #[test]
+    fn test_is_owner() {
+        assert!(MembershipRole::PrimaryOwner.is_owner());
+        assert!(MembershipRole::Owner.is_owner());
+        assert!(!MembershipRole::Member.is_owner());
+    }
+
     #[test]
     fn test_as_vec() {
         assert_eq!(
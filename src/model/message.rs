@@ -24,6 +24,9 @@ pub use crate::model::stream::{Stream, streams};
 use crate::model::user::User;
 pub use crate::schema::messages;
 
+// agent_id of messages sent by users who have been deleted
+pub const ANONYMOUS_AGENT_ID: i64 = 0;
+
 /// NewMessage
 #[derive(Debug, Insertable)]
 #[table_name = "messages"]
@@ -189,34 +192,201 @@ impl Message {
         Self::all().filter(Self::with_user(user))
     }
 
-    pub fn fetch_by_stream_slug(
-        stream_slug: String,
+    /// Fetches messages on the stream from the latest one.
+    pub fn fetch_by_stream(
+        stream: &Stream,
         offset: i64,
         limit: i64,
         conn: &PgConnection,
         logger: &Logger,
     ) -> Option<Vec<Self>> {
-        if stream_slug.is_empty() {
-            return None;
+        let q = messages::table
+            .filter(messages::stream_id.eq(stream.id))
+            .order(messages::created_at.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Ok(r) => Some(r),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Counts messages on the stream in given levels since the time.
+    pub fn count_by_stream_id_since(
+        stream_id: i64,
+        levels: Vec<LogLevel>,
+        since: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<i64> {
+        let q = messages::table
+            .filter(messages::stream_id.eq(stream_id))
+            .filter(messages::level.eq_any(levels))
+            .filter(messages::created_at.ge(since))
+            .count();
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<i64>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(n) => Some(n),
         }
+    }
 
-        // TODO: Fix clause id = slug
-        let stream_id = 1;
+    /// Counts messages in the namespace by level for the period.
+    pub fn count_by_level_in(
+        namespace_id: i64,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(LogLevel, i64)>> {
         let q = messages::table
             .inner_join(streams::table)
-            .filter(streams::id.eq(stream_id))
-            .order(messages::created_at.desc())
-            .offset(offset)
+            .select((messages::level, dsl::count_star()))
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(messages::created_at.ge(since))
+            .filter(messages::created_at.lt(until))
+            .group_by(messages::level)
+            .order(messages::level.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(LogLevel, i64)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns the names of streams which have the most messages for the
+    /// period with their counts.
+    pub fn rank_streams_in(
+        namespace_id: i64,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(String, i64)>> {
+        let q = messages::table
+            .inner_join(streams::table)
+            .select((streams::name, dsl::count_star()))
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(messages::created_at.ge(since))
+            .filter(messages::created_at.lt(until))
+            .group_by((streams::id, streams::name))
+            .order((dsl::count_star().desc(), streams::id.asc()))
             .limit(limit);
 
         info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
 
-        match q.load::<(Self, Stream)>(conn) {
-            Ok(r) => Some(r.into_iter().map(|(m, _)| m).collect::<Vec<Self>>()),
+        match q.load::<(String, i64)>(conn) {
             Err(e) => {
-                println!("err: {}", e);
+                error!(logger, "err: {}", e);
                 None
             },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns the most frequent codes for the period with their counts.
+    pub fn rank_codes_in(
+        namespace_id: i64,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(String, i64)>> {
+        let q = messages::table
+            .inner_join(streams::table)
+            .select((messages::code.assume_not_null(), dsl::count_star()))
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(messages::code.is_not_null())
+            .filter(messages::created_at.ge(since))
+            .filter(messages::created_at.lt(until))
+            .group_by(messages::code)
+            .order((dsl::count_star().desc(), messages::code.asc()))
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(String, i64)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns titles in given levels for the period with their counts.
+    pub fn rank_titles_in(
+        namespace_id: i64,
+        levels: Vec<LogLevel>,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(String, i64)>> {
+        let q = messages::table
+            .inner_join(streams::table)
+            .select((messages::title, dsl::count_star()))
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(messages::level.eq_any(levels))
+            .filter(messages::created_at.ge(since))
+            .filter(messages::created_at.lt(until))
+            .group_by(messages::title)
+            .order((dsl::count_star().desc(), messages::title.asc()));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(String, i64)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns the titles out of given ones which appeared in the namespace
+    /// before the time.
+    pub fn find_titles_before(
+        namespace_id: i64,
+        titles: Vec<String>,
+        before: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<String>> {
+        let q = messages::table
+            .inner_join(streams::table)
+            .select(messages::title)
+            .distinct()
+            .filter(streams::namespace_id.eq(namespace_id))
+            .filter(messages::title.eq_any(titles))
+            .filter(messages::created_at.lt(before));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<String>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
         }
     }
 
@@ -240,6 +410,59 @@ impl Message {
         }
     }
 
+    /// Deletes messages on streams in the namespace up to the limit, and
+    /// returns the number of deleted messages.
+    pub fn delete_by_namespace_id(
+        namespace_id: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let stream_ids = streams::table
+            .select(streams::id)
+            .filter(streams::namespace_id.eq(namespace_id));
+        let ids = messages::table
+            .select(messages::id)
+            .filter(messages::stream_id.eq_any(stream_ids))
+            .limit(limit);
+        let q =
+            diesel::delete(messages::table.filter(messages::id.eq_any(ids)));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to delete messages"
+        })
+    }
+
+    /// Anonymizes messages sent by the user (via personal access token) in
+    /// batches, and returns the number of updated ones. The stream keeps them.
+    pub fn anonymize_by_user_id(
+        user_id: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let ids = messages::table
+            .select(messages::id)
+            .filter(messages::agent_id.eq(user_id))
+            .filter(messages::agent_type.eq(AgentType::Person))
+            .limit(limit);
+        let q = diesel::update(messages::table.filter(messages::id.eq_any(ids)))
+            .set((
+                messages::agent_id.eq(ANONYMOUS_AGENT_ID),
+                messages::updated_at.eq(Utc::now().naive_utc()),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to anonymize messages"
+        })
+    }
+
     /// Save new message.
     ///
     /// `created_at` and `updated_at` will be filled on PostgreSQL side
@@ -429,4 +652,52 @@ mod test {
             assert_eq!(title, "updated");
         })
     }
+
+    #[test]
+    fn test_anonymize_by_user_id() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("ball").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("weenie's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            for (i, agent_type) in
+                [AgentType::Person, AgentType::Person, AgentType::Client]
+                    .iter()
+                    .enumerate()
+            {
+                let mut m = MESSAGES.get("blank message").unwrap().clone();
+                m.id = i as i64 + 1;
+                m.agent_id = 2;
+                m.agent_type = agent_type.clone();
+                m.stream_id = stream.id;
+                let _ = diesel::insert_into(messages::table)
+                    .values(m)
+                    .execute(conn)
+                    .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+            }
+
+            let result = Message::anonymize_by_user_id(2, 1, conn, logger);
+            assert_eq!(result, Ok(1));
+            let result = Message::anonymize_by_user_id(2, 10, conn, logger);
+            assert_eq!(result, Ok(1));
+            let result = Message::anonymize_by_user_id(2, 10, conn, logger);
+            assert_eq!(result, Ok(0));
+
+            let rows_count: i64 = messages::table
+                .filter(messages::agent_id.eq(ANONYMOUS_AGENT_ID))
+                .count()
+                .first(conn)
+                .expect("Failed to count rows");
+            assert_eq!(2, rows_count);
+        })
+    }
 }
@@ -5,28 +5,50 @@
 // sql types
 mod access_token_state;
 mod agent_type;
+mod alert_state;
+mod audit_action;
+mod digest_frequency;
 mod log_level;
 mod log_format;
 mod membership_role;
+mod redaction_detector;
+mod role;
 mod user_email_identification_state;
 mod user_email_role;
 mod user_reset_password_state;
 mod user_state;
+mod webhook_delivery_state;
+mod webhook_event;
 
 // non-persistent (deciduous) entities
+pub mod digest;
+pub mod permission;
 pub mod token;
 
 // models
 pub mod access_token;
+pub mod alert;
+pub mod alert_rule;
+pub mod audit_event;
+pub mod digest_subscription;
+pub mod invitation;
 pub mod message;
 pub mod membership;
 pub mod namespace;
+pub mod namespace_key_redirect;
+pub mod namespace_usage;
+pub mod redaction_rule;
 pub mod stream;
+pub mod stream_grant;
 pub mod user;
 pub mod user_email;
+pub mod user_two_factor;
+pub mod webhook;
+pub mod webhook_delivery;
 
 use diesel::pg::PgConnection;
 
+use crate::config::Config;
 use crate::logger::Logger;
 
 // Note
@@ -34,7 +56,7 @@ use crate::logger::Logger;
 // traits:
 // - Authenticatable (User)
 // - Activatable (User, UserEmail)
-// - Verifiable (UserEmail)
+// - Verifiable (UserEmail, Invitation)
 //
 // claims
 // - AuthenticationClaims
@@ -49,6 +71,7 @@ use crate::logger::Logger;
 // Verification [verefication_token_{issuer|key_id|secret}]
 // * password reset ... reset_password_token (user)
 // * identify       ... identification_token (new general user email)
+// * invite         ... token (invitation to a namespace)
 pub trait Activatable {
     fn activate(
         &self,
@@ -61,6 +84,7 @@ pub trait Authenticatable {
     fn update_password(
         &mut self,
         new_password: &str,
+        config: &Config,
         conn: &PgConnection,
         logger: &Logger,
     ) -> Result<(), &'static str>;
@@ -153,6 +177,18 @@ pub mod test {
             "messages",
             "namespaces",
             "streams",
+            "alert_rules",
+            "alerts",
+            "webhooks",
+            "webhook_deliveries",
+            "digest_subscriptions",
+            "namespace_usages",
+            "redaction_rules",
+            "namespace_key_redirects",
+            "invitations",
+            "stream_grants",
+            "audit_events",
+            "user_two_factors",
         ]
         .join(", ");
         let q = format!("TRUNCATE TABLE {} RESTART IDENTITY CASCADE;", tables);
@@ -2,7 +2,7 @@
 use std::fmt;
 use std::str;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{Identifiable, Insertable, Queryable, debug_query, prelude::*};
 use diesel::dsl;
 use diesel::pg::{Pg, PgConnection};
@@ -12,7 +12,16 @@ use uuid::Uuid;
 use crate::logger::Logger;
 use crate::request::namespace::Namespace as RequestData;
 use crate::model::membership::{Membership, memberships};
+use crate::model::namespace_key_redirect::{
+    NamespaceKeyRedirect, namespace_key_redirects,
+};
+use crate::model::stream::streams;
 use crate::model::user::User;
+use crate::schema::{
+    alert_rules, alerts, digest_subscriptions, invitations, namespace_usages,
+    redaction_rules, stream_grants, webhook_deliveries, webhooks,
+};
+use crate::util::{is_reserved_word, slugify};
 
 pub use crate::schema::namespaces;
 
@@ -20,8 +29,10 @@ pub use crate::schema::namespaces;
 #[derive(Debug)]
 pub struct NewNamespace {
     pub name: String,
+    // generated from the name if empty
+    pub key: String,
     pub description: Option<String>,
-    pub streams_count: i64,
+    pub streams_count: i32,
 }
 
 impl fmt::Display for NewNamespace {
@@ -35,6 +46,7 @@ impl Default for NewNamespace {
     fn default() -> Self {
         Self {
             name: "".to_string(),
+            key: "".to_string(),
             description: None,
             streams_count: 0,
         }
@@ -45,6 +57,7 @@ impl From<RequestData> for NewNamespace {
     fn from(data: RequestData) -> Self {
         Self {
             name: data.name.unwrap_or_else(|| "".to_string()),
+            key: data.key.unwrap_or_else(|| "".to_string()),
             description: data.description,
             streams_count: 0,
         }
@@ -55,8 +68,11 @@ type AllColumns = (
     namespaces::id,
     namespaces::uuid,
     namespaces::name,
+    namespaces::key,
     namespaces::description,
     namespaces::streams_count,
+    namespaces::ingestion_rate_limit,
+    namespaces::ingestion_daily_quota,
     namespaces::archived_at,
     namespaces::created_at,
     namespaces::updated_at,
@@ -66,8 +82,11 @@ const ALL_COLUMNS: AllColumns = (
     namespaces::id,
     namespaces::uuid,
     namespaces::name,
+    namespaces::key,
     namespaces::description,
     namespaces::streams_count,
+    namespaces::ingestion_rate_limit,
+    namespaces::ingestion_daily_quota,
     namespaces::archived_at,
     namespaces::created_at,
     namespaces::updated_at,
@@ -92,8 +111,13 @@ pub struct Namespace {
     #[serde(with = "uuid_as_string")]
     pub uuid: Uuid,
     pub name: String,
+    pub key: String,
     pub description: Option<String>,
     pub streams_count: i32,
+    // requests per minute, overrides the default in Config
+    pub ingestion_rate_limit: Option<i32>,
+    // messages per day, overrides the default in Config
+    pub ingestion_daily_quota: Option<i64>,
     pub archived_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
@@ -114,6 +138,7 @@ impl Clone for Namespace {
         Self {
             uuid: self.uuid,
             name: self.name.clone(),
+            key: self.key.clone(),
             description: self.description.clone(),
             streams_count: self.streams_count,
             archived_at: None,
@@ -130,6 +155,11 @@ impl fmt::Display for Namespace {
 }
 
 type All = dsl::Select<namespaces::table, AllColumns>;
+type Archived = dsl::IsNotNull<namespaces::archived_at>;
+type ArchivedTo = dsl::Filter<
+    dsl::InnerJoin<All, memberships::table>,
+    dsl::And<crate::model::membership::WithUser, Archived>,
+>;
 type Visible = dsl::IsNull<namespaces::archived_at>;
 type VisibleTo = dsl::Filter<
     dsl::InnerJoin<All, memberships::table>,
@@ -142,6 +172,16 @@ impl Namespace {
         namespaces::table.select(ALL_COLUMNS)
     }
 
+    pub fn archived() -> Archived {
+        namespaces::archived_at.is_not_null()
+    }
+
+    pub fn archived_to(user: &User) -> ArchivedTo {
+        Self::all()
+            .inner_join(memberships::table)
+            .filter(Membership::with_user(user).and(Self::archived()))
+    }
+
     pub fn find_all(
         user: &User,
         conn: &PgConnection,
@@ -164,6 +204,94 @@ impl Namespace {
         }
     }
 
+    /// Returns archived namespaces which the user is a member of.
+    pub fn find_all_archived(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = Self::archived_to(user);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_archived_by_uuid(
+        uuid: &str,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = Self::archived_to(user)
+            .filter(Self::with_uuid(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(namespaces::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_key(
+        key: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::all().filter(namespaces::key.eq(key)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn find_by_uuid(
         uuid: &str,
         user: &User,
@@ -195,9 +323,15 @@ impl Namespace {
         logger: &Logger,
     ) -> Option<Self> {
         let uuid = Uuid::new_v4();
+        let key = if namespace.key.is_empty() {
+            Self::generate_key(&namespace.name, conn, logger)
+        } else {
+            namespace.key.clone()
+        };
         let q = diesel::insert_into(namespaces::table).values((
             namespaces::uuid.eq(uuid),
             namespaces::name.eq(&namespace.name),
+            namespaces::key.eq(&key),
             namespaces::description.eq(&namespace.description),
         ));
 
@@ -212,6 +346,314 @@ impl Namespace {
         }
     }
 
+    /// Returns true if the key is used by any namespace or is an old key of
+    /// a renamed one.
+    pub fn key_exists(key: &str, conn: &PgConnection, logger: &Logger) -> bool {
+        let q = namespaces::table.select(namespaces::id).filter(
+            namespaces::key.eq(key).or(namespaces::id.eq_any(
+                namespace_key_redirects::table
+                    .select(namespace_key_redirects::namespace_id)
+                    .filter(namespace_key_redirects::key.eq(key)),
+            )),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<i64>(conn) {
+            Ok(_) => true,
+            Err(diesel::result::Error::NotFound) => false,
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                true
+            },
+        }
+    }
+
+    /// Generates a unique key from the name (e.g. "Alice's Logs" ->
+    /// "alice-s-logs", "alice-s-logs-2").
+    pub fn generate_key(
+        name: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> String {
+        let mut base = slugify(name, 64);
+        if base.is_empty() {
+            base = "namespace".to_string();
+        }
+
+        let mut key = base.clone();
+        let mut i = 1;
+        while is_reserved_word(&key) || Self::key_exists(&key, conn, logger) {
+            i += 1;
+            let suffix = format!("-{}", i);
+            key = format!("{}{}", slugify(&base, 64 - suffix.len()), suffix);
+        }
+        key
+    }
+
+    /// Archives the namespace and its visible streams.
+    ///
+    /// Streams are archived at the same time as the namespace, so that
+    /// `unarchive` can restore only them. This should be called in a
+    /// transaction.
+    pub fn archive(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let now = Utc::now().naive_utc();
+
+        let q = diesel::update(
+            streams::table
+                .filter(streams::namespace_id.eq(self.id))
+                .filter(streams::archived_at.is_null()),
+        )
+        .set(streams::archived_at.eq(now));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let count = q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to archive streams"
+        })?;
+
+        let q = diesel::update(self).set((
+            namespaces::streams_count
+                .eq(namespaces::streams_count - count as i32),
+            namespaces::archived_at.eq(now),
+            namespaces::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to archive namespace"
+        })
+    }
+
+    /// Restores the namespace and the streams archived together with it.
+    ///
+    /// This should be called in a transaction.
+    pub fn unarchive(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let archived_at = match self.archived_at {
+            None => return Err("namespace is not archived"),
+            Some(t) => t,
+        };
+
+        let q = diesel::update(
+            streams::table
+                .filter(streams::namespace_id.eq(self.id))
+                .filter(streams::archived_at.eq(archived_at)),
+        )
+        .set(streams::archived_at.eq(None::<NaiveDateTime>));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let count = q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to unarchive streams"
+        })?;
+
+        let q = diesel::update(self).set((
+            namespaces::streams_count
+                .eq(namespaces::streams_count + count as i32),
+            namespaces::archived_at.eq(None::<NaiveDateTime>),
+            namespaces::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to unarchive namespace"
+        })
+    }
+
+    /// Updates the name, the key and the description.
+    ///
+    /// The old key is kept as a redirect if it changes. This should be called
+    /// in a transaction.
+    pub fn update(
+        &self,
+        name: &str,
+        key: &str,
+        description: &Option<String>,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if key != self.key {
+            NamespaceKeyRedirect::rename(self, key, conn, logger)?;
+        }
+
+        let q = diesel::update(self).set((
+            namespaces::name.eq(name),
+            namespaces::key.eq(key),
+            namespaces::description.eq(description),
+            namespaces::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update namespace"
+        })
+    }
+
+    /// Updates the overrides of the ingestion rate limit and the daily quota.
+    /// `None` falls back to the defaults in Config.
+    pub fn update_ingestion_limits(
+        &self,
+        rate_limit: Option<i32>,
+        daily_quota: Option<i64>,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            namespaces::ingestion_rate_limit.eq(rate_limit),
+            namespaces::ingestion_daily_quota.eq(daily_quota),
+            namespaces::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update namespace"
+        })
+    }
+
+    /// Deletes the namespace and all records belong to it.
+    ///
+    /// Messages must be deleted beforehand in batches (see
+    /// `Message::delete_by_namespace_id`). This should be called in a
+    /// transaction.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let stream_ids = streams::table
+            .select(streams::id)
+            .filter(streams::namespace_id.eq(self.id))
+            .load::<i64>(conn)
+            .map_err(|e| {
+                error!(logger, "err: {}", e);
+                "failed to load streams"
+            })?;
+        let alert_rule_ids = alert_rules::table
+            .select(alert_rules::id)
+            .filter(alert_rules::stream_id.eq_any(&stream_ids))
+            .load::<i64>(conn)
+            .map_err(|e| {
+                error!(logger, "err: {}", e);
+                "failed to load alert rules"
+            })?;
+        let webhook_ids = webhooks::table
+            .select(webhooks::id)
+            .filter(webhooks::namespace_id.eq(self.id))
+            .load::<i64>(conn)
+            .map_err(|e| {
+                error!(logger, "err: {}", e);
+                "failed to load webhooks"
+            })?;
+
+        let q = diesel::delete(
+            alerts::table.filter(alerts::alert_rule_id.eq_any(&alert_rule_ids)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete alerts")?;
+
+        let q = diesel::delete(
+            alert_rules::table.filter(alert_rules::id.eq_any(&alert_rule_ids)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete alert rules")?;
+
+        let q = diesel::delete(
+            webhook_deliveries::table
+                .filter(webhook_deliveries::webhook_id.eq_any(&webhook_ids)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn)
+            .map_err(|_| "failed to delete webhook deliveries")?;
+
+        let q = diesel::delete(
+            webhooks::table.filter(webhooks::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete webhooks")?;
+
+        let q = diesel::delete(
+            digest_subscriptions::table
+                .filter(digest_subscriptions::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn)
+            .map_err(|_| "failed to delete digest subscriptions")?;
+
+        let q = diesel::delete(
+            namespace_usages::table
+                .filter(namespace_usages::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete usages")?;
+
+        let q = diesel::delete(
+            redaction_rules::table
+                .filter(redaction_rules::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete redaction rules")?;
+
+        let q = diesel::delete(
+            namespace_key_redirects::table
+                .filter(namespace_key_redirects::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete key redirects")?;
+
+        let q = diesel::delete(
+            invitations::table.filter(invitations::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete invitations")?;
+
+        let q = diesel::delete(
+            stream_grants::table
+                .filter(stream_grants::stream_id.eq_any(&stream_ids)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete stream grants")?;
+
+        let q = diesel::delete(
+            streams::table.filter(streams::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete streams")?;
+
+        let q = diesel::delete(
+            memberships::table.filter(memberships::namespace_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete memberships")?;
+
+        let q = diesel::delete(self);
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete namespace")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
     pub fn with_uuid(s: &str) -> WithUuid {
         let uuid = Uuid::parse_str(s).unwrap_or_else(|_| Uuid::nil());
         namespaces::uuid.eq(uuid)
@@ -245,8 +687,11 @@ pub mod data {
                 id: 1,
                 uuid: Uuid::new_v4(),
                 name: "oswald".to_string(),
+                key: "piano".to_string(),
                 description: Some("description".to_string()),
                 streams_count: 0,
+                ingestion_rate_limit: None,
+                ingestion_daily_quota: None,
                 archived_at: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
                 updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -255,8 +700,11 @@ pub mod data {
                 id: 2,
                 uuid: Uuid::new_v4(),
                 name: "weenie".to_string(),
+                key: "ball".to_string(),
                 description: Some("description".to_string()),
                 streams_count: 0,
+                ingestion_rate_limit: None,
+                ingestion_daily_quota: None,
                 archived_at: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
                 updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -265,8 +713,11 @@ pub mod data {
                 id: 3,
                 uuid: Uuid::new_v4(),
                 name: "henry".to_string(),
+                key: "fish".to_string(),
                 description: Some("description".to_string()),
                 streams_count: 0,
+                ingestion_rate_limit: None,
+                ingestion_daily_quota: None,
                 archived_at: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
                 updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -280,6 +731,8 @@ mod test {
     use super::*;
 
     use crate::model::membership::{Membership, memberships};
+    use crate::model::message::{Message, NewMessage};
+    use crate::model::stream::{NewStream, Stream};
     use crate::model::user::{User, users};
 
     use crate::model::membership::data::MEMBERSHIPS;
@@ -294,6 +747,7 @@ mod test {
         };
 
         assert_eq!(ns.name, "".to_string());
+        assert_eq!(ns.key, "".to_string());
         assert_eq!(ns.description, None);
         assert_eq!(ns.streams_count, 0);
     }
@@ -339,6 +793,15 @@ mod test {
 
             let result = Namespace::find_all(&user, conn, logger);
             assert_eq!(result, Some(vec![namespace1]));
+
+            // revoked
+            let _ = diesel::update(&membership)
+                .set(memberships::revoked_at.eq(Utc::now().naive_utc()))
+                .execute(conn)
+                .unwrap_or_else(|e| panic!("Error at updating: {}", e));
+
+            let result = Namespace::find_all(&user, conn, logger);
+            assert_eq!(result, Some(vec![]));
         });
     }
 
@@ -346,7 +809,10 @@ mod test {
     fn test_find_by_uuid() {
         run(|conn, _, logger| {
             let namespace = diesel::insert_into(namespaces::table)
-                .values((namespaces::name.eq("name"),))
+                .values((
+                    namespaces::name.eq("name"),
+                    namespaces::key.eq("name"),
+                ))
                 .get_result::<Namespace>(conn)
                 .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
 
@@ -385,6 +851,7 @@ mod test {
         run(|conn, _, logger| {
             let ns = NewNamespace {
                 name: "".to_string(),
+                key: "".to_string(),
                 description: None,
                 streams_count: 0,
             };
@@ -400,6 +867,216 @@ mod test {
                 .expect("Failed to get a record");
 
             assert_eq!(result.streams_count, 0);
+            assert_eq!(result.key, "namespace");
+        })
+    }
+
+    #[test]
+    fn test_generate_key() {
+        run(|conn, _, logger| {
+            let ns = NewNamespace {
+                name: "Alice's Logs".to_string(),
+                ..Default::default()
+            };
+            let namespace = Namespace::insert(&ns, conn, logger).unwrap();
+            assert_eq!(namespace.key, "alice-s-logs");
+
+            let key = Namespace::generate_key("Alice's Logs", conn, logger);
+            assert_eq!(key, "alice-s-logs-2");
+
+            // reserved
+            let key = Namespace::generate_key("Admin", conn, logger);
+            assert_eq!(key, "admin-2");
+
+            // old key of a renamed namespace
+            let _ = namespace
+                .update("Alice's Logs", "alice", &None, conn, logger)
+                .unwrap();
+            let key = Namespace::generate_key("Alice's Logs", conn, logger);
+            assert_eq!(key, "alice-s-logs-2");
+        })
+    }
+
+    #[test]
+    fn test_archive_and_unarchive() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let m = MEMBERSHIPS.get("oswald as a primary owner").unwrap();
+            let _ = diesel::insert_into(memberships::table)
+                .values(m)
+                .get_result::<Membership>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = NewStream {
+                namespace_id: namespace.id,
+                name: "awesome-melody".to_string(),
+                slug: "".to_string(),
+                description: None,
+            };
+            let s1 = Stream::insert(&s, conn, logger).unwrap();
+            s.name = "lovely-melody".to_string();
+            let s2 = Stream::insert(&s, conn, logger).unwrap();
+
+            // archived before the namespace
+            let _ = s2.archive(conn, logger).unwrap();
+
+            let namespace = Namespace::find_by_id(namespace.id, conn, logger)
+                .unwrap()
+                .archive(conn, logger)
+                .unwrap();
+            assert!(namespace.archived_at.is_some());
+            assert_eq!(namespace.streams_count, 0);
+
+            let uuid = namespace.uuid.to_string();
+            let result = Namespace::find_by_uuid(&uuid, &user, conn, logger);
+            assert!(result.is_none());
+            let result = Namespace::find_all_archived(&user, conn, logger);
+            assert_eq!(result.map(|v| v.len()), Some(1));
+
+            let s1 = Stream::find_by_id(s1.id, conn, logger).unwrap();
+            assert!(s1.archived_at.is_some());
+
+            let namespace =
+                Namespace::find_archived_by_uuid(&uuid, &user, conn, logger)
+                    .unwrap()
+                    .unarchive(conn, logger)
+                    .unwrap();
+            assert!(namespace.archived_at.is_none());
+            assert_eq!(namespace.streams_count, 1);
+
+            // only streams archived together are restored
+            let s1 = Stream::find_by_id(s1.id, conn, logger).unwrap();
+            assert!(s1.archived_at.is_none());
+            let s2 = Stream::find_by_id(s2.id, conn, logger).unwrap();
+            assert!(s2.archived_at.is_some());
+        })
+    }
+
+    #[test]
+    fn test_update() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result =
+                namespace.update("organ", "organ", &None, conn, logger);
+            assert!(result.is_ok());
+
+            let namespace = result.unwrap();
+            assert_eq!(namespace.name, "organ");
+            assert_eq!(namespace.key, "organ");
+            assert_eq!(namespace.description, None);
+
+            let result = Namespace::find_by_key("piano", conn, logger);
+            assert!(result.is_none());
+            let result =
+                NamespaceKeyRedirect::find_by_key("piano", conn, logger);
+            assert_eq!(result.map(|r| r.namespace_id), Some(namespace.id));
+        })
+    }
+
+    #[test]
+    fn test_update_ingestion_limits() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = namespace.update_ingestion_limits(
+                Some(1200),
+                Some(500_000),
+                conn,
+                logger,
+            );
+            assert!(result.is_ok());
+
+            let namespace = result.unwrap();
+            assert_eq!(namespace.ingestion_rate_limit, Some(1200));
+            assert_eq!(namespace.ingestion_daily_quota, Some(500_000));
+
+            let result =
+                namespace.update_ingestion_limits(None, None, conn, logger);
+            assert!(result.is_ok());
+
+            let namespace = result.unwrap();
+            assert_eq!(namespace.ingestion_rate_limit, None);
+            assert_eq!(namespace.ingestion_daily_quota, None);
+        })
+    }
+
+    #[test]
+    fn test_delete() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let u = USERS.get("oswald").unwrap();
+            let _ = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let m = MEMBERSHIPS.get("oswald as a primary owner").unwrap();
+            let _ = diesel::insert_into(memberships::table)
+                .values(m)
+                .get_result::<Membership>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let s = NewStream {
+                namespace_id: namespace.id,
+                name: "awesome-melody".to_string(),
+                slug: "".to_string(),
+                description: None,
+            };
+            let stream = Stream::insert(&s, conn, logger).unwrap();
+            for _ in 0..3 {
+                let m = NewMessage {
+                    agent_id: 1,
+                    stream_id: stream.id,
+                    title: Some("title".to_string()),
+                    ..Default::default()
+                };
+                let _ = Message::insert(&m, conn, logger).unwrap();
+            }
+
+            let result =
+                Message::delete_by_namespace_id(namespace.id, 2, conn, logger);
+            assert_eq!(result, Ok(2));
+            let result =
+                Message::delete_by_namespace_id(namespace.id, 2, conn, logger);
+            assert_eq!(result, Ok(1));
+
+            assert!(namespace.delete(conn, logger).is_ok());
+
+            let result = Namespace::find_by_id(namespace.id, conn, logger);
+            assert!(result.is_none());
+            let result = Stream::find_by_id(stream.id, conn, logger);
+            assert!(result.is_none());
+
+            let rows_count: i64 = memberships::table
+                .count()
+                .first(conn)
+                .expect("Failed to count rows");
+            assert_eq!(0, rows_count);
         })
     }
 }
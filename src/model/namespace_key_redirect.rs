@@ -0,0 +1,141 @@
+//! # NamespaceKeyRedirect
+//!
+//! NamespaceKeyRedirect holds an old key of a renamed namespace, so that URLs
+//! which contain the key keep working.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+
+pub use crate::schema::namespace_key_redirects;
+
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+
+/// NamespaceKeyRedirect
+#[derive(
+    Associations, Clone, Debug, Identifiable, Insertable, PartialEq, Queryable,
+)]
+#[belongs_to(Namespace)]
+#[table_name = "namespace_key_redirects"]
+pub struct NamespaceKeyRedirect {
+    pub id: i64,
+    pub namespace_id: i64,
+    pub key: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl fmt::Display for NamespaceKeyRedirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NamespaceKeyRedirect {key}>", key = &self.key)
+    }
+}
+
+impl NamespaceKeyRedirect {
+    pub fn find_by_key(
+        key: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = namespace_key_redirects::table
+            .filter(namespace_key_redirects::key.eq(key))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Saves the old key of the namespace.
+    ///
+    /// If the namespace gets back its old key, the redirect for the key is
+    /// removed. This should be called in a transaction.
+    pub fn rename(
+        namespace: &Namespace,
+        key: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(
+            namespace_key_redirects::table
+                .filter(namespace_key_redirects::namespace_id.eq(namespace.id))
+                .filter(namespace_key_redirects::key.eq(key)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to delete redirect"
+        })?;
+
+        let q = diesel::insert_into(namespace_key_redirects::table).values((
+            namespace_key_redirects::namespace_id.eq(namespace.id),
+            namespace_key_redirects::key.eq(&namespace.key),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to insert redirect")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+
+    #[test]
+    fn test_rename() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result =
+                NamespaceKeyRedirect::rename(&namespace, "organ", conn, logger);
+            assert!(result.is_ok());
+
+            let redirect =
+                NamespaceKeyRedirect::find_by_key(&namespace.key, conn, logger)
+                    .unwrap();
+            assert_eq!(redirect.namespace_id, namespace.id);
+
+            // gets back the old key
+            let mut renamed = namespace.clone();
+            renamed.key = "organ".to_string();
+            let result = NamespaceKeyRedirect::rename(
+                &renamed,
+                &namespace.key,
+                conn,
+                logger,
+            );
+            assert!(result.is_ok());
+
+            let result =
+                NamespaceKeyRedirect::find_by_key(&namespace.key, conn, logger);
+            assert!(result.is_none());
+            let result =
+                NamespaceKeyRedirect::find_by_key("organ", conn, logger);
+            assert!(result.is_some());
+        })
+    }
+}
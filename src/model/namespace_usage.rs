@@ -0,0 +1,328 @@
+//! # NamespaceUsage
+//!
+//! NamespaceUsage is a daily counter of ingestion into a namespace. The date
+//! is in UTC.
+use std::fmt;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+
+pub use crate::schema::namespace_usages;
+
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+
+type AllColumns = (
+    namespace_usages::id,
+    namespace_usages::namespace_id,
+    namespace_usages::date,
+    namespace_usages::messages_count,
+    namespace_usages::bytes_count,
+    namespace_usages::dropped_count,
+    namespace_usages::notified_percent,
+    namespace_usages::created_at,
+    namespace_usages::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    namespace_usages::id,
+    namespace_usages::namespace_id,
+    namespace_usages::date,
+    namespace_usages::messages_count,
+    namespace_usages::bytes_count,
+    namespace_usages::dropped_count,
+    namespace_usages::notified_percent,
+    namespace_usages::created_at,
+    namespace_usages::updated_at,
+);
+
+/// NamespaceUsage
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "namespace_usages"]
+pub struct NamespaceUsage {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(skip)]
+    pub namespace_id: i64,
+    pub date: NaiveDate,
+    pub messages_count: i64,
+    pub bytes_count: i64,
+    pub dropped_count: i64,
+    #[serde(skip)]
+    pub notified_percent: i16,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for NamespaceUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NamespaceUsage {date}>", date = &self.date)
+    }
+}
+
+type All = dsl::Select<namespace_usages::table, AllColumns>;
+type WithNamespaceId = dsl::Eq<namespace_usages::namespace_id, i64>;
+type ByNamespaceId = dsl::Filter<All, WithNamespaceId>;
+
+impl NamespaceUsage {
+    pub fn all() -> All {
+        namespace_usages::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_namespace_id(namespace_id: i64) -> ByNamespaceId {
+        Self::all().filter(Self::with_namespace_id(namespace_id))
+    }
+
+    /// Returns daily usages of the namespace from the latest one.
+    pub fn fetch_by_namespace(
+        namespace: &Namespace,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if limit < 1 {
+            return None;
+        }
+
+        let q = Self::by_namespace_id(namespace.id)
+            .order(namespace_usages::date.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(namespace_usages::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_namespace_id_and_date(
+        namespace_id: i64,
+        date: NaiveDate,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace_id)
+            .filter(namespace_usages::date.eq(date))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Counts up the usage of the date, and returns the updated one.
+    ///
+    /// The row for the date will be created if it doesn't exist yet.
+    pub fn increment(
+        namespace_id: i64,
+        date: NaiveDate,
+        messages_count: i64,
+        bytes_count: i64,
+        dropped_count: i64,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(namespace_usages::table)
+            .values((
+                namespace_usages::namespace_id.eq(namespace_id),
+                namespace_usages::date.eq(date),
+                namespace_usages::messages_count.eq(messages_count),
+                namespace_usages::bytes_count.eq(bytes_count),
+                namespace_usages::dropped_count.eq(dropped_count),
+            ))
+            .on_conflict((
+                namespace_usages::namespace_id,
+                namespace_usages::date,
+            ))
+            .do_update()
+            .set((
+                namespace_usages::messages_count
+                    .eq(namespace_usages::messages_count + messages_count),
+                namespace_usages::bytes_count
+                    .eq(namespace_usages::bytes_count + bytes_count),
+                namespace_usages::dropped_count
+                    .eq(namespace_usages::dropped_count + dropped_count),
+                namespace_usages::updated_at.eq(now),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(u) => Some(u),
+        }
+    }
+
+    /// Marks the threshold (%) as notified.
+    ///
+    /// This returns None if it (or a higher one) has already been notified,
+    /// so that only one of concurrent requests sends the notification.
+    pub fn notify(
+        &self,
+        percent: i16,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::update(
+            namespace_usages::table
+                .filter(namespace_usages::id.eq(self.id))
+                .filter(namespace_usages::notified_percent.lt(percent)),
+        )
+        .set(namespace_usages::notified_percent.eq(percent));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(u) => Some(u),
+        }
+    }
+
+    pub fn with_namespace_id(namespace_id: i64) -> WithNamespaceId {
+        namespace_usages::namespace_id.eq(namespace_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Utc;
+
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+
+    #[test]
+    fn test_increment() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let now = Utc::now().naive_utc();
+            let today = now.date();
+
+            let usage = NamespaceUsage::increment(
+                namespace.id,
+                today,
+                1,
+                64,
+                0,
+                now,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(usage.messages_count, 1);
+            assert_eq!(usage.bytes_count, 64);
+
+            let usage = NamespaceUsage::increment(
+                namespace.id,
+                today,
+                0,
+                0,
+                1,
+                now,
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(usage.messages_count, 1);
+            assert_eq!(usage.dropped_count, 1);
+
+            let result = NamespaceUsage::fetch_by_namespace(
+                &namespace, 0, 10, conn, logger,
+            );
+            assert_eq!(result, Some(vec![usage]));
+        })
+    }
+
+    #[test]
+    fn test_notify() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let now = Utc::now().naive_utc();
+            let usage = NamespaceUsage::increment(
+                namespace.id,
+                now.date(),
+                1,
+                64,
+                0,
+                now,
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            let usage = usage.notify(80, conn, logger).unwrap();
+            assert_eq!(usage.notified_percent, 80);
+
+            // already notified
+            assert!(usage.notify(80, conn, logger).is_none());
+
+            let usage = usage.notify(100, conn, logger).unwrap();
+            assert_eq!(usage.notified_percent, 100);
+        })
+    }
+}
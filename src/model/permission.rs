@@ -0,0 +1,243 @@
+//! Permission is an action on resources of a namespace.
+//!
+//! Which role can do what is declared in the policy tables below, it's not
+//! persisted. Roles of stream grants have their own table.
+use std::fmt;
+
+use crate::model::membership::MembershipRole;
+use crate::model::stream_grant::Role;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    NamespaceRead,
+    NamespaceManage,
+    NamespaceDelete,
+    StreamsRead,
+    StreamsManage,
+    MessagesRead,
+    MessagesWrite,
+    MembersRead,
+    MembersManage,
+    OwnershipTransfer,
+    AlertRulesRead,
+    AlertRulesManage,
+    WebhooksRead,
+    WebhooksManage,
+    RedactionRulesRead,
+    RedactionRulesManage,
+    UsageRead,
+    DigestsSubscribe,
+    AuditRead,
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::NamespaceRead => write!(f, "namespace:read"),
+            Self::NamespaceManage => write!(f, "namespace:manage"),
+            Self::NamespaceDelete => write!(f, "namespace:delete"),
+            Self::StreamsRead => write!(f, "streams:read"),
+            Self::StreamsManage => write!(f, "streams:manage"),
+            Self::MessagesRead => write!(f, "messages:read"),
+            Self::MessagesWrite => write!(f, "messages:write"),
+            Self::MembersRead => write!(f, "members:read"),
+            Self::MembersManage => write!(f, "members:manage"),
+            Self::OwnershipTransfer => write!(f, "ownership:transfer"),
+            Self::AlertRulesRead => write!(f, "alert_rules:read"),
+            Self::AlertRulesManage => write!(f, "alert_rules:manage"),
+            Self::WebhooksRead => write!(f, "webhooks:read"),
+            Self::WebhooksManage => write!(f, "webhooks:manage"),
+            Self::RedactionRulesRead => write!(f, "redaction_rules:read"),
+            Self::RedactionRulesManage => write!(f, "redaction_rules:manage"),
+            Self::UsageRead => write!(f, "usage:read"),
+            Self::DigestsSubscribe => write!(f, "digests:subscribe"),
+            Self::AuditRead => write!(f, "audit:read"),
+        }
+    }
+}
+
+// policy table
+//
+// Each role has also all the permissions of the roles below it.
+const MEMBER_PERMISSIONS: [Permission; 10] = [
+    Permission::NamespaceRead,
+    Permission::StreamsRead,
+    Permission::MessagesRead,
+    Permission::MessagesWrite,
+    Permission::MembersRead,
+    Permission::AlertRulesRead,
+    Permission::WebhooksRead,
+    Permission::RedactionRulesRead,
+    Permission::UsageRead,
+    Permission::DigestsSubscribe,
+];
+
+const OWNER_PERMISSIONS: [Permission; 7] = [
+    Permission::NamespaceManage,
+    Permission::StreamsManage,
+    Permission::MembersManage,
+    Permission::AlertRulesManage,
+    Permission::WebhooksManage,
+    Permission::RedactionRulesManage,
+    Permission::AuditRead,
+];
+
+const PRIMARY_OWNER_PERMISSIONS: [Permission; 2] =
+    [Permission::NamespaceDelete, Permission::OwnershipTransfer];
+
+// policy table for stream grants
+//
+// A grant replaces the role in the namespace only on the stream.
+const WRITER_PERMISSIONS: [Permission; 1] = [Permission::MessagesWrite];
+
+const STREAM_MEMBER_PERMISSIONS: [Permission; 3] = [
+    Permission::StreamsRead,
+    Permission::MessagesRead,
+    Permission::AlertRulesRead,
+];
+
+const MAINTAINER_PERMISSIONS: [Permission; 4] = [
+    Permission::StreamsRead,
+    Permission::MessagesRead,
+    Permission::MessagesWrite,
+    Permission::AlertRulesRead,
+];
+
+const ADMIN_PERMISSIONS: [Permission; 5] = [
+    Permission::StreamsRead,
+    Permission::MessagesRead,
+    Permission::MessagesWrite,
+    Permission::AlertRulesRead,
+    Permission::AlertRulesManage,
+];
+
+impl Permission {
+    /// Returns true if the role is allowed to do it by the policy.
+    pub fn is_granted_to(self, role: &MembershipRole) -> bool {
+        MEMBER_PERMISSIONS.contains(&self) ||
+            (role.is_owner() && OWNER_PERMISSIONS.contains(&self)) ||
+            (*role == MembershipRole::PrimaryOwner &&
+                PRIMARY_OWNER_PERMISSIONS.contains(&self))
+    }
+
+    /// Returns true if the role of a stream grant is allowed to do it on the
+    /// stream.
+    pub fn is_granted_on_stream_to(self, role: &Role) -> bool {
+        match *role {
+            Role::Writer => WRITER_PERMISSIONS.contains(&self),
+            Role::Member => STREAM_MEMBER_PERMISSIONS.contains(&self),
+            Role::Maintainer => MAINTAINER_PERMISSIONS.contains(&self),
+            Role::Admin => ADMIN_PERMISSIONS.contains(&self),
+        }
+    }
+
+    /// Returns all the permissions granted to the role.
+    pub fn granted_to(role: &MembershipRole) -> Vec<Self> {
+        MEMBER_PERMISSIONS
+            .iter()
+            .chain(OWNER_PERMISSIONS.iter())
+            .chain(PRIMARY_OWNER_PERMISSIONS.iter())
+            .filter(|p| p.is_granted_to(role))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("messages:write", format!("{}", Permission::MessagesWrite));
+        assert_eq!("streams:manage", format!("{}", Permission::StreamsManage));
+        assert_eq!(
+            "ownership:transfer",
+            format!("{}", Permission::OwnershipTransfer)
+        );
+    }
+
+    #[test]
+    fn test_is_granted_to_member() {
+        let role = MembershipRole::Member;
+
+        assert!(Permission::NamespaceRead.is_granted_to(&role));
+        assert!(Permission::StreamsRead.is_granted_to(&role));
+        assert!(Permission::MessagesRead.is_granted_to(&role));
+        assert!(Permission::MessagesWrite.is_granted_to(&role));
+        assert!(Permission::DigestsSubscribe.is_granted_to(&role));
+
+        assert!(!Permission::NamespaceManage.is_granted_to(&role));
+        assert!(!Permission::StreamsManage.is_granted_to(&role));
+        assert!(!Permission::MembersManage.is_granted_to(&role));
+        assert!(!Permission::WebhooksManage.is_granted_to(&role));
+        assert!(!Permission::NamespaceDelete.is_granted_to(&role));
+        assert!(!Permission::OwnershipTransfer.is_granted_to(&role));
+        assert!(!Permission::AuditRead.is_granted_to(&role));
+    }
+
+    #[test]
+    fn test_is_granted_to_owner() {
+        let role = MembershipRole::Owner;
+
+        assert!(Permission::MessagesWrite.is_granted_to(&role));
+        assert!(Permission::NamespaceManage.is_granted_to(&role));
+        assert!(Permission::StreamsManage.is_granted_to(&role));
+        assert!(Permission::MembersManage.is_granted_to(&role));
+        assert!(Permission::AlertRulesManage.is_granted_to(&role));
+        assert!(Permission::RedactionRulesManage.is_granted_to(&role));
+        assert!(Permission::AuditRead.is_granted_to(&role));
+
+        assert!(!Permission::NamespaceDelete.is_granted_to(&role));
+        assert!(!Permission::OwnershipTransfer.is_granted_to(&role));
+    }
+
+    #[test]
+    fn test_is_granted_to_primary_owner() {
+        let role = MembershipRole::PrimaryOwner;
+
+        assert!(Permission::MessagesRead.is_granted_to(&role));
+        assert!(Permission::StreamsManage.is_granted_to(&role));
+        assert!(Permission::NamespaceDelete.is_granted_to(&role));
+        assert!(Permission::OwnershipTransfer.is_granted_to(&role));
+    }
+
+    #[test]
+    fn test_is_granted_on_stream_to() {
+        let role = Role::Writer;
+        assert!(Permission::MessagesWrite.is_granted_on_stream_to(&role));
+        assert!(!Permission::MessagesRead.is_granted_on_stream_to(&role));
+
+        let role = Role::Member;
+        assert!(Permission::MessagesRead.is_granted_on_stream_to(&role));
+        assert!(!Permission::MessagesWrite.is_granted_on_stream_to(&role));
+
+        let role = Role::Maintainer;
+        assert!(Permission::MessagesRead.is_granted_on_stream_to(&role));
+        assert!(Permission::MessagesWrite.is_granted_on_stream_to(&role));
+        assert!(!Permission::AlertRulesManage.is_granted_on_stream_to(&role));
+
+        let role = Role::Admin;
+        assert!(Permission::MessagesWrite.is_granted_on_stream_to(&role));
+        assert!(Permission::AlertRulesManage.is_granted_on_stream_to(&role));
+
+        // never on the namespace
+        for role in Role::iter() {
+            assert!(!Permission::StreamsManage.is_granted_on_stream_to(role));
+            assert!(!Permission::MembersManage.is_granted_on_stream_to(role));
+        }
+    }
+
+    #[test]
+    fn test_granted_to() {
+        assert_eq!(
+            MEMBER_PERMISSIONS.to_vec(),
+            Permission::granted_to(&MembershipRole::Member)
+        );
+        assert_eq!(17, Permission::granted_to(&MembershipRole::Owner).len());
+        assert_eq!(
+            19,
+            Permission::granted_to(&MembershipRole::PrimaryOwner).len()
+        );
+    }
+}
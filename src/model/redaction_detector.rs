@@ -0,0 +1,162 @@
+//! # A type RedactionDetector for RedactionRule in redaction_rule.rs
+//!
+//! ERedactionDetector represents SQL type value `e_redaction_detector` and
+//! RedactionDetector is an Enum holds all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_redaction_detector")]
+pub struct ERedactionDetector;
+
+#[derive(AsExpression, Clone, Debug, FromSqlRow, PartialEq, Serialize)]
+#[sql_type = "ERedactionDetector"]
+pub enum RedactionDetector {
+    Email,
+    CreditCard,
+    Jwt,
+    BearerToken,
+    Custom, // default
+}
+
+const REDACTION_DETECTORS: [RedactionDetector; 5] = [
+    RedactionDetector::Email,
+    RedactionDetector::CreditCard,
+    RedactionDetector::Jwt,
+    RedactionDetector::BearerToken,
+    RedactionDetector::Custom,
+];
+
+impl fmt::Display for RedactionDetector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Email => write!(f, "email"),
+            Self::CreditCard => write!(f, "credit_card"),
+            Self::Jwt => write!(f, "jwt"),
+            Self::BearerToken => write!(f, "bearer_token"),
+            Self::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+impl ToSql<ERedactionDetector, Pg> for RedactionDetector {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            Self::Email => out.write_all(b"email")?,
+            Self::CreditCard => out.write_all(b"credit_card")?,
+            Self::Jwt => out.write_all(b"jwt")?,
+            Self::BearerToken => out.write_all(b"bearer_token")?,
+            Self::Custom => out.write_all(b"custom")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<ERedactionDetector, Pg> for RedactionDetector {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"email" => Ok(Self::Email),
+            b"credit_card" => Ok(Self::CreditCard),
+            b"jwt" => Ok(Self::Jwt),
+            b"bearer_token" => Ok(Self::BearerToken),
+            b"custom" => Ok(Self::Custom),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl From<String> for RedactionDetector {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "email" => Self::Email,
+            "credit_card" => Self::CreditCard,
+            "jwt" => Self::Jwt,
+            "bearer_token" => Self::BearerToken,
+            "custom" => Self::Custom,
+            _ => Self::Custom,
+        }
+    }
+}
+
+impl RedactionDetector {
+    pub fn iter() -> Iter<'static, RedactionDetector> {
+        REDACTION_DETECTORS.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+
+    /// Returns the regular expression of the built-in detector.
+    ///
+    /// A custom detector has its own pattern in the rule.
+    pub fn pattern(&self) -> Option<&'static str> {
+        match *self {
+            Self::Email => {
+                Some(r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9\-]+(?:\.[A-Za-z0-9\-]+)+")
+            },
+            // checked with Luhn algorithm after match
+            Self::CreditCard => Some(r"\b\d(?:[ \-]?\d){12,18}\b"),
+            Self::Jwt => {
+                Some(r"\beyJ[A-Za-z0-9_\-]+\.[A-Za-z0-9_\-]+\.[A-Za-z0-9_\-]+")
+            },
+            Self::BearerToken => Some(r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]+=*"),
+            Self::Custom => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(
+            RedactionDetector::Email,
+            RedactionDetector::from("email".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::CreditCard,
+            RedactionDetector::from("credit_card".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::Jwt,
+            RedactionDetector::from("JWT".to_string())
+        );
+        assert_eq!(
+            RedactionDetector::BearerToken,
+            RedactionDetector::from("bearer_token".to_string())
+        );
+
+        // default
+        assert_eq!(
+            RedactionDetector::Custom,
+            RedactionDetector::from("unknown".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("email", format!("{}", RedactionDetector::Email));
+        assert_eq!("credit_card", format!("{}", RedactionDetector::CreditCard));
+        assert_eq!("jwt", format!("{}", RedactionDetector::Jwt));
+        assert_eq!(
+            "bearer_token",
+            format!("{}", RedactionDetector::BearerToken)
+        );
+        assert_eq!("custom", format!("{}", RedactionDetector::Custom));
+    }
+
+    #[test]
+    fn test_pattern() {
+        assert!(RedactionDetector::Email.pattern().is_some());
+        assert!(RedactionDetector::Custom.pattern().is_none());
+    }
+}
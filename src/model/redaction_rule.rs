@@ -0,0 +1,376 @@
+//! # RedactionRule
+//!
+//! RedactionRule tells what should be masked in messages of a namespace
+//! before they are saved. It's a built-in detector or a custom regular
+//! expression.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::model::redaction_detector::*;
+pub use crate::schema::redaction_rules;
+
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+use crate::request::redaction_rule::RedactionRule as RequestData;
+
+/// NewRedactionRule
+#[derive(Debug)]
+pub struct NewRedactionRule {
+    pub namespace_id: i64,
+    pub name: String,
+    pub detector: RedactionDetector,
+    pub pattern: Option<String>,
+}
+
+impl fmt::Display for NewRedactionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewRedactionRule {name}>", name = &self.name)
+    }
+}
+
+impl Default for NewRedactionRule {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            namespace_id: -1,
+            name: "".to_string(),
+            detector: RedactionDetector::Custom,
+            pattern: None,
+        }
+    }
+}
+
+impl From<RequestData> for NewRedactionRule {
+    fn from(data: RequestData) -> Self {
+        let detector = RedactionDetector::from(
+            data.detector.unwrap_or_else(|| "custom".to_string()),
+        );
+        // built-in detectors have their own pattern
+        let pattern = if detector == RedactionDetector::Custom {
+            data.pattern
+        } else {
+            None
+        };
+        Self {
+            name: data.name.unwrap_or_else(|| "".to_string()),
+            detector,
+            pattern,
+
+            ..Default::default()
+        }
+    }
+}
+
+type AllColumns = (
+    redaction_rules::id,
+    redaction_rules::uuid,
+    redaction_rules::namespace_id,
+    redaction_rules::name,
+    redaction_rules::detector,
+    redaction_rules::pattern,
+    redaction_rules::created_at,
+    redaction_rules::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    redaction_rules::id,
+    redaction_rules::uuid,
+    redaction_rules::namespace_id,
+    redaction_rules::name,
+    redaction_rules::detector,
+    redaction_rules::pattern,
+    redaction_rules::created_at,
+    redaction_rules::updated_at,
+);
+
+/// RedactionRule
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "redaction_rules"]
+pub struct RedactionRule {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub namespace_id: i64,
+    pub name: String,
+    pub detector: RedactionDetector,
+    pub pattern: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+impl fmt::Display for RedactionRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<RedactionRule {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+type All = dsl::Select<redaction_rules::table, AllColumns>;
+type WithNamespaceId = dsl::Eq<redaction_rules::namespace_id, i64>;
+type WithUuid = dsl::Eq<redaction_rules::uuid, Uuid>;
+type ByNamespaceId = dsl::Filter<All, WithNamespaceId>;
+
+impl RedactionRule {
+    pub fn all() -> All {
+        redaction_rules::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_namespace_id(namespace_id: i64) -> ByNamespaceId {
+        Self::all().filter(Self::with_namespace_id(namespace_id))
+    }
+
+    /// Returns rules of the namespace in the order they are applied.
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_namespace_id(namespace_id)
+            .order(redaction_rules::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_uuid_in(
+        namespace: &Namespace,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(Self::with_uuid(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn insert(
+        rule: &NewRedactionRule,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let q = diesel::insert_into(redaction_rules::table).values((
+            redaction_rules::uuid.eq(uuid),
+            redaction_rules::namespace_id.eq(rule.namespace_id),
+            redaction_rules::name.eq(&rule.name),
+            redaction_rules::detector.eq(&rule.detector),
+            redaction_rules::pattern.eq(&rule.pattern),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(r) => Some(r),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete redaction rule")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Returns the label of placeholders (e.g. `[REDACTED:email]`).
+    pub fn label(&self) -> String {
+        match self.detector {
+            RedactionDetector::Custom => self.name.to_string(),
+            _ => self.detector.to_string(),
+        }
+    }
+
+    pub fn with_namespace_id(namespace_id: i64) -> WithNamespaceId {
+        redaction_rules::namespace_id.eq(namespace_id)
+    }
+
+    pub fn with_uuid(s: &str) -> WithUuid {
+        let uuid = Uuid::parse_str(s).unwrap_or_else(|_| Uuid::nil());
+        redaction_rules::uuid.eq(uuid)
+    }
+}
+
+#[cfg(test)]
+pub mod data {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+    use fnv::FnvHashMap;
+
+    use crate::fnvhashmap;
+    use crate::model::namespace::data::NAMESPACES;
+
+    type RedactionRuleFixture = FnvHashMap<&'static str, RedactionRule>;
+
+    lazy_static! {
+        pub static ref REDACTION_RULES: RedactionRuleFixture = fnvhashmap! {
+            "piano's email rule" => RedactionRule {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                namespace_id: NAMESPACES.get("piano").unwrap().id,
+                name: "email".to_string(),
+                detector: RedactionDetector::Email,
+                pattern: None,
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            },
+            "piano's session rule" => RedactionRule {
+                id: 2,
+                uuid: Uuid::new_v4(),
+                namespace_id: NAMESPACES.get("piano").unwrap().id,
+                name: "session".to_string(),
+                detector: RedactionDetector::Custom,
+                pattern: Some(r"sid=[0-9a-f]+".to_string()),
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::redaction_rule::data::REDACTION_RULES;
+    use crate::model::test::run;
+
+    #[test]
+    fn test_new_redaction_rule_default() {
+        let r = NewRedactionRule {
+            ..Default::default()
+        };
+
+        assert_eq!(r.namespace_id, -1);
+        assert_eq!(r.name, "".to_string());
+        assert_eq!(r.detector, RedactionDetector::Custom);
+        assert_eq!(r.pattern, None);
+    }
+
+    #[test]
+    fn test_new_redaction_rule_from_built_in_detector() {
+        let r = NewRedactionRule::from(RequestData {
+            name: Some("jwt".to_string()),
+            detector: Some("jwt".to_string()),
+            pattern: Some(".*".to_string()),
+        });
+
+        assert_eq!(r.detector, RedactionDetector::Jwt);
+        assert_eq!(r.pattern, None);
+    }
+
+    #[test]
+    fn test_label() {
+        let r = REDACTION_RULES.get("piano's email rule").unwrap();
+        assert_eq!(r.label(), "email");
+
+        let r = REDACTION_RULES.get("piano's session rule").unwrap();
+        assert_eq!(r.label(), "session");
+    }
+
+    #[test]
+    fn test_insert() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let r = NewRedactionRule {
+                namespace_id: namespace.id,
+                name: "session".to_string(),
+                detector: RedactionDetector::Custom,
+                pattern: Some(r"sid=[0-9a-f]+".to_string()),
+            };
+            let result = RedactionRule::insert(&r, conn, logger);
+            assert!(result.is_some());
+
+            let rule = result.unwrap();
+            assert_eq!(rule.namespace_id, namespace.id);
+
+            let result = RedactionRule::find_by_uuid_in(
+                &namespace,
+                &rule.uuid.to_string(),
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(rule.clone()));
+
+            let result = RedactionRule::find_all_by_namespace_id(
+                namespace.id,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(vec![rule.clone()]));
+
+            assert!(rule.delete(conn, logger).is_ok());
+
+            let result = RedactionRule::find_all_by_namespace_id(
+                namespace.id,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(vec![]));
+        })
+    }
+}
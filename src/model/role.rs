@@ -1,4 +1,4 @@
-//! # A role for user's Mateship in mateship.rs.
+//! # A type Role for StreamGrant in stream_grant.rs
 //!
 //! ERole represents SQL type value `e_role` and Role is
 //! an Enum holds all possible values.
@@ -11,7 +11,7 @@ use diesel::pg::Pg;
 use diesel::serialize::{self, IsNull, Output, ToSql};
 use serde::Serialize;
 
-#[derive(SqlType)]
+#[derive(QueryId, SqlType)]
 #[postgres(type_name = "e_role")]
 pub struct ERole;
 
@@ -41,7 +41,7 @@ impl ToSql<ERole, Pg> for Role {
             Role::Writer => out.write_all(b"writer")?,
             Role::Member => out.write_all(b"member")?,
             Role::Maintainer => out.write_all(b"maintainer")?,
-            Role::Admin => out.write_all(b"role")?,
+            Role::Admin => out.write_all(b"admin")?,
         }
         Ok(IsNull::No)
     }
@@ -59,9 +59,11 @@ impl FromSql<ERole, Pg> for Role {
     }
 }
 
+// An unknown role (it should be rejected by validation beforehand) falls back
+// to the least privileged one, which can't write anything.
 impl From<String> for Role {
     fn from(s: String) -> Self {
-        match s.to_ascii_uppercase().as_ref() {
+        match s.to_ascii_lowercase().as_ref() {
             "writer" => Role::Writer,
             "member" => Role::Member,
             "maintainer" => Role::Maintainer,
@@ -109,8 +111,9 @@ mod test {
         assert_eq!(Role::Admin, Role::from("admin".to_string()));
         assert_eq!(Role::Admin, Role::from("ADMIN".to_string()));
 
-        // default
-        assert_eq!(Role::Writer, Role::from("unknown".to_string()));
+        // least privileged
+        assert_eq!(Role::Member, Role::from("unknown".to_string()));
+        assert_eq!(Role::Member, Role::from("".to_string()));
     }
 
     #[test]
@@ -126,6 +129,6 @@ mod test {
         assert_eq!(
             vec![Role::Writer, Role::Member, Role::Maintainer, Role::Admin],
             Role::as_vec()
-         );
+        );
     }
 }
@@ -2,13 +2,19 @@
 use std::fmt;
 use std::str;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{Identifiable, Queryable, debug_query, prelude::*};
 use diesel::dsl;
 use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
 use uuid::Uuid;
 
 use crate::logger::Logger;
+use crate::model::membership::{Membership, memberships};
+use crate::model::namespace::{Namespace, namespaces};
+use crate::model::user::User;
+use crate::request::stream::Stream as RequestData;
+use crate::util::{is_reserved_word, slugify};
 
 pub use crate::schema::streams;
 
@@ -17,25 +23,47 @@ pub use crate::schema::streams;
 pub struct NewStream {
     pub namespace_id: i64,
     pub name: String,
+    // generated from the name if empty
+    pub slug: String,
     pub description: Option<String>,
 }
 
+impl fmt::Display for NewStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewStream {name}>", name = &self.name)
+    }
+}
+
 impl Default for NewStream {
     // includes validation errors
     fn default() -> Self {
         Self {
             namespace_id: -1,
             name: "main".to_string(),
+            slug: "".to_string(),
             description: None,
         }
     }
 }
 
+impl From<RequestData> for NewStream {
+    fn from(data: RequestData) -> Self {
+        Self {
+            name: data.name.unwrap_or_else(|| "".to_string()),
+            slug: data.slug.unwrap_or_else(|| "".to_string()),
+            description: data.description,
+
+            ..Default::default()
+        }
+    }
+}
+
 type AllColumns = (
     streams::id,
     streams::uuid,
     streams::namespace_id,
     streams::name,
+    streams::slug,
     streams::description,
     streams::archived_at,
     streams::created_at,
@@ -47,6 +75,7 @@ const ALL_COLUMNS: AllColumns = (
     streams::uuid,
     streams::namespace_id,
     streams::name,
+    streams::slug,
     streams::description,
     streams::archived_at,
     streams::created_at,
@@ -62,26 +91,42 @@ const ALL_COLUMNS: AllColumns = (
     Insertable,
     PartialEq,
     Queryable,
+    Serialize,
 )]
 #[table_name = "streams"]
 #[changeset_options(treat_none_as_null = "true")]
 pub struct Stream {
+    #[serde(skip)]
     pub id: i64,
+    #[serde(with = "uuid_as_string")]
     pub uuid: Uuid,
+    #[serde(skip)]
     pub namespace_id: i64,
     pub name: String,
+    pub slug: String,
     pub description: Option<String>,
     pub archived_at: Option<NaiveDateTime>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
 }
 
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
 impl Clone for Stream {
     fn clone(&self) -> Self {
         Self {
             uuid: self.uuid,
             namespace_id: self.namespace_id,
             name: self.name.clone(),
+            slug: self.slug.clone(),
             description: self.description.clone(),
             archived_at: None,
 
@@ -97,8 +142,11 @@ impl fmt::Display for Stream {
 }
 
 type All = dsl::Select<streams::table, AllColumns>;
+type Archived = dsl::IsNotNull<streams::archived_at>;
+type WithNamespaceId = dsl::Eq<streams::namespace_id, i64>;
 type WithUuid = dsl::Eq<streams::uuid, Uuid>;
 type Visible = dsl::IsNull<streams::archived_at>;
+type ByNamespaceId = dsl::Filter<All, WithNamespaceId>;
 type ByUuid = dsl::Filter<All, WithUuid>;
 
 impl Stream {
@@ -106,10 +154,105 @@ impl Stream {
         streams::table.select(ALL_COLUMNS)
     }
 
+    pub fn archived() -> Archived {
+        streams::archived_at.is_not_null()
+    }
+
+    pub fn by_namespace_id(namespace_id: i64) -> ByNamespaceId {
+        Self::all().filter(Self::with_namespace_id(namespace_id))
+    }
+
     pub fn by_uuid(uuid: &str) -> ByUuid {
         Self::all().filter(Self::with_uuid(uuid))
     }
 
+    /// Returns visible streams in the namespace.
+    pub fn find_all_by_namespace(
+        namespace: &Namespace,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(Self::visible())
+            .order(streams::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns archived streams in the namespace.
+    pub fn find_all_archived_by_namespace(
+        namespace: &Namespace,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(Self::archived())
+            .order(streams::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns an archived stream in the namespace.
+    pub fn find_archived_by_uuid_in(
+        namespace: &Namespace,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(Self::with_uuid(uuid))
+            .filter(Self::archived())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(streams::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn find_by_uuid(
         uuid: &str,
         conn: &PgConnection,
@@ -128,28 +271,266 @@ impl Stream {
         }
     }
 
+    /// Returns a stream which has the name in the namespace (if any).
+    ///
+    /// The name is unique in a namespace, including archived streams.
+    pub fn find_by_name_in(
+        namespace: &Namespace,
+        name: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(streams::name.eq(name))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns a stream which has the slug in the namespace (if any).
+    ///
+    /// The slug is unique in a namespace, including archived streams.
+    pub fn find_by_slug_in(
+        namespace: &Namespace,
+        slug: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(streams::slug.eq(slug))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns a visible stream in the namespace.
+    pub fn find_by_uuid_in(
+        namespace: &Namespace,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(Self::with_uuid(uuid))
+            .filter(Self::visible())
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Returns a stream in the namespaces which the user is a member of.
+    pub fn owned_by_uuid(
+        user: &User,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let namespace_ids = memberships::table
+            .select(memberships::namespace_id)
+            .filter(Membership::with_user(user));
+        let q = Self::by_uuid(uuid)
+            .filter(streams::namespace_id.eq_any(namespace_ids))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
     pub fn insert(
         stream: &NewStream,
         conn: &PgConnection,
         logger: &Logger,
     ) -> Option<Self> {
         let uuid = Uuid::new_v4();
+        let slug = if stream.slug.is_empty() {
+            Self::generate_slug(stream.namespace_id, &stream.name, conn, logger)
+        } else {
+            stream.slug.clone()
+        };
         let q = diesel::insert_into(streams::table).values((
             streams::uuid.eq(uuid),
             streams::namespace_id.eq(stream.namespace_id),
             streams::name.eq(&stream.name),
+            streams::slug.eq(&slug),
             streams::description.eq(&stream.description),
         ));
 
         info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
 
-        match q.get_result::<Self>(conn) {
+        // the count is updated in the same transaction
+        let result = conn.transaction::<Self, diesel::result::Error, _>(|| {
+            let s = q.get_result::<Self>(conn)?;
+
+            let q = diesel::update(
+                namespaces::table.filter(namespaces::id.eq(s.namespace_id)),
+            )
+            .set(namespaces::streams_count.eq(namespaces::streams_count + 1));
+
+            info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+            q.execute(conn)?;
+            Ok(s)
+        });
+        match result {
             Err(e) => {
                 error!(logger, "err: {}", e);
                 None
             },
-            Ok(u) => Some(u),
+            Ok(s) => Some(s),
+        }
+    }
+
+    /// Generates a unique slug in the namespace from the name (e.g.
+    /// "Production" -> "production", "production-2").
+    pub fn generate_slug(
+        namespace_id: i64,
+        name: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> String {
+        let mut base = slugify(name, 64);
+        if base.is_empty() {
+            base = "stream".to_string();
+        }
+
+        let q = streams::table
+            .select(streams::slug)
+            .filter(Self::with_namespace_id(namespace_id))
+            .filter(streams::slug.like(format!("{}%", base)));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let slugs = q.load::<String>(conn).unwrap_or_else(|e| {
+            error!(logger, "err: {}", e);
+            vec![]
+        });
+
+        let mut slug = base.clone();
+        let mut i = 1;
+        while is_reserved_word(&slug) || slugs.contains(&slug) {
+            i += 1;
+            let suffix = format!("-{}", i);
+            slug = format!("{}{}", slugify(&base, 64 - suffix.len()), suffix);
         }
+        slug
+    }
+
+    /// Archives the stream. This should be called in a transaction.
+    pub fn archive(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let now = Utc::now().naive_utc();
+        self.set_archived_at(Some(now), -1, conn, logger)
+    }
+
+    /// Restores the stream. This should be called in a transaction.
+    pub fn unarchive(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        self.set_archived_at(None, 1, conn, logger)
+    }
+
+    fn set_archived_at(
+        &self,
+        archived_at: Option<NaiveDateTime>,
+        diff: i32,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            streams::archived_at.eq(archived_at),
+            streams::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        let s = q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update stream"
+        })?;
+
+        let q = diesel::update(
+            namespaces::table.filter(namespaces::id.eq(s.namespace_id)),
+        )
+        .set(namespaces::streams_count.eq(namespaces::streams_count + diff));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update namespace"
+        })?;
+        Ok(s)
+    }
+
+    /// Updates the name, the slug and the description.
+    pub fn update(
+        &self,
+        name: &str,
+        slug: &str,
+        description: &Option<String>,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            streams::name.eq(name),
+            streams::slug.eq(slug),
+            streams::description.eq(description),
+            streams::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to update stream")
+            },
+            Ok(s) => Ok(s),
+        }
+    }
+
+    pub fn with_namespace_id(namespace_id: i64) -> WithNamespaceId {
+        streams::namespace_id.eq(namespace_id)
     }
 
     pub fn with_uuid(s: &str) -> WithUuid {
@@ -181,6 +562,7 @@ pub mod data {
                 uuid: Uuid::new_v4(),
                 namespace_id: NAMESPACES.get("piano").unwrap().id,
                 name: "oswald's stream".to_string(),
+                slug: "oswald-s-stream".to_string(),
                 description: Some("description".to_string()),
                 archived_at: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -191,6 +573,7 @@ pub mod data {
                 uuid: Uuid::new_v4(),
                 namespace_id: NAMESPACES.get("ball").unwrap().id,
                 name: "weenie's stream".to_string(),
+                slug: "weenie-s-stream".to_string(),
                 description: Some("description".to_string()),
                 archived_at: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -201,6 +584,7 @@ pub mod data {
                 uuid: Uuid::new_v4(),
                 namespace_id: NAMESPACES.get("fish").unwrap().id,
                 name: "personal access token".to_string(),
+                slug: "personal-access-token".to_string(),
                 description: Some("description".to_string()),
                 archived_at: None,
                 created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -227,6 +611,7 @@ mod test {
 
         assert_eq!(at.namespace_id, -1);
         assert_eq!(at.name, "main".to_string());
+        assert_eq!(at.slug, "".to_string());
         assert_eq!(at.description, None);
     }
 
@@ -249,6 +634,7 @@ mod test {
                 .values((
                     streams::uuid.eq(Uuid::new_v4()),
                     streams::name.eq("name"),
+                    streams::slug.eq("name"),
                     streams::namespace_id.eq(namespace.id),
                 ))
                 .get_result::<Stream>(conn)
@@ -272,6 +658,7 @@ mod test {
             let s = NewStream {
                 namespace_id: namespace.id,
                 name: "awesome-melody".to_string(),
+                slug: "".to_string(),
                 description: None,
             };
 
@@ -287,12 +674,153 @@ mod test {
 
             assert!(result.description.is_none());
             assert_eq!(result.namespace_id, namespace.id);
+            assert_eq!(result.slug, "awesome-melody");
 
             let rows_count: i64 = streams::table
                 .count()
                 .first(conn)
                 .expect("Failed to count rows");
             assert_eq!(1, rows_count);
+
+            let streams_count: i32 = namespaces::table
+                .select(namespaces::streams_count)
+                .filter(namespaces::id.eq(namespace.id))
+                .first(conn)
+                .expect("Failed to get a record");
+            assert_eq!(1, streams_count);
+        })
+    }
+
+    #[test]
+    fn test_update() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let s = NewStream {
+                namespace_id: namespace.id,
+                name: "awesome-melody".to_string(),
+                slug: "".to_string(),
+                description: None,
+            };
+            let stream = Stream::insert(&s, conn, logger).unwrap();
+
+            let description = Some("A melody".to_string());
+            let result = stream.update(
+                "lovely-melody",
+                "lovely",
+                &description,
+                conn,
+                logger,
+            );
+            assert!(result.is_ok());
+
+            let stream = result.unwrap();
+            assert_eq!(stream.name, "lovely-melody");
+            assert_eq!(stream.slug, "lovely");
+            assert_eq!(stream.description, description);
+
+            let result = Stream::find_by_name_in(
+                &namespace,
+                "lovely-melody",
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(stream.clone()));
+
+            let result =
+                Stream::find_by_slug_in(&namespace, "lovely", conn, logger);
+            assert_eq!(result, Some(stream.clone()));
+
+            let result =
+                Stream::find_all_by_namespace(&namespace, conn, logger);
+            assert_eq!(result, Some(vec![stream]));
+        })
+    }
+
+    #[test]
+    fn test_generate_slug() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let s = NewStream {
+                namespace_id: namespace.id,
+                name: "Production".to_string(),
+                ..Default::default()
+            };
+            let stream = Stream::insert(&s, conn, logger).unwrap();
+            assert_eq!(stream.slug, "production");
+
+            let slug =
+                Stream::generate_slug(namespace.id, "Production", conn, logger);
+            assert_eq!(slug, "production-2");
+
+            // unique only in the namespace
+            let slug = Stream::generate_slug(-1, "Production", conn, logger);
+            assert_eq!(slug, "production");
+
+            // reserved
+            let slug = Stream::generate_slug(namespace.id, "API", conn, logger);
+            assert_eq!(slug, "api-2");
+        })
+    }
+
+    #[test]
+    fn test_archive_and_unarchive() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let s = NewStream {
+                namespace_id: namespace.id,
+                name: "awesome-melody".to_string(),
+                slug: "".to_string(),
+                description: None,
+            };
+            let stream = Stream::insert(&s, conn, logger).unwrap();
+            let uuid = stream.uuid.to_string();
+
+            let stream = stream.archive(conn, logger).unwrap();
+            assert!(stream.archived_at.is_some());
+
+            let result =
+                Stream::find_by_uuid_in(&namespace, &uuid, conn, logger);
+            assert!(result.is_none());
+            let result = Stream::find_all_archived_by_namespace(
+                &namespace, conn, logger,
+            );
+            assert_eq!(result.map(|v| v.len()), Some(1));
+
+            let streams_count: i32 = namespaces::table
+                .select(namespaces::streams_count)
+                .filter(namespaces::id.eq(namespace.id))
+                .first(conn)
+                .expect("Failed to get a record");
+            assert_eq!(0, streams_count);
+
+            let stream = Stream::find_archived_by_uuid_in(
+                &namespace, &uuid, conn, logger,
+            )
+            .unwrap();
+            let stream = stream.unarchive(conn, logger).unwrap();
+            assert!(stream.archived_at.is_none());
+
+            let streams_count: i32 = namespaces::table
+                .select(namespaces::streams_count)
+                .filter(namespaces::id.eq(namespace.id))
+                .first(conn)
+                .expect("Failed to get a record");
+            assert_eq!(1, streams_count);
         })
     }
 }
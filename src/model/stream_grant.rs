@@ -0,0 +1,327 @@
+//! # StreamGrant
+//!
+//! StreamGrant gives a role to a member of the namespace on a stream. It
+//! refines the membership: members (not owners) who have any grant in the
+//! namespace can access only the granted streams, by the role of each grant.
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+
+pub use crate::model::role::*;
+pub use crate::schema::stream_grants;
+
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+use crate::model::stream::{Stream, streams};
+use crate::model::user::{User, users};
+use crate::request::stream_grant::StreamGrant as RequestData;
+
+/// NewStreamGrant
+#[derive(Debug)]
+pub struct NewStreamGrant {
+    pub stream_id: i64,
+    pub user_id: i64,
+    pub role: Role,
+}
+
+impl fmt::Display for NewStreamGrant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewStreamGrant {role}>", role = &self.role)
+    }
+}
+
+impl Default for NewStreamGrant {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            stream_id: -1,
+            user_id: -1,
+            role: Role::Writer,
+        }
+    }
+}
+
+impl From<RequestData> for NewStreamGrant {
+    fn from(data: RequestData) -> Self {
+        Self {
+            role: Role::from(data.role.unwrap_or_default()),
+
+            ..Default::default()
+        }
+    }
+}
+
+/// StreamGrant
+#[derive(
+    Clone, Debug, Identifiable, Insertable, PartialEq, Queryable, Serialize,
+)]
+#[table_name = "stream_grants"]
+pub struct StreamGrant {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(skip)]
+    pub stream_id: i64,
+    #[serde(skip)]
+    pub user_id: i64,
+    pub role: Role,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for StreamGrant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<StreamGrant {role}>", role = &self.role)
+    }
+}
+
+impl StreamGrant {
+    /// Returns grants on the stream with their users.
+    pub fn find_all_by_stream(
+        stream: &Stream,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<(Self, User)>> {
+        let q = stream_grants::table
+            .inner_join(users::table)
+            .filter(stream_grants::stream_id.eq(stream.id))
+            .order(stream_grants::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<(Self, User)>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns grants of the user on streams in the namespace.
+    pub fn find_all_by_user_in(
+        namespace: &Namespace,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if user.id < 1 {
+            return None;
+        }
+
+        let q = stream_grants::table
+            .inner_join(streams::table)
+            .select(stream_grants::all_columns)
+            .filter(streams::namespace_id.eq(namespace.id))
+            .filter(stream_grants::user_id.eq(user.id));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_stream_and_user(
+        stream: &Stream,
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = stream_grants::table
+            .filter(stream_grants::stream_id.eq(stream.id))
+            .filter(stream_grants::user_id.eq(user.id))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Saves the grant, or changes the role if the user has a grant on the
+    /// stream already.
+    pub fn upsert(
+        grant: &NewStreamGrant,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = diesel::insert_into(stream_grants::table)
+            .values((
+                stream_grants::stream_id.eq(grant.stream_id),
+                stream_grants::user_id.eq(grant.user_id),
+                stream_grants::role.eq(&grant.role),
+            ))
+            .on_conflict((stream_grants::stream_id, stream_grants::user_id))
+            .do_update()
+            .set((
+                stream_grants::role.eq(&grant.role),
+                stream_grants::updated_at.eq(Utc::now().naive_utc()),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(g) => Some(g),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete stream grant")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Deletes all the grants of the user on streams in the namespace (e.g.
+    /// on revocation of the membership).
+    pub fn delete_by_user_in(
+        namespace_id: i64,
+        user_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let stream_ids = streams::table
+            .select(streams::id)
+            .filter(streams::namespace_id.eq(namespace_id));
+        let q = diesel::delete(
+            stream_grants::table
+                .filter(stream_grants::stream_id.eq_any(stream_ids))
+                .filter(stream_grants::user_id.eq(user_id)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete stream grants")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::membership::{Membership, MembershipRole, NewMembership};
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::data::STREAMS;
+    use crate::model::test::run;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_new_stream_grant_default() {
+        let g = NewStreamGrant {
+            ..Default::default()
+        };
+
+        assert_eq!(g.stream_id, -1);
+        assert_eq!(g.user_id, -1);
+        assert_eq!(g.role, Role::Writer);
+    }
+
+    #[test]
+    fn test_new_stream_grant_from() {
+        let g = NewStreamGrant::from(RequestData {
+            role: Some("maintainer".to_string()),
+        });
+
+        assert_eq!(g.role, Role::Maintainer);
+    }
+
+    #[test]
+    fn test_upsert_and_delete() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(&s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let m = NewMembership {
+                namespace_id: namespace.id,
+                user_id: user.id,
+                role: MembershipRole::Member,
+            };
+            let _ = Membership::insert(&m, conn, logger).unwrap();
+
+            let mut g = NewStreamGrant {
+                stream_id: stream.id,
+                user_id: user.id,
+                role: Role::Writer,
+            };
+            let grant = StreamGrant::upsert(&g, conn, logger).unwrap();
+            assert_eq!(grant.role, Role::Writer);
+
+            // changes the role
+            g.role = Role::Admin;
+            let result = StreamGrant::upsert(&g, conn, logger).unwrap();
+            assert_eq!(result.id, grant.id);
+            assert_eq!(result.role, Role::Admin);
+
+            let grants = StreamGrant::find_all_by_user_in(
+                &namespace, &user, conn, logger,
+            )
+            .unwrap();
+            assert_eq!(grants, vec![result]);
+
+            let result = StreamGrant::find_all_by_stream(&stream, conn, logger);
+            assert_eq!(result.unwrap().len(), 1);
+
+            assert!(StreamGrant::delete_by_user_in(
+                namespace.id,
+                user.id,
+                conn,
+                logger
+            )
+            .is_ok());
+            assert!(StreamGrant::find_by_stream_and_user(
+                &stream, &user, conn, logger
+            )
+            .is_none());
+        })
+    }
+}
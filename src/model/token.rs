@@ -179,6 +179,40 @@ pub struct AuthenticationClaims {
     pub iss: String,
     pub exp: usize,
     pub nbf: usize,
+    // session id (only for a browser token)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+}
+
+impl AuthenticationClaims {
+    /// Encodes a browser token bound to the session (as `jti`).
+    pub fn encode_for_session(
+        data: TokenData,
+        session_id: &str,
+        issuer: &str,
+        key_id: &str,
+        secret: &str,
+    ) -> String {
+        let c = Self {
+            sub: data.value,
+            iat: data.granted_at as usize,
+            iss: issuer.to_string(),
+            exp: data.expires_at as usize,
+            nbf: data.granted_at as usize,
+            jti: Some(session_id.to_string()),
+        };
+        Self::encode_claims(&c, key_id, secret)
+    }
+
+    fn encode_claims(c: &Self, key_id: &str, secret: &str) -> String {
+        let h = Header {
+            alg: Self::ALGORITHM,
+            kid: Some(key_id.to_string()),
+            ..Default::default()
+        };
+        encode_data(&h, c, &EncodingKey::from_secret(secret.as_bytes()))
+            .unwrap()
+    }
 }
 
 impl Claims for AuthenticationClaims {
@@ -209,14 +243,24 @@ impl Claims for AuthenticationClaims {
             ..Validation::default()
         };
 
-        match decode_token::<Self>(
+        let claims = decode_token::<Self>(
             token,
             &DecodingKey::from_secret(secret.as_bytes()),
             &v,
-        ) {
-            Ok(v) => Ok(v.claims),
-            Err(e) => Err(e),
+        )?
+        .claims;
+
+        // NOTE:
+        // `exp` is validated here instead of Validation, because a personal
+        // access token has no expiration (exp: 0). A browser token must have
+        // it (see FromRequest for User).
+        let now = Utc::now().timestamp() as u64;
+        if claims.exp != 0 && (claims.exp as u64) + Self::LEEWAY < now {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::ExpiredSignature,
+            ));
         }
+        Ok(claims)
     }
 
     fn encode(
@@ -232,15 +276,9 @@ impl Claims for AuthenticationClaims {
             iss: issuer.to_string(),
             exp: data.expires_at as usize,
             nbf: data.granted_at as usize,
+            jti: None,
         };
-
-        let h = Header {
-            alg: Self::ALGORITHM,
-            kid: Some(key_id.to_string()),
-            ..Default::default()
-        };
-        encode_data(&h, &c, &EncodingKey::from_secret(secret.as_bytes()))
-            .unwrap()
+        Self::encode_claims(&c, key_id, secret)
     }
 
     fn get_subject(&self) -> String {
@@ -429,4 +467,100 @@ mod test {
 
         assert_eq!(claims.nbf, data.granted_at as usize);
     }
+
+    #[test]
+    fn authentication_claims_decode_expired() {
+        let granted_at = Utc::now() - Duration::hours(1);
+        let data = TokenData {
+            value: "dummy".to_string(),
+            granted_at: granted_at.timestamp(),
+            expires_at: (granted_at + Duration::minutes(15)).timestamp(),
+        };
+
+        let token = AuthenticationClaims::encode(
+            data,
+            &CONFIG.authentication_token_issuer,
+            &CONFIG.authentication_token_key_id,
+            &CONFIG.authentication_token_secret,
+        );
+
+        let result = AuthenticationClaims::decode(
+            &token,
+            &CONFIG.authentication_token_issuer,
+            &CONFIG.authentication_token_secret,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn authentication_claims_decode_success() {
+        let now = Utc::now();
+        for expires_at in &[(now + Duration::minutes(15)).timestamp(), 0] {
+            let data = TokenData {
+                value: "dummy".to_string(),
+                granted_at: now.timestamp(),
+                expires_at: *expires_at,
+            };
+
+            let token = AuthenticationClaims::encode(
+                data,
+                &CONFIG.authentication_token_issuer,
+                &CONFIG.authentication_token_key_id,
+                &CONFIG.authentication_token_secret,
+            );
+
+            let claims = AuthenticationClaims::decode(
+                &token,
+                &CONFIG.authentication_token_issuer,
+                &CONFIG.authentication_token_secret,
+            )
+            .ok()
+            .unwrap();
+            assert_eq!(claims.sub, "dummy");
+            assert_eq!(claims.exp, *expires_at as usize);
+        }
+    }
+
+    #[test]
+    fn authentication_claims_encode_for_session() {
+        let now = Utc::now();
+        let data = TokenData {
+            value: "dummy".to_string(),
+            granted_at: now.timestamp(),
+            expires_at: (now + Duration::minutes(15)).timestamp(),
+        };
+
+        let token = AuthenticationClaims::encode_for_session(
+            data.clone(),
+            "session",
+            &CONFIG.authentication_token_issuer,
+            &CONFIG.authentication_token_key_id,
+            &CONFIG.authentication_token_secret,
+        );
+
+        let claims = AuthenticationClaims::decode(
+            &token,
+            &CONFIG.authentication_token_issuer,
+            &CONFIG.authentication_token_secret,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(claims.jti, Some("session".to_string()));
+
+        let token = AuthenticationClaims::encode(
+            data,
+            &CONFIG.authentication_token_issuer,
+            &CONFIG.authentication_token_key_id,
+            &CONFIG.authentication_token_secret,
+        );
+
+        let claims = AuthenticationClaims::decode(
+            &token,
+            &CONFIG.authentication_token_issuer,
+            &CONFIG.authentication_token_secret,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(claims.jti, None);
+    }
 }
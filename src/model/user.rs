@@ -2,11 +2,13 @@ use std::any::Any;
 use std::fmt;
 use std::str;
 
-use bcrypt::{hash, verify};
+use argon2::{ThreadMode, Variant, Version};
+use bcrypt::verify;
 use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::{Identifiable, Queryable, debug_query, prelude::*};
 use diesel::pg::{Pg, PgConnection};
 use diesel::result::Error;
+use rand::prelude::*;
 use uuid::Uuid;
 
 pub use crate::model::user_state::*;
@@ -21,7 +23,12 @@ pub use crate::model::token::{
 pub use crate::schema::users;
 pub use crate::schema::user_emails;
 
+use crate::config::Config;
 use crate::model::{Activatable, Authenticatable, Verifiable};
+use crate::model::membership::memberships;
+use crate::schema::{
+    digest_subscriptions, invitations, stream_grants, user_two_factors,
+};
 use crate::model::user_email::{
     UserEmail, UserEmailRole, UserEmailIdentificationState,
 };
@@ -29,14 +36,63 @@ use crate::logger::Logger;
 use crate::request::user::registration::UserRegistration as RequestData;
 use crate::util::generate_random_hash;
 
-const BCRYPT_COST: u32 = 12;
+const PASSWORD_HASH_LENGTH: u32 = 32;
+const PASSWORD_SALT_LENGTH: usize = 16;
 const RESET_PASSWORD_HASH_LENGTH: i32 = 128;
 const RESET_PASSWORD_HASH_SOURCE: &[u8] =
     b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
 
-/// Returns encrypted password hash as bytes using bcrypt.
-pub fn encrypt_password(password: &str) -> Option<Vec<u8>> {
-    match hash(password, BCRYPT_COST) {
+/// PasswordHashAlgorithm is an algorithm of a stored password hash.
+///
+/// Hashes made by bcrypt (before Argon2id) are still verified, and they are
+/// upgraded on the next sign in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordHashAlgorithm {
+    Argon2id,
+    Bcrypt,
+}
+
+impl PasswordHashAlgorithm {
+    /// Detects the algorithm by the prefix of the encoded hash.
+    pub fn detect(encoded: &str) -> Option<Self> {
+        if encoded.starts_with("$argon2id$") {
+            Some(Self::Argon2id)
+        } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|p| encoded.starts_with(p))
+        {
+            Some(Self::Bcrypt)
+        } else {
+            None
+        }
+    }
+}
+
+fn password_hash_config(config: &Config) -> argon2::Config<'static> {
+    argon2::Config {
+        variant: Variant::Argon2id,
+        version: Version::Version13,
+        mem_cost: config.password_hash_memory_cost,
+        time_cost: config.password_hash_time_cost,
+        lanes: config.password_hash_parallelism,
+        thread_mode: ThreadMode::Sequential,
+        secret: &[],
+        ad: &[],
+        hash_length: PASSWORD_HASH_LENGTH,
+    }
+}
+
+/// Returns encrypted password hash as bytes using Argon2id.
+///
+/// The hash is encoded in PHC string format, and it carries the algorithm
+/// and the parameters (e.g. `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`).
+pub fn encrypt_password(password: &str, config: &Config) -> Option<Vec<u8>> {
+    let salt: [u8; PASSWORD_SALT_LENGTH] = thread_rng().gen();
+    match argon2::hash_encoded(
+        password.as_bytes(),
+        &salt,
+        &password_hash_config(config),
+    ) {
         Ok(v) => Some(v.into_bytes()),
         Err(e) => {
             println!("err: {:?}", e);
@@ -45,6 +101,32 @@ pub fn encrypt_password(password: &str) -> Option<Vec<u8>> {
     }
 }
 
+/// Hashes the password against a fixed dummy salt, and returns always false.
+///
+/// This is for a login as an user who doesn't exist. It takes as long as the
+/// verification of a real password, so that the response time doesn't reveal
+/// whether the user exists or not.
+pub fn verify_dummy_password(password: &str, config: &Config) -> bool {
+    let _ = argon2::hash_encoded(
+        password.as_bytes(),
+        &[0; PASSWORD_SALT_LENGTH],
+        &password_hash_config(config),
+    );
+    false
+}
+
+/// Returns true if the hash has been made by another algorithm or by other
+/// parameters than the current ones in config.
+pub fn needs_rehash(encoded: &str, config: &Config) -> bool {
+    let prefix = format!(
+        "$argon2id$v=19$m={},t={},p={}$",
+        config.password_hash_memory_cost,
+        config.password_hash_time_cost,
+        config.password_hash_parallelism,
+    );
+    !encoded.starts_with(&prefix)
+}
+
 /// NewUser
 #[derive(Debug)]
 pub struct NewUser {
@@ -92,8 +174,8 @@ impl<'a> From<&'a RequestData> for NewUser {
 impl NewUser {
     // NOTE:
     // run asynchronously? It (encrypt_password) may slow.
-    pub fn set_password(&mut self, password: &str) {
-        self.password = encrypt_password(password).unwrap();
+    pub fn set_password(&mut self, password: &str, config: &Config) {
+        self.password = encrypt_password(password, config).unwrap();
     }
 }
 
@@ -122,6 +204,9 @@ impl fmt::Display for User {
 }
 
 impl User {
+    /// Checks whether the email is used by nobody, as either the primary
+    /// address or a secondary one. A pending secondary address isn't counted
+    /// once its token has expired.
     pub fn check_email_uniqueness(
         email: &str,
         conn: &PgConnection,
@@ -132,6 +217,24 @@ impl User {
             .filter(users::email.eq(email))
             .limit(1);
 
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        if !matches!(q.load::<i64>(conn), Ok(ref v) if v.is_empty()) {
+            return false;
+        }
+
+        let now = Utc::now().naive_utc();
+        let q = user_emails::table
+            .select(user_emails::id)
+            .filter(user_emails::email.eq(email))
+            .filter(
+                user_emails::identification_state
+                    .eq(UserEmailIdentificationState::Done)
+                    .or(user_emails::role.eq(UserEmailRole::Primary))
+                    .or(user_emails::identification_token_expires_at.is_null())
+                    .or(user_emails::identification_token_expires_at.gt(now)),
+            )
+            .limit(1);
+
         info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
         matches!(q.load::<i64>(conn), Ok(ref v) if v.is_empty())
     }
@@ -224,6 +327,35 @@ impl User {
         }
     }
 
+    /// Returns active users who are members of the namespace.
+    pub fn find_all_by_namespace_id(
+        namespace_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if namespace_id < 1 {
+            return None;
+        }
+
+        let q = users::table
+            .inner_join(memberships::table)
+            .select(users::all_columns)
+            .filter(memberships::namespace_id.eq(namespace_id))
+            .filter(memberships::revoked_at.is_null())
+            .filter(users::state.eq(UserState::Active))
+            .order(users::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
     pub fn find_by_primary_email_in_pending(
         s: &str,
         conn: &PgConnection,
@@ -262,7 +394,10 @@ impl User {
             return None;
         }
 
-        let u = Uuid::parse_str(s).unwrap();
+        let u = match Uuid::parse_str(s) {
+            Ok(u) => u,
+            Err(_) => return None,
+        };
         let q = users::table
             .filter(users::uuid.eq(u))
             .filter(users::state.eq(UserState::Active))
@@ -285,14 +420,12 @@ impl User {
     ) -> Option<Self> {
         let t = T::decode(token, issuer, secret).expect("invalid value");
         let c = &t as &dyn Any;
+        // NOTE:
+        // PersonalAccessTokenClaims is the same type as this, use
+        // find_by_personal_access_token for it instead.
         if let Some(claims) = c.downcast_ref::<BrowserCookieTokenClaims>() {
             let uuid = claims.get_subject();
             return Self::find_by_uuid(&uuid, conn, logger);
-        } else if let Some(claims) =
-            c.downcast_ref::<PersonalAccessTokenClaims>()
-        {
-            let token = claims.get_subject();
-            return Self::find_by_access_token(&token, conn, logger);
         } else if let Some(claims) = c.downcast_ref::<VerificationClaims>() {
             let token = claims.get_subject();
             return Self::load_by_concrete_token(&token, conn, logger).ok();
@@ -300,6 +433,26 @@ impl User {
         None
     }
 
+    /// Finds the user by a personal access token.
+    ///
+    /// A browser token is rejected, because it has an expiration and a
+    /// session id (jti) which a personal access token doesn't have. It must
+    /// be checked whether the session has been revoked or not.
+    pub fn find_by_personal_access_token(
+        token: &str,
+        issuer: &str,
+        secret: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let c = PersonalAccessTokenClaims::decode(token, issuer, secret).ok()?;
+        if c.exp != 0 || c.jti.is_some() {
+            error!(logger, "err: not a personal access token");
+            return None;
+        }
+        Self::find_by_access_token(&c.get_subject(), conn, logger)
+    }
+
     pub fn find_by_access_token(
         token: &str,
         conn: &PgConnection,
@@ -362,8 +515,47 @@ impl User {
         )
     }
 
-    pub fn change_password(&mut self, password: &str) {
-        self.password = encrypt_password(password).unwrap();
+    pub fn change_password(&mut self, password: &str, config: &Config) {
+        self.password = encrypt_password(password, config).unwrap();
+    }
+
+    /// Returns true if the password hash should be upgraded (see
+    /// `needs_rehash`).
+    pub fn password_needs_rehash(&self, config: &Config) -> bool {
+        str::from_utf8(&self.password)
+            .map(|encoded| needs_rehash(encoded, config))
+            .unwrap_or(true)
+    }
+
+    /// Saves a new hash of the password with the current algorithm and
+    /// parameters. The password must have been verified beforehand (e.g. on
+    /// sign in), and it's not saved if the hash has been changed meanwhile.
+    pub fn rehash_password(
+        &mut self,
+        password: &str,
+        config: &Config,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let current = self.password.clone();
+        self.change_password(password, config);
+
+        let q = diesel::update(
+            users::table
+                .filter(users::id.eq(self.id))
+                .filter(users::password.eq(&current)),
+        )
+        .set(users::password.eq(&self.password));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to rehash password")
+            },
+            Ok(_) => Ok(()),
+        }
     }
 
     pub fn grant_token<T: Claims>(
@@ -395,6 +587,133 @@ impl User {
             Ok(user) => Ok(user.reset_password_token.unwrap()),
         }
     }
+
+    /// Changes the password of the active user by the user themselves (not
+    /// via password reset) through `change_password`.
+    pub fn renew_password(
+        &mut self,
+        new_password: &str,
+        config: &Config,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        if self.state != UserState::Active {
+            return Err("password can't be changed");
+        }
+        self.change_password(new_password, config);
+
+        let q = diesel::update(users::table.find(self.id)).set((
+            users::password.eq(&self.password),
+            users::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to change password")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Marks the user as deleted. The user can't sign in anymore, and the
+    /// records which belong to the user are removed later by `delete`.
+    pub fn deregister(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if self.state != UserState::Active {
+            return Err("user can't be deregistered");
+        }
+
+        let q = diesel::update(self).set((
+            users::state.eq(UserState::Deleted),
+            users::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to deregister user"
+        })
+    }
+
+    /// Deletes the deregistered user and all records belong to it.
+    ///
+    /// Messages must be anonymized beforehand in batches (see
+    /// `Message::anonymize_by_user_id`). This should be called in a
+    /// transaction.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        if self.state != UserState::Deleted {
+            return Err("user must be deregistered beforehand");
+        }
+
+        let q = diesel::delete(
+            access_tokens::table
+                .filter(access_tokens::agent_id.eq(self.id))
+                .filter(access_tokens::agent_type.eq(AgentType::Person)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete access tokens")?;
+
+        let q = diesel::delete(
+            digest_subscriptions::table
+                .filter(digest_subscriptions::user_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn)
+            .map_err(|_| "failed to delete digest subscriptions")?;
+
+        let q = diesel::delete(
+            invitations::table.filter(invitations::inviter_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete invitations")?;
+
+        let q = diesel::delete(
+            stream_grants::table.filter(stream_grants::user_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete stream grants")?;
+
+        let q = diesel::delete(
+            user_two_factors::table
+                .filter(user_two_factors::user_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn)
+            .map_err(|_| "failed to delete two-factor authentication")?;
+
+        let q = diesel::delete(
+            memberships::table.filter(memberships::user_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete memberships")?;
+
+        let q = diesel::delete(
+            user_emails::table.filter(user_emails::user_id.eq(self.id)),
+        );
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|_| "failed to delete user emails")?;
+
+        let q = diesel::delete(self);
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete user")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
 }
 
 impl Activatable for User {
@@ -458,10 +777,11 @@ impl Authenticatable for User {
     fn update_password(
         &mut self,
         new_password: &str,
+        config: &Config,
         conn: &PgConnection,
         logger: &Logger,
     ) -> Result<(), &'static str> {
-        self.change_password(new_password);
+        self.change_password(new_password, config);
 
         let q = diesel::update(
             users::table
@@ -487,7 +807,20 @@ impl Authenticatable for User {
     /// Checks whether the password given as an argument is valid or not.
     /// This takes a bit long til returning the result.
     fn verify_password(&self, password: &str) -> bool {
-        verify(password, str::from_utf8(&self.password).unwrap()).unwrap()
+        let encoded = match str::from_utf8(&self.password) {
+            Ok(v) => v,
+            Err(_) => return false,
+        };
+        match PasswordHashAlgorithm::detect(encoded) {
+            Some(PasswordHashAlgorithm::Argon2id) => {
+                argon2::verify_encoded(encoded, password.as_bytes())
+                    .unwrap_or(false)
+            },
+            Some(PasswordHashAlgorithm::Bcrypt) => {
+                verify(password, encoded).unwrap_or(false)
+            },
+            None => false,
+        }
     }
 }
 
@@ -638,6 +971,7 @@ pub mod data {
 mod test {
     use super::*;
 
+    use crate::model::access_token::data::ACCESS_TOKENS;
     use crate::model::test::run;
     use crate::model::token::{
         AuthenticationClaims, BrowserCookieTokenClaims, Claims, TokenData,
@@ -680,6 +1014,7 @@ mod test {
             username: "hennry".to_string(),
             email: "hennry@example.org".to_string(),
             password: "password".to_string(),
+            invitation_token: None,
         };
 
         let u = NewUser::from(&data);
@@ -713,6 +1048,55 @@ mod test {
                 conn,
                 logger,
             ));
+
+            // secondary address
+            let mut ue =
+                USER_EMAILS.get("oswald's primary address").unwrap().clone();
+            ue.email = Some("oswald.new@example.org".to_string());
+            ue.role = UserEmailRole::General;
+            let _ = diesel::insert_into(user_emails::table)
+                .values(&ue)
+                .execute(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            assert!(!User::check_email_uniqueness(
+                "oswald.new@example.org",
+                conn,
+                logger,
+            ));
+
+            // pending secondary address
+            let now = Utc::now().naive_utc();
+            ue.id = 2;
+            ue.email = Some("oswald.pending@example.org".to_string());
+            ue.identification_state = UserEmailIdentificationState::Pending;
+            ue.identification_token_expires_at = Some(now + Duration::hours(1));
+            let id = diesel::insert_into(user_emails::table)
+                .values(&ue)
+                .returning(user_emails::id)
+                .get_result::<i64>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            assert!(!User::check_email_uniqueness(
+                "oswald.pending@example.org",
+                conn,
+                logger,
+            ));
+
+            // it's released once the token has expired
+            diesel::update(user_emails::table.find(id))
+                .set(
+                    user_emails::identification_token_expires_at
+                        .eq(now - Duration::hours(1)),
+                )
+                .execute(conn)
+                .unwrap_or_else(|e| panic!("Error at updating: {}", e));
+
+            assert!(User::check_email_uniqueness(
+                "oswald.pending@example.org",
+                conn,
+                logger,
+            ));
         });
     }
 
@@ -938,6 +1322,75 @@ mod test {
         });
     }
 
+    #[test]
+    fn test_find_by_personal_access_token() {
+        run(|conn, config, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let t = ACCESS_TOKENS.get("oswald's personal token").unwrap();
+            let _ = diesel::insert_into(access_tokens::table)
+                .values(t)
+                .get_result::<AccessToken>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let now = Utc::now().timestamp();
+            let find = |token: &str| {
+                User::find_by_personal_access_token(
+                    token,
+                    &config.authentication_token_issuer,
+                    &config.authentication_token_secret,
+                    conn,
+                    logger,
+                )
+            };
+
+            let data = TokenData {
+                value: "token".to_string(),
+                granted_at: now,
+                expires_at: 0,
+            };
+            let token = AuthenticationClaims::encode(
+                data,
+                &config.authentication_token_issuer,
+                &config.authentication_token_key_id,
+                &config.authentication_token_secret,
+            );
+            assert_eq!(find(&token).map(|u| u.id), Some(user.id));
+
+            // a browser token (even if its subject is the value)
+            let data = TokenData {
+                value: "token".to_string(),
+                granted_at: now,
+                expires_at: now + 900,
+            };
+            let token = AuthenticationClaims::encode_for_session(
+                data,
+                "session",
+                &config.authentication_token_issuer,
+                &config.authentication_token_key_id,
+                &config.authentication_token_secret,
+            );
+            assert!(find(&token).is_none());
+
+            let data = TokenData {
+                value: user.uuid.to_urn().to_string(),
+                granted_at: now,
+                expires_at: 0,
+            };
+            let token = AuthenticationClaims::encode(
+                data,
+                &config.authentication_token_issuer,
+                &config.authentication_token_key_id,
+                &config.authentication_token_secret,
+            );
+            assert!(find(&token).is_none());
+        });
+    }
+
     #[test]
     fn test_find_by_token_with_authentication_claims() {
         run(|conn, config, logger| {
@@ -1063,7 +1516,7 @@ mod test {
 
     #[test]
     fn test_insert() {
-        run(|conn, _, logger| {
+        run(|conn, config, logger| {
             let mut u = NewUser {
                 name: Some("Johnny Snowman".to_string()),
                 username: "johnny".to_string(),
@@ -1071,7 +1524,7 @@ mod test {
 
                 ..Default::default()
             };
-            u.set_password("password");
+            u.set_password("password", config);
             let result = User::insert(&u, conn, logger);
             assert!(result.is_some());
 
@@ -1082,4 +1535,156 @@ mod test {
             assert_eq!(1, rows_count);
         })
     }
+
+    #[test]
+    fn test_renew_password() {
+        run(|conn, config, logger| {
+            let u = USERS.get("weenie").unwrap();
+            let mut user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            assert!(user
+                .renew_password("n3w-Passw0rd", config, conn, logger)
+                .is_ok());
+            assert!(user.verify_password("n3w-Passw0rd"));
+
+            let user = User::find_by_id(user.id, conn, logger).unwrap();
+            assert!(user.verify_password("n3w-Passw0rd"));
+
+            let mut user = user.deregister(conn, logger).unwrap();
+            assert!(user
+                .renew_password("an0ther-Pass", config, conn, logger)
+                .is_err());
+        })
+    }
+
+    #[test]
+    fn test_password_hash_algorithm_detect() {
+        assert_eq!(
+            PasswordHashAlgorithm::detect("$argon2id$v=19$m=1024,t=1,p=1$"),
+            Some(PasswordHashAlgorithm::Argon2id)
+        );
+        assert_eq!(
+            PasswordHashAlgorithm::detect("$2b$12$"),
+            Some(PasswordHashAlgorithm::Bcrypt)
+        );
+        assert_eq!(
+            PasswordHashAlgorithm::detect("$2y$04$"),
+            Some(PasswordHashAlgorithm::Bcrypt)
+        );
+        assert_eq!(PasswordHashAlgorithm::detect("$argon2i$v=19$"), None);
+        assert_eq!(PasswordHashAlgorithm::detect("Pa$$w0rd"), None);
+    }
+
+    #[test]
+    fn test_encrypt_password() {
+        run(|_, config, _| {
+            let password = encrypt_password("Pa$$w0rd", config).unwrap();
+            let encoded = str::from_utf8(&password).unwrap();
+            assert!(encoded.starts_with(&format!(
+                "$argon2id$v=19$m={},t={},p={}$",
+                config.password_hash_memory_cost,
+                config.password_hash_time_cost,
+                config.password_hash_parallelism,
+            )));
+            assert!(!needs_rehash(encoded, config));
+            assert_ne!(password, encrypt_password("Pa$$w0rd", config).unwrap());
+
+            let mut c = config.clone();
+            c.password_hash_time_cost += 1;
+            assert!(needs_rehash(encoded, &c));
+
+            let encoded = bcrypt::hash("Pa$$w0rd", 4).unwrap();
+            assert!(needs_rehash(&encoded, config));
+        })
+    }
+
+    #[test]
+    fn test_verify_dummy_password() {
+        run(|_, config, _| {
+            assert!(!verify_dummy_password("Pa$$w0rd", config));
+            assert!(!verify_dummy_password("", config));
+        })
+    }
+
+    #[test]
+    fn test_verify_password() {
+        run(|_, config, _| {
+            let mut user = USERS.get("oswald").unwrap().clone();
+            user.change_password("Pa$$w0rd", config);
+            assert!(user.verify_password("Pa$$w0rd"));
+            assert!(!user.verify_password("password"));
+            assert!(!user.password_needs_rehash(config));
+
+            // bcrypt
+            user.password = bcrypt::hash("Pa$$w0rd", 4).unwrap().into_bytes();
+            assert!(user.verify_password("Pa$$w0rd"));
+            assert!(!user.verify_password("password"));
+            assert!(user.password_needs_rehash(config));
+
+            // unknown
+            user.password = b"Pa$$w0rd".to_vec();
+            assert!(!user.verify_password("Pa$$w0rd"));
+        })
+    }
+
+    #[test]
+    fn test_rehash_password() {
+        run(|conn, config, logger| {
+            let mut u = USERS.get("weenie").unwrap().clone();
+            u.password = bcrypt::hash("Pa$$w0rd", 4).unwrap().into_bytes();
+            let mut user = diesel::insert_into(users::table)
+                .values(&u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+            let mut stale = user.clone();
+
+            assert!(user
+                .rehash_password("Pa$$w0rd", config, conn, logger)
+                .is_ok());
+
+            let user = User::find_by_id(user.id, conn, logger).unwrap();
+            assert!(!user.password_needs_rehash(config));
+            assert!(user.verify_password("Pa$$w0rd"));
+
+            // it has been changed already
+            assert!(stale
+                .rehash_password("Pa$$w0rd", config, conn, logger)
+                .is_ok());
+            let u = User::find_by_id(user.id, conn, logger).unwrap();
+            assert_eq!(u.password, user.password);
+        })
+    }
+
+    #[test]
+    fn test_deregister_and_delete() {
+        run(|conn, _, logger| {
+            let u = USERS.get("weenie").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            // not deregistered yet
+            assert!(user.delete(conn, logger).is_err());
+
+            let user = user.deregister(conn, logger).unwrap();
+            assert_eq!(user.state, UserState::Deleted);
+            assert!(user.deregister(conn, logger).is_err());
+
+            let uuid = user.uuid.to_string();
+            let result = User::find_by_uuid(&uuid, conn, logger);
+            assert!(result.is_none());
+
+            assert!(user.delete(conn, logger).is_ok());
+
+            let rows_count: i64 = users::table
+                .count()
+                .first(conn)
+                .expect("Failed to count rows");
+            assert_eq!(0, rows_count);
+        })
+    }
 }
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::{Associations, Identifiable, Queryable, debug_query, prelude::*};
 use diesel::pg::{Pg, PgConnection};
 
@@ -11,7 +11,7 @@ pub use crate::schema::user_emails;
 
 use crate::logger::Logger;
 use crate::model::Activatable;
-use crate::model::user::User;
+use crate::model::user::{User, users};
 use crate::util::generate_random_hash;
 
 const VERIFICATION_HASH_LENGTH: i32 = 128;
@@ -142,6 +142,79 @@ impl UserEmail {
         }
     }
 
+    /// Returns all the addresses of the user, the primary one first.
+    pub fn find_all_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = user_emails::table
+            .filter(user_emails::user_id.eq(user.id))
+            .order((user_emails::role.desc(), user_emails::id.asc()));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_email_of(
+        user: &User,
+        email: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if email.is_empty() {
+            return None;
+        }
+
+        let q = user_emails::table
+            .filter(user_emails::user_id.eq(user.id))
+            .filter(user_emails::email.eq(email))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Counts pending (secondary) addresses of the user whose token has not
+    /// expired yet.
+    pub fn count_pending_by_user(
+        user: &User,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<i64> {
+        let now = Utc::now().naive_utc();
+        let q = user_emails::table
+            .filter(user_emails::user_id.eq(user.id))
+            .filter(user_emails::role.eq(UserEmailRole::General))
+            .filter(
+                user_emails::identification_state
+                    .eq(UserEmailIdentificationState::Pending),
+            )
+            .filter(user_emails::identification_token_expires_at.gt(now))
+            .count();
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<i64>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(n) => Some(n),
+        }
+    }
+
     pub fn generate_token() -> String {
         generate_random_hash(VERIFICATION_HASH_SOURCE, VERIFICATION_HASH_LENGTH)
     }
@@ -164,7 +237,7 @@ impl UserEmail {
         let q = diesel::insert_into(user_emails::table).values((
             user_emails::user_id.eq(&user_email.user_id),
             Some(user_emails::email.eq(&user_email.email)),
-            user_emails::role.eq(UserEmailRole::Primary),
+            user_emails::role.eq(&user_email.role),
             user_emails::identification_state
                 .eq(UserEmailIdentificationState::Pending),
         ));
@@ -215,6 +288,112 @@ impl UserEmail {
     pub fn is_primary(&self) -> bool {
         self.role == UserEmailRole::Primary
     }
+
+    pub fn is_verified(&self) -> bool {
+        self.identification_state == UserEmailIdentificationState::Done
+    }
+
+    /// Makes the verified address primary instead of the current one, and
+    /// updates `users.email` as well. This should be called in a transaction.
+    pub fn promote(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        if self.is_primary() || !self.is_verified() || self.email.is_none() {
+            return Err("email can't be primary");
+        }
+
+        let now = Utc::now().naive_utc();
+
+        // the current one must be demoted first (see the unique index)
+        let q = diesel::update(
+            user_emails::table
+                .filter(user_emails::user_id.eq(self.user_id))
+                .filter(user_emails::role.eq(UserEmailRole::Primary)),
+        )
+        .set((
+            user_emails::role.eq(UserEmailRole::General),
+            user_emails::updated_at.eq(now),
+        ));
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to demote primary email"
+        })?;
+
+        let q = diesel::update(self).set((
+            user_emails::role.eq(UserEmailRole::Primary),
+            user_emails::updated_at.eq(now),
+        ));
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        let user_email = q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to promote email"
+        })?;
+
+        let q = diesel::update(users::table.find(self.user_id)).set((
+            users::email.eq(self.email.as_ref().unwrap()),
+            users::updated_at.eq(now),
+        ));
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to update email of user"
+        })?;
+
+        Ok(user_email)
+    }
+
+    /// Deletes the pending (secondary) address of anyone if its token has
+    /// expired. It releases the address to be added again.
+    pub fn delete_expired_by_email(
+        email: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<usize, &'static str> {
+        let now = Utc::now().naive_utc();
+        let q = diesel::delete(
+            user_emails::table
+                .filter(user_emails::email.eq(email))
+                .filter(user_emails::role.eq(UserEmailRole::General))
+                .filter(
+                    user_emails::identification_state
+                        .eq(UserEmailIdentificationState::Pending),
+                )
+                .filter(user_emails::identification_token_expires_at.le(now)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.execute(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to delete expired emails"
+        })
+    }
+
+    /// Deletes the address. The primary one can't be deleted.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        if self.is_primary() {
+            return Err("primary email can't be deleted");
+        }
+
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete email")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
 }
 
 impl Activatable for UserEmail {
@@ -518,6 +697,56 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_promote_and_delete() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let ue = NewUserEmail::from(&user);
+            let primary = UserEmail::insert(&ue, conn, logger).unwrap();
+
+            let ue = NewUserEmail {
+                user_id: user.id,
+                email: "oswald@example.com".to_string(),
+
+                ..Default::default()
+            };
+            let general = UserEmail::insert(&ue, conn, logger).unwrap();
+            assert_eq!(general.role, UserEmailRole::General);
+
+            // not verified yet
+            assert!(general.promote(conn, logger).is_err());
+
+            general.activate(conn, logger).unwrap();
+            let general = UserEmail::find_by_id(general.id, conn, logger)
+                .unwrap()
+                .promote(conn, logger)
+                .unwrap();
+            assert!(general.is_primary());
+
+            let email = users::table
+                .select(users::email)
+                .filter(users::id.eq(user.id))
+                .first::<String>(conn)
+                .expect("Failed to load");
+            assert_eq!(email, "oswald@example.com");
+
+            let result = UserEmail::find_all_by_user(&user, conn, logger)
+                .unwrap();
+            assert_eq!(result.len(), 2);
+            assert_eq!(result[0].id, general.id);
+            assert_eq!(result[1].id, primary.id);
+            assert_eq!(result[1].role, UserEmailRole::General);
+
+            assert!(general.delete(conn, logger).is_err());
+            assert!(result[1].delete(conn, logger).is_ok());
+        })
+    }
+
     #[test]
     fn test_insert() {
         run(|conn, _, logger| {
@@ -655,4 +884,57 @@ mod test {
             );
         });
     }
+
+    #[test]
+    fn test_count_pending_and_delete_expired() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let now = Utc::now().naive_utc();
+            for (i, hours) in [1, -1].iter().enumerate() {
+                let ue = NewUserEmail {
+                    user_id: user.id,
+                    email: format!("oswald+{}@example.org", i),
+
+                    ..Default::default()
+                };
+                let user_email = UserEmail::insert(&ue, conn, logger).unwrap();
+                diesel::update(&user_email)
+                    .set(
+                        user_emails::identification_token_expires_at
+                            .eq(now + Duration::hours(*hours)),
+                    )
+                    .execute(conn)
+                    .unwrap_or_else(|e| panic!("Error updating: {}", e));
+            }
+
+            // the expired one isn't counted
+            let result = UserEmail::count_pending_by_user(&user, conn, logger);
+            assert_eq!(result, Some(1));
+
+            let result = UserEmail::delete_expired_by_email(
+                "oswald+0@example.org",
+                conn,
+                logger,
+            );
+            assert_eq!(result, Ok(0));
+
+            let result = UserEmail::delete_expired_by_email(
+                "oswald+1@example.org",
+                conn,
+                logger,
+            );
+            assert_eq!(result, Ok(1));
+
+            let rows_count: i64 = user_emails::table
+                .count()
+                .first(conn)
+                .expect("failed to count rows");
+            assert_eq!(1, rows_count);
+        });
+    }
 }
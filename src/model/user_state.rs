@@ -22,6 +22,7 @@ pub struct EUserState;
 pub enum UserState {
     Pending, // default
     Active,
+    Deleted,
 }
 
 impl fmt::Display for UserState {
@@ -29,6 +30,7 @@ impl fmt::Display for UserState {
         match *self {
             Self::Pending => write!(f, "pending"),
             Self::Active => write!(f, "active"),
+            Self::Deleted => write!(f, "deleted"),
         }
     }
 }
@@ -38,6 +40,7 @@ impl ToSql<EUserState, Pg> for UserState {
         match *self {
             Self::Pending => out.write_all(b"pending")?,
             Self::Active => out.write_all(b"active")?,
+            Self::Deleted => out.write_all(b"deleted")?,
         }
         Ok(IsNull::No)
     }
@@ -48,6 +51,7 @@ impl FromSql<EUserState, Pg> for UserState {
         match not_none!(bytes) {
             b"pending" => Ok(Self::Pending),
             b"active" => Ok(Self::Active),
+            b"deleted" => Ok(Self::Deleted),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
@@ -58,6 +62,7 @@ impl From<String> for UserState {
         match s.to_ascii_lowercase().as_ref() {
             "pending" => Self::Pending,
             "active" => Self::Active,
+            "deleted" => Self::Deleted,
             _ => Self::Pending,
         }
     }
@@ -65,8 +70,8 @@ impl From<String> for UserState {
 
 impl UserState {
     pub fn iter() -> Iter<'static, Self> {
-        static USER_STATES: [UserState; 2] =
-            [UserState::Pending, UserState::Active];
+        static USER_STATES: [UserState; 3] =
+            [UserState::Pending, UserState::Active, UserState::Deleted];
         USER_STATES.iter()
     }
 
@@ -84,6 +89,7 @@ mod test {
     fn test_from() {
         assert_eq!(UserState::Pending, UserState::from("pending".to_string()));
         assert_eq!(UserState::Active, UserState::from("active".to_string()));
+        assert_eq!(UserState::Deleted, UserState::from("deleted".to_string()));
 
         // default
         assert_eq!(UserState::Pending, UserState::from("unknown".to_string()));
@@ -93,12 +99,13 @@ mod test {
     fn test_fmt() {
         assert_eq!("pending", format!("{}", UserState::Pending));
         assert_eq!("active", format!("{}", UserState::Active));
+        assert_eq!("deleted", format!("{}", UserState::Deleted));
     }
 
     #[test]
     fn test_as_vec() {
         assert_eq!(
-            vec![UserState::Pending, UserState::Active],
+            vec![UserState::Pending, UserState::Active, UserState::Deleted],
             UserState::as_vec()
         )
     }
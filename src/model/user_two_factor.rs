@@ -0,0 +1,379 @@
+//! # UserTwoFactor
+//!
+//! UserTwoFactor holds a TOTP secret (encrypted) and recovery codes (digests)
+//! of the user. Two-factor authentication is enabled once the setup is
+//! confirmed with the first code (see `enable`).
+use std::fmt;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::pg::{Pg, PgConnection};
+use openssl::sha::sha256;
+
+pub use crate::schema::user_two_factors;
+
+use crate::logger::Logger;
+use crate::util::generate_random_hash;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LENGTH: i32 = 10;
+const RECOVERY_CODE_SOURCE: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+/// Returns a digest of the recovery code. Hyphens and case are ignored.
+pub fn digest_recovery_code(code: &str) -> String {
+    let code: String = code
+        .trim()
+        .chars()
+        .filter(|c| *c != '-')
+        .collect::<String>()
+        .to_lowercase();
+    let digest = sha256(code.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// UserTwoFactor
+#[derive(Clone, Debug, Identifiable, Insertable, PartialEq, Queryable)]
+#[table_name = "user_two_factors"]
+pub struct UserTwoFactor {
+    pub id: i64,
+    pub user_id: i64,
+    pub secret: String,
+    pub recovery_codes: Vec<String>,
+    pub last_used_step: Option<i64>,
+    pub enabled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl fmt::Display for UserTwoFactor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<UserTwoFactor {user_id}>", user_id = &self.user_id)
+    }
+}
+
+impl UserTwoFactor {
+    /// Generates new recovery codes like `abcde-fghij`.
+    ///
+    /// The codes are shown to the user only once, and only their digests are
+    /// saved.
+    pub fn generate_recovery_codes() -> Vec<String> {
+        (0..RECOVERY_CODE_COUNT)
+            .map(|_| {
+                let s = generate_random_hash(
+                    RECOVERY_CODE_SOURCE,
+                    RECOVERY_CODE_LENGTH,
+                );
+                let (a, b) = s.split_at(s.len() / 2);
+                format!("{}-{}", a, b)
+            })
+            .collect()
+    }
+
+    pub fn find_by_user_id(
+        user_id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = user_two_factors::table
+            .filter(user_two_factors::user_id.eq(user_id))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Saves a new (encrypted) secret of the user. It replaces the one not
+    /// enabled yet.
+    pub fn setup(
+        user_id: i64,
+        secret: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let empty: Vec<String> = vec![];
+        let q = diesel::insert_into(user_two_factors::table)
+            .values((
+                user_two_factors::user_id.eq(user_id),
+                user_two_factors::secret.eq(secret),
+            ))
+            .on_conflict(user_two_factors::user_id)
+            .do_update()
+            .set((
+                user_two_factors::secret.eq(secret),
+                user_two_factors::recovery_codes.eq(empty),
+                user_two_factors::last_used_step.eq(None::<i64>),
+                user_two_factors::enabled_at.eq(None::<NaiveDateTime>),
+                user_two_factors::updated_at.eq(Utc::now().naive_utc()),
+            ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+
+    /// Enables two-factor authentication with the step of the first code and
+    /// digests of recovery codes.
+    pub fn enable(
+        &self,
+        step: i64,
+        recovery_codes: &[String],
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let now = Utc::now().naive_utc();
+        let q = diesel::update(
+            user_two_factors::table
+                .filter(user_two_factors::id.eq(self.id))
+                .filter(user_two_factors::enabled_at.is_null()),
+        )
+        .set((
+            user_two_factors::recovery_codes.eq(recovery_codes),
+            user_two_factors::last_used_step.eq(step),
+            user_two_factors::enabled_at.eq(now),
+            user_two_factors::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        q.get_result::<Self>(conn).map_err(|e| {
+            error!(logger, "err: {}", e);
+            "failed to enable two-factor authentication"
+        })
+    }
+
+    /// Marks the step of a code as used. It fails if the step (or a later one)
+    /// has been used already.
+    pub fn use_step(
+        &self,
+        step: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::update(
+            user_two_factors::table
+                .filter(user_two_factors::id.eq(self.id))
+                .filter(
+                    user_two_factors::last_used_step
+                        .is_null()
+                        .or(user_two_factors::last_used_step.lt(step)),
+                ),
+        )
+        .set((
+            user_two_factors::last_used_step.eq(step),
+            user_two_factors::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to use the code")
+            },
+            Ok(0) => Err("code has been used already"),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Consumes the recovery code. It can't be used again.
+    pub fn use_recovery_code(
+        &self,
+        code: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let digest = digest_recovery_code(code);
+        if !self.recovery_codes.contains(&digest) {
+            return Err("unknown recovery code");
+        }
+        let recovery_codes: Vec<String> = self
+            .recovery_codes
+            .iter()
+            .filter(|c| **c != digest)
+            .cloned()
+            .collect();
+
+        // fails if the codes have been changed in the meantime
+        let current = &self.recovery_codes;
+        let q = diesel::update(
+            user_two_factors::table
+                .filter(user_two_factors::id.eq(self.id))
+                .filter(user_two_factors::recovery_codes.eq(current)),
+        )
+        .set((
+            user_two_factors::recovery_codes.eq(recovery_codes),
+            user_two_factors::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to use the recovery code")
+            },
+            Ok(0) => Err("recovery code has been used already"),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    /// Replaces the recovery codes with new ones (digests).
+    pub fn renew_recovery_codes(
+        &self,
+        recovery_codes: &[String],
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::update(self).set((
+            user_two_factors::recovery_codes.eq(recovery_codes),
+            user_two_factors::updated_at.eq(Utc::now().naive_utc()),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to renew recovery codes")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete two-factor authentication")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::test::run;
+    use crate::model::user::{User, users};
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_digest_recovery_code() {
+        let digest = digest_recovery_code("abcde-fghij");
+        assert_eq!(digest.len(), 64);
+        assert_eq!(digest, digest_recovery_code(" ABCDEFGHIJ "));
+        assert_ne!(digest, digest_recovery_code("abcde-fghik"));
+    }
+
+    #[test]
+    fn test_generate_recovery_codes() {
+        let codes = UserTwoFactor::generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        for code in codes.iter() {
+            assert_eq!(code.len(), 11);
+            assert_eq!(code.find('-'), Some(5));
+        }
+    }
+
+    #[test]
+    fn test_setup_and_enable() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            assert!(UserTwoFactor::find_by_user_id(user.id, conn, logger)
+                .is_none());
+
+            let t = UserTwoFactor::setup(user.id, "old", conn, logger).unwrap();
+            assert!(!t.is_enabled());
+
+            let t = UserTwoFactor::setup(user.id, "new", conn, logger).unwrap();
+            assert_eq!(t.secret, "new");
+
+            let codes = vec![digest_recovery_code("abcde-fghij")];
+            let t = t.enable(10, &codes, conn, logger).unwrap();
+            assert!(t.is_enabled());
+            assert_eq!(t.last_used_step, Some(10));
+            assert_eq!(t.recovery_codes, codes);
+
+            // enabled once
+            assert!(t.enable(11, &codes, conn, logger).is_err());
+        });
+    }
+
+    #[test]
+    fn test_use_step() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let t = UserTwoFactor::setup(user.id, "", conn, logger).unwrap();
+            assert!(t.use_step(10, conn, logger).is_ok());
+            assert!(t.use_step(10, conn, logger).is_err());
+            assert!(t.use_step(9, conn, logger).is_err());
+            assert!(t.use_step(11, conn, logger).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_use_recovery_code() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let user = diesel::insert_into(users::table)
+                .values(u)
+                .get_result::<User>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let t = UserTwoFactor::setup(user.id, "", conn, logger).unwrap();
+            let codes = vec![
+                digest_recovery_code("abcde-fghij"),
+                digest_recovery_code("klmno-pqrst"),
+            ];
+            let t = t.enable(10, &codes, conn, logger).unwrap();
+
+            assert!(t.use_recovery_code("unknown", conn, logger).is_err());
+            assert!(t.use_recovery_code("abcde-fghij", conn, logger).is_ok());
+            // stale
+            assert!(t.use_recovery_code("klmno-pqrst", conn, logger).is_err());
+
+            let t = UserTwoFactor::find_by_user_id(user.id, conn, logger)
+                .unwrap();
+            assert_eq!(t.recovery_codes, vec![codes[1].clone()]);
+            assert!(t.use_recovery_code("abcde-fghij", conn, logger).is_err());
+            assert!(t.use_recovery_code("KLMNOPQRST", conn, logger).is_ok());
+        });
+    }
+}
@@ -0,0 +1,415 @@
+//! # Webhook
+//!
+//! Webhook is an outbound endpoint of a namespace. It receives the events
+//! which it subscribes as JSON payloads signed with its `secret`.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::model::webhook_event::*;
+pub use crate::schema::webhooks;
+
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+use crate::request::webhook::Webhook as RequestData;
+use crate::util::generate_random_hash;
+
+const SECRET_LENGTH: i32 = 32;
+const SECRET_SOURCE: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// NewWebhook
+#[derive(Debug)]
+pub struct NewWebhook {
+    pub namespace_id: i64,
+    pub url: String,
+    pub events: Vec<String>,
+}
+
+impl fmt::Display for NewWebhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<NewWebhook {url}>", url = &self.url)
+    }
+}
+
+impl Default for NewWebhook {
+    // includes validation errors
+    fn default() -> Self {
+        Self {
+            namespace_id: -1,
+            url: "".to_string(),
+            events: vec![],
+        }
+    }
+}
+
+impl From<RequestData> for NewWebhook {
+    fn from(data: RequestData) -> Self {
+        Self {
+            url: data.url.unwrap_or_else(|| "".to_string()),
+            events: data.events.unwrap_or_default(),
+
+            ..Default::default()
+        }
+    }
+}
+
+type AllColumns = (
+    webhooks::id,
+    webhooks::uuid,
+    webhooks::namespace_id,
+    webhooks::url,
+    webhooks::secret,
+    webhooks::events,
+    webhooks::created_at,
+    webhooks::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    webhooks::id,
+    webhooks::uuid,
+    webhooks::namespace_id,
+    webhooks::url,
+    webhooks::secret,
+    webhooks::events,
+    webhooks::created_at,
+    webhooks::updated_at,
+);
+
+/// Webhook
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "webhooks"]
+pub struct Webhook {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub namespace_id: i64,
+    pub url: String,
+    #[serde(skip)]
+    pub secret: String,
+    pub events: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+impl fmt::Display for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<Webhook {uuid}>", uuid = &self.uuid.to_string())
+    }
+}
+
+type All = dsl::Select<webhooks::table, AllColumns>;
+type WithNamespaceId = dsl::Eq<webhooks::namespace_id, i64>;
+type WithUuid = dsl::Eq<webhooks::uuid, Uuid>;
+type ByNamespaceId = dsl::Filter<All, WithNamespaceId>;
+
+impl Webhook {
+    pub fn all() -> All {
+        webhooks::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_namespace_id(namespace_id: i64) -> ByNamespaceId {
+        Self::all().filter(Self::with_namespace_id(namespace_id))
+    }
+
+    pub fn find_all_by_namespace(
+        namespace: &Namespace,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_namespace_id(namespace.id).order(webhooks::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns webhooks in the namespace which subscribe the event.
+    pub fn find_all_subscribing(
+        namespace_id: i64,
+        event: &WebhookEvent,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::by_namespace_id(namespace_id)
+            .filter(webhooks::events.contains(vec![event.to_string()]))
+            .order(webhooks::id.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(webhooks::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn find_by_uuid_in(
+        namespace: &Namespace,
+        uuid: &str,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let q = Self::by_namespace_id(namespace.id)
+            .filter(Self::with_uuid(uuid))
+            .limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    /// Saves a new webhook with a generated secret.
+    pub fn insert(
+        webhook: &NewWebhook,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let secret = generate_random_hash(SECRET_SOURCE, SECRET_LENGTH);
+        let q = diesel::insert_into(webhooks::table).values((
+            webhooks::uuid.eq(uuid),
+            webhooks::namespace_id.eq(webhook.namespace_id),
+            webhooks::url.eq(&webhook.url),
+            webhooks::secret.eq(secret),
+            webhooks::events.eq(&webhook.events),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(w) => Some(w),
+        }
+    }
+
+    /// Deletes the webhook together with its deliveries.
+    pub fn delete(
+        &self,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<(), &'static str> {
+        use crate::model::webhook_delivery::webhook_deliveries;
+
+        let q = diesel::delete(
+            webhook_deliveries::table
+                .filter(webhook_deliveries::webhook_id.eq(self.id)),
+        );
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        if let Err(e) = q.execute(conn) {
+            error!(logger, "err: {}", e);
+            return Err("failed to delete webhook deliveries");
+        }
+
+        let q = diesel::delete(self);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.execute(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to delete webhook")
+            },
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn with_namespace_id(namespace_id: i64) -> WithNamespaceId {
+        webhooks::namespace_id.eq(namespace_id)
+    }
+
+    pub fn with_uuid(s: &str) -> WithUuid {
+        let uuid = Uuid::parse_str(s).unwrap_or_else(|_| Uuid::nil());
+        webhooks::uuid.eq(uuid)
+    }
+}
+
+#[cfg(test)]
+pub mod data {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+    use fnv::FnvHashMap;
+
+    use crate::fnvhashmap;
+    use crate::model::namespace::data::NAMESPACES;
+
+    type WebhookFixture = FnvHashMap<&'static str, Webhook>;
+
+    lazy_static! {
+        pub static ref WEBHOOKS: WebhookFixture = fnvhashmap! {
+            "piano's incident hook" => Webhook {
+                id: 1,
+                uuid: Uuid::new_v4(),
+                namespace_id: NAMESPACES.get("piano").unwrap().id,
+                url: "http://127.0.0.1/hook".to_string(),
+                secret: "secret".to_string(),
+                events: vec![
+                    "message.critical".to_string(),
+                    "alert.fired".to_string(),
+                ],
+                created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+                updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::model::webhook::data::WEBHOOKS;
+
+    #[test]
+    fn test_new_webhook_default() {
+        let w = NewWebhook {
+            ..Default::default()
+        };
+
+        assert_eq!(w.namespace_id, -1);
+        assert_eq!(w.url, "".to_string());
+        assert!(w.events.is_empty());
+    }
+
+    #[test]
+    fn test_webhook_format() {
+        let w = WEBHOOKS.get("piano's incident hook").unwrap();
+        assert_eq!(format!("{}", w), format!("<Webhook {}>", w.uuid));
+    }
+
+    #[test]
+    fn test_insert() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let w = NewWebhook {
+                namespace_id: namespace.id,
+                url: "https://example.org/hook".to_string(),
+                events: vec!["alert.fired".to_string()],
+            };
+            let result = Webhook::insert(&w, conn, logger);
+            assert!(result.is_some());
+
+            let webhook = result.unwrap();
+            assert_eq!(webhook.namespace_id, namespace.id);
+            assert_eq!(webhook.secret.len(), SECRET_LENGTH as usize);
+
+            let result = Webhook::find_by_uuid_in(
+                &namespace,
+                &webhook.uuid.to_string(),
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(webhook));
+        })
+    }
+
+    #[test]
+    fn test_find_all_subscribing() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut w = WEBHOOKS.get("piano's incident hook").unwrap().clone();
+            w.namespace_id = namespace.id;
+            let webhook = diesel::insert_into(webhooks::table)
+                .values(&w)
+                .get_result::<Webhook>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = Webhook::find_all_subscribing(
+                namespace.id,
+                &WebhookEvent::AlertFired,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(vec![webhook]));
+
+            let result = Webhook::find_all_subscribing(
+                namespace.id,
+                &WebhookEvent::AlertResolved,
+                conn,
+                logger,
+            );
+            assert_eq!(result, Some(vec![]));
+        })
+    }
+}
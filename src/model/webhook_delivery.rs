@@ -0,0 +1,408 @@
+//! # WebhookDelivery
+//!
+//! WebhookDelivery is a log of an event sent to a Webhook. It stays
+//! `pending` while it's retried, and it will be either `succeeded` or
+//! `failed` in the end.
+use std::fmt;
+
+use chrono::NaiveDateTime;
+use diesel::{Identifiable, Queryable, debug_query, prelude::*};
+use diesel::dsl;
+use diesel::pg::{Pg, PgConnection};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub use crate::model::webhook_delivery_state::*;
+pub use crate::schema::webhook_deliveries;
+
+use crate::logger::Logger;
+use crate::model::webhook::Webhook;
+
+/// NewWebhookDelivery
+#[derive(Debug)]
+pub struct NewWebhookDelivery {
+    pub webhook_id: i64,
+    pub event: String,
+    pub payload: String,
+}
+
+type AllColumns = (
+    webhook_deliveries::id,
+    webhook_deliveries::uuid,
+    webhook_deliveries::webhook_id,
+    webhook_deliveries::event,
+    webhook_deliveries::payload,
+    webhook_deliveries::state,
+    webhook_deliveries::attempts,
+    webhook_deliveries::response_status,
+    webhook_deliveries::error_message,
+    webhook_deliveries::next_attempt_at,
+    webhook_deliveries::delivered_at,
+    webhook_deliveries::created_at,
+    webhook_deliveries::updated_at,
+);
+
+const ALL_COLUMNS: AllColumns = (
+    webhook_deliveries::id,
+    webhook_deliveries::uuid,
+    webhook_deliveries::webhook_id,
+    webhook_deliveries::event,
+    webhook_deliveries::payload,
+    webhook_deliveries::state,
+    webhook_deliveries::attempts,
+    webhook_deliveries::response_status,
+    webhook_deliveries::error_message,
+    webhook_deliveries::next_attempt_at,
+    webhook_deliveries::delivered_at,
+    webhook_deliveries::created_at,
+    webhook_deliveries::updated_at,
+);
+
+/// WebhookDelivery
+#[derive(
+    AsChangeset,
+    Clone,
+    Debug,
+    Identifiable,
+    Insertable,
+    PartialEq,
+    Queryable,
+    Serialize,
+)]
+#[table_name = "webhook_deliveries"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct WebhookDelivery {
+    #[serde(skip)]
+    pub id: i64,
+    #[serde(with = "uuid_as_string")]
+    pub uuid: Uuid,
+    #[serde(skip)]
+    pub webhook_id: i64,
+    pub event: String,
+    pub payload: String,
+    pub state: WebhookDeliveryState,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub error_message: Option<String>,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+mod uuid_as_string {
+    use uuid::Uuid;
+    use serde::{Serialize, Serializer};
+
+    pub fn serialize<S>(val: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        val.to_string().serialize(serializer)
+    }
+}
+
+impl fmt::Display for WebhookDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "<WebhookDelivery {uuid}>",
+            uuid = &self.uuid.to_string()
+        )
+    }
+}
+
+type All = dsl::Select<webhook_deliveries::table, AllColumns>;
+type WithWebhook = dsl::Eq<webhook_deliveries::webhook_id, i64>;
+type ByWebhook = dsl::Filter<All, WithWebhook>;
+
+impl WebhookDelivery {
+    pub fn all() -> All {
+        webhook_deliveries::table.select(ALL_COLUMNS)
+    }
+
+    pub fn by_webhook(webhook: &Webhook) -> ByWebhook {
+        Self::all().filter(Self::with_webhook(webhook))
+    }
+
+    pub fn fetch_by_webhook(
+        webhook: &Webhook,
+        offset: i64,
+        limit: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        if limit < 1 {
+            return None;
+        }
+
+        let q = Self::by_webhook(webhook)
+            .order(webhook_deliveries::created_at.desc())
+            .offset(offset)
+            .limit(limit);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    /// Returns pending deliveries which should be retried until the time.
+    pub fn find_all_due(
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Vec<Self>> {
+        let q = Self::all()
+            .filter(
+                webhook_deliveries::state.eq(WebhookDeliveryState::Pending),
+            )
+            .filter(webhook_deliveries::next_attempt_at.le(now))
+            .order(webhook_deliveries::next_attempt_at.asc());
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.load::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(v) => Some(v),
+        }
+    }
+
+    pub fn find_by_id(
+        id: i64,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        if id < 1 {
+            return None;
+        }
+
+        let q = Self::all().filter(webhook_deliveries::id.eq(id)).limit(1);
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.first::<Self>(conn) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+        }
+    }
+
+    pub fn insert(
+        delivery: &NewWebhookDelivery,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Option<Self> {
+        let uuid = Uuid::new_v4();
+        let q = diesel::insert_into(webhook_deliveries::table).values((
+            webhook_deliveries::uuid.eq(uuid),
+            webhook_deliveries::webhook_id.eq(delivery.webhook_id),
+            webhook_deliveries::event.eq(&delivery.event),
+            webhook_deliveries::payload.eq(&delivery.payload),
+            webhook_deliveries::state.eq(WebhookDeliveryState::Pending),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                None
+            },
+            Ok(d) => Some(d),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.state == WebhookDeliveryState::Pending
+    }
+
+    /// Records a successful attempt.
+    pub fn succeed(
+        &self,
+        response_status: i32,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let q = diesel::update(self).set((
+            webhook_deliveries::state.eq(WebhookDeliveryState::Succeeded),
+            webhook_deliveries::attempts.eq(self.attempts + 1),
+            webhook_deliveries::response_status.eq(Some(response_status)),
+            webhook_deliveries::error_message.eq(None::<String>),
+            webhook_deliveries::next_attempt_at.eq(None::<NaiveDateTime>),
+            webhook_deliveries::delivered_at.eq(Some(now)),
+            webhook_deliveries::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to update webhook delivery")
+            },
+            Ok(d) => Ok(d),
+        }
+    }
+
+    /// Records a failed attempt.
+    ///
+    /// The delivery stays pending if `next_attempt_at` is given, otherwise
+    /// it will be marked as failed.
+    pub fn fail(
+        &self,
+        response_status: Option<i32>,
+        error_message: &str,
+        next_attempt_at: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+        conn: &PgConnection,
+        logger: &Logger,
+    ) -> Result<Self, &'static str> {
+        let state = if next_attempt_at.is_some() {
+            WebhookDeliveryState::Pending
+        } else {
+            WebhookDeliveryState::Failed
+        };
+        // error_message is varchar(256)
+        let message: String = error_message.chars().take(256).collect();
+        let q = diesel::update(self).set((
+            webhook_deliveries::state.eq(state),
+            webhook_deliveries::attempts.eq(self.attempts + 1),
+            webhook_deliveries::response_status.eq(response_status),
+            webhook_deliveries::error_message.eq(Some(message)),
+            webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+            webhook_deliveries::updated_at.eq(now),
+        ));
+
+        info!(logger, "{}", debug_query::<Pg, _>(&q).to_string());
+
+        match q.get_result::<Self>(conn) {
+            Err(e) => {
+                error!(logger, "err: {}", e);
+                Err("failed to update webhook delivery")
+            },
+            Ok(d) => Ok(d),
+        }
+    }
+
+    pub fn with_webhook(webhook: &Webhook) -> WithWebhook {
+        webhook_deliveries::webhook_id.eq(webhook.id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{Duration, Utc};
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::model::webhook::webhooks;
+    use crate::model::webhook::data::WEBHOOKS;
+
+    fn load_webhook(conn: &PgConnection) -> Webhook {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let mut w = WEBHOOKS.get("piano's incident hook").unwrap().clone();
+        w.namespace_id = namespace.id;
+        diesel::insert_into(webhooks::table)
+            .values(&w)
+            .get_result::<Webhook>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_fail_and_find_all_due() {
+        run(|conn, _, logger| {
+            let webhook = load_webhook(conn);
+
+            let delivery = WebhookDelivery::insert(
+                &NewWebhookDelivery {
+                    webhook_id: webhook.id,
+                    event: "ping".to_string(),
+                    payload: "{}".to_string(),
+                },
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert!(delivery.is_pending());
+            assert_eq!(delivery.attempts, 0);
+
+            let now = Utc::now().naive_utc();
+
+            // not scheduled yet
+            let result = WebhookDelivery::find_all_due(now, conn, logger);
+            assert_eq!(result, Some(vec![]));
+
+            let delivery = delivery
+                .fail(
+                    Some(500),
+                    "Internal Server Error",
+                    Some(now + Duration::minutes(1)),
+                    now,
+                    conn,
+                    logger,
+                )
+                .unwrap();
+            assert!(delivery.is_pending());
+            assert_eq!(delivery.attempts, 1);
+
+            let result = WebhookDelivery::find_all_due(now, conn, logger);
+            assert_eq!(result, Some(vec![]));
+
+            let result = WebhookDelivery::find_all_due(
+                now + Duration::minutes(1),
+                conn,
+                logger,
+            );
+            assert_eq!(result.unwrap().len(), 1);
+
+            let delivery = delivery
+                .fail(None, "connection refused", None, now, conn, logger)
+                .unwrap();
+            assert_eq!(delivery.state, WebhookDeliveryState::Failed);
+            assert_eq!(delivery.attempts, 2);
+        })
+    }
+
+    #[test]
+    fn test_succeed() {
+        run(|conn, _, logger| {
+            let webhook = load_webhook(conn);
+
+            let delivery = WebhookDelivery::insert(
+                &NewWebhookDelivery {
+                    webhook_id: webhook.id,
+                    event: "ping".to_string(),
+                    payload: "{}".to_string(),
+                },
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            let now = Utc::now().naive_utc();
+            let delivery = delivery.succeed(204, now, conn, logger).unwrap();
+            assert_eq!(delivery.state, WebhookDeliveryState::Succeeded);
+            assert_eq!(delivery.attempts, 1);
+            assert_eq!(delivery.response_status, Some(204));
+            assert!(delivery.delivered_at.is_some());
+        })
+    }
+}
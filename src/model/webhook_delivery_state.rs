@@ -0,0 +1,132 @@
+//! # A type WebhookDeliveryState for WebhookDelivery in webhook_delivery.rs
+//!
+//! EWebhookDeliveryState represents SQL type value `e_webhook_delivery_state`
+//! and WebhookDeliveryState is an Enum holds all the values.
+use std::fmt;
+use std::io::Write;
+use std::slice::Iter;
+
+use serde::Serialize;
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+
+#[derive(QueryId, SqlType)]
+#[postgres(type_name = "e_webhook_delivery_state")]
+pub struct EWebhookDeliveryState;
+
+#[derive(AsExpression, Clone, Debug, FromSqlRow, PartialEq, Serialize)]
+#[sql_type = "EWebhookDeliveryState"]
+pub enum WebhookDeliveryState {
+    Pending, // default
+    Succeeded,
+    Failed,
+}
+
+const WEBHOOK_DELIVERY_STATES: [WebhookDeliveryState; 3] = [
+    WebhookDeliveryState::Pending,
+    WebhookDeliveryState::Succeeded,
+    WebhookDeliveryState::Failed,
+];
+
+impl fmt::Display for WebhookDeliveryState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::Pending => write!(f, "pending"),
+            Self::Succeeded => write!(f, "succeeded"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl ToSql<EWebhookDeliveryState, Pg> for WebhookDeliveryState {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        match *self {
+            Self::Pending => out.write_all(b"pending")?,
+            Self::Succeeded => out.write_all(b"succeeded")?,
+            Self::Failed => out.write_all(b"failed")?,
+        }
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<EWebhookDeliveryState, Pg> for WebhookDeliveryState {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        match not_none!(bytes) {
+            b"pending" => Ok(Self::Pending),
+            b"succeeded" => Ok(Self::Succeeded),
+            b"failed" => Ok(Self::Failed),
+            _ => Err("Unrecognized enum variant".into()),
+        }
+    }
+}
+
+impl From<String> for WebhookDeliveryState {
+    fn from(s: String) -> Self {
+        match s.to_ascii_lowercase().as_ref() {
+            "pending" => Self::Pending,
+            "succeeded" => Self::Succeeded,
+            "failed" => Self::Failed,
+            _ => Self::Pending,
+        }
+    }
+}
+
+impl WebhookDeliveryState {
+    pub fn iter() -> Iter<'static, WebhookDeliveryState> {
+        WEBHOOK_DELIVERY_STATES.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(
+            WebhookDeliveryState::Pending,
+            WebhookDeliveryState::from("pending".to_string())
+        );
+        assert_eq!(
+            WebhookDeliveryState::Succeeded,
+            WebhookDeliveryState::from("succeeded".to_string())
+        );
+        assert_eq!(
+            WebhookDeliveryState::Failed,
+            WebhookDeliveryState::from("failed".to_string())
+        );
+
+        // default
+        assert_eq!(
+            WebhookDeliveryState::Pending,
+            WebhookDeliveryState::from("unknown".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!("pending", format!("{}", WebhookDeliveryState::Pending));
+        assert_eq!(
+            "succeeded",
+            format!("{}", WebhookDeliveryState::Succeeded)
+        );
+        assert_eq!("failed", format!("{}", WebhookDeliveryState::Failed));
+    }
+
+    #[test]
+    fn test_as_vec() {
+        assert_eq!(
+            vec![
+                WebhookDeliveryState::Pending,
+                WebhookDeliveryState::Succeeded,
+                WebhookDeliveryState::Failed,
+            ],
+            WebhookDeliveryState::as_vec()
+        )
+    }
+}
@@ -0,0 +1,86 @@
+//! # A type WebhookEvent for Webhook in webhook.rs
+//!
+//! WebhookEvent is an Enum holds all the event types which a webhook can
+//! subscribe. It's stored as text (in an array) in `webhooks.events`.
+use std::fmt;
+use std::slice::Iter;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebhookEvent {
+    MessageCritical,
+    AlertFired,
+    AlertResolved,
+    Ping,
+}
+
+const WEBHOOK_EVENTS: [WebhookEvent; 4] = [
+    WebhookEvent::MessageCritical,
+    WebhookEvent::AlertFired,
+    WebhookEvent::AlertResolved,
+    WebhookEvent::Ping,
+];
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::MessageCritical => write!(f, "message.critical"),
+            Self::AlertFired => write!(f, "alert.fired"),
+            Self::AlertResolved => write!(f, "alert.resolved"),
+            Self::Ping => write!(f, "ping"),
+        }
+    }
+}
+
+impl WebhookEvent {
+    pub fn iter() -> Iter<'static, WebhookEvent> {
+        WEBHOOK_EVENTS.iter()
+    }
+
+    pub fn as_vec() -> Vec<Self> {
+        Self::iter().cloned().collect()
+    }
+
+    /// Returns the event for the name if it's known.
+    ///
+    /// NOTE: `ping` is sent to any webhook, thus it cannot be subscribed.
+    pub fn find(s: &str) -> Option<Self> {
+        Self::iter()
+            .find(|e| *e != &Self::Ping && e.to_string() == s)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fmt() {
+        assert_eq!(
+            "message.critical",
+            format!("{}", WebhookEvent::MessageCritical)
+        );
+        assert_eq!("alert.fired", format!("{}", WebhookEvent::AlertFired));
+        assert_eq!(
+            "alert.resolved",
+            format!("{}", WebhookEvent::AlertResolved)
+        );
+        assert_eq!("ping", format!("{}", WebhookEvent::Ping));
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(
+            Some(WebhookEvent::MessageCritical),
+            WebhookEvent::find("message.critical")
+        );
+        assert_eq!(
+            Some(WebhookEvent::AlertResolved),
+            WebhookEvent::find("alert.resolved")
+        );
+
+        assert_eq!(None, WebhookEvent::find("ping"));
+        assert_eq!(None, WebhookEvent::find("Alert.Fired"));
+        assert_eq!(None, WebhookEvent::find("unknown"));
+    }
+}
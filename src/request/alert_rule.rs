@@ -0,0 +1,21 @@
+/// AlertRule
+#[derive(Clone, Deserialize)]
+pub struct AlertRule {
+    pub name: Option<String>,
+    pub level: Option<String>,
+    pub threshold: Option<i32>,
+    pub window_minutes: Option<i32>,
+    pub cooldown_minutes: Option<i32>,
+}
+
+impl Default for AlertRule {
+    fn default() -> Self {
+        Self {
+            name: None,
+            level: None,
+            threshold: None,
+            window_minutes: None,
+            cooldown_minutes: None,
+        }
+    }
+}
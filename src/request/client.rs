@@ -0,0 +1,41 @@
+//! The client information of a request, recorded in audit events.
+use rocket::{Request, State};
+use rocket::request::{FromRequest, Outcome};
+
+use crate::config::Config;
+
+const USER_AGENT_MAX_LENGTH: usize = 256;
+
+#[derive(Clone, Debug, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// This never fails. The IP address is taken from the remote address.
+//
+// X-Real-IP header is taken instead only if the proxy is trusted by the config
+// (`TRUST_PROXY`), because any client can send the header. The proxy must
+// overwrite it with the address of the peer.
+impl<'a, 'r> FromRequest<'a, 'r> for ClientInfo {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> Outcome<Self, Self::Error> {
+        let config = req.guard::<State<Config>>().unwrap();
+        let ip = if config.trust_proxy {
+            req.client_ip()
+        } else {
+            req.remote().map(|addr| addr.ip())
+        };
+        let ip_address = ip.map(|ip| ip.to_string());
+        let user_agent = req
+            .headers()
+            .get_one("User-Agent")
+            .map(|v| v.chars().take(USER_AGENT_MAX_LENGTH).collect());
+
+        Outcome::Success(Self {
+            ip_address,
+            user_agent,
+        })
+    }
+}
@@ -0,0 +1,11 @@
+/// DigestSubscription
+#[derive(Clone, Deserialize)]
+pub struct DigestSubscription {
+    pub frequency: Option<String>,
+}
+
+impl Default for DigestSubscription {
+    fn default() -> Self {
+        Self { frequency: None }
+    }
+}
@@ -0,0 +1,15 @@
+/// EmailChange
+#[derive(Clone, Deserialize)]
+pub struct EmailChange {
+    pub email: String,
+    pub password: String,
+}
+
+impl Default for EmailChange {
+    fn default() -> Self {
+        Self {
+            email: "".to_string(),
+            password: "".to_string(),
+        }
+    }
+}
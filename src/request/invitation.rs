@@ -0,0 +1,21 @@
+/// Invitation
+#[derive(Clone, Deserialize)]
+pub struct Invitation {
+    pub email: Option<String>,
+    pub role: Option<String>,
+}
+
+impl Default for Invitation {
+    fn default() -> Self {
+        Self {
+            email: None,
+            role: None,
+        }
+    }
+}
+
+/// Acceptance requires the token delivered via email
+#[derive(Clone, Deserialize)]
+pub struct Acceptance {
+    pub token: Option<String>,
+}
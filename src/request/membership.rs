@@ -0,0 +1,11 @@
+/// Membership
+#[derive(Clone, Deserialize)]
+pub struct Membership {
+    pub role: Option<String>,
+}
+
+impl Default for Membership {
+    fn default() -> Self {
+        Self { role: None }
+    }
+}
@@ -1,10 +1,23 @@
 pub mod access_token;
+pub mod alert_rule;
 pub mod agent_type;
+pub mod client;
+pub mod digest_subscription;
+pub mod email_change;
+pub mod invitation;
+pub mod membership;
 pub mod message;
 pub mod namespace;
+pub mod password_change;
 pub mod password_reset;
+pub mod redaction_rule;
+pub mod stream;
+pub mod stream_grant;
 pub mod token;
+pub mod two_factor;
 pub mod user;
+pub mod user_email;
+pub mod webhook;
 
 #[macro_export]
 macro_rules! bad_request_by {
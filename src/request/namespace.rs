@@ -2,14 +2,27 @@
 #[derive(Clone, Deserialize)]
 pub struct Namespace {
     pub name: Option<String>,
+    pub key: Option<String>,
     pub description: Option<String>,
+    // overrides of the defaults in Config (0 resets it)
+    pub ingestion_rate_limit: Option<i32>,
+    pub ingestion_daily_quota: Option<i64>,
 }
 
 impl Default for Namespace {
     fn default() -> Self {
         Self {
             name: None,
+            key: None,
             description: None,
+            ingestion_rate_limit: None,
+            ingestion_daily_quota: None,
         }
     }
 }
+
+/// Deletion requires the name of the namespace as confirmation
+#[derive(Clone, Deserialize)]
+pub struct Deletion {
+    pub name: Option<String>,
+}
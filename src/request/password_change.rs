@@ -0,0 +1,18 @@
+/// PasswordChange
+#[derive(Clone, Deserialize)]
+pub struct PasswordChange {
+    pub password: String,
+    pub new_password: String,
+    // revokes other browser sessions if true
+    pub revoke_sessions: Option<bool>,
+}
+
+impl Default for PasswordChange {
+    fn default() -> Self {
+        Self {
+            password: "".to_string(),
+            new_password: "".to_string(),
+            revoke_sessions: None,
+        }
+    }
+}
@@ -29,6 +29,7 @@ impl Default for PasswordResetUpdate {
 /// PasswordReset
 #[derive(Clone, Deserialize)]
 pub struct PasswordReset {
+    pub email: String,
     pub password: String,
     pub username: String,
 }
@@ -36,6 +37,7 @@ pub struct PasswordReset {
 impl Default for PasswordReset {
     fn default() -> Self {
         Self {
+            email: "".to_string(),
             password: "".to_string(),
             username: "".to_string(),
         }
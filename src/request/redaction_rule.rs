@@ -0,0 +1,35 @@
+/// RedactionRule
+#[derive(Clone, Deserialize)]
+pub struct RedactionRule {
+    pub name: Option<String>,
+    pub detector: Option<String>,
+    pub pattern: Option<String>,
+}
+
+impl Default for RedactionRule {
+    fn default() -> Self {
+        Self {
+            name: None,
+            detector: None,
+            pattern: None,
+        }
+    }
+}
+
+/// RedactionTest is a sample text to try rules (dry-run).
+///
+/// The rules of the namespace are used unless a rule is given.
+#[derive(Clone, Deserialize)]
+pub struct RedactionTest {
+    pub text: Option<String>,
+    pub rule: Option<RedactionRule>,
+}
+
+impl Default for RedactionTest {
+    fn default() -> Self {
+        Self {
+            text: None,
+            rule: None,
+        }
+    }
+}
@@ -0,0 +1,17 @@
+/// Stream
+#[derive(Clone, Deserialize)]
+pub struct Stream {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Default for Stream {
+    fn default() -> Self {
+        Self {
+            name: None,
+            slug: None,
+            description: None,
+        }
+    }
+}
@@ -0,0 +1,11 @@
+/// StreamGrant
+#[derive(Clone, Deserialize)]
+pub struct StreamGrant {
+    pub role: Option<String>,
+}
+
+impl Default for StreamGrant {
+    fn default() -> Self {
+        Self { role: None }
+    }
+}
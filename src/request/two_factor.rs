@@ -0,0 +1,43 @@
+/// TwoFactorSetup
+#[derive(Clone, Deserialize)]
+pub struct TwoFactorSetup {
+    pub password: String,
+}
+
+impl Default for TwoFactorSetup {
+    fn default() -> Self {
+        Self {
+            password: "".to_string(),
+        }
+    }
+}
+
+/// TwoFactorCode (a TOTP code or a recovery code)
+#[derive(Clone, Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+impl Default for TwoFactorCode {
+    fn default() -> Self {
+        Self {
+            code: "".to_string(),
+        }
+    }
+}
+
+/// TwoFactorConfirmation (for disable and renewal of recovery codes)
+#[derive(Clone, Deserialize)]
+pub struct TwoFactorConfirmation {
+    pub password: String,
+    pub code: String,
+}
+
+impl Default for TwoFactorConfirmation {
+    fn default() -> Self {
+        Self {
+            password: "".to_string(),
+            code: "".to_string(),
+        }
+    }
+}
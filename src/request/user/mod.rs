@@ -1,16 +1,66 @@
 pub mod authentication;
+pub mod deregistration;
 pub mod registration;
 
+use redis::Commands;
 use rocket::{Request, State, request};
 use rocket::request::FromRequest;
 use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
-use crate::model::token::{BrowserCookieTokenClaims, PersonalAccessTokenClaims};
+use crate::model::token::{BrowserCookieTokenClaims, Claims};
 use crate::model::user::User;
+use crate::request::client::ClientInfo;
 use crate::request::token::TokenType;
 use crate::request::token::authentication::AuthenticationToken;
+use crate::service::session_manager::SessionManager;
+use crate::ss::{SsConn, user_sessions_revoked_at_key};
+use crate::util::SystemClock;
+
+// Checks whether the browser session has been destroyed (e.g. logout) or
+// has been issued before its revocation (e.g. by email change). It's treated
+// as revoked if the session store isn't available. A browser token must have
+// its expiration time (see AuthenticationClaims::decode) and the session id
+// (jti), otherwise it's treated as revoked as well.
+fn is_revoked(req: &Request, user: &User, token: &str) -> bool {
+    let config = req.guard::<State<Config>>().unwrap();
+    let mut ss_conn = match req.guard::<SsConn>() {
+        request::Outcome::Success(c) => c,
+        _ => return true,
+    };
+    let client = req.guard::<ClientInfo>().unwrap();
+    let logger = req.guard::<SyncLogger>().unwrap();
+
+    let claims = match BrowserCookieTokenClaims::decode(
+        token,
+        &config.authentication_token_issuer,
+        &config.authentication_token_secret,
+    ) {
+        Ok(c) if c.exp != 0 && c.jti.is_some() => c,
+        _ => return true,
+    };
+
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    match manager.touch(claims.jti.as_ref().unwrap(), &client) {
+        Ok(true) => (),
+        _ => return true,
+    }
+
+    let key = user_sessions_revoked_at_key(user.id);
+    let revoked_at: Option<i64> = match ss_conn.get(&key) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(logger, "error: {}", e);
+            return true;
+        },
+    };
+    match revoked_at {
+        None => false,
+        Some(t) => claims.get_issued_at().timestamp() <= t,
+    }
+}
 
 /// User
 impl<'a, 'r> FromRequest<'a, 'r> for &'a User {
@@ -39,9 +89,10 @@ impl<'a, 'r> FromRequest<'a, 'r> for &'a User {
                         &db_conn,
                         &logger,
                     )
+                    .filter(|u| !is_revoked(req, u, &authentication_token))
                 },
                 TokenType::PersonalAccessToken => {
-                    User::find_by_token::<PersonalAccessTokenClaims>(
+                    User::find_by_personal_access_token(
                         &authentication_token,
                         &config.authentication_token_issuer,
                         &config.authentication_token_secret,
@@ -5,6 +5,7 @@ pub struct UserRegistration {
     pub name: Option<String>,
     pub username: String,
     pub password: String,
+    pub invitation_token: Option<String>,
 }
 
 impl Default for UserRegistration {
@@ -14,6 +15,7 @@ impl Default for UserRegistration {
             name: None,
             username: "".to_string(),
             password: "".to_string(),
+            invitation_token: None,
         }
     }
 }
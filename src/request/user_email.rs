@@ -0,0 +1,17 @@
+/// UserEmail
+#[derive(Clone, Deserialize)]
+pub struct UserEmail {
+    pub email: Option<String>,
+}
+
+impl Default for UserEmail {
+    fn default() -> Self {
+        Self { email: None }
+    }
+}
+
+/// Verification requires the token delivered via email
+#[derive(Clone, Deserialize)]
+pub struct Verification {
+    pub token: Option<String>,
+}
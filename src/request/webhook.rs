@@ -0,0 +1,15 @@
+/// Webhook
+#[derive(Clone, Deserialize)]
+pub struct Webhook {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+}
+
+impl Default for Webhook {
+    fn default() -> Self {
+        Self {
+            url: None,
+            events: None,
+        }
+    }
+}
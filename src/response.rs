@@ -15,6 +15,7 @@ const VARY: &str = "Accept-Encoding,Origin";
 #[derive(Debug)]
 pub struct Response<'a> {
     pub cookies: Cookies<'a>,
+    pub headers: Vec<(&'static str, String)>,
     pub status: Status,
     pub data: JsonValue,
 }
@@ -23,6 +24,7 @@ impl<'a> Default for Response<'a> {
     fn default() -> Self {
         Self {
             cookies: Cookies::empty(),
+            headers: vec![],
             status: Status::Ok,
             data: json!(null),
         }
@@ -35,6 +37,12 @@ impl<'a> Response<'a> {
         self
     }
 
+    // set a raw header (e.g. Retry-After)
+    pub fn header(mut self, name: &'static str, value: String) -> Response<'a> {
+        self.headers.push((name, value));
+        self
+    }
+
     pub fn status(mut self, status: Status) -> Response<'a> {
         self.status = status;
         self
@@ -56,6 +64,9 @@ impl<'r> Responder<'r> for Response<'r> {
         self.cookies.iter().for_each(|c| {
             builder.header(c);
         });
+        self.headers.into_iter().for_each(|(name, value)| {
+            builder.raw_header(name, value);
+        });
 
         let config = req.guard::<State<Config>>().unwrap();
         builder
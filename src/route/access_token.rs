@@ -7,9 +7,11 @@ use serde_json::Value;
 use crate::config::Config;
 use crate::db::DbConn;
 use crate::model::access_token::{AccessToken, AgentType};
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
 use crate::model::token::{AuthenticationClaims, Claims, TokenData};
 use crate::model::user::User;
 use crate::request::access_token::AccessTokenData as RequestData;
+use crate::request::client::ClientInfo;
 use crate::response::Response;
 
 pub mod preflight {
@@ -81,6 +83,7 @@ pub mod preflight {
 pub fn dump<'a>(
     uuid: String,
     user: &User,
+    client: ClientInfo,
     conn: DbConn,
     config: State<Config>,
     logger: SyncLogger,
@@ -105,6 +108,15 @@ pub fn dump<'a>(
                             Err(Error::RollbackTransaction)
                         },
                         Ok(a) => {
+                            let mut e = NewAuditEvent::new(
+                                AuditAction::AccessTokenDump,
+                                Some(user),
+                                &client,
+                            );
+                            e.target = Some(a.uuid.to_string());
+                            AuditEvent::insert(&e, &conn, &logger)
+                                .ok_or(Error::RollbackTransaction)?;
+
                             let value =
                                 String::from_utf8(a.token.unwrap()).unwrap();
 
@@ -156,6 +168,7 @@ pub fn dump<'a>(
 pub fn del<'a>(
     uuid: String,
     user: &User,
+    client: ClientInfo,
     conn: DbConn,
     logger: SyncLogger,
 ) -> Response<'a> {
@@ -180,7 +193,20 @@ pub fn del<'a>(
                             error!(logger, "err: {}", e);
                             Err(Error::RollbackTransaction)
                         },
-                        Ok(_) => Ok(()),
+                        Ok(r) => {
+                            let mut e = NewAuditEvent::new(
+                                AuditAction::AccessTokenRevoke,
+                                Some(user),
+                                &client,
+                            );
+                            e.target = Some(r.uuid.to_string());
+                            e.diff = serde_json::json!({
+                                "revoked_at": [t.revoked_at, r.revoked_at],
+                            });
+                            AuditEvent::insert(&e, &conn, &logger)
+                                .map(|_| ())
+                                .ok_or(Error::RollbackTransaction)
+                        },
                     }
                 },
             }
@@ -205,6 +231,7 @@ pub fn hset_state<'a>(
     uuid: String,
     data: RequestData,
     user: &User,
+    client: ClientInfo,
     conn: DbConn,
     logger: SyncLogger,
 ) -> Response<'a> {
@@ -230,7 +257,20 @@ pub fn hset_state<'a>(
                             error!(logger, "err: {}", e);
                             Err(Error::RollbackTransaction)
                         },
-                        Ok(_) => Ok(()),
+                        Ok(state) => {
+                            let mut e = NewAuditEvent::new(
+                                AuditAction::AccessTokenStateChange,
+                                Some(user),
+                                &client,
+                            );
+                            e.target = Some(t.uuid.to_string());
+                            let (old, new) =
+                                (t.state.to_string(), state.to_string());
+                            e.diff = serde_json::json!({ "state": [old, new] });
+                            AuditEvent::insert(&e, &conn, &logger)
+                                .map(|_| ())
+                                .ok_or(Error::RollbackTransaction)
+                        },
                     }
                 },
             }
@@ -4,8 +4,10 @@ use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
 use crate::model::user::User;
 use crate::model::user_email::UserEmail;
+use crate::request::client::ClientInfo;
 use crate::request::token::verification::VerificationToken;
 use crate::response::Response;
 use crate::service::account_activator::AccountActivator;
@@ -33,6 +35,7 @@ pub mod preflight {
 pub fn activate(
     session_id: String,
     token: VerificationToken,
+    client: ClientInfo,
     db_conn: DbConn,
     logger: SyncLogger,
     config: State<Config>,
@@ -45,7 +48,16 @@ pub fn activate(
         AccountActivator::<User, UserEmail>::new(&db_conn, &config, &logger)
             .load(&token)
             .map(|a| {
-                let _ = a.activate();
+                if a.activate().is_ok() {
+                    let user = a.target.as_ref().map(|v| &v.0);
+                    let mut e = NewAuditEvent::new(
+                        AuditAction::UserActivate,
+                        user,
+                        &client,
+                    );
+                    e.target = user.map(|u| u.uuid.to_string());
+                    let _ = AuditEvent::insert(&e, &db_conn, &logger);
+                }
                 a
             });
     if activation.is_ok() {
@@ -0,0 +1,297 @@
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::model::alert::Alert;
+use crate::model::alert_rule::{AlertRule, NewAlertRule};
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::stream::Stream;
+use crate::model::user::User;
+use crate::response::Response;
+use crate::request::alert_rule::AlertRule as RequestData;
+use crate::route::stream::authorize_stream;
+use crate::validation::alert_rule::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/alert_rule/<stream_uuid>/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        stream_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "stream: {}, uuid: {}", stream_uuid, uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/alert_rule/<stream_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        stream_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "stream: {}", stream_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/alert_rule/<stream_uuid>/hset", rank = 2)]
+    pub fn hset<'a>(
+        stream_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "stream: {}", stream_uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options(
+        "/alert_rule/<stream_uuid>/lrange/<uuid>/<start>/<stop>",
+        rank = 2
+    )]
+    pub fn lrange<'a>(
+        stream_uuid: String,
+        uuid: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "stream: {}, uuid: {}, start: {}, stop: {}",
+            stream_uuid,
+            uuid,
+            start,
+            stop
+        );
+        no_content_for("GET", &config)
+    }
+}
+
+// Finds the stream of the user, and checks the permission in its namespace.
+fn find_stream<'a>(
+    stream_uuid: &str,
+    user: &User,
+    permission: Permission,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<Stream, Response<'a>> {
+    let not_found = || Response::default().status(Status::NotFound);
+
+    let stream = Stream::owned_by_uuid(user, stream_uuid, conn, logger)
+        .ok_or_else(not_found)?;
+    let namespace = Namespace::find_by_id(stream.namespace_id, conn, logger)
+        .ok_or_else(not_found)?;
+    authorize_stream(&namespace, &stream, user, permission, conn, logger)?;
+    Ok(stream)
+}
+
+#[delete("/alert_rule/<stream_uuid>/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    stream_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, stream: {}, uuid: {}", user.uuid, stream_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let stream = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::AlertRulesManage,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(s) => s,
+    };
+
+    let result: Result<(), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            match AlertRule::find_by_uuid_in(&stream, &uuid, &conn, &logger) {
+                None => {
+                    error!(logger, "err: not found {}", uuid);
+                    Err(Error::RollbackTransaction)
+                },
+                Some(r) => {
+                    r.delete(&conn, &logger).map_err(|e| {
+                        error!(logger, "err: {}", e);
+                        Error::RollbackTransaction
+                    })
+                },
+            }
+        });
+
+    if result.is_err() {
+        return res.status(Status::NotFound);
+    }
+
+    res.format(json!({
+        "alert_rule": 1,
+    }))
+}
+
+#[get("/alert_rule/<stream_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    stream_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, stream: {}", user.uuid, stream_uuid);
+
+    let res: Response = Default::default();
+
+    let stream = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::AlertRulesRead,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(s) => s,
+    };
+
+    let data = match AlertRule::find_all_by_stream(&stream, &conn, &logger) {
+        None => {
+            error!(logger, "err: no alert rule for stream: {}", stream.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|r| json!({ "alert_rule": r })).collect(),
+    };
+    res.format(json!(data))
+}
+
+#[post(
+    "/alert_rule/<stream_uuid>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    stream_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, stream: {}", user.uuid, stream_uuid);
+
+    let res: Response = Default::default();
+
+    let stream = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::AlertRulesManage,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(s) => s,
+    };
+
+    let v = Validator::new(&data, &logger);
+    match v.validate() {
+        Err(errors) => {
+            res.status(Status::UnprocessableEntity).format(json!({
+                "errors": errors,
+            }))
+        },
+        Ok(_) => {
+            let mut r = NewAlertRule::from(data.0.clone());
+            r.stream_id = stream.id;
+            if let Some(rule) = AlertRule::insert(&r, &conn, &logger) {
+                info!(logger, "alert_rule: {}", rule.id);
+                return res.format(json!({"alert_rule": {
+                    "uuid": rule.uuid.to_string(),
+                }}));
+            }
+            res.status(Status::InternalServerError)
+        },
+    }
+}
+
+#[get(
+    "/alert_rule/<stream_uuid>/lrange/<uuid>/<start>/<stop>",
+    rank = 1
+)]
+pub fn lrange<'a>(
+    stream_uuid: String,
+    uuid: String,
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, stream: {}, uuid: {}, start: {}, stop: {}",
+        user.uuid,
+        stream_uuid,
+        uuid,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let stream = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::AlertRulesRead,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(s) => s,
+    };
+    let rule = AlertRule::find_by_uuid_in(&stream, &uuid, &conn, &logger);
+    if rule.is_none() {
+        return res.status(Status::NotFound);
+    }
+
+    // TODO
+    let mut offset = start;
+    if offset < 1 {
+        offset = 0;
+    }
+
+    let mut limit = stop - start + 2;
+    if limit < 1 {
+        limit = 1;
+    }
+
+    let data = match Alert::fetch_by_rule(
+        &rule.unwrap(),
+        offset,
+        limit,
+        &conn,
+        &logger,
+    ) {
+        None => vec![],
+        Some(a) => a.iter().map(|a| json!({ "alert": a })).collect(),
+    };
+    res.format(json!(data))
+}
@@ -0,0 +1,138 @@
+use rocket::http::Status;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::model::audit_event::{AuditAction, AuditEvent, AuditEventFilter};
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::user::User;
+use crate::response::Response;
+use crate::route::namespace::authorize;
+use crate::validation::ValidationError;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/audit_event/<namespace_uuid>/lrange/<start>/<stop>", rank = 2)]
+    pub fn lrange<'a>(
+        namespace_uuid: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, start: {}, stop: {}", namespace_uuid, start, stop
+        );
+        no_content_for("GET", &config)
+    }
+}
+
+// Returns audit events in the namespace from the latest one. Only owners can
+// read them.
+//
+// The events can be narrowed down by `action` (e.g. `stream.archive`) and
+// `actor` (uuid of the user) in the query string.
+#[get(
+    "/audit_event/<namespace_uuid>/lrange/<start>/<stop>?<action>&<actor>",
+    rank = 1
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn lrange<'a>(
+    namespace_uuid: String,
+    start: i64,
+    stop: i64,
+    action: Option<String>,
+    actor: Option<String>,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_uuid,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            Some(n) => n,
+            None => return res.status(Status::NotFound),
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::AuditRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let mut filter = AuditEventFilter::default();
+    if let Some(ref name) = action {
+        filter.action = AuditAction::find(name);
+        if filter.action.is_none() {
+            let actions = AuditAction::iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            let errors = vec![ValidationError {
+                field: "action".to_string(),
+                messages: vec![format!("Must be one of {}", actions)],
+            }];
+            return res.status(Status::UnprocessableEntity).format(json!({
+                "errors": errors,
+            }));
+        }
+    }
+    if let Some(ref uuid) = actor {
+        match User::find_by_uuid(uuid, &conn, &logger) {
+            Some(u) => filter.actor_id = Some(u.id),
+            None => return res.format(json!([])),
+        }
+    }
+
+    // TODO
+    let mut offset = start;
+    if offset < 1 {
+        offset = 0;
+    }
+
+    let mut limit = stop - start + 2;
+    if limit < 1 {
+        limit = 1;
+    }
+
+    let data = match AuditEvent::fetch_by_namespace(
+        &namespace, &filter, offset, limit, &conn, &logger,
+    ) {
+        None => vec![],
+        Some(a) => {
+            a.iter()
+                .map(|(e, u)| {
+                    // the actor might have been deleted
+                    let actor = u.as_ref().map(|u| {
+                        serde_json::json!({
+                            "uuid": u.uuid.to_string(),
+                            "name": u.name,
+                            "username": u.username,
+                        })
+                    });
+                    json!({
+                        "audit_event": e,
+                        "actor": actor,
+                    })
+                })
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
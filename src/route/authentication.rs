@@ -1,18 +1,33 @@
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use redis::{Commands, RedisError};
 use rocket::State;
-use rocket::http::{Cookie, Cookies, Status};
+use rocket::http::{Cookies, Status};
+use rocket_contrib::json::Json;
 use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
-use crate::model::user::User;
+use crate::logger::Logger;
+use crate::model::user::{User, UserState, verify_dummy_password};
+use crate::model::user_two_factor::UserTwoFactor;
 use crate::model::Authenticatable;
-use crate::model::token::{AuthenticationClaims, Claims, TokenData};
+use crate::mq::MqConn;
+use crate::request::client::ClientInfo;
+use crate::request::token::authentication::AuthenticationToken;
+use crate::request::two_factor::TwoFactorCode;
 use crate::request::user::authentication::UserAuthentication as RequestData;
 use crate::response::Response;
+use crate::route::two_factor::verify_code;
+use crate::route::unlock::request_unlock;
+use crate::service::attempt_limiter::{AttemptLimiter, Scope};
+use crate::service::session_manager::{
+    Session, SessionManager, issue_token, session_id_of,
+};
 use crate::ss::SsConn;
-use crate::util::{split_token, make_cookie};
+use crate::util::{
+    SystemClock, generate_random_hash, make_two_factor_cookie,
+    remove_session_cookies, remove_two_factor_cookie,
+};
 
 pub mod preflight {
     use rocket::State;
@@ -26,10 +41,20 @@ pub mod preflight {
         no_content_for("HEAD,POST", &config)
     }
 
+    #[options("/login/two_factor", rank = 2)]
+    pub fn login_two_factor<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("POST", &config)
+    }
+
     #[options("/logout", rank = 2)]
     pub fn logout<'a>(config: State<Config>) -> RawResponse<'a> {
         no_content_for("POST", &config)
     }
+
+    #[options("/refresh", rank = 2)]
+    pub fn refresh<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("POST", &config)
+    }
 }
 
 pub mod preignition {
@@ -83,13 +108,19 @@ pub mod preignition {
     }
 }
 
+// Failed attempts are counted per account and per IP address, and further
+// attempts are delayed or locked out (see AttemptLimiter). The responses are
+// same whether the account exists or not.
+#[allow(clippy::too_many_arguments)]
 #[post("/login", data = "<data>", format = "json", rank = 1)]
 pub fn login<'a>(
     config: State<Config>,
     mut cookies: Cookies<'a>,
     data: RequestData,
+    client: ClientInfo,
     db_conn: DbConn,
     logger: SyncLogger,
+    mut mq_conn: MqConn,
     mut ss_conn: SsConn,
 ) -> Response<'a> {
     let res: Response = Default::default();
@@ -112,45 +143,100 @@ pub fn login<'a>(
         }));
     }
 
+    let clock = SystemClock;
+    let mut limiter = AttemptLimiter::new(&mut ss_conn, &clock, &logger);
+    // it's accepted if the attempts can't be checked (e.g. Redis is down)
+    let wait = limiter
+        .check(Scope::Login, &data.username, &client)
+        .unwrap_or(0);
+    if wait > 0 {
+        warn!(logger, "login rejected: username {}", data.username);
+
+        return res
+            .header("Retry-After", wait.to_string())
+            .status(Status::TooManyRequests)
+            .format(json!({
+                "message": "Too many failed attempts. Retry later."
+            }));
+    }
+
     match User::find_by_email(&data.username, &db_conn, &logger) {
         Some(ref user) if user.verify_password(&data.password) => {
-            // TODO:
-            // set valid expires_at and impl review mechanism (check also
-            // `validate_exp` for Validation struct for JWT)
-            // e.g. let expires_at = (now + Duration::weeks(2)).timestamp();
-            let data = TokenData {
-                value: user.uuid.to_urn().to_string(),
-                granted_at: Utc::now().timestamp(),
-                expires_at: 0,
-            };
-            let authentication_token = AuthenticationClaims::encode(
-                data,
-                &config.authentication_token_issuer,
-                &config.authentication_token_key_id,
-                &config.authentication_token_secret,
-            );
+            let _ = limiter.reset(Scope::Login, &data.username);
+
+            // upgrades the hash made by an old algorithm (or parameters)
+            if user.password_needs_rehash(&config) {
+                let mut u = user.clone();
+                if let Err(e) = u.rehash_password(
+                    &data.password,
+                    &config,
+                    &db_conn,
+                    &logger,
+                ) {
+                    error!(logger, "error: {}", e);
+                }
+            }
 
-            // TODO:
-            // * consider about implementation "Are you there?" modal
-            // * consider about extension (re-set it again?)
-            let (token, sign) = match split_token(authentication_token) {
-                Some(result) => result,
+            // the second step is required (see login_two_factor)
+            if UserTwoFactor::find_by_user_id(user.id, &db_conn, &logger)
+                .map_or(false, |t| t.is_enabled())
+            {
+                return match start_challenge(
+                    user,
+                    &config,
+                    &mut cookies,
+                    &mut ss_conn,
+                    &logger,
+                ) {
+                    Ok(_) => res
+                        .cookies(cookies)
+                        .status(Status::Accepted)
+                        .format(json!({ "two_factor": true })),
+                    Err(_) => res.status(Status::InternalServerError),
+                };
+            }
+
+            let token = sign_in(
+                user,
+                &client,
+                &config,
+                &mut cookies,
+                &mut ss_conn,
+                &logger,
+            );
+            match token {
+                Some(token) => {
+                    res.cookies(cookies).format(json!({ "token": token }))
+                },
                 None => {
-                    return res.status(Status::InternalServerError).format(
-                        json!({
-                         "message": "Something wrong happen, sorry :'("
-                        }),
-                    );
+                    res.status(Status::InternalServerError).format(json!({
+                        "message": "Something wrong happen, sorry :'("
+                    }))
                 },
-            };
-
-            let cookie = make_cookie(sign, &config);
-            cookies.add_private(cookie);
-            res.cookies(cookies).format(json!({ "token": token }))
+            }
         },
-        _ => {
+        user => {
             warn!(logger, "login failed: username {}", data.username);
 
+            // not to reveal whether the user exists by the response time
+            if user.is_none() {
+                let _ = verify_dummy_password(&data.password, &config);
+            }
+
+            let locked = limiter
+                .record(Scope::Login, &data.username, &client)
+                .map(|a| a.locked)
+                .unwrap_or(false);
+            if let (true, Some(user)) = (locked, user) {
+                let _ = request_unlock(
+                    &user,
+                    &config,
+                    &mut ss_conn,
+                    &mut mq_conn,
+                    &logger,
+                );
+            }
+
             res.status(Status::Unauthorized).format(json!({
                 "message": "The credentials you've entered are incorrect."
             }))
@@ -158,21 +244,236 @@ pub fn login<'a>(
     }
 }
 
+// Starts a browser session of the user, and returns a token bound to it.
+//
+// The authentication token expires shortly, and it's renewed via the refresh
+// token bound to the session. The session expires if it's not refreshed
+// within the idle timeout ("Are you there?").
+fn sign_in(
+    user: &User,
+    client: &ClientInfo,
+    config: &Config,
+    cookies: &mut Cookies,
+    ss_conn: &mut redis::Connection,
+    logger: &Logger,
+) -> Option<String> {
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(ss_conn, &clock, logger);
+    manager.start(user.id, client).ok().and_then(|session| {
+        let now = Utc::now().timestamp();
+        issue_token(user, &session, now, config, cookies)
+    })
+}
+
+// Stores a challenge of two-factor authentication for the user who has
+// passed the password, and sets its key into the cookie (two_factor).
+fn start_challenge(
+    user: &User,
+    config: &Config,
+    cookies: &mut Cookies,
+    ss_conn: &mut redis::Connection,
+    logger: &Logger,
+) -> Result<(), &'static str> {
+    let key = format!(
+        "tf-{}",
+        generate_random_hash(
+            Config::CSRF_HASH_SOURCE,
+            Config::SESSION_HASH_LENGTH,
+        )
+    );
+    let duration = Duration::minutes(Config::TWO_FACTOR_CHALLENGE_DURATION);
+    ss_conn
+        .set_ex(&key, user.id, duration.num_seconds() as usize)
+        .map(|_: String| ())
+        .map_err(|e: RedisError| {
+            error!(logger, "error: {}", e);
+            "failed to store challenge"
+        })?;
+    cookies.add_private(make_two_factor_cookie(key, config));
+    Ok(())
+}
+
+// The second step of login for the user who has enabled two-factor
+// authentication. A code from the app (or a recovery code) is verified for
+// the challenge started by login, and failed attempts are limited as login.
+#[allow(clippy::too_many_arguments)]
+#[post("/login/two_factor", data = "<payload>", format = "json", rank = 1)]
+pub fn login_two_factor<'a>(
+    config: State<Config>,
+    mut cookies: Cookies<'a>,
+    payload: Json<TwoFactorCode>,
+    client: ClientInfo,
+    db_conn: DbConn,
+    logger: SyncLogger,
+    mut ss_conn: SsConn,
+) -> Response<'a> {
+    let res: Response = Default::default();
+
+    let key = match cookies.get_private("two_factor") {
+        Some(c) => c.value().to_string(),
+        None => return expire(cookies),
+    };
+    let user_id: Option<i64> = ss_conn
+        .get(&key)
+        .map_err(|e: RedisError| {
+            error!(logger, "error: {}", e);
+            e
+        })
+        .ok()
+        .flatten();
+    let user = user_id
+        .and_then(|id| User::find_by_id(id, &db_conn, &logger))
+        .filter(|u| u.state == UserState::Active);
+    let two_factor = user
+        .as_ref()
+        .and_then(|u| UserTwoFactor::find_by_user_id(u.id, &db_conn, &logger))
+        .filter(|t| t.is_enabled());
+    let (user, two_factor) = match (user, two_factor) {
+        (Some(u), Some(t)) => (u, t),
+        _ => {
+            remove_two_factor_cookie(&mut cookies);
+            return expire(cookies);
+        },
+    };
+
+    let identifier = user.uuid.to_string();
+    let clock = SystemClock;
+    let mut limiter = AttemptLimiter::new(&mut ss_conn, &clock, &logger);
+    let wait = limiter
+        .check(Scope::TwoFactor, &identifier, &client)
+        .unwrap_or(0);
+    if wait > 0 {
+        warn!(logger, "login rejected: user {}", user.uuid);
+
+        return res
+            .header("Retry-After", wait.to_string())
+            .status(Status::TooManyRequests)
+            .format(json!({
+                "message": "Too many failed attempts. Retry later."
+            }));
+    }
+
+    if !verify_code(&two_factor, &payload.0.code, &config, &db_conn, &logger) {
+        warn!(logger, "login failed: user {}", user.uuid);
+
+        let locked = limiter
+            .record(Scope::TwoFactor, &identifier, &client)
+            .map(|a| a.locked)
+            .unwrap_or(false);
+        if locked {
+            // starts over from the password
+            let _: Result<i64, RedisError> = ss_conn.del(&key);
+            remove_two_factor_cookie(&mut cookies);
+        }
+        return res.cookies(cookies).status(Status::Unauthorized).format(
+            json!({
+                "message": "The code you've entered is incorrect."
+            }),
+        );
+    }
+    let _ = limiter.reset(Scope::TwoFactor, &identifier);
+
+    // the challenge can be used only once
+    let _: Result<i64, RedisError> = ss_conn.del(&key).map_err(|e| {
+        error!(logger, "error: {}", e);
+        e
+    });
+    remove_two_factor_cookie(&mut cookies);
+
+    let token =
+        sign_in(&user, &client, &config, &mut cookies, &mut ss_conn, &logger);
+    match token {
+        Some(token) => res.cookies(cookies).format(json!({ "token": token })),
+        None => {
+            res.status(Status::InternalServerError).format(json!({
+                "message": "Something wrong happen, sorry :'("
+            }))
+        },
+    }
+}
+
+// Removes cookies of the session, and returns 401.
+fn expire(mut cookies: Cookies) -> Response {
+    remove_session_cookies(&mut cookies);
+
+    let res: Response = Default::default();
+    res.cookies(cookies).status(Status::Unauthorized).format(json!({
+        "message": "The session has been expired. Sign in again."
+    }))
+}
+
+// Renews the authentication token with the refresh token, which is rotated
+// at the same time.
+#[post("/refresh", format = "json", rank = 1)]
+pub fn refresh<'a>(
+    config: State<Config>,
+    mut cookies: Cookies<'a>,
+    db_conn: DbConn,
+    logger: SyncLogger,
+    mut ss_conn: SsConn,
+) -> Response<'a> {
+    let res: Response = Default::default();
+
+    let value = cookies
+        .get_private("refresh")
+        .and_then(|c| Session::parse_cookie_value(c.value()));
+    let (id, refresh_token) = match value {
+        Some(v) => v,
+        None => return expire(cookies),
+    };
+
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    let session = match manager.rotate(&id, &refresh_token) {
+        Ok(s) => s,
+        Err(e) => {
+            info!(logger, "error: {}", e);
+            return expire(cookies);
+        },
+    };
+
+    let user = match User::find_by_id(session.user_id, &db_conn, &logger) {
+        Some(u) if u.state == UserState::Active => u,
+        _ => {
+            let _ = manager.finish(&session.id);
+            return expire(cookies);
+        },
+    };
+
+    let now = Utc::now().timestamp();
+    match issue_token(&user, &session, now, &config, &mut cookies) {
+        Some(token) => res.cookies(cookies).format(json!({ "token": token })),
+        None => res.status(Status::InternalServerError),
+    }
+}
+
 // logout
 //
-// * Remove a cookie
-// * Delete session value in Redis
+// * Remove cookies
+// * Destroy the session in Redis (the token and the refresh token are no
+//   longer available)
 #[post("/logout", format = "json", rank = 1)]
 pub fn logout<'a>(
     mut cookies: Cookies,
     user: &User,
+    token: AuthenticationToken,
+    config: State<Config>,
     logger: SyncLogger,
+    mut ss_conn: SsConn,
 ) -> Response<'a> {
     let res: Response = Default::default();
     info!(logger, "user: {}", user.uuid);
 
-    // TODO: remove_private
-    cookies.remove(Cookie::named("sign"));
+    if let Some(id) = session_id_of(&token, &config) {
+        let clock = SystemClock;
+        let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+        if let Err(e) = manager.finish_of(user.id, &id) {
+            error!(logger, "error: {}", e);
+            return res.status(Status::InternalServerError);
+        }
+    }
+
+    remove_session_cookies(&mut cookies);
 
     res.status(Status::Ok)
 }
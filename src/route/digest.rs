@@ -0,0 +1,191 @@
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::model::digest_subscription::{DigestFrequency, DigestSubscription};
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::user::User;
+use crate::response::Response;
+use crate::request::digest_subscription::DigestSubscription as RequestData;
+use crate::route::namespace::authorize;
+use crate::validation::digest_subscription::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/digest/<namespace_uuid>/del", rank = 2)]
+    pub fn del<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/digest/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "hgetall");
+        no_content_for("GET", &config)
+    }
+
+    #[options("/digest/<namespace_uuid>/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+// Opts out of the digest of the namespace.
+#[delete("/digest/<namespace_uuid>/del", rank = 1)]
+pub fn del<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) = authorize(
+        &namespace,
+        user,
+        Permission::DigestsSubscribe,
+        &conn,
+        &logger,
+    ) {
+        return forbidden;
+    }
+
+    match DigestSubscription::find_by_user_and_namespace_id(
+        user,
+        namespace.id,
+        &conn,
+        &logger,
+    ) {
+        None => res.status(Status::NotFound),
+        Some(s) => {
+            if s.delete(&conn, &logger).is_err() {
+                return res.status(Status::InternalServerError);
+            }
+            res.format(json!({
+                "digest_subscription": 1,
+            }))
+        },
+    }
+}
+
+// Returns digest subscriptions of the user in visible namespaces.
+#[get("/digest/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let namespaces =
+        Namespace::find_all(user, &conn, &logger).unwrap_or_default();
+    let subscriptions =
+        DigestSubscription::find_all_by_user(user, &conn, &logger)
+            .unwrap_or_default();
+
+    let data: Vec<_> = subscriptions
+        .iter()
+        .filter_map(|s| {
+            namespaces.iter().find(|n| n.id == s.namespace_id).map(|n| {
+                json!({
+                    "digest_subscription": s,
+                    "namespace": {
+                        "uuid": n.uuid.to_string(),
+                        "name": n.name,
+                    },
+                })
+            })
+        })
+        .collect();
+    res.format(json!(data))
+}
+
+// Opts in to the digest of the namespace or changes its frequency.
+#[post(
+    "/digest/<namespace_uuid>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) = authorize(
+        &namespace,
+        user,
+        Permission::DigestsSubscribe,
+        &conn,
+        &logger,
+    ) {
+        return forbidden;
+    }
+
+    let v = Validator::new(&data, &logger);
+    match v.validate() {
+        Err(errors) => {
+            res.status(Status::UnprocessableEntity).format(json!({
+                "errors": errors,
+            }))
+        },
+        Ok(_) => {
+            let s = data.0.frequency.clone().unwrap_or_default();
+            let frequency = DigestFrequency::from(s);
+            match DigestSubscription::upsert(
+                user,
+                namespace.id,
+                frequency,
+                &conn,
+                &logger,
+            ) {
+                None => res.status(Status::InternalServerError),
+                Some(s) => {
+                    res.format(json!({
+                        "digest_subscription": s,
+                    }))
+                },
+            }
+        },
+    }
+}
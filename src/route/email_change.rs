@@ -0,0 +1,323 @@
+use chrono::{Duration, Utc};
+use diesel::result::Error;
+use fourche::queue::Queue;
+use redis::{Commands, RedisError};
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::job::{Job, JobKind};
+use crate::model::{Activatable, Authenticatable};
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::token::{Claims, TokenData, VerificationClaims};
+use crate::model::user::User;
+use crate::model::user_email::{NewUserEmail, UserEmail, UserEmailRole};
+use crate::mq::MqConn;
+use crate::request::client::ClientInfo;
+use crate::request::email_change::EmailChange;
+use crate::request::token::verification::VerificationToken;
+use crate::request::user_email::UserEmail as UserEmailData;
+use crate::response::Response;
+use crate::service::session_manager::SessionManager;
+use crate::ss::{SsConn, user_sessions_key, user_sessions_revoked_at_key};
+use crate::util::{SystemClock, split_token};
+use crate::validation::user_email::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/email/change", rank = 2)]
+    pub fn request<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PUT", &config)
+    }
+
+    #[options("/email/change/<session_id>", rank = 2)]
+    pub fn confirm_cancel<'a>(
+        session_id: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "session_id: {}", session_id);
+        no_content_for("PATCH,DELETE", &config)
+    }
+}
+
+// Adds the new address as a pending one, and sends a verification link to it
+// and a notice (with a cancel link) to the current address. The primary
+// address is swapped only when the new one is confirmed.
+#[put("/email/change", data = "<payload>", format = "json", rank = 1)]
+pub fn request<'a>(
+    user: &User,
+    payload: Json<EmailChange>,
+    config: State<Config>,
+    db_conn: DbConn,
+    mut mq_conn: MqConn,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    if !user.verify_password(&payload.0.password) {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "password",
+                "messages": ["Must match the current password"],
+            }],
+        }));
+    }
+
+    let data = Json(UserEmailData {
+        email: Some(payload.0.email.to_string()),
+    });
+    let v = Validator::new(&db_conn, &data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let pending = UserEmail::count_pending_by_user(user, &db_conn, &logger);
+    if pending.map_or(true, |n| n >= Config::USER_EMAIL_PENDING_LIMIT) {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "email",
+                "messages": ["Too many unverified addresses"],
+            }],
+        }));
+    }
+
+    let ue = NewUserEmail {
+        user_id: user.id,
+        email: payload.0.email.to_string(),
+        role: UserEmailRole::General,
+
+        ..Default::default()
+    };
+
+    let duration = Duration::hours(Config::USER_EMAIL_TOKEN_DURATION);
+    let result: Result<(i64, String), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(i64, String), diesel::result::Error, _>(|| {
+            // the address may be left by someone as pending
+            UserEmail::delete_expired_by_email(&ue.email, &db_conn, &logger)
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+            let user_email = UserEmail::insert(&ue, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            let now = Utc::now();
+            let data = TokenData {
+                value: UserEmail::generate_token(),
+                granted_at: now.timestamp(),
+                expires_at: (now + duration).timestamp(),
+            };
+            let raw_token = VerificationClaims::encode(
+                data,
+                &config.verification_token_issuer,
+                &config.verification_token_key_id,
+                &config.verification_token_secret,
+            );
+            user_email
+                .grant_token::<VerificationClaims>(
+                    &raw_token,
+                    &config.verification_token_issuer,
+                    &config.verification_token_secret,
+                    &db_conn,
+                    &logger,
+                )
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+            Ok((user_email.id, raw_token))
+        });
+
+    if let Ok((id, raw_token)) = result {
+        if let Some((token, sign)) = split_token(raw_token) {
+            // TODO: use general value
+            let session_id = UserEmail::generate_token();
+            let key = format!("ec-{}", session_id);
+
+            // the signature is kept in session store (see password reset)
+            let ttl = duration.num_seconds() as usize;
+            let result: Result<String, RedisError> =
+                ss_conn.set_ex(&key, sign, ttl).map_err(|e| {
+                    error!(logger, "error: {}", e);
+                    e
+                });
+
+            if result.is_ok() {
+                let sessions_key = user_sessions_key(user.id);
+                let _: Result<(), RedisError> = ss_conn
+                    .sadd(&sessions_key, &key)
+                    .and_then(|_: i64| ss_conn.expire(&sessions_key, ttl))
+                    .map_err(|e| {
+                        error!(logger, "error: {}", e);
+                        e
+                    });
+
+                let args = vec![id.to_string(), session_id, token];
+                let jobs = vec![
+                    Job::<String> {
+                        kind: JobKind::SendEmailChangeVerificationEmail,
+                        args: args.clone(),
+                    },
+                    Job::<String> {
+                        kind: JobKind::SendEmailChangeNoticeEmail,
+                        args,
+                    },
+                ];
+                let mut queue = Queue::new("default", &mut *mq_conn);
+                if jobs
+                    .into_iter()
+                    .all(|job| queue.enqueue::<Job<String>>(job).is_ok())
+                {
+                    return res;
+                }
+                error!(logger, "error: failed to enqueue jobs");
+            }
+        }
+    }
+    res.status(Status::InternalServerError).format(json!({
+        "message": "Something wrong happen, sorry :'("
+    }))
+}
+
+// Swaps the primary address (and `users.email`) to the confirmed new one.
+// All the browser sessions of the user are revoked after that.
+#[patch("/email/change/<session_id>", format = "json", rank = 1)]
+pub fn confirm<'a>(
+    session_id: String,
+    token: VerificationToken,
+    client: ClientInfo,
+    config: State<Config>,
+    db_conn: DbConn,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "session_id: {}", session_id);
+
+    let res: Response = Default::default();
+
+    let user_email = match UserEmail::find_by_token::<VerificationClaims>(
+        &token,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+        &db_conn,
+        &logger,
+    ) {
+        Some(ue) => ue,
+        None => return res.status(Status::NotFound),
+    };
+    let user = match User::find_by_id(user_email.user_id, &db_conn, &logger) {
+        Some(u) => u,
+        None => return res.status(Status::NotFound),
+    };
+
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            user_email.activate(&db_conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+            let ue = UserEmail::find_by_id(user_email.id, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            let ue = ue.promote(&db_conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::UserEmailChange,
+                Some(&user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            e.diff = serde_json::json!({"email": [user.email, ue.email]});
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(())
+        });
+    if result.is_err() {
+        return res.status(Status::InternalServerError);
+    }
+
+    // clear session, and revoke the current browser sessions of the user
+    let key = format!("ec-{}", session_id);
+    let revoked_at_key = user_sessions_revoked_at_key(user.id);
+    let result: Result<(), RedisError> = ss_conn
+        .del(&key)
+        .and_then(|_: i64| ss_conn.set(&revoked_at_key, Utc::now().timestamp()))
+        .map_err(|e| {
+            error!(logger, "error: {}", e);
+            e
+        });
+    if result.is_err() {
+        return res.status(Status::InternalServerError);
+    }
+
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    if let Err(e) = manager.finish_all(user.id, None) {
+        error!(logger, "error: {}", e);
+        return res.status(Status::InternalServerError);
+    }
+    res.status(Status::Ok)
+}
+
+// Cancels the email change via the link sent to the current address.
+#[delete("/email/change/<session_id>", format = "json", rank = 1)]
+pub fn cancel<'a>(
+    session_id: String,
+    token: VerificationToken,
+    config: State<Config>,
+    db_conn: DbConn,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "session_id: {}", session_id);
+
+    let res: Response = Default::default();
+
+    let user_email = match UserEmail::find_by_token::<VerificationClaims>(
+        &token,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+        &db_conn,
+        &logger,
+    ) {
+        Some(ue) => ue,
+        None => return res.status(Status::NotFound),
+    };
+    if let Err(e) = user_email.delete(&db_conn, &logger) {
+        error!(logger, "err: {}", e);
+        return res.status(Status::InternalServerError);
+    }
+
+    // clear session
+    let key = format!("ec-{}", session_id);
+    let _: Result<i64, RedisError> = ss_conn.del(&key).map_err(|e| {
+        error!(logger, "error: {}", e);
+        e
+    });
+    res.status(Status::Ok)
+}
@@ -1,12 +1,14 @@
 use rocket::Request;
 use rocket::http::{Cookies, Status};
 
+use crate::model::permission::Permission;
 use crate::response::Response;
 
 #[catch(400)]
 pub fn bad_request<'a>(_req: &Request) -> Response<'a> {
     Response {
         cookies: Cookies::empty(),
+        headers: vec![],
         status: Status::BadRequest,
         data: json!({
             "data": {
@@ -20,6 +22,7 @@ pub fn bad_request<'a>(_req: &Request) -> Response<'a> {
 pub fn unauthorized<'a>(_req: &Request) -> Response<'a> {
     Response {
         cookies: Cookies::empty(),
+        headers: vec![],
         status: Status::Unauthorized,
         data: json!({
             "data": {
@@ -33,10 +36,27 @@ pub fn unauthorized<'a>(_req: &Request) -> Response<'a> {
 pub fn forbidden<'a>(_req: &Request) -> Response<'a> {
     Response {
         cookies: Cookies::empty(),
-        status: Status::Unauthorized,
+        headers: vec![],
+        status: Status::Forbidden,
+        data: json!({
+            "data": {
+                "message": "The request is forbidden".to_string(),
+            }
+        }),
+    }
+}
+
+// Returns a response for a request which needs the permission in the
+// namespace. It has the same body with the catcher above.
+pub fn forbidden_by<'a>(permission: Permission) -> Response<'a> {
+    Response {
+        cookies: Cookies::empty(),
+        headers: vec![],
+        status: Status::Forbidden,
         data: json!({
             "data": {
-                "message": "The request is not prohibited".to_string(),
+                "message": "The request is forbidden".to_string(),
+                "permission": permission.to_string(),
             }
         }),
     }
@@ -46,6 +66,7 @@ pub fn forbidden<'a>(_req: &Request) -> Response<'a> {
 pub fn not_found<'a>(req: &Request) -> Response<'a> {
     Response {
         cookies: Cookies::empty(),
+        headers: vec![],
         status: Status::NotFound,
         data: json!({
             "data": {
@@ -59,6 +80,7 @@ pub fn not_found<'a>(req: &Request) -> Response<'a> {
 pub fn unprocessable_entity<'a>(_req: &Request) -> Response<'a> {
     Response {
         cookies: Cookies::empty(),
+        headers: vec![],
         status: Status::UnprocessableEntity,
         data: json!({
             "data": {
@@ -72,6 +94,7 @@ pub fn unprocessable_entity<'a>(_req: &Request) -> Response<'a> {
 pub fn internal_server_error<'a>(_req: &Request) -> Response<'a> {
     Response {
         cookies: Cookies::empty(),
+        headers: vec![],
         status: Status::InternalServerError,
         data: json!({
             "data": {
@@ -0,0 +1,417 @@
+use chrono::{Duration, Utc};
+use diesel::result::Error;
+use fourche::queue::Queue;
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::job::{Job, JobKind};
+use crate::model::Verifiable;
+use crate::model::invitation::{Invitation, NewInvitation};
+use crate::model::membership::Membership;
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::token::{Claims, TokenData, VerificationClaims};
+use crate::model::user::User;
+use crate::mq::MqConn;
+use crate::response::Response;
+use crate::request::invitation::{
+    Acceptance as AcceptanceData, Invitation as RequestData,
+};
+use crate::route::namespace::authorize;
+use crate::validation::invitation::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/invitation/accept", rank = 2)]
+    pub fn accept<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("POST", &config)
+    }
+
+    #[options("/invitation/<namespace_uuid>/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/invitation/<namespace_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/invitation/<namespace_uuid>/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/invitation/<namespace_uuid>/resend/<uuid>", rank = 2)]
+    pub fn resend<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+// Grants a new token to the invitation, and returns the raw token. Tokens
+// granted before won't be available anymore.
+fn grant_token(
+    invitation: &Invitation,
+    config: &Config,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<String, &'static str> {
+    let now = Utc::now();
+    let duration = Duration::days(Config::INVITATION_TOKEN_DURATION);
+    let data = TokenData {
+        value: Invitation::generate_token(),
+        granted_at: now.timestamp(),
+        expires_at: (now + duration).timestamp(),
+    };
+    let raw_token = VerificationClaims::encode(
+        data,
+        &config.verification_token_issuer,
+        &config.verification_token_key_id,
+        &config.verification_token_secret,
+    );
+    invitation.grant_token::<VerificationClaims>(
+        &raw_token,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+        conn,
+        logger,
+    )?;
+    Ok(raw_token)
+}
+
+fn enqueue(
+    invitation: &Invitation,
+    raw_token: String,
+    mq_conn: &mut MqConn,
+    logger: &SyncLogger,
+) -> Result<(), &'static str> {
+    let job = Job::<String> {
+        kind: JobKind::SendInvitationEmail,
+        args: vec![invitation.id.to_string(), raw_token],
+    };
+    let mut queue = Queue::new("default", &mut **mq_conn);
+    queue.enqueue::<Job<String>>(job).map_err(|e| {
+        error!(logger, "error: {}", e);
+        "failed to enqueue a job"
+    })
+}
+
+// Accepts the invitation by the token for the user (in a transaction). This
+// is used also on registration.
+pub fn accept_by_token(
+    token: &str,
+    user: &User,
+    config: &Config,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<Invitation, &'static str> {
+    let concrete_token = Invitation::extract_concrete_token(
+        token,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+    )?;
+    let invitation =
+        Invitation::load_by_concrete_token(&concrete_token, conn, logger)?;
+    invitation.accept(user, conn, logger)?;
+    Ok(invitation)
+}
+
+#[post("/invitation/accept", data = "<data>", format = "json", rank = 1)]
+pub fn accept<'a>(
+    user: &User,
+    data: Json<AcceptanceData>,
+    conn: DbConn,
+    logger: SyncLogger,
+    config: State<Config>,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let token = data.0.token.clone().unwrap_or_default();
+    let result: Result<Invitation, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Invitation, diesel::result::Error, _>(|| {
+            accept_by_token(&token, user, &config, &conn, &logger).map_err(
+                |e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                },
+            )
+        });
+
+    let invitation = match result {
+        Err(_) => {
+            return res.status(Status::BadRequest).format(json!({
+                "message": "The invitation has been expired or is invalid"
+            }));
+        },
+        Ok(i) => i,
+    };
+    match Namespace::find_by_id(invitation.namespace_id, &conn, &logger) {
+        None => res.status(Status::NotFound),
+        Some(n) => {
+            res.format(json!({"namespace": {
+                "uuid": n.uuid.to_string(),
+            }}))
+        },
+    }
+}
+
+// Cancels the pending invitation. Only owners can cancel it.
+#[delete("/invitation/<namespace_uuid>/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::MembersManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let invitation = match Invitation::find_pending_by_uuid_in(
+        &namespace, &uuid, &conn, &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(i) => i,
+    };
+
+    if let Err(e) = invitation.delete(&conn, &logger) {
+        error!(logger, "err: {}", e);
+        return res.status(Status::InternalServerError);
+    }
+
+    res.format(json!({
+        "invitation": 1,
+    }))
+}
+
+// Lists pending invitations. Only owners can see them.
+#[get("/invitation/<namespace_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::MembersManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let data = match Invitation::find_all_pending_by_namespace(
+        &namespace, &conn, &logger,
+    ) {
+        None => {
+            error!(logger, "err: no invitation for namespace: {}", namespace);
+            vec![]
+        },
+        Some(a) => a.iter().map(|i| json!({ "invitation": i })).collect(),
+    };
+    res.format(json!(data))
+}
+
+// Invites someone to the namespace by email. Only owners can invite.
+#[post(
+    "/invitation/<namespace_uuid>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: SyncLogger,
+    config: State<Config>,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::MembersManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let mut i = NewInvitation::from(data.0.clone());
+    i.namespace_id = namespace.id;
+    i.inviter_id = user.id;
+
+    let invited = Invitation::find_pending_by_email_in(
+        &namespace, &i.email, &conn, &logger,
+    )
+    .is_some();
+    let joined = User::find_by_email(&i.email, &conn, &logger)
+        .and_then(|u| {
+            Membership::find_by_namespace_id_and_user(
+                namespace.id,
+                &u,
+                &conn,
+                &logger,
+            )
+        })
+        .is_some();
+    if invited || joined {
+        let message = if invited {
+            "Has been invited already"
+        } else {
+            "Is a member already"
+        };
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "email",
+                "messages": [message],
+            }],
+        }));
+    }
+
+    let result: Result<(Invitation, String), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(Invitation, String), diesel::result::Error, _>(|| {
+            let invitation = Invitation::insert(&i, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            let raw_token = grant_token(&invitation, &config, &conn, &logger)
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+            Ok((invitation, raw_token))
+        });
+
+    match result {
+        Ok((invitation, raw_token)) => {
+            info!(logger, "invitation: {}", invitation.id);
+            if enqueue(&invitation, raw_token, &mut mq_conn, &logger).is_ok() {
+                return res.format(json!({ "invitation": invitation }));
+            }
+            res.status(Status::InternalServerError)
+        },
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Sends the invitation again with a new token. Only owners can resend it.
+#[post("/invitation/<namespace_uuid>/resend/<uuid>", rank = 1)]
+pub fn resend<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: SyncLogger,
+    config: State<Config>,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::MembersManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let invitation = match Invitation::find_pending_by_uuid_in(
+        &namespace, &uuid, &conn, &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(i) => i,
+    };
+
+    let result = grant_token(&invitation, &config, &conn, &logger)
+        .and_then(|t| enqueue(&invitation, t, &mut mq_conn, &logger));
+    if let Err(e) = result {
+        error!(logger, "err: {}", e);
+        return res.status(Status::InternalServerError);
+    }
+
+    res.status(Status::Accepted).format(json!({"invitation": {
+        "uuid": invitation.uuid.to_string(),
+    }}))
+}
@@ -0,0 +1,387 @@
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::membership::{Membership, MembershipRole};
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::user::User;
+use crate::response::Response;
+use crate::request::client::ClientInfo;
+use crate::request::membership::Membership as RequestData;
+use crate::route::error::forbidden_by;
+use crate::route::namespace::authorize;
+use crate::validation::membership::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/membership/<namespace_uuid>/del/<user_uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_uuid: String,
+        user_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, user: {}", namespace_uuid, user_uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/membership/<namespace_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/membership/<namespace_uuid>/hset/<user_uuid>", rank = 2)]
+    pub fn hupdate<'a>(
+        namespace_uuid: String,
+        user_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, user: {}", namespace_uuid, user_uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/membership/<namespace_uuid>/transfer/<user_uuid>", rank = 2)]
+    pub fn transfer<'a>(
+        namespace_uuid: String,
+        user_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, user: {}", namespace_uuid, user_uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+// Returns the (active) membership of the user in the namespace.
+fn find_member(
+    namespace: &Namespace,
+    user_uuid: &str,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Option<Membership> {
+    let user = User::find_by_uuid(user_uuid, conn, logger)?;
+    Membership::find_by_namespace_id_and_user(namespace.id, &user, conn, logger)
+}
+
+// Revokes the membership. Owners can revoke others, and anyone can leave the
+// namespace by themselves. The primary owner can't be revoked.
+#[delete("/membership/<namespace_uuid>/del/<user_uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_uuid: String,
+    user_uuid: String,
+    user: &User,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, member: {}",
+        user.uuid,
+        namespace_uuid,
+        user_uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    let current = match authorize(
+        &namespace,
+        user,
+        Permission::MembersRead,
+        &conn,
+        &logger,
+    ) {
+        Err(forbidden) => return forbidden,
+        Ok(m) => m,
+    };
+    let member = match find_member(&namespace, &user_uuid, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(m) => m,
+    };
+    let permission = Permission::MembersManage;
+    if current.id != member.id && !permission.is_granted_to(&current.role) {
+        return forbidden_by(permission);
+    }
+    if member.role == MembershipRole::PrimaryOwner {
+        return forbidden_by(Permission::OwnershipTransfer);
+    }
+
+    let result: Result<Membership, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Membership, diesel::result::Error, _>(|| {
+            let revoked = member.revoke(&conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::MembershipRevoke,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user_uuid.clone());
+            e.namespace_id = Some(namespace.id);
+            e.diff = serde_json::json!({
+                "role": [member.role, null],
+                "revoked_at": [member.revoked_at, revoked.revoked_at],
+            });
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(revoked)
+        });
+    if result.is_err() {
+        return res.status(Status::InternalServerError);
+    }
+
+    res.format(json!({
+        "membership": 1,
+    }))
+}
+
+#[get("/membership/<namespace_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::MembersRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let data = match Membership::find_all_by_namespace(
+        &namespace, &conn, &logger,
+    ) {
+        None => {
+            error!(logger, "err: no membership for namespace: {}", namespace);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|(m, u)| {
+                    json!({"membership": {
+                        "role": m.role,
+                        "created_at": m.created_at,
+                        "user": {
+                            "uuid": u.uuid.to_string(),
+                            "name": u.name,
+                            "username": u.username,
+                        },
+                    }})
+                })
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Changes the role of the member. Only owners can change it, and the primary
+// owner can't be demoted (see transfer).
+#[patch(
+    "/membership/<namespace_uuid>/hset/<user_uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hupdate<'a>(
+    namespace_uuid: String,
+    user_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, member: {}",
+        user.uuid,
+        namespace_uuid,
+        user_uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::MembersManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let member = match find_member(&namespace, &user_uuid, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(m) => m,
+    };
+    if member.role == MembershipRole::PrimaryOwner {
+        return forbidden_by(Permission::OwnershipTransfer);
+    }
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let role = MembershipRole::from(data.0.role.clone().unwrap_or_default());
+    let result: Result<Membership, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Membership, diesel::result::Error, _>(|| {
+            let changed =
+                member.change_role(role, &conn, &logger).map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::MembershipRoleChange,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user_uuid.clone());
+            e.namespace_id = Some(namespace.id);
+            e.diff = serde_json::json!({
+                "role": [member.role, changed.role],
+            });
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(changed)
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(m) => {
+            res.format(json!({"membership": {
+                "role": m.role,
+                "user": {
+                    "uuid": user_uuid,
+                },
+            }}))
+        },
+    }
+}
+
+// Transfers the primary ownership to the member. Only the primary owner can
+// transfer it, and becomes an owner.
+#[post("/membership/<namespace_uuid>/transfer/<user_uuid>", rank = 1)]
+pub fn transfer<'a>(
+    namespace_uuid: String,
+    user_uuid: String,
+    user: &User,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, member: {}",
+        user.uuid,
+        namespace_uuid,
+        user_uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    let current = match authorize(
+        &namespace,
+        user,
+        Permission::OwnershipTransfer,
+        &conn,
+        &logger,
+    ) {
+        Err(forbidden) => return forbidden,
+        Ok(m) => m,
+    };
+    let member = match find_member(&namespace, &user_uuid, &conn, &logger) {
+        Some(m) if m.id != current.id => m,
+        _ => return res.status(Status::NotFound),
+    };
+
+    let result: Result<Membership, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Membership, diesel::result::Error, _>(|| {
+            let promoted = current
+                .transfer_primary_ownership(&member, &conn, &logger)
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::MembershipTransfer,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user_uuid.clone());
+            e.namespace_id = Some(namespace.id);
+            e.diff = serde_json::json!({
+                "role": [member.role, promoted.role],
+                "primary_owner": [user.uuid.to_string(), user_uuid],
+            });
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(promoted)
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(m) => {
+            res.format(json!({"membership": {
+                "role": m.role,
+                "user": {
+                    "uuid": user_uuid,
+                },
+            }}))
+        },
+    }
+}
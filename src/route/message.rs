@@ -1,12 +1,32 @@
+use fourche::queue::Queue;
+use rocket::State;
 use rocket::http::Status;
 use rocket_contrib::json::Json;
 use rocket_slog::SyncLogger;
 
+use crate::config::Config;
 use crate::db::DbConn;
-use crate::model::message::{AgentType, Message, NewMessage};
+use crate::job::Job;
+use crate::model::message::{AgentType, LogLevel, Message, NewMessage};
+use crate::model::namespace::Namespace;
+use crate::model::namespace_key_redirect::NamespaceKeyRedirect;
+use crate::model::permission::Permission;
+use crate::model::redaction_rule::RedactionRule;
+use crate::model::stream::Stream;
+use crate::model::token::{BrowserCookieTokenClaims, Claims};
 use crate::model::user::User;
+use crate::model::webhook::WebhookEvent;
+use crate::mq::MqConn;
 use crate::response::Response;
+use crate::service::rate_limiter::{self, RateLimit, RateLimiter};
+use crate::service::redactor::Redactor;
+use crate::service::usage_meter::{self, Admission, UsageMeter};
+use crate::service::webhook_sender;
 use crate::request::message::Message as RequestData;
+use crate::request::token::TokenType;
+use crate::request::token::authentication::AuthenticationToken;
+use crate::route::stream::authorize_stream;
+use crate::util::{Clock, SystemClock};
 use crate::validation::message::Validator;
 
 const MESSAGES_PER_REQUEST: i64 = 100;
@@ -77,15 +97,20 @@ pub mod preflight {
     data = "<data>",
     rank = 1
 )]
+#[allow(clippy::too_many_arguments)]
 pub fn append(
     user: &User,
+    token: AuthenticationToken,
+    token_type: TokenType,
     namespace_key: String,
     stream_slug: String,
     data: Json<RequestData>,
     conn: DbConn,
+    mut mq_conn: MqConn,
+    config: State<Config>,
     logger: SyncLogger,
 ) -> Response {
-    let res: Response = Default::default();
+    let mut res: Response = Default::default();
 
     info!(
         logger,
@@ -95,9 +120,50 @@ pub fn append(
         stream_slug
     );
 
+    if let Some(key) = renamed_key(&namespace_key, &conn, &logger) {
+        let location = format!("/v1/message/{}/append/{}", key, stream_slug);
+        return res
+            .header("Location", location)
+            .status(Status::PermanentRedirect);
+    }
+
+    let (namespace, stream) = match find_stream(
+        &namespace_key,
+        &stream_slug,
+        user,
+        Permission::MessagesWrite,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(v) => v,
+    };
+    if stream.archived_at.is_some() {
+        return res.status(Status::Conflict).format(json!({
+            "message": "Stream is archived",
+        }));
+    }
+
+    if let Some(limit) = take_rate_limit(
+        &token,
+        &token_type,
+        &namespace,
+        &config,
+        &mut mq_conn,
+        &logger,
+    ) {
+        for (name, value) in limit.headers() {
+            res = res.header(name, value);
+        }
+        if limit.is_exceeded() {
+            return res.status(Status::TooManyRequests).format(json!({
+                "message": "Too many requests, retry later",
+            }));
+        }
+    }
+
     // FIXME
-    // * namespace
-    // * validations for stream_id (slug) and agent_* fields
+    // * validations for agent_* fields
     let v = Validator::new(&data, &logger);
     match v.validate() {
         Err(errors) => {
@@ -106,14 +172,44 @@ pub fn append(
             }))
         },
         Ok(_) => {
-            // FIXME
-            let stream_id = 1;
+            let clock = SystemClock;
+            let meter = UsageMeter::new(&conn, &clock, &logger);
+            match meter.admit(&namespace, &config) {
+                Admission::Accept => {},
+                Admission::Drop => {
+                    return res.status(Status::Accepted).format(json!({
+                        "message": null,
+                    }));
+                },
+                Admission::Reject => {
+                    let retry_after =
+                        usage_meter::seconds_until_reset(clock.now());
+                    return res
+                        .header("Retry-After", retry_after.to_string())
+                        .status(Status::TooManyRequests)
+                        .format(json!({
+                            "message": "Daily quota exceeded",
+                        }));
+                },
+            }
+
             let mut m = NewMessage::from(data.0.clone());
-            m.stream_id = stream_id;
+            m.stream_id = stream.id;
             m.agent_id = user.id;
             m.agent_type = AgentType::Person;
+            redact(&mut m, &namespace, &conn, &logger);
             if let Some(id) = Message::insert(&m, &conn, &logger) {
                 info!(logger, "user: {}", user.uuid);
+                let bytes = bytes_of(&m);
+                if let Some(job) = meter.record(&namespace, bytes, &config) {
+                    let mut queue = Queue::new("default", &mut **mq_conn);
+                    if let Err(err) = queue.enqueue::<Job<String>>(job) {
+                        error!(logger, "error: {}", err);
+                    }
+                }
+                if m.level == LogLevel::Critical {
+                    notify_critical(id, &m, &conn, &mut mq_conn, &logger);
+                }
                 return res.format(json!({"message": {
                     "id": id,
                 }}));
@@ -123,6 +219,162 @@ pub fn append(
     }
 }
 
+// Finds the namespace by the key and the stream by the slug in it, and checks
+// the permission of the user on the stream.
+fn find_stream<'a>(
+    namespace_key: &str,
+    stream_slug: &str,
+    user: &User,
+    permission: Permission,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<(Namespace, Stream), Response<'a>> {
+    let not_found = || Response::default().status(Status::NotFound);
+
+    let namespace = Namespace::find_by_key(namespace_key, conn, logger)
+        .ok_or_else(not_found)?;
+    let stream = Stream::find_by_slug_in(&namespace, stream_slug, conn, logger)
+        .ok_or_else(not_found)?;
+    authorize_stream(&namespace, &stream, user, permission, conn, logger)?;
+    Ok((namespace, stream))
+}
+
+// Returns the current key of the namespace if the key is an old one.
+fn renamed_key(
+    key: &str,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Option<String> {
+    NamespaceKeyRedirect::find_by_key(key, conn, logger)
+        .and_then(|r| Namespace::find_by_id(r.namespace_id, conn, logger))
+        .map(|n| n.key)
+}
+
+// Masks personal data in the message by redaction rules of the namespace.
+fn redact(
+    m: &mut NewMessage,
+    namespace: &Namespace,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) {
+    let rules =
+        RedactionRule::find_all_by_namespace_id(namespace.id, conn, logger)
+            .unwrap_or_default();
+    if rules.is_empty() {
+        return;
+    }
+    let count = Redactor::new(&rules, logger).apply(m);
+    if count > 0 {
+        info!(logger, "redacted: {} in {}", count, m);
+    }
+}
+
+// Returns the size of the message to be metered.
+fn bytes_of(m: &NewMessage) -> i64 {
+    [&m.code, &m.title, &m.content]
+        .iter()
+        .map(|v| v.as_ref().map_or(0, |s| s.len() as i64))
+        .sum()
+}
+
+// Returns a bucket key for the token. A browser token is reissued on every
+// refresh, so its bucket is keyed by the session (jti) instead.
+fn token_bucket_key(
+    token: &str,
+    token_type: &TokenType,
+    config: &Config,
+) -> String {
+    if *token_type == TokenType::BrowserCookieToken {
+        let jti = BrowserCookieTokenClaims::decode(
+            token,
+            &config.authentication_token_issuer,
+            &config.authentication_token_secret,
+        )
+        .ok()
+        .and_then(|c| c.jti);
+        if let Some(jti) = jti {
+            return rate_limiter::session_key(&jti);
+        }
+    }
+    rate_limiter::token_key(token)
+}
+
+// Takes a token from buckets for the access token and the namespace of the
+// stream at once, and returns the most restrictive limit.
+//
+// The request is accepted if the limit can't be checked (e.g. Redis is down).
+fn take_rate_limit(
+    token: &str,
+    token_type: &TokenType,
+    namespace: &Namespace,
+    config: &Config,
+    mq_conn: &mut MqConn,
+    logger: &SyncLogger,
+) -> Option<RateLimit> {
+    let clock = SystemClock;
+    let mut limiter = RateLimiter::new(&mut **mq_conn, &clock, logger);
+
+    let token_key = token_bucket_key(token, token_type, config);
+    let namespace_key = rate_limiter::namespace_key(namespace.id);
+    let limits = limiter
+        .take_all(&[
+            (&token_key, config.ingestion_rate_limit_per_token),
+            (
+                &namespace_key,
+                namespace
+                    .ingestion_rate_limit
+                    .unwrap_or(config.ingestion_rate_limit_per_namespace),
+            ),
+        ])
+        .map_err(|e| error!(logger, "err: {}", e))
+        .ok()?;
+
+    // an exceeded one first, then the one with fewer remaining
+    limits
+        .into_iter()
+        .min_by_key(|l| (!l.is_exceeded(), l.remaining))
+}
+
+// Sends the critical message to webhooks (if any).
+fn notify_critical(
+    id: i64,
+    m: &NewMessage,
+    conn: &DbConn,
+    mq_conn: &mut MqConn,
+    logger: &SyncLogger,
+) {
+    let stream = match Stream::find_by_id(m.stream_id, conn, logger) {
+        Some(s) => s,
+        None => return,
+    };
+    let data = serde_json::json!({
+        "message": {
+            "id": id,
+            "code": m.code,
+            "level": m.level,
+            "title": m.title,
+        },
+        "stream": {
+            "uuid": stream.uuid.to_string(),
+            "name": stream.name,
+        },
+    });
+    let jobs = webhook_sender::dispatch(
+        stream.namespace_id,
+        &WebhookEvent::MessageCritical,
+        data,
+        conn,
+        logger,
+    );
+
+    let mut queue = Queue::new("default", &mut **mq_conn);
+    for job in jobs {
+        if let Err(err) = queue.enqueue::<Job<String>>(job) {
+            error!(logger, "error: {}", err);
+        }
+    }
+}
+
 #[get(
     "/message/<namespace_key>/lrange/<stream_slug>/<start>/<stop>",
     rank = 1
@@ -148,6 +400,16 @@ pub fn lrange(
         stop
     );
 
+    if let Some(key) = renamed_key(&namespace_key, &conn, &logger) {
+        let location = format!(
+            "/v1/message/{}/lrange/{}/{}/{}",
+            key, stream_slug, start, stop
+        );
+        return res
+            .header("Location", location)
+            .status(Status::PermanentRedirect);
+    }
+
     // TODO
     let offset = start as i64;
     let mut limit = (stop - start + 2) as i64;
@@ -155,18 +417,23 @@ pub fn lrange(
         limit = 1;
     }
 
-    // FIXME
-    // * visible to user (and use namespace_key)
-
-    let data = match Message::fetch_by_stream_slug(
-        stream_slug,
-        offset,
-        limit,
+    let (_, stream) = match find_stream(
+        &namespace_key,
+        &stream_slug,
+        user,
+        Permission::MessagesRead,
         &conn,
         &logger,
+    ) {
+        Err(r) => return r,
+        Ok(v) => v,
+    };
+
+    let data = match Message::fetch_by_stream(
+        &stream, offset, limit, &conn, &logger,
     ) {
         None => {
-            error!(logger, "err: not found user.id {}", user.uuid);
+            error!(logger, "err: no message for stream: {}", stream.uuid);
             vec![]
         },
         Some(a) => a.iter().map(|m| json!({ "message": m })).collect(),
@@ -1,9 +1,25 @@
 pub mod access_token;
+pub mod alert_rule;
 pub mod activation;
+pub mod audit_event;
 pub mod authentication;
+pub mod digest;
+pub mod email_change;
 pub mod error;
 pub mod health;
+pub mod invitation;
+pub mod membership;
 pub mod message;
 pub mod namespace;
+pub mod password_change;
 pub mod password_reset;
+pub mod redaction_rule;
 pub mod registration;
+pub mod stream;
+pub mod stream_grant;
+pub mod two_factor;
+pub mod unlock;
+pub mod usage;
+pub mod user_email;
+pub mod user_session;
+pub mod webhook;
@@ -1,14 +1,24 @@
 use diesel::result::Error;
+use fourche::queue::Queue;
 use rocket::http::Status;
 use rocket_contrib::json::{Json, JsonValue};
 use rocket_slog::SyncLogger;
 
 use crate::db::DbConn;
+use crate::job::{Job, JobKind};
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
 use crate::model::namespace::{Namespace, NewNamespace};
+use crate::model::namespace_key_redirect::NamespaceKeyRedirect;
 use crate::model::user::User;
 use crate::model::membership::{Membership, MembershipRole, NewMembership};
+use crate::model::permission::Permission;
+use crate::mq::MqConn;
 use crate::response::Response;
-use crate::request::namespace::Namespace as RequestData;
+use crate::request::client::ClientInfo;
+use crate::request::namespace::{
+    Deletion as DeletionData, Namespace as RequestData,
+};
+use crate::route::error::forbidden_by;
 use crate::validation::namespace::Validator;
 
 pub mod preflight {
@@ -19,6 +29,35 @@ pub mod preflight {
     use crate::config::Config;
     use crate::response::no_content_for;
 
+    #[options("/namespace/archive/<uuid>", rank = 2)]
+    pub fn archive<'a>(
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "archive uuid: {}", uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/namespace/archived/hgetall", rank = 2)]
+    pub fn archived<'a>(
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "archived");
+        no_content_for("GET", &config)
+    }
+
+    #[options("/namespace/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "del uuid: {}", uuid);
+        no_content_for("DELETE", &config)
+    }
+
     #[options("/namespace/hget/<uuid>", rank = 2)]
     pub fn hget<'a>(
         uuid: String,
@@ -46,6 +85,155 @@ pub mod preflight {
         info!(logger, "hset");
         no_content_for("POST", &config)
     }
+
+    #[options("/namespace/hset/<uuid>", rank = 2)]
+    pub fn hupdate<'a>(
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "hupdate uuid: {}", uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/namespace/unarchive/<uuid>", rank = 2)]
+    pub fn unarchive<'a>(
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "unarchive uuid: {}", uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+// Archives the namespace and its streams. Only owners can archive it.
+#[post("/namespace/archive/<uuid>", rank = 1)]
+pub fn archive(
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response {
+    info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
+
+    let res: Response = Default::default();
+
+    let namespace = match Namespace::find_by_uuid(&uuid, user, &conn, &logger)
+    {
+        None => return res.status(Status::NotFound),
+        Some(n) => n,
+    };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::NamespaceManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let result: Result<Namespace, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Namespace, diesel::result::Error, _>(|| {
+            namespace.archive(&conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(n) => res.format(json!({ "namespace": n })),
+    }
+}
+
+#[get("/namespace/archived/hgetall", rank = 1)]
+pub fn archived(user: &User, conn: DbConn, logger: SyncLogger) -> Response {
+    let res: Response = Default::default();
+
+    info!(logger, "user: {}", user.uuid);
+
+    let data = match Namespace::find_all_archived(user, &conn, &logger) {
+        None => {
+            error!(logger, "err: no namespace for user: {}", user.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|n| json!({ "namespace": n })).collect(),
+    };
+    res.format(json!(data))
+}
+
+// Deletes the namespace permanently. Only the primary owner can delete it,
+// by typing its name as confirmation.
+//
+// The namespace is archived at once, and records which belong to it are
+// removed by a job in background.
+#[delete("/namespace/del/<uuid>", data = "<data>", format = "json", rank = 1)]
+pub fn del(
+    uuid: String,
+    user: &User,
+    data: Json<DeletionData>,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: SyncLogger,
+) -> Response {
+    info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
+
+    let res: Response = Default::default();
+
+    let namespace = match Namespace::find_by_uuid(&uuid, user, &conn, &logger)
+        .or_else(|| {
+            Namespace::find_archived_by_uuid(&uuid, user, &conn, &logger)
+        }) {
+        None => return res.status(Status::NotFound),
+        Some(n) => n,
+    };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::NamespaceDelete, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    if data.0.name.as_deref() != Some(namespace.name.as_str()) {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "name",
+                "messages": ["Must match the name of the namespace"],
+            }],
+        }));
+    }
+
+    if namespace.archived_at.is_none() {
+        let result: Result<Namespace, Error> = conn
+            .build_transaction()
+            .serializable()
+            .deferrable()
+            .read_write()
+            .run::<Namespace, diesel::result::Error, _>(|| {
+                namespace.archive(&conn, &logger).map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })
+            });
+        if result.is_err() {
+            return res.status(Status::InternalServerError);
+        }
+    }
+
+    let job = Job::<String> {
+        kind: JobKind::DeleteNamespace,
+        args: vec![namespace.id.to_string()],
+    };
+    let mut queue = Queue::new("default", &mut *mq_conn);
+    if let Err(err) = queue.enqueue::<Job<String>>(job) {
+        error!(logger, "error: {}", err);
+        return res.status(Status::InternalServerError);
+    }
+
+    res.status(Status::Accepted).format(json!({"namespace": {
+        "uuid": namespace.uuid.to_string(),
+    }}))
 }
 
 #[get("/namespace/hget/<uuid>", rank = 1)]
@@ -65,7 +253,18 @@ pub fn hget(
                 error!(logger, "err: no namespace for uuid: {}", uuid);
                 Err(Error::NotFound)
             },
-            Some(n) => Ok(json!({ "namespace": n })),
+            Some(n) => {
+                if let Err(forbidden) = authorize(
+                    &n,
+                    user,
+                    Permission::NamespaceRead,
+                    &conn,
+                    &logger,
+                ) {
+                    return forbidden;
+                }
+                Ok(json!({ "namespace": n }))
+            },
         };
     if data.is_err() {
         return res.status(Status::NotFound);
@@ -92,6 +291,7 @@ pub fn hgetall(user: &User, conn: DbConn, logger: SyncLogger) -> Response {
 #[post("/namespace/hset", data = "<data>", format = "json", rank = 1)]
 pub fn hset(
     user: &User,
+    client: ClientInfo,
     data: Json<RequestData>,
     conn: DbConn,
     logger: SyncLogger,
@@ -108,13 +308,17 @@ pub fn hset(
             }))
         },
         Ok(_) => {
+            let n = NewNamespace::from(data.0.clone());
+            if !n.key.is_empty() && key_taken(&n.key, 0, &conn, &logger) {
+                return key_taken_response(res);
+            }
+
             let result: Result<String, Error> = conn
                 .build_transaction()
                 .serializable()
                 .deferrable()
                 .read_write()
                 .run::<String, diesel::result::Error, _>(|| {
-                    let n = NewNamespace::from(data.0.clone());
                     if let Some(namespace) =
                         Namespace::insert(&n, &conn, &logger)
                     {
@@ -125,6 +329,20 @@ pub fn hset(
                             role: MembershipRole::PrimaryOwner,
                         };
                         let _ = Membership::insert(&m, &conn, &logger).unwrap();
+
+                        let mut e = NewAuditEvent::new(
+                            AuditAction::NamespaceCreate,
+                            Some(user),
+                            &client,
+                        );
+                        e.target = Some(namespace.uuid.to_string());
+                        e.namespace_id = Some(namespace.id);
+                        e.diff = serde_json::json!({
+                            "name": [null, namespace.name],
+                            "key": [null, namespace.key],
+                        });
+                        AuditEvent::insert(&e, &conn, &logger)
+                            .ok_or(Error::RollbackTransaction)?;
                         return Ok(namespace.uuid.to_string());
                     }
                     Err(Error::RollbackTransaction)
@@ -138,3 +356,219 @@ pub fn hset(
         },
     }
 }
+
+// Changes the name, the key and the description. Only owners can change them.
+//
+// The old key keeps working as a redirect. The overrides of the ingestion
+// rate limit and the daily quota are changed only if they are given, and 0
+// resets them to the defaults.
+#[patch("/namespace/hset/<uuid>", data = "<data>", format = "json", rank = 1)]
+pub fn hupdate(
+    uuid: String,
+    user: &User,
+    client: ClientInfo,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response {
+    info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
+
+    let res: Response = Default::default();
+
+    let namespace = match Namespace::find_by_uuid(&uuid, user, &conn, &logger)
+    {
+        None => return res.status(Status::NotFound),
+        Some(n) => n,
+    };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::NamespaceManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let mut n = NewNamespace::from(data.0.clone());
+    if n.key.is_empty() {
+        n.key = namespace.key.clone();
+    } else if key_taken(&n.key, namespace.id, &conn, &logger) {
+        return key_taken_response(res);
+    }
+
+    let result: Result<Namespace, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Namespace, diesel::result::Error, _>(|| {
+            let mut updated = namespace
+                .update(&n.name, &n.key, &n.description, &conn, &logger)
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+
+            if data.ingestion_rate_limit.is_some() ||
+                data.ingestion_daily_quota.is_some()
+            {
+                updated = updated
+                    .update_ingestion_limits(
+                        ingestion_override(
+                            data.ingestion_rate_limit,
+                            updated.ingestion_rate_limit,
+                        ),
+                        ingestion_override(
+                            data.ingestion_daily_quota,
+                            updated.ingestion_daily_quota,
+                        ),
+                        &conn,
+                        &logger,
+                    )
+                    .map_err(|e| {
+                        error!(logger, "err: {}", e);
+                        Error::RollbackTransaction
+                    })?;
+            }
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::NamespaceUpdate,
+                Some(user),
+                &client,
+            );
+            e.target = Some(namespace.uuid.to_string());
+            e.namespace_id = Some(namespace.id);
+            e.diff = serde_json::json!({
+                "name": [namespace.name, updated.name],
+                "key": [namespace.key, updated.key],
+                "description": [namespace.description, updated.description],
+                "ingestion_rate_limit": [
+                    namespace.ingestion_rate_limit,
+                    updated.ingestion_rate_limit,
+                ],
+                "ingestion_daily_quota": [
+                    namespace.ingestion_daily_quota,
+                    updated.ingestion_daily_quota,
+                ],
+            });
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(updated)
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(namespace) => res.format(json!({ "namespace": namespace })),
+    }
+}
+
+// Restores the namespace and the streams archived with it. Only owners can
+// restore it.
+#[post("/namespace/unarchive/<uuid>", rank = 1)]
+pub fn unarchive(
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response {
+    info!(logger, "user: {}, uuid: {}", user.uuid, uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_archived_by_uuid(&uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::NamespaceManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let result: Result<Namespace, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Namespace, diesel::result::Error, _>(|| {
+            namespace.unarchive(&conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(n) => res.format(json!({ "namespace": n })),
+    }
+}
+
+// Checks whether the key is used by another namespace, including old keys.
+fn key_taken(
+    key: &str,
+    namespace_id: i64,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> bool {
+    Namespace::find_by_key(key, conn, logger)
+        .map_or(false, |n| n.id != namespace_id) ||
+        NamespaceKeyRedirect::find_by_key(key, conn, logger)
+            .map_or(false, |r| r.namespace_id != namespace_id)
+}
+
+// Returns a response for a key which is already taken.
+fn key_taken_response(res: Response) -> Response {
+    res.status(Status::UnprocessableEntity).format(json!({
+        "errors": [{
+            "field": "key",
+            "messages": ["Must be unique"],
+        }],
+    }))
+}
+
+// Returns the new override. The current one is kept if it's not given, and 0
+// removes it.
+fn ingestion_override<T>(value: Option<T>, current: Option<T>) -> Option<T>
+where T: Default + PartialEq {
+    match value {
+        None => current,
+        Some(v) if v == T::default() => None,
+        v => v,
+    }
+}
+
+// Loads the membership of the user in the namespace, and checks whether its
+// role is granted the permission by the policy (see model/permission.rs).
+//
+// Returns a forbidden response as an error if it's not granted.
+pub fn authorize<'a>(
+    namespace: &Namespace,
+    user: &User,
+    permission: Permission,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<Membership, Response<'a>> {
+    match Membership::find_by_namespace_id_and_user(
+        namespace.id,
+        user,
+        conn,
+        logger,
+    ) {
+        Some(m) if permission.is_granted_to(&m.role) => Ok(m),
+        _ => {
+            warn!(
+                logger,
+                "forbidden user: {}, namespace: {}, permission: {}",
+                user.uuid,
+                namespace.uuid,
+                permission
+            );
+            Err(forbidden_by(permission))
+        },
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::Utc;
+use diesel::result::Error;
+use fourche::queue::Queue;
+use redis::{Commands, RedisError};
+use rocket::State;
+use rocket::http::{Cookies, Status};
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::job::{Job, JobKind};
+use crate::model::Authenticatable;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::user::User;
+use crate::mq::MqConn;
+use crate::request::client::ClientInfo;
+use crate::request::password_change::PasswordChange;
+use crate::request::password_reset::PasswordReset;
+use crate::response::Response;
+use crate::service::session_manager::{Session, SessionManager, issue_token};
+use crate::ss::{SsConn, user_sessions_revoked_at_key};
+use crate::util::SystemClock;
+use crate::validation::ValidationError;
+use crate::validation::password::PasswordPolicy;
+use crate::validation::password_reset::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/password/change", rank = 2)]
+    pub fn change<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PATCH", &config)
+    }
+}
+
+// Changes the password of the signed in user. If `revoke_sessions` is given,
+// the other browser sessions are revoked and a new token is returned for the
+// current one.
+#[allow(clippy::too_many_arguments)]
+#[patch("/password/change", data = "<payload>", format = "json", rank = 1)]
+pub fn change<'a>(
+    user: &User,
+    mut cookies: Cookies<'a>,
+    payload: Json<PasswordChange>,
+    client: ClientInfo,
+    config: State<Config>,
+    policy: State<PasswordPolicy>,
+    db_conn: DbConn,
+    mut mq_conn: MqConn,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    if !user.verify_password(&payload.0.password) {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "password",
+                "messages": ["Must match the current password"],
+            }],
+        }));
+    }
+
+    let new_password = payload.0.new_password.to_string();
+    let data = Json(PasswordReset {
+        email: user.email.to_string(),
+        username: user.username.to_string(),
+        password: new_password.to_string(),
+    });
+    if let Err(errors) =
+        Validator::new(&db_conn, &data, &policy, &logger).validate()
+    {
+        // password -> new_password
+        let errors: Vec<ValidationError> = errors
+            .into_iter()
+            .filter(|v| v.field == "password")
+            .map(|mut v| {
+                v.field = "new_password".to_string();
+                v
+            })
+            .collect();
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            let mut u = user.clone();
+            u.renew_password(&new_password, &config, &db_conn, &logger)
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::PasswordChange,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(())
+        });
+    if result.is_err() {
+        return res.status(Status::InternalServerError);
+    }
+
+    let job = Job::<String> {
+        kind: JobKind::SendPasswordChangeNoticeEmail,
+        args: vec![user.id.to_string()],
+    };
+    let mut queue = Queue::new("default", &mut *mq_conn);
+    if let Err(err) = queue.enqueue::<Job<String>>(job) {
+        error!(logger, "error: {}", err);
+    }
+
+    if payload.0.revoke_sessions != Some(true) {
+        return res.status(Status::Ok);
+    }
+
+    let revoked_at = Utc::now().timestamp();
+    let key = user_sessions_revoked_at_key(user.id);
+    let result: Result<(), RedisError> =
+        ss_conn.set(&key, revoked_at).map_err(|e| {
+            error!(logger, "error: {}", e);
+            e
+        });
+    if result.is_err() {
+        return res.status(Status::InternalServerError);
+    }
+
+    // destroys the other sessions, and issues a new token for the current
+    // one (see login). It must be issued after the revocation.
+    let current = cookies
+        .get_private("refresh")
+        .and_then(|c| Session::parse_cookie_value(c.value()));
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    let session = match current {
+        Some((id, refresh_token)) => {
+            manager
+                .finish_all(user.id, Some(&id))
+                .and_then(|_| manager.rotate(&id, &refresh_token))
+        },
+        None => {
+            manager
+                .finish_all(user.id, None)
+                .and_then(|_| manager.start(user.id, &client))
+        },
+    };
+    let token = session.ok().and_then(|s| {
+        issue_token(user, &s, revoked_at + 1, &config, &mut cookies)
+    });
+    match token {
+        Some(token) => res.cookies(cookies).format(json!({ "token": token })),
+        None => res.status(Status::InternalServerError),
+    }
+}
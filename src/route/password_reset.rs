@@ -10,20 +10,24 @@ use rocket_slog::SyncLogger;
 use crate::config::Config;
 use crate::db::DbConn;
 use crate::job::{Job, JobKind};
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
 use crate::model::token::{VerificationClaims, Claims, TokenData};
 use crate::model::user::User;
 use crate::mq::MqConn;
+use crate::request::client::ClientInfo;
 use crate::request::password_reset::{
     PasswordReset, PasswordResetRequest, PasswordResetUpdate,
 };
 use crate::request::token::verification::VerificationToken;
 use crate::response::Response;
+use crate::service::attempt_limiter::{AttemptLimiter, Scope};
 use crate::service::password_updater::PasswordUpdater;
 use crate::validation::ValidationError;
+use crate::validation::password::PasswordPolicy;
 use crate::validation::password_reset::Validator as PasswordResetValidator;
 use crate::validation::password_reset_request::Validator as PasswordResetRequestValidator;
-use crate::ss::SsConn;
-use crate::util::split_token;
+use crate::ss::{SsConn, user_sessions_key};
+use crate::util::{SystemClock, split_token};
 
 pub mod preflight {
     use rocket::State;
@@ -141,9 +145,14 @@ pub mod preignition {
     }
 }
 
+// Requests are counted per account and per IP address against email bombing
+// (see AttemptLimiter). The response is same whether the account exists or
+// not.
+#[allow(clippy::too_many_arguments)]
 #[put("/password/reset", data = "<payload>", format = "json", rank = 1)]
 pub fn request<'a>(
     logger: SyncLogger,
+    client: ClientInfo,
     mut cookies: Cookies,
     config: State<Config>,
     mut ss_conn: SsConn,
@@ -182,6 +191,22 @@ pub fn request<'a>(
     let email = payload.0.email;
     info!(logger, "email: {}", &email);
 
+    let clock = SystemClock;
+    let mut limiter = AttemptLimiter::new(&mut ss_conn, &clock, &logger);
+    // it's accepted if the attempts can't be checked (e.g. Redis is down)
+    let wait = limiter
+        .check(Scope::PasswordReset, &email, &client)
+        .unwrap_or(0);
+    if wait > 0 {
+        return res
+            .header("Retry-After", wait.to_string())
+            .status(Status::TooManyRequests)
+            .format(json!({
+                "message": "Too many requests. Retry later."
+            }));
+    }
+    let _ = limiter.record(Scope::PasswordReset, &email, &client);
+
     if let Some(user) = User::find_by_email_only_in_available_to_reset(
         &email, &db_conn, &logger,
     ) {
@@ -240,6 +265,17 @@ pub fn request<'a>(
                     });
 
                 if result.is_ok() {
+                    let sessions_key = user_sessions_key(id);
+                    let _: Result<(), RedisError> = ss_conn
+                        .sadd(&sessions_key, &key)
+                        .and_then(|_: i64| {
+                            ss_conn.expire(&sessions_key, expires_at as usize)
+                        })
+                        .map_err(|e| {
+                            error!(logger, "error: {}", e);
+                            e
+                        });
+
                     let job = Job::<String> {
                         kind: JobKind::SendPasswordResetEmail,
                         args: vec![id.to_string(), session_id, token],
@@ -257,7 +293,7 @@ pub fn request<'a>(
             "message": "Something wrong happen, sorry :'("
         }));
     }
-    res.status(Status::NotFound)
+    res
 }
 
 // TODO:
@@ -288,8 +324,10 @@ pub fn update<'a>(
     mut cookies: Cookies,
     token: VerificationToken,
     config: State<Config>,
+    policy: State<PasswordPolicy>,
     session_id: String,
     mut ss_conn: SsConn,
+    client: ClientInfo,
     payload: Json<PasswordResetUpdate>,
     db_conn: DbConn,
 ) -> Response<'a> {
@@ -330,11 +368,14 @@ pub fn update<'a>(
                     // FIXME: can we omit this clone?
                     let user = u.target.clone().unwrap();
                     let data = Json(PasswordReset {
+                        email: user.email,
                         username: user.username,
                         password: new_password.to_string(),
                     });
-                    match PasswordResetValidator::new(&db_conn, &data, &logger)
-                        .validate()
+                    match PasswordResetValidator::new(
+                        &db_conn, &data, &policy, &logger,
+                    )
+                    .validate()
                     {
                         Err(validation_errors) => {
                             // password -> new_password
@@ -349,6 +390,16 @@ pub fn update<'a>(
                             Err(Error::RollbackTransaction)
                         },
                         Ok(_) if u.update(&new_password).is_ok() => {
+                            let target = u.target.as_ref();
+                            let mut e = NewAuditEvent::new(
+                                AuditAction::PasswordReset,
+                                target,
+                                &client,
+                            );
+                            e.target = target.map(|v| v.uuid.to_string());
+                            AuditEvent::insert(&e, &db_conn, &logger)
+                                .ok_or(Error::RollbackTransaction)?;
+
                             // clear session
                             let key = format!("pr-{}", session_id);
                             ss_conn
@@ -0,0 +1,312 @@
+use chrono::Utc;
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+use uuid::Uuid;
+
+use crate::db::DbConn;
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::redaction_rule::{NewRedactionRule, RedactionRule};
+use crate::model::user::User;
+use crate::response::Response;
+use crate::request::redaction_rule::{
+    RedactionRule as RequestData, RedactionTest as TestData,
+};
+use crate::route::namespace::authorize;
+use crate::service::redactor::Redactor;
+use crate::validation::redaction_rule::Validator;
+
+// a limit for sample text of dry-run
+const TEXT_MAX_LENGTH: usize = 8192;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/redaction_rule/<namespace_uuid>/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/redaction_rule/<namespace_uuid>/eval", rank = 2)]
+    pub fn eval<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/redaction_rule/<namespace_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/redaction_rule/<namespace_uuid>/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+#[delete("/redaction_rule/<namespace_uuid>/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) = authorize(
+        &namespace,
+        user,
+        Permission::RedactionRulesManage,
+        &conn,
+        &logger,
+    ) {
+        return forbidden;
+    }
+
+    let result: Result<(), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            match RedactionRule::find_by_uuid_in(
+                &namespace, &uuid, &conn, &logger,
+            ) {
+                None => {
+                    error!(logger, "err: not found {}", uuid);
+                    Err(Error::RollbackTransaction)
+                },
+                Some(r) => {
+                    r.delete(&conn, &logger).map_err(|e| {
+                        error!(logger, "err: {}", e);
+                        Error::RollbackTransaction
+                    })
+                },
+            }
+        });
+
+    if result.is_err() {
+        return res.status(Status::NotFound);
+    }
+
+    res.format(json!({
+        "redaction_rule": 1,
+    }))
+}
+
+// Applies rules to the sample text without saving anything (dry-run).
+//
+// The rules of the namespace are used unless a rule is given.
+#[post(
+    "/redaction_rule/<namespace_uuid>/eval",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn eval<'a>(
+    namespace_uuid: String,
+    user: &User,
+    data: Json<TestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) = authorize(
+        &namespace,
+        user,
+        Permission::RedactionRulesRead,
+        &conn,
+        &logger,
+    ) {
+        return forbidden;
+    }
+
+    let text = data.0.text.clone().unwrap_or_default();
+    if text.len() > TEXT_MAX_LENGTH {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "text",
+                "messages": [
+                    format!("Must not be longer than {}", TEXT_MAX_LENGTH),
+                ],
+            }],
+        }));
+    }
+
+    let rules = match data.0.rule.clone() {
+        Some(rule) => {
+            let rule = Json(rule);
+            let v = Validator::new(&rule, &logger);
+            if let Err(errors) = v.validate() {
+                return res.status(Status::UnprocessableEntity).format(json!({
+                    "errors": errors,
+                }));
+            }
+            let r = NewRedactionRule::from(rule.0);
+            let now = Utc::now().naive_utc();
+            // not saved
+            vec![RedactionRule {
+                id: 0,
+                uuid: Uuid::nil(),
+                namespace_id: namespace.id,
+                name: r.name,
+                detector: r.detector,
+                pattern: r.pattern,
+                created_at: now,
+                updated_at: now,
+            }]
+        },
+        None => {
+            RedactionRule::find_all_by_namespace_id(
+                namespace.id,
+                &conn,
+                &logger,
+            )
+            .unwrap_or_default()
+        },
+    };
+
+    let redactor = Redactor::new(&rules, &logger);
+    let (text, redactions) = redactor.redact(&text);
+    res.format(json!({
+        "text": text,
+        "redactions": redactions,
+    }))
+}
+
+#[get("/redaction_rule/<namespace_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) = authorize(
+        &namespace,
+        user,
+        Permission::RedactionRulesRead,
+        &conn,
+        &logger,
+    ) {
+        return forbidden;
+    }
+
+    let data = match RedactionRule::find_all_by_namespace_id(
+        namespace.id,
+        &conn,
+        &logger,
+    ) {
+        None => {
+            error!(logger, "err: no rule for namespace: {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|r| json!({ "redaction_rule": r })).collect(),
+    };
+    res.format(json!(data))
+}
+
+#[post(
+    "/redaction_rule/<namespace_uuid>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) = authorize(
+        &namespace,
+        user,
+        Permission::RedactionRulesManage,
+        &conn,
+        &logger,
+    ) {
+        return forbidden;
+    }
+
+    let v = Validator::new(&data, &logger);
+    match v.validate() {
+        Err(errors) => {
+            res.status(Status::UnprocessableEntity).format(json!({
+                "errors": errors,
+            }))
+        },
+        Ok(_) => {
+            let mut r = NewRedactionRule::from(data.0.clone());
+            r.namespace_id = namespace.id;
+            if let Some(rule) = RedactionRule::insert(&r, &conn, &logger) {
+                info!(logger, "redaction_rule: {}", rule.id);
+                return res.format(json!({ "redaction_rule": rule }));
+            }
+            res.status(Status::InternalServerError)
+        },
+    }
+}
@@ -3,25 +3,32 @@ use diesel::result::Error;
 use fourche::queue::Queue;
 use redis::{Commands, RedisError};
 use rocket::State;
-use rocket::http::{Cookies, Status};
+use rocket::http::{Cookie, Cookies, Status};
 use rocket_contrib::json::Json;
 use rocket_slog::SyncLogger;
 
 use crate::config::Config;
 use crate::db::DbConn;
 use crate::job::{Job, JobKind};
+use crate::model::Authenticatable;
+use crate::model::access_token::AccessToken;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
 use crate::model::token::{VerificationClaims, Claims, TokenData};
 use crate::model::namespace::{Namespace, NewNamespace};
 use crate::model::membership::{Membership, MembershipRole, NewMembership};
 use crate::model::stream::{Stream, NewStream};
-use crate::model::user::{NewUser, User};
+use crate::model::user::{NewUser, User, UserState};
 use crate::model::user_email::{NewUserEmail, UserEmail};
 use crate::mq::MqConn;
 use crate::response::Response;
+use crate::route::invitation::accept_by_token;
+use crate::request::client::ClientInfo;
+use crate::request::user::deregistration::UserDeregistration;
 use crate::request::user::registration::UserRegistration;
+use crate::validation::password::PasswordPolicy;
 use crate::validation::user::Validator;
-use crate::ss::SsConn;
-use crate::util::split_token;
+use crate::ss::{SsConn, user_sessions_key};
+use crate::util::{remove_session_cookies, split_token};
 
 pub mod preflight {
     use rocket::State;
@@ -92,6 +99,7 @@ pub mod preignition {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[post("/register", data = "<data>", format = "json", rank = 1)]
 pub fn register<'a>(
     data: Json<UserRegistration>,
@@ -101,6 +109,7 @@ pub fn register<'a>(
     mut ss_conn: SsConn,
     logger: SyncLogger,
     config: State<Config>,
+    policy: State<PasswordPolicy>,
 ) -> Response<'a> {
     // FIXME: create `account_registrar` service
     let res: Response = Default::default();
@@ -123,7 +132,7 @@ pub fn register<'a>(
         }));
     }
 
-    let v = Validator::new(&db_conn, &data, &logger);
+    let v = Validator::new(&db_conn, &data, &policy, &logger);
     match v.validate() {
         Err(errors) => {
             res.status(Status::UnprocessableEntity).format(json!({
@@ -145,7 +154,7 @@ pub fn register<'a>(
                 .read_write()
                 .run::<(i64, String), diesel::result::Error, _>(|| {
                     let mut u = NewUser::from(&data.0);
-                    u.set_password(&data.password);
+                    u.set_password(&data.password, &config);
                     let user = User::insert(&u, &db_conn, &logger).unwrap();
                     let ue = NewUserEmail::from(&user);
                     let user_email =
@@ -155,6 +164,8 @@ pub fn register<'a>(
                         // TODO: async
                         let ns = NewNamespace {
                             name: format!("{}'s default namespace", u.username),
+                            // generated from the name
+                            key: "".to_string(),
                             description: None,
                             streams_count: 0,
                         };
@@ -164,6 +175,7 @@ pub fn register<'a>(
                         let s = NewStream {
                             namespace_id: namespace.id,
                             name: "main".to_string(),
+                            slug: "main".to_string(),
                             description: None,
                         };
                         let _ = Stream::insert(&s, &db_conn, &logger).unwrap();
@@ -177,6 +189,15 @@ pub fn register<'a>(
                             Membership::insert(&m, &db_conn, &logger).unwrap();
                     }
 
+                    // the user has signed up via an invitation (optional)
+                    if let Some(ref t) = data.invitation_token {
+                        if let Err(e) = accept_by_token(
+                            t, &user, &config, &db_conn, &logger,
+                        ) {
+                            warn!(logger, "invitation is ignored: {}", e);
+                        }
+                    }
+
                     let data = TokenData {
                         value: UserEmail::generate_token(),
                         granted_at,
@@ -244,10 +265,21 @@ pub fn register<'a>(
     }
 }
 
-#[post("/deregister", format = "json", rank = 1)]
+// Deregisters the user with the current password.
+//
+// The namespaces the user owns as the primary owner are transferred to the
+// earliest owner, or archived and deleted in background if there is no owner.
+// The account is disabled at once, and the records which belong to it are
+// removed by a job (a confirmation email is sent at the end).
+#[post("/deregister", data = "<data>", format = "json", rank = 1)]
+#[allow(clippy::too_many_arguments)]
 pub fn deregister<'a>(
+    data: Json<UserDeregistration>,
     mut cookies: Cookies,
     user: &User,
+    client: ClientInfo,
+    db_conn: DbConn,
+    mut mq_conn: MqConn,
     mut ss_conn: SsConn,
     logger: SyncLogger,
 ) -> Response<'a> {
@@ -273,6 +305,129 @@ pub fn deregister<'a>(
         }));
     }
 
-    // TODO
-    res.status(Status::UnprocessableEntity)
+    if !user.verify_password(&data.password) {
+        warn!(logger, "deregistration failed: user {}", user.uuid);
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "password",
+                "messages": ["Must match the current password"],
+            }],
+        }));
+    }
+
+    let result: Result<Vec<i64>, Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Vec<i64>, diesel::result::Error, _>(|| {
+            // namespaces to be deleted
+            let mut namespace_ids = vec![];
+
+            let memberships = Membership::find_all_primary_by_user(
+                user, &db_conn, &logger,
+            )
+            .ok_or(Error::RollbackTransaction)?;
+            for m in memberships {
+                let namespace =
+                    Namespace::find_by_id(m.namespace_id, &db_conn, &logger)
+                        .ok_or(Error::RollbackTransaction)?;
+                let owner = Membership::find_all_by_namespace(
+                    &namespace, &db_conn, &logger,
+                )
+                .unwrap_or_default()
+                .into_iter()
+                .find(|(o, u)| {
+                    o.role == MembershipRole::Owner &&
+                        u.state == UserState::Active
+                });
+
+                if let Some((ref o, _)) = owner {
+                    m.transfer_primary_ownership(o, &db_conn, &logger)
+                        .map_err(|e| {
+                            error!(logger, "err: {}", e);
+                            Error::RollbackTransaction
+                        })?;
+                } else {
+                    if namespace.archived_at.is_none() {
+                        namespace.archive(&db_conn, &logger).map_err(|e| {
+                            error!(logger, "err: {}", e);
+                            Error::RollbackTransaction
+                        })?;
+                    }
+                    namespace_ids.push(namespace.id);
+                }
+            }
+
+            AccessToken::revoke_all_by_user(user, &db_conn, &logger).map_err(
+                |e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                },
+            )?;
+            let u = user.deregister(&db_conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::UserDeregister,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            e.diff = serde_json::json!({
+                "state": [user.state.to_string(), u.state.to_string()],
+            });
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            Ok(namespace_ids)
+        });
+
+    let namespace_ids = match result {
+        Ok(v) => v,
+        Err(_) => {
+            return res.status(Status::InternalServerError).format(json!({
+                "message": "Something wrong happen, sorry :'("
+            }));
+        },
+    };
+
+    let mut jobs = namespace_ids
+        .iter()
+        .map(|id| Job::<String> {
+            kind: JobKind::DeleteNamespace,
+            args: vec![id.to_string()],
+        })
+        .collect::<Vec<Job<String>>>();
+    jobs.push(Job::<String> {
+        kind: JobKind::DeleteUser,
+        args: vec![user.id.to_string()],
+    });
+    let mut queue = Queue::new("default", &mut *mq_conn);
+    for job in jobs {
+        if let Err(err) = queue.enqueue::<Job<String>>(job) {
+            error!(logger, "error: {}", err);
+        }
+    }
+
+    // clear session values of the user
+    let sessions_key = user_sessions_key(user.id);
+    let mut keys: Vec<String> =
+        ss_conn.smembers(&sessions_key).unwrap_or_else(|e| {
+            error!(logger, "error: {}", e);
+            vec![]
+        });
+    keys.push(sessions_key);
+    keys.push(key);
+    let _: Result<i64, RedisError> = ss_conn.del(keys).map_err(|e| {
+        error!(logger, "error: {}", e);
+        e
+    });
+
+    cookies.remove_private(Cookie::named("csrf_token"));
+    remove_session_cookies(&mut cookies);
+
+    res.status(Status::Accepted)
 }
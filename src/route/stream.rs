@@ -0,0 +1,522 @@
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::membership::Membership;
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::stream::{NewStream, Stream};
+use crate::model::stream_grant::StreamGrant;
+use crate::model::user::User;
+use crate::response::Response;
+use crate::request::client::ClientInfo;
+use crate::request::stream::Stream as RequestData;
+use crate::route::error::forbidden_by;
+use crate::route::namespace::authorize;
+use crate::validation::stream::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/stream/<namespace_uuid>/archive/<uuid>", rank = 2)]
+    pub fn archive<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/stream/<namespace_uuid>/archived/hgetall", rank = 2)]
+    pub fn archived<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/stream/<namespace_uuid>/hget/<uuid>", rank = 2)]
+    pub fn hget<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/stream/<namespace_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/stream/<namespace_uuid>/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options("/stream/<namespace_uuid>/hset/<uuid>", rank = 2)]
+    pub fn hupdate<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/stream/<namespace_uuid>/unarchive/<uuid>", rank = 2)]
+    pub fn unarchive<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+// Returns a response for a value of the field which is already taken in the
+// namespace.
+fn taken<'a>(res: Response<'a>, field: &str) -> Response<'a> {
+    res.status(Status::UnprocessableEntity).format(json!({
+        "errors": [{
+            "field": field,
+            "messages": ["Must be unique in the namespace"],
+        }],
+    }))
+}
+
+// Archives the stream. Only owners of the namespace can archive it.
+#[post("/stream/<namespace_uuid>/archive/<uuid>", rank = 1)]
+pub fn archive<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let stream =
+        match Stream::find_by_uuid_in(&namespace, &uuid, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(s) => s,
+        };
+
+    let result: Result<Stream, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Stream, diesel::result::Error, _>(|| {
+            let archived = stream.archive(&conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::StreamArchive,
+                Some(user),
+                &client,
+            );
+            e.target = Some(stream.uuid.to_string());
+            e.namespace_id = Some(namespace.id);
+            e.diff = serde_json::json!({
+                "archived_at": [stream.archived_at, archived.archived_at],
+            });
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(archived)
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(s) => res.format(json!({ "stream": s })),
+    }
+}
+
+#[get("/stream/<namespace_uuid>/archived/hgetall", rank = 1)]
+pub fn archived<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let data = match Stream::find_all_archived_by_namespace(
+        &namespace, &conn, &logger,
+    ) {
+        None => {
+            error!(logger, "err: no stream for namespace: {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|s| json!({ "stream": s })).collect(),
+    };
+    res.format(json!(data))
+}
+
+#[get("/stream/<namespace_uuid>/hget/<uuid>", rank = 1)]
+pub fn hget<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    match Stream::find_by_uuid_in(&namespace, &uuid, &conn, &logger) {
+        None => res.status(Status::NotFound),
+        Some(s) => res.format(json!({ "stream": s })),
+    }
+}
+
+#[get("/stream/<namespace_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let data = match Stream::find_all_by_namespace(&namespace, &conn, &logger)
+    {
+        None => {
+            error!(logger, "err: no stream for namespace: {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|s| json!({ "stream": s })).collect(),
+    };
+    res.format(json!(data))
+}
+
+// Creates a new stream in the namespace. Only owners can create it.
+#[post(
+    "/stream/<namespace_uuid>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let mut s = NewStream::from(data.0.clone());
+    s.namespace_id = namespace.id;
+    if Stream::find_by_name_in(&namespace, &s.name, &conn, &logger).is_some() {
+        return taken(res, "name");
+    }
+    if !s.slug.is_empty() &&
+        Stream::find_by_slug_in(&namespace, &s.slug, &conn, &logger).is_some()
+    {
+        return taken(res, "slug");
+    }
+
+    let result: Result<Stream, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Stream, diesel::result::Error, _>(|| {
+            Stream::insert(&s, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(stream) => {
+            info!(logger, "stream: {}", stream.id);
+            res.format(json!({"stream": {
+                "uuid": stream.uuid.to_string(),
+            }}))
+        },
+    }
+}
+
+// Changes the name, the slug and the description of the stream. Only owners
+// can change them.
+#[patch(
+    "/stream/<namespace_uuid>/hset/<uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hupdate<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let stream =
+        match Stream::find_by_uuid_in(&namespace, &uuid, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(s) => s,
+        };
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let mut s = NewStream::from(data.0.clone());
+    if let Some(other) =
+        Stream::find_by_name_in(&namespace, &s.name, &conn, &logger)
+    {
+        if other.id != stream.id {
+            return taken(res, "name");
+        }
+    }
+    if s.slug.is_empty() {
+        s.slug = stream.slug.clone();
+    } else if let Some(other) =
+        Stream::find_by_slug_in(&namespace, &s.slug, &conn, &logger)
+    {
+        if other.id != stream.id {
+            return taken(res, "slug");
+        }
+    }
+
+    match stream.update(&s.name, &s.slug, &s.description, &conn, &logger) {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(stream) => res.format(json!({ "stream": stream })),
+    }
+}
+
+// Restores the stream. Only owners of the namespace can restore it.
+#[post("/stream/<namespace_uuid>/unarchive/<uuid>", rank = 1)]
+pub fn unarchive<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::StreamsManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let stream = match Stream::find_archived_by_uuid_in(
+        &namespace, &uuid, &conn, &logger,
+    ) {
+        None => return res.status(Status::NotFound),
+        Some(s) => s,
+    };
+
+    let result: Result<Stream, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<Stream, diesel::result::Error, _>(|| {
+            stream.unarchive(&conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(s) => res.format(json!({ "stream": s })),
+    }
+}
+
+// Checks whether the user is granted the permission on the stream.
+//
+// Owners follow the namespace policy. Members who have any stream grant in the
+// namespace can access only the granted streams, by the role of each grant
+// (see model/stream_grant.rs). Other members follow the namespace policy.
+//
+// Returns a forbidden response as an error if it's not granted.
+pub fn authorize_stream<'a>(
+    namespace: &Namespace,
+    stream: &Stream,
+    user: &User,
+    permission: Permission,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<Membership, Response<'a>> {
+    let membership = Membership::find_by_namespace_id_and_user(
+        namespace.id,
+        user,
+        conn,
+        logger,
+    );
+    let granted = match membership {
+        None => false,
+        Some(ref m) if m.role.is_owner() => permission.is_granted_to(&m.role),
+        Some(ref m) => {
+            let grants =
+                StreamGrant::find_all_by_user_in(namespace, user, conn, logger);
+            match grants {
+                None => false,
+                Some(ref v) if v.is_empty() => {
+                    permission.is_granted_to(&m.role)
+                },
+                Some(v) => {
+                    v.iter().any(|g| {
+                        g.stream_id == stream.id &&
+                            permission.is_granted_on_stream_to(&g.role)
+                    })
+                },
+            }
+        },
+    };
+    match membership {
+        Some(m) if granted => Ok(m),
+        _ => {
+            warn!(
+                logger,
+                "forbidden user: {}, stream: {}, permission: {}",
+                user.uuid,
+                stream.uuid,
+                permission
+            );
+            Err(forbidden_by(permission))
+        },
+    }
+}
@@ -0,0 +1,305 @@
+use diesel::result::Error;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::membership::Membership;
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::stream::Stream;
+use crate::model::stream_grant::{NewStreamGrant, StreamGrant};
+use crate::model::user::User;
+use crate::response::Response;
+use crate::request::client::ClientInfo;
+use crate::request::stream_grant::StreamGrant as RequestData;
+use crate::route::namespace::authorize;
+use crate::validation::stream_grant::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/stream_grant/<stream_uuid>/del/<user_uuid>", rank = 2)]
+    pub fn del<'a>(
+        stream_uuid: String,
+        user_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "stream: {}, user: {}", stream_uuid, user_uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/stream_grant/<stream_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        stream_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "stream: {}", stream_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/stream_grant/<stream_uuid>/hset/<user_uuid>", rank = 2)]
+    pub fn hset<'a>(
+        stream_uuid: String,
+        user_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "stream: {}, user: {}", stream_uuid, user_uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+// Finds the stream by the uuid, and checks the permission of the user in the
+// namespace. Grants are managed by the namespace policy, not by themselves.
+fn find_stream<'a>(
+    stream_uuid: &str,
+    user: &User,
+    permission: Permission,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Result<(Namespace, Stream), Response<'a>> {
+    let not_found = || Response::default().status(Status::NotFound);
+
+    let stream = Stream::owned_by_uuid(user, stream_uuid, conn, logger)
+        .ok_or_else(not_found)?;
+    let namespace = Namespace::find_by_id(stream.namespace_id, conn, logger)
+        .ok_or_else(not_found)?;
+    authorize(&namespace, user, permission, conn, logger)?;
+    Ok((namespace, stream))
+}
+
+// Returns the user if it's an (active) member of the namespace.
+fn find_member(
+    namespace: &Namespace,
+    user_uuid: &str,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Option<User> {
+    let user = User::find_by_uuid(user_uuid, conn, logger)?;
+    Membership::find_by_namespace_id_and_user(namespace.id, &user, conn, logger)
+        .map(|_| user)
+}
+
+#[delete("/stream_grant/<stream_uuid>/del/<user_uuid>", rank = 1)]
+pub fn del<'a>(
+    stream_uuid: String,
+    user_uuid: String,
+    user: &User,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, stream: {}, member: {}", user.uuid, stream_uuid, user_uuid
+    );
+
+    let res: Response = Default::default();
+
+    let (namespace, stream) = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::StreamsManage,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(v) => v,
+    };
+    let member = match find_member(&namespace, &user_uuid, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(u) => u,
+    };
+
+    let result: Result<(), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            match StreamGrant::find_by_stream_and_user(
+                &stream, &member, &conn, &logger,
+            ) {
+                None => {
+                    error!(logger, "err: not found {}", user_uuid);
+                    Err(Error::RollbackTransaction)
+                },
+                Some(g) => {
+                    g.delete(&conn, &logger).map_err(|e| {
+                        error!(logger, "err: {}", e);
+                        Error::RollbackTransaction
+                    })?;
+
+                    let mut e = NewAuditEvent::new(
+                        AuditAction::StreamGrantRevoke,
+                        Some(user),
+                        &client,
+                    );
+                    e.target = Some(user_uuid.clone());
+                    e.namespace_id = Some(namespace.id);
+                    e.diff = serde_json::json!({
+                        "stream": stream.uuid.to_string(),
+                        "role": [g.role, null],
+                    });
+                    AuditEvent::insert(&e, &conn, &logger)
+                        .ok_or(Error::RollbackTransaction)?;
+                    Ok(())
+                },
+            }
+        });
+
+    if result.is_err() {
+        return res.status(Status::NotFound);
+    }
+
+    res.format(json!({
+        "stream_grant": 1,
+    }))
+}
+
+#[get("/stream_grant/<stream_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    stream_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, stream: {}", user.uuid, stream_uuid);
+
+    let res: Response = Default::default();
+
+    let (_, stream) = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::StreamsRead,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(v) => v,
+    };
+
+    let data = match StreamGrant::find_all_by_stream(&stream, &conn, &logger) {
+        None => {
+            error!(logger, "err: no stream grant for stream: {}", stream.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|(g, u)| {
+                    json!({"stream_grant": {
+                        "role": g.role,
+                        "created_at": g.created_at,
+                        "user": {
+                            "uuid": u.uuid.to_string(),
+                            "name": u.name,
+                            "username": u.username,
+                        },
+                    }})
+                })
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Grants the role on the stream to the member, or changes the role of the
+// grant if it exists.
+#[post(
+    "/stream_grant/<stream_uuid>/hset/<user_uuid>",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    stream_uuid: String,
+    user_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, stream: {}, member: {}", user.uuid, stream_uuid, user_uuid
+    );
+
+    let res: Response = Default::default();
+
+    let (namespace, stream) = match find_stream(
+        &stream_uuid,
+        user,
+        Permission::StreamsManage,
+        &conn,
+        &logger,
+    ) {
+        Err(r) => return r,
+        Ok(v) => v,
+    };
+    let member = match find_member(&namespace, &user_uuid, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(u) => u,
+    };
+
+    let v = Validator::new(&data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let mut g = NewStreamGrant::from(data.0.clone());
+    g.stream_id = stream.id;
+    g.user_id = member.id;
+
+    let result: Result<StreamGrant, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<StreamGrant, diesel::result::Error, _>(|| {
+            let current = StreamGrant::find_by_stream_and_user(
+                &stream, &member, &conn, &logger,
+            );
+            let grant = StreamGrant::upsert(&g, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::StreamGrantChange,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user_uuid.clone());
+            e.namespace_id = Some(namespace.id);
+            e.diff = serde_json::json!({
+                "stream": stream.uuid.to_string(),
+                "role": [current.map(|c| c.role), grant.role],
+            });
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(grant)
+        });
+
+    match result {
+        Err(_) => res.status(Status::InternalServerError),
+        Ok(grant) => {
+            info!(logger, "stream_grant: {}", grant.id);
+            res.format(json!({"stream_grant": {
+                "role": grant.role,
+                "user": {
+                    "uuid": user_uuid,
+                },
+            }}))
+        },
+    }
+}
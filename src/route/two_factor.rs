@@ -0,0 +1,380 @@
+use chrono::Utc;
+use diesel::PgConnection;
+use diesel::result::Error;
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::logger::Logger;
+use crate::model::Authenticatable;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::user::User;
+use crate::model::user_two_factor::{UserTwoFactor, digest_recovery_code};
+use crate::request::client::ClientInfo;
+use crate::request::two_factor::{
+    TwoFactorCode, TwoFactorConfirmation, TwoFactorSetup,
+};
+use crate::response::Response;
+use crate::service::attempt_limiter::{AttemptLimiter, Scope};
+use crate::service::totp;
+use crate::ss::SsConn;
+use crate::util::SystemClock;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/two_factor/disable", rank = 2)]
+    pub fn disable<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/two_factor/enable", rank = 2)]
+    pub fn enable<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/two_factor/recovery_codes", rank = 2)]
+    pub fn recovery_codes<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PUT", &config)
+    }
+
+    #[options("/two_factor/setup", rank = 2)]
+    pub fn setup<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PUT", &config)
+    }
+}
+
+/// Verifies a TOTP code or a recovery code of the user. Neither of them can
+/// be used twice.
+pub fn verify_code(
+    two_factor: &UserTwoFactor,
+    code: &str,
+    config: &Config,
+    conn: &PgConnection,
+    logger: &Logger,
+) -> bool {
+    let secret =
+        totp::decrypt_secret(&two_factor.secret, &config.two_factor_secret_key);
+    if secret.is_none() {
+        error!(logger, "error: failed to decrypt secret of {}", two_factor);
+    }
+    let now = Utc::now().timestamp();
+    let step = secret.and_then(|secret| {
+        totp::verify(&secret, code, now, two_factor.last_used_step)
+    });
+    match step {
+        Some(step) => two_factor.use_step(step, conn, logger).is_ok(),
+        None => two_factor.use_recovery_code(code, conn, logger).is_ok(),
+    }
+}
+
+fn invalid_password<'a>() -> Response<'a> {
+    let res: Response = Default::default();
+    res.status(Status::UnprocessableEntity).format(json!({
+        "errors": [{
+            "field": "password",
+            "messages": ["Must match the current password"],
+        }],
+    }))
+}
+
+fn too_many_attempts<'a>(wait: i64) -> Response<'a> {
+    let res: Response = Default::default();
+    res.header("Retry-After", wait.to_string())
+        .status(Status::TooManyRequests)
+        .format(json!({
+            "message": "Too many failed attempts. Retry later."
+        }))
+}
+
+fn invalid_code<'a>() -> Response<'a> {
+    let res: Response = Default::default();
+    res.status(Status::UnprocessableEntity).format(json!({
+        "errors": [{
+            "field": "code",
+            "messages": ["Is invalid"],
+        }],
+    }))
+}
+
+// Disables two-factor authentication. Both the password and a code (or a
+// recovery code) are required.
+#[patch("/two_factor/disable", data = "<payload>", format = "json", rank = 1)]
+pub fn disable<'a>(
+    user: &User,
+    payload: Json<TwoFactorConfirmation>,
+    client: ClientInfo,
+    config: State<Config>,
+    db_conn: DbConn,
+    logger: SyncLogger,
+    mut ss_conn: SsConn,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let identifier = user.uuid.to_string();
+    let clock = SystemClock;
+    let mut limiter = AttemptLimiter::new(&mut ss_conn, &clock, &logger);
+    let wait = limiter
+        .check(Scope::TwoFactor, &identifier, &client)
+        .unwrap_or(0);
+    if wait > 0 {
+        warn!(logger, "attempt rejected: user {}", user.uuid);
+        return too_many_attempts(wait);
+    }
+
+    if !user.verify_password(&payload.0.password) {
+        let _ = limiter.record(Scope::TwoFactor, &identifier, &client);
+        return invalid_password();
+    }
+
+    let two_factor = match UserTwoFactor::find_by_user_id(
+        user.id, &db_conn, &logger,
+    ) {
+        Some(t) if t.is_enabled() => t,
+        _ => return res.status(Status::NotFound),
+    };
+    if !verify_code(&two_factor, &payload.0.code, &config, &db_conn, &logger) {
+        let _ = limiter.record(Scope::TwoFactor, &identifier, &client);
+        return invalid_code();
+    }
+    let _ = limiter.reset(Scope::TwoFactor, &identifier);
+
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            two_factor.delete(&db_conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::TwoFactorDisable,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(())
+        });
+    match result {
+        Ok(_) => res.status(Status::Ok),
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Enables two-factor authentication with the first code from the app, and
+// returns recovery codes. They are shown only once.
+#[patch("/two_factor/enable", data = "<payload>", format = "json", rank = 1)]
+pub fn enable<'a>(
+    user: &User,
+    payload: Json<TwoFactorCode>,
+    client: ClientInfo,
+    config: State<Config>,
+    db_conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let two_factor = match UserTwoFactor::find_by_user_id(
+        user.id, &db_conn, &logger,
+    ) {
+        Some(t) if !t.is_enabled() => t,
+        Some(_) => return res.status(Status::Conflict),
+        None => return res.status(Status::NotFound),
+    };
+
+    let now = Utc::now().timestamp();
+    let step =
+        totp::decrypt_secret(&two_factor.secret, &config.two_factor_secret_key)
+            .and_then(|secret| {
+                totp::verify(&secret, &payload.0.code, now, None)
+            });
+    let step = match step {
+        Some(s) => s,
+        None => return invalid_code(),
+    };
+
+    let recovery_codes = UserTwoFactor::generate_recovery_codes();
+    let digests: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| digest_recovery_code(c))
+        .collect();
+
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            two_factor.enable(step, &digests, &db_conn, &logger).map_err(
+                |e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                },
+            )?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::TwoFactorEnable,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(())
+        });
+    match result {
+        Ok(_) => res.format(json!({ "recovery_codes": recovery_codes })),
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Renews the recovery codes. The old ones are no longer available.
+#[put(
+    "/two_factor/recovery_codes",
+    data = "<payload>",
+    format = "json",
+    rank = 1
+)]
+pub fn recovery_codes<'a>(
+    user: &User,
+    payload: Json<TwoFactorConfirmation>,
+    client: ClientInfo,
+    config: State<Config>,
+    db_conn: DbConn,
+    logger: SyncLogger,
+    mut ss_conn: SsConn,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let identifier = user.uuid.to_string();
+    let clock = SystemClock;
+    let mut limiter = AttemptLimiter::new(&mut ss_conn, &clock, &logger);
+    let wait = limiter
+        .check(Scope::TwoFactor, &identifier, &client)
+        .unwrap_or(0);
+    if wait > 0 {
+        warn!(logger, "attempt rejected: user {}", user.uuid);
+        return too_many_attempts(wait);
+    }
+
+    if !user.verify_password(&payload.0.password) {
+        let _ = limiter.record(Scope::TwoFactor, &identifier, &client);
+        return invalid_password();
+    }
+
+    let two_factor = match UserTwoFactor::find_by_user_id(
+        user.id, &db_conn, &logger,
+    ) {
+        Some(t) if t.is_enabled() => t,
+        _ => return res.status(Status::NotFound),
+    };
+    if !verify_code(&two_factor, &payload.0.code, &config, &db_conn, &logger) {
+        let _ = limiter.record(Scope::TwoFactor, &identifier, &client);
+        return invalid_code();
+    }
+    let _ = limiter.reset(Scope::TwoFactor, &identifier);
+
+    let recovery_codes = UserTwoFactor::generate_recovery_codes();
+    let digests: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| digest_recovery_code(c))
+        .collect();
+
+    let result: Result<(), Error> = db_conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            // reload it as the code may have been consumed above
+            let t = UserTwoFactor::find_by_user_id(user.id, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            t.renew_recovery_codes(&digests, &db_conn, &logger).map_err(
+                |e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                },
+            )?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::TwoFactorRecoveryCodesRenew,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            AuditEvent::insert(&e, &db_conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(())
+        });
+    match result {
+        Ok(_) => res.format(json!({ "recovery_codes": recovery_codes })),
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Starts the setup of two-factor authentication, and returns a new secret and
+// its provisioning URI for authenticator apps. It must be confirmed by
+// `enable`.
+#[put("/two_factor/setup", data = "<payload>", format = "json", rank = 1)]
+pub fn setup<'a>(
+    user: &User,
+    payload: Json<TwoFactorSetup>,
+    config: State<Config>,
+    db_conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    if !user.verify_password(&payload.0.password) {
+        return invalid_password();
+    }
+
+    if let Some(t) = UserTwoFactor::find_by_user_id(user.id, &db_conn, &logger)
+    {
+        if t.is_enabled() {
+            return res.status(Status::Conflict);
+        }
+    }
+
+    let secret = match totp::generate_secret() {
+        Some(s) => s,
+        None => return res.status(Status::InternalServerError),
+    };
+    let two_factor =
+        totp::encrypt_secret(&secret, &config.two_factor_secret_key)
+            .and_then(|s| {
+                UserTwoFactor::setup(user.id, &s, &db_conn, &logger)
+            });
+    if two_factor.is_none() {
+        return res.status(Status::InternalServerError);
+    }
+
+    res.format(json!({
+        "secret": totp::base32_encode(&secret),
+        "uri": totp::provisioning_uri(
+            &secret,
+            &user.email,
+            Config::TWO_FACTOR_ISSUER,
+        ),
+    }))
+}
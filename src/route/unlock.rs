@@ -0,0 +1,135 @@
+use chrono::{Duration, Utc};
+use fourche::queue::Queue;
+use redis::{Commands, RedisError};
+use rocket::State;
+use rocket::http::Status;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::job::{Job, JobKind};
+use crate::logger::Logger;
+use crate::model::token::{Claims, TokenData, VerificationClaims};
+use crate::model::user::User;
+use crate::mq::MqConn;
+use crate::request::token::verification::VerificationToken;
+use crate::response::Response;
+use crate::service::attempt_limiter::{AttemptLimiter, Scope};
+use crate::ss::{SsConn, user_sessions_key};
+use crate::util::{SystemClock, split_token};
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/unlock/<session_id>", rank = 2)]
+    pub fn unlock<'a>(
+        session_id: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "session_id: {}", session_id);
+        no_content_for("PATCH", &config)
+    }
+}
+
+/// Sends an unlock link to the user locked out by failed sign in attempts.
+///
+/// The signature of the token is kept in session store until the lockout
+/// ends (see password reset).
+pub fn request_unlock(
+    user: &User,
+    config: &Config,
+    ss_conn: &mut SsConn,
+    mq_conn: &mut MqConn,
+    logger: &Logger,
+) -> Result<(), &'static str> {
+    let now = Utc::now();
+    let duration = Duration::minutes(Config::ATTEMPT_LOCKOUT_DURATION);
+    let data = TokenData {
+        value: user.uuid.to_string(),
+        granted_at: now.timestamp(),
+        expires_at: (now + duration).timestamp(),
+    };
+    let raw_token = VerificationClaims::encode(
+        data,
+        &config.verification_token_issuer,
+        &config.verification_token_key_id,
+        &config.verification_token_secret,
+    );
+    let (token, sign) = split_token(raw_token).ok_or("invalid token")?;
+
+    // TODO: use general value
+    let session_id = User::generate_password_reset_token();
+    let key = format!("ul-{}", session_id);
+    let ttl = duration.num_seconds() as usize;
+    let sessions_key = user_sessions_key(user.id);
+    let _: () = ss_conn
+        .set_ex(&key, sign, ttl)
+        .and_then(|_: String| ss_conn.sadd(&sessions_key, &key))
+        .and_then(|_: i64| ss_conn.expire(&sessions_key, ttl))
+        .map_err(|e: RedisError| {
+            error!(logger, "error: {}", e);
+            "failed to store session"
+        })?;
+
+    let job = Job::<String> {
+        kind: JobKind::SendAccountUnlockEmail,
+        args: vec![user.id.to_string(), session_id, token],
+    };
+    let mut queue = Queue::new("default", &mut **mq_conn);
+    queue.enqueue::<Job<String>>(job).map_err(|e| {
+        error!(logger, "error: {}", e);
+        "failed to enqueue job"
+    })
+}
+
+// Unlocks the account locked out by failed sign in attempts via the link sent
+// to the user.
+#[patch("/unlock/<session_id>", format = "json", rank = 1)]
+pub fn unlock<'a>(
+    session_id: String,
+    token: VerificationToken,
+    config: State<Config>,
+    db_conn: DbConn,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "session_id: {}", session_id);
+
+    let res: Response = Default::default();
+
+    let user = match VerificationClaims::decode(
+        &token,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+    ) {
+        Ok(c) => User::find_by_uuid(&c.get_subject(), &db_conn, &logger),
+        Err(e) => {
+            error!(logger, "error: {}", e);
+            None
+        },
+    };
+    let user = match user {
+        Some(u) => u,
+        None => return res.status(Status::NotFound),
+    };
+
+    let clock = SystemClock;
+    let mut limiter = AttemptLimiter::new(&mut ss_conn, &clock, &logger);
+    if limiter.reset(Scope::Login, &user.email).is_err() {
+        return res.status(Status::InternalServerError);
+    }
+
+    // clear session
+    let key = format!("ul-{}", session_id);
+    let _: Result<i64, RedisError> = ss_conn.del(&key).map_err(|e| {
+        error!(logger, "error: {}", e);
+        e
+    });
+    res.status(Status::Ok)
+}
@@ -0,0 +1,148 @@
+use rocket::State;
+use rocket::http::Status;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::model::namespace::Namespace;
+use crate::model::namespace_usage::NamespaceUsage;
+use crate::model::permission::Permission;
+use crate::model::user::User;
+use crate::response::Response;
+use crate::route::namespace::authorize;
+use crate::service::usage_meter;
+use crate::util::{Clock, SystemClock};
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/usage/<namespace_uuid>/hget", rank = 2)]
+    pub fn hget<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/usage/<namespace_uuid>/lrange/<start>/<stop>", rank = 2)]
+    pub fn lrange<'a>(
+        namespace_uuid: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, start: {}, stop: {}", namespace_uuid, start, stop
+        );
+        no_content_for("GET", &config)
+    }
+}
+
+// Returns the usage of today and the daily quota of the namespace.
+#[get("/usage/<namespace_uuid>/hget", rank = 1)]
+pub fn hget<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    config: State<Config>,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            Some(n) => n,
+            None => return res.status(Status::NotFound),
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::UsageRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let now = SystemClock.now();
+    let usage = NamespaceUsage::find_by_namespace_id_and_date(
+        namespace.id,
+        now.date(),
+        &conn,
+        &logger,
+    );
+    let (messages_count, bytes_count, dropped_count) = usage
+        .map_or((0, 0, 0), |u| {
+            (u.messages_count, u.bytes_count, u.dropped_count)
+        });
+
+    res.format(json!({
+        "usage": {
+            "date": now.date(),
+            "messages_count": messages_count,
+            "bytes_count": bytes_count,
+            "dropped_count": dropped_count,
+            "quota": usage_meter::quota_for(&namespace, &config),
+            "reset": usage_meter::seconds_until_reset(now),
+        },
+    }))
+}
+
+// Returns daily usages of the namespace from the latest one.
+#[get("/usage/<namespace_uuid>/lrange/<start>/<stop>", rank = 1)]
+pub fn lrange<'a>(
+    namespace_uuid: String,
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_uuid,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            Some(n) => n,
+            None => return res.status(Status::NotFound),
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::UsageRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    // TODO
+    let mut offset = start;
+    if offset < 1 {
+        offset = 0;
+    }
+
+    let mut limit = stop - start + 2;
+    if limit < 1 {
+        limit = 1;
+    }
+
+    let data = match NamespaceUsage::fetch_by_namespace(
+        &namespace, offset, limit, &conn, &logger,
+    ) {
+        None => vec![],
+        Some(a) => a.iter().map(|u| json!({ "usage": u })).collect(),
+    };
+    res.format(json!(data))
+}
@@ -0,0 +1,339 @@
+use chrono::{Duration, Utc};
+use diesel::result::Error;
+use fourche::queue::Queue;
+use rocket::State;
+use rocket::http::Status;
+use rocket_contrib::json::{Json, JsonValue};
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::db::DbConn;
+use crate::job::{Job, JobKind};
+use crate::model::Activatable;
+use crate::model::audit_event::{AuditAction, AuditEvent, NewAuditEvent};
+use crate::model::token::{Claims, TokenData, VerificationClaims};
+use crate::model::user::User;
+use crate::model::user_email::{NewUserEmail, UserEmail, UserEmailRole};
+use crate::mq::MqConn;
+use crate::request::client::ClientInfo;
+use crate::request::user_email::{
+    UserEmail as RequestData, Verification as VerificationData,
+};
+use crate::response::Response;
+use crate::validation::user_email::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/user_email/append", rank = 2)]
+    pub fn append<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("POST", &config)
+    }
+
+    #[options("/user_email/del", rank = 2)]
+    pub fn del<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/user_email/hgetall", rank = 2)]
+    pub fn hgetall<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("GET", &config)
+    }
+
+    #[options("/user_email/primary", rank = 2)]
+    pub fn primary<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("PATCH", &config)
+    }
+
+    #[options("/user_email/verify", rank = 2)]
+    pub fn verify<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("POST", &config)
+    }
+}
+
+fn format(user_email: &UserEmail) -> JsonValue {
+    json!({
+        "email": user_email.email,
+        "role": user_email.role.to_string(),
+        "identification_state": user_email.identification_state.to_string(),
+        "created_at": user_email.created_at,
+    })
+}
+
+fn find_user_email(
+    user: &User,
+    data: &RequestData,
+    conn: &DbConn,
+    logger: &SyncLogger,
+) -> Option<UserEmail> {
+    let email = data.email.as_deref().unwrap_or_default();
+    UserEmail::find_by_email_of(user, email, conn, logger)
+}
+
+// Adds a new (secondary) address to the user. It must be verified via the
+// token sent to the address before being primary.
+#[post("/user_email/append", data = "<data>", format = "json", rank = 1)]
+pub fn append<'a>(
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: SyncLogger,
+    config: State<Config>,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let v = Validator::new(&conn, &data, &logger);
+    if let Err(errors) = v.validate() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": errors,
+        }));
+    }
+
+    let pending = UserEmail::count_pending_by_user(user, &conn, &logger);
+    if pending.map_or(true, |n| n >= Config::USER_EMAIL_PENDING_LIMIT) {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "email",
+                "messages": ["Too many unverified addresses"],
+            }],
+        }));
+    }
+
+    let ue = NewUserEmail {
+        user_id: user.id,
+        email: data.0.email.clone().unwrap_or_default(),
+        role: UserEmailRole::General,
+
+        ..Default::default()
+    };
+
+    let result: Result<(UserEmail, String), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(UserEmail, String), diesel::result::Error, _>(|| {
+            // the address may be left by someone as pending
+            UserEmail::delete_expired_by_email(&ue.email, &conn, &logger)
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+            let user_email = UserEmail::insert(&ue, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+
+            let now = Utc::now();
+            let duration = Duration::hours(Config::USER_EMAIL_TOKEN_DURATION);
+            let data = TokenData {
+                value: UserEmail::generate_token(),
+                granted_at: now.timestamp(),
+                expires_at: (now + duration).timestamp(),
+            };
+            let raw_token = VerificationClaims::encode(
+                data,
+                &config.verification_token_issuer,
+                &config.verification_token_key_id,
+                &config.verification_token_secret,
+            );
+            user_email
+                .grant_token::<VerificationClaims>(
+                    &raw_token,
+                    &config.verification_token_issuer,
+                    &config.verification_token_secret,
+                    &conn,
+                    &logger,
+                )
+                .map_err(|e| {
+                    error!(logger, "err: {}", e);
+                    Error::RollbackTransaction
+                })?;
+            Ok((user_email, raw_token))
+        });
+
+    let (user_email, raw_token) = match result {
+        Ok(v) => v,
+        Err(_) => return res.status(Status::InternalServerError),
+    };
+
+    let job = Job::<String> {
+        kind: JobKind::SendEmailVerificationEmail,
+        args: vec![user_email.id.to_string(), raw_token],
+    };
+    let mut queue = Queue::new("default", &mut *mq_conn);
+    if let Err(err) = queue.enqueue::<Job<String>>(job) {
+        error!(logger, "error: {}", err);
+        return res.status(Status::InternalServerError);
+    }
+
+    res.format(json!({ "user_email": format(&user_email) }))
+}
+
+// Removes the address. The primary one can't be removed.
+#[delete("/user_email/del", data = "<data>", format = "json", rank = 1)]
+pub fn del<'a>(
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let user_email = match find_user_email(user, &data, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(ue) => ue,
+    };
+    if user_email.is_primary() {
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "email",
+                "messages": ["Must not be the primary address"],
+            }],
+        }));
+    }
+
+    if let Err(e) = user_email.delete(&conn, &logger) {
+        error!(logger, "err: {}", e);
+        return res.status(Status::InternalServerError);
+    }
+
+    res.format(json!({
+        "user_email": 1,
+    }))
+}
+
+// Lists addresses of the user, the primary one first.
+#[get("/user_email/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let data = match UserEmail::find_all_by_user(user, &conn, &logger) {
+        None => {
+            error!(logger, "err: no email for user: {}", user.uuid);
+            vec![]
+        },
+        Some(a) => {
+            a.iter()
+                .map(|ue| json!({ "user_email": format(ue) }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Makes the verified address primary. `users.email` (used to sign in) is
+// changed as well.
+#[patch("/user_email/primary", data = "<data>", format = "json", rank = 1)]
+pub fn primary<'a>(
+    user: &User,
+    data: Json<RequestData>,
+    client: ClientInfo,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let user_email = match find_user_email(user, &data, &conn, &logger) {
+        None => return res.status(Status::NotFound),
+        Some(ue) => ue,
+    };
+    if user_email.is_primary() || !user_email.is_verified() {
+        let message = if user_email.is_primary() {
+            "Is the primary address already"
+        } else {
+            "Must be verified"
+        };
+        return res.status(Status::UnprocessableEntity).format(json!({
+            "errors": [{
+                "field": "email",
+                "messages": [message],
+            }],
+        }));
+    }
+
+    let result: Result<UserEmail, Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<UserEmail, diesel::result::Error, _>(|| {
+            let ue = user_email.promote(&conn, &logger).map_err(|e| {
+                error!(logger, "err: {}", e);
+                Error::RollbackTransaction
+            })?;
+
+            let mut e = NewAuditEvent::new(
+                AuditAction::UserEmailPromote,
+                Some(user),
+                &client,
+            );
+            e.target = Some(user.uuid.to_string());
+            e.diff = serde_json::json!({"email": [user.email, ue.email]});
+            AuditEvent::insert(&e, &conn, &logger)
+                .ok_or(Error::RollbackTransaction)?;
+            Ok(ue)
+        });
+
+    match result {
+        Ok(ue) => res.format(json!({ "user_email": format(&ue) })),
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Verifies the address added by the user with the token sent to it.
+#[post("/user_email/verify", data = "<data>", format = "json", rank = 1)]
+pub fn verify<'a>(
+    user: &User,
+    data: Json<VerificationData>,
+    conn: DbConn,
+    logger: SyncLogger,
+    config: State<Config>,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let token = data.0.token.clone().unwrap_or_default();
+    let user_email = match UserEmail::find_by_token::<VerificationClaims>(
+        &token,
+        &config.verification_token_issuer,
+        &config.verification_token_secret,
+        &conn,
+        &logger,
+    ) {
+        // the token must be used by the user who has added the address
+        Some(ue) if ue.user_id == user.id => ue,
+        _ => {
+            return res.status(Status::BadRequest).format(json!({
+                "message":
+                    "The verification link has been expired or is invalid"
+            }));
+        },
+    };
+
+    if let Err(e) = user_email.activate(&conn, &logger) {
+        error!(logger, "err: {}", e);
+        return res.status(Status::InternalServerError);
+    }
+
+    match UserEmail::find_by_id(user_email.id, &conn, &logger) {
+        Some(ue) => res.format(json!({ "user_email": format(&ue) })),
+        None => res.status(Status::NotFound),
+    }
+}
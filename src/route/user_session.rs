@@ -0,0 +1,118 @@
+use rocket::State;
+use rocket::http::Status;
+use rocket_slog::SyncLogger;
+
+use crate::config::Config;
+use crate::model::user::User;
+use crate::request::token::authentication::AuthenticationToken;
+use crate::response::Response;
+use crate::service::session_manager::{SessionManager, session_id_of};
+use crate::ss::SsConn;
+use crate::util::SystemClock;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/user_session/clear", rank = 2)]
+    pub fn clear<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/user_session/del/<id>", rank = 2)]
+    pub fn del<'a>(
+        id: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "id: {}", id);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/user_session/hgetall", rank = 2)]
+    pub fn hgetall<'a>(config: State<Config>) -> RawResponse<'a> {
+        no_content_for("GET", &config)
+    }
+}
+
+// Destroys all the browser sessions of the user except the current one.
+#[delete("/user_session/clear", rank = 1)]
+pub fn clear<'a>(
+    user: &User,
+    token: AuthenticationToken,
+    config: State<Config>,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let current = session_id_of(&token, &config);
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    match manager.finish_all(user.id, current.as_deref()) {
+        Ok(_) => res.status(Status::Ok),
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Destroys the browser session of the user. The token bound to it is no
+// longer available.
+#[delete("/user_session/del/<id>", rank = 1)]
+pub fn del<'a>(
+    id: String,
+    user: &User,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    match manager.finish_of(user.id, &id) {
+        Ok(true) => res.status(Status::Ok),
+        Ok(false) => res.status(Status::NotFound),
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
+
+// Lists the active browser sessions of the user with the device (user agent),
+// IP address and last seen time, the latest one first.
+#[get("/user_session/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    user: &User,
+    token: AuthenticationToken,
+    config: State<Config>,
+    mut ss_conn: SsConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}", user.uuid);
+
+    let res: Response = Default::default();
+
+    let current = session_id_of(&token, &config);
+    let clock = SystemClock;
+    let mut manager = SessionManager::new(&mut ss_conn, &clock, &logger);
+    match manager.list(user.id) {
+        Ok(sessions) => {
+            let data: Vec<_> = sessions
+                .iter()
+                .map(|s| {
+                    json!({
+                        "user_session": s,
+                        "current": Some(&s.id) == current.as_ref(),
+                    })
+                })
+                .collect();
+            res.format(json!(data))
+        },
+        Err(_) => res.status(Status::InternalServerError),
+    }
+}
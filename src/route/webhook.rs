@@ -0,0 +1,363 @@
+use diesel::result::Error;
+use fourche::queue::Queue;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use rocket_slog::SyncLogger;
+
+use crate::db::DbConn;
+use crate::job::Job;
+use crate::model::namespace::Namespace;
+use crate::model::permission::Permission;
+use crate::model::user::User;
+use crate::model::webhook::{NewWebhook, Webhook, WebhookEvent};
+use crate::model::webhook_delivery::WebhookDelivery;
+use crate::mq::MqConn;
+use crate::response::Response;
+use crate::request::webhook::Webhook as RequestData;
+use crate::route::namespace::authorize;
+use crate::service::webhook_sender;
+use crate::validation::webhook::Validator;
+
+pub mod preflight {
+    use rocket::State;
+    use rocket::response::Response as RawResponse;
+    use rocket_slog::SyncLogger;
+
+    use crate::config::Config;
+    use crate::response::no_content_for;
+
+    #[options("/webhook/<namespace_uuid>/del/<uuid>", rank = 2)]
+    pub fn del<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("DELETE", &config)
+    }
+
+    #[options("/webhook/<namespace_uuid>/hgetall", rank = 2)]
+    pub fn hgetall<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("GET", &config)
+    }
+
+    #[options("/webhook/<namespace_uuid>/hset", rank = 2)]
+    pub fn hset<'a>(
+        namespace_uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}", namespace_uuid);
+        no_content_for("POST", &config)
+    }
+
+    #[options(
+        "/webhook/<namespace_uuid>/lrange/<uuid>/<start>/<stop>",
+        rank = 2
+    )]
+    pub fn lrange<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        start: i64,
+        stop: i64,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(
+            logger,
+            "namespace: {}, uuid: {}, start: {}, stop: {}",
+            namespace_uuid,
+            uuid,
+            start,
+            stop
+        );
+        no_content_for("GET", &config)
+    }
+
+    #[options("/webhook/<namespace_uuid>/ping/<uuid>", rank = 2)]
+    pub fn ping<'a>(
+        namespace_uuid: String,
+        uuid: String,
+        config: State<Config>,
+        logger: SyncLogger,
+    ) -> RawResponse<'a> {
+        info!(logger, "namespace: {}, uuid: {}", namespace_uuid, uuid);
+        no_content_for("POST", &config)
+    }
+}
+
+#[delete("/webhook/<namespace_uuid>/del/<uuid>", rank = 1)]
+pub fn del<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::WebhooksManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let result: Result<(), Error> = conn
+        .build_transaction()
+        .serializable()
+        .deferrable()
+        .read_write()
+        .run::<(), diesel::result::Error, _>(|| {
+            match Webhook::find_by_uuid_in(&namespace, &uuid, &conn, &logger) {
+                None => {
+                    error!(logger, "err: not found {}", uuid);
+                    Err(Error::RollbackTransaction)
+                },
+                Some(w) => {
+                    w.delete(&conn, &logger).map_err(|e| {
+                        error!(logger, "err: {}", e);
+                        Error::RollbackTransaction
+                    })
+                },
+            }
+        });
+
+    if result.is_err() {
+        return res.status(Status::NotFound);
+    }
+
+    res.format(json!({
+        "webhook": 1,
+    }))
+}
+
+#[get("/webhook/<namespace_uuid>/hgetall", rank = 1)]
+pub fn hgetall<'a>(
+    namespace_uuid: String,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::WebhooksRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let data = match Webhook::find_all_by_namespace(&namespace, &conn, &logger)
+    {
+        None => {
+            error!(logger, "err: no webhook for namespace: {}", namespace.uuid);
+            vec![]
+        },
+        Some(a) => a.iter().map(|w| json!({ "webhook": w })).collect(),
+    };
+    res.format(json!(data))
+}
+
+// Creates a new webhook.
+//
+// The secret is returned only in this response.
+#[post(
+    "/webhook/<namespace_uuid>/hset",
+    data = "<data>",
+    format = "json",
+    rank = 1
+)]
+pub fn hset<'a>(
+    namespace_uuid: String,
+    user: &User,
+    data: Json<RequestData>,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(logger, "user: {}, namespace: {}", user.uuid, namespace_uuid);
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::WebhooksManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let v = Validator::new(&data, &logger);
+    match v.validate() {
+        Err(errors) => {
+            res.status(Status::UnprocessableEntity).format(json!({
+                "errors": errors,
+            }))
+        },
+        Ok(_) => {
+            let mut w = NewWebhook::from(data.0.clone());
+            w.namespace_id = namespace.id;
+            if let Some(webhook) = Webhook::insert(&w, &conn, &logger) {
+                info!(logger, "webhook: {}", webhook.id);
+                return res.format(json!({"webhook": {
+                    "uuid": webhook.uuid.to_string(),
+                    "secret": webhook.secret,
+                }}));
+            }
+            res.status(Status::InternalServerError)
+        },
+    }
+}
+
+#[get(
+    "/webhook/<namespace_uuid>/lrange/<uuid>/<start>/<stop>",
+    rank = 1
+)]
+pub fn lrange<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    start: i64,
+    stop: i64,
+    user: &User,
+    conn: DbConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}, start: {}, stop: {}",
+        user.uuid,
+        namespace_uuid,
+        uuid,
+        start,
+        stop,
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::WebhooksRead, &conn, &logger)
+    {
+        return forbidden;
+    }
+
+    let webhook = Webhook::find_by_uuid_in(&namespace, &uuid, &conn, &logger);
+    if webhook.is_none() {
+        return res.status(Status::NotFound);
+    }
+
+    // TODO
+    let mut offset = start;
+    if offset < 1 {
+        offset = 0;
+    }
+
+    let mut limit = stop - start + 2;
+    if limit < 1 {
+        limit = 1;
+    }
+
+    let data = match WebhookDelivery::fetch_by_webhook(
+        &webhook.unwrap(),
+        offset,
+        limit,
+        &conn,
+        &logger,
+    ) {
+        None => vec![],
+        Some(a) => {
+            a.iter()
+                .map(|d| json!({ "webhook_delivery": d }))
+                .collect()
+        },
+    };
+    res.format(json!(data))
+}
+
+// Sends a test event (ping) to the webhook.
+#[post("/webhook/<namespace_uuid>/ping/<uuid>", rank = 1)]
+pub fn ping<'a>(
+    namespace_uuid: String,
+    uuid: String,
+    user: &User,
+    conn: DbConn,
+    mut mq_conn: MqConn,
+    logger: SyncLogger,
+) -> Response<'a> {
+    info!(
+        logger,
+        "user: {}, namespace: {}, uuid: {}", user.uuid, namespace_uuid, uuid
+    );
+
+    let res: Response = Default::default();
+
+    let namespace =
+        match Namespace::find_by_uuid(&namespace_uuid, user, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(n) => n,
+        };
+    if let Err(forbidden) =
+        authorize(&namespace, user, Permission::WebhooksManage, &conn, &logger)
+    {
+        return forbidden;
+    }
+    let webhook =
+        match Webhook::find_by_uuid_in(&namespace, &uuid, &conn, &logger) {
+            None => return res.status(Status::NotFound),
+            Some(w) => w,
+        };
+
+    let data = serde_json::json!({
+        "webhook": {
+            "uuid": webhook.uuid.to_string(),
+        },
+    });
+    if let Some(delivery) = webhook_sender::prepare(
+        &webhook,
+        &WebhookEvent::Ping,
+        data,
+        &conn,
+        &logger,
+    ) {
+        let job = webhook_sender::job_for(&delivery);
+        let mut queue = Queue::new("default", &mut *mq_conn);
+        if let Err(err) = queue.enqueue::<Job<String>>(job) {
+            error!(logger, "error: {}", err);
+        } else {
+            return res.format(json!({"webhook_delivery": {
+                "uuid": delivery.uuid.to_string(),
+            }}));
+        }
+    }
+    res.status(Status::InternalServerError).format(json!({
+        "message": "Something wrong happen, sorry :'("
+    }))
+}
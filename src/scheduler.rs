@@ -0,0 +1,144 @@
+//! Scheduler tells which periodic jobs are due.
+//!
+//! The worker ticks it in a loop and enqueues returned jobs. Each worker has
+//! its own scheduler, so a job must be locked for its interval before it's
+//! enqueued (see `lock_key`).
+use chrono::{Duration, NaiveDateTime};
+
+use crate::job::{Job, JobKind};
+
+/// Returns the key of the lock which is held by a worker enqueueing the job.
+pub fn lock_key(kind: &JobKind) -> String {
+    format!("scheduler-{}", kind)
+}
+
+struct Schedule {
+    kind: JobKind,
+    interval: Duration,
+    next_at: Option<NaiveDateTime>,
+}
+
+pub struct Scheduler {
+    schedules: Vec<Schedule>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+            .every(
+                JobKind::EvaluateAlertRules,
+                Duration::seconds(Self::ALERT_EVALUATION_INTERVAL),
+            )
+            .every(
+                JobKind::RetryWebhookDeliveries,
+                Duration::seconds(Self::WEBHOOK_RETRY_INTERVAL),
+            )
+            .every(
+                JobKind::SendDigestEmails,
+                Duration::seconds(Self::DIGEST_INTERVAL),
+            )
+    }
+}
+
+impl Scheduler {
+    pub const ALERT_EVALUATION_INTERVAL: i64 = 60; // seconds
+    pub const DIGEST_INTERVAL: i64 = 3600; // seconds
+    pub const TICK_INTERVAL: u64 = 5; // seconds
+    pub const WEBHOOK_RETRY_INTERVAL: i64 = 60; // seconds
+
+    pub fn new() -> Self {
+        Self { schedules: vec![] }
+    }
+
+    /// Registers a job kind which runs in every interval.
+    pub fn every(mut self, kind: JobKind, interval: Duration) -> Self {
+        self.schedules.push(Schedule {
+            kind,
+            interval,
+            next_at: None,
+        });
+        self
+    }
+
+    /// Returns the interval of the job kind.
+    pub fn interval(&self, kind: &JobKind) -> Option<Duration> {
+        self.schedules
+            .iter()
+            .find(|s| s.kind == *kind)
+            .map(|s| s.interval)
+    }
+
+    /// Returns jobs which are due at the time.
+    pub fn tick(&mut self, now: NaiveDateTime) -> Vec<Job<String>> {
+        let mut jobs = vec![];
+        for s in self.schedules.iter_mut() {
+            match s.next_at {
+                Some(t) if t > now => continue,
+                _ => {
+                    s.next_at = Some(now + s.interval);
+                    jobs.push(Job::<String> {
+                        kind: s.kind.clone(),
+                        args: vec![],
+                    });
+                },
+            }
+        }
+        jobs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{Utc, TimeZone};
+
+    #[test]
+    fn test_tick() {
+        let now = Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc();
+
+        let mut scheduler = Scheduler::new()
+            .every(JobKind::EvaluateAlertRules, Duration::minutes(1));
+
+        let jobs = scheduler.tick(now);
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].kind, JobKind::EvaluateAlertRules);
+
+        let jobs = scheduler.tick(now + Duration::seconds(59));
+        assert!(jobs.is_empty());
+
+        let jobs = scheduler.tick(now + Duration::seconds(60));
+        assert_eq!(jobs.len(), 1);
+
+        let jobs = scheduler.tick(now + Duration::seconds(90));
+        assert!(jobs.is_empty());
+    }
+
+    #[test]
+    fn test_interval() {
+        let scheduler = Scheduler::new()
+            .every(JobKind::EvaluateAlertRules, Duration::minutes(1));
+
+        assert_eq!(
+            scheduler.interval(&JobKind::EvaluateAlertRules),
+            Some(Duration::minutes(1))
+        );
+        assert_eq!(scheduler.interval(&JobKind::SendDigestEmails), None);
+    }
+
+    #[test]
+    fn test_lock_key() {
+        assert_eq!(
+            lock_key(&JobKind::SendDigestEmails),
+            "scheduler-SendDigestEmails"
+        );
+    }
+
+    #[test]
+    fn test_tick_without_schedule() {
+        let now = Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc();
+
+        let mut scheduler = Scheduler::new();
+        assert!(scheduler.tick(now).is_empty());
+    }
+}
@@ -5,8 +5,11 @@ table! {
         id -> Int8,
         uuid -> Uuid,
         name -> Varchar,
+        key -> Varchar,
         description -> Nullable<VarChar>,
         streams_count -> Integer,
+        ingestion_rate_limit -> Nullable<Integer>,
+        ingestion_daily_quota -> Nullable<Int8>,
         archived_at -> Nullable<Timestamp>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
@@ -21,6 +24,7 @@ table! {
         uuid -> Uuid,
         namespace_id -> Int8,
         name -> Varchar,
+        slug -> Varchar,
         description -> Nullable<VarChar>,
         archived_at -> Nullable<Timestamp>,
         created_at -> Timestamp,
@@ -130,17 +134,258 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::alert_rule::ELogLevel;
+
+    alert_rules (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        stream_id -> Int8,
+        name -> Varchar,
+        level -> ELogLevel,
+        threshold -> Integer,
+        window_minutes -> Integer,
+        cooldown_minutes -> Integer,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::alert::EAlertState;
+
+    alerts (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        alert_rule_id -> Int8,
+        state -> EAlertState,
+        messages_count -> Int8,
+        fired_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    webhooks (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        namespace_id -> Int8,
+        url -> Varchar,
+        secret -> Varchar,
+        events -> Array<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::webhook_delivery::EWebhookDeliveryState;
+
+    webhook_deliveries (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        webhook_id -> Int8,
+        event -> Varchar,
+        payload -> Text,
+        state -> EWebhookDeliveryState,
+        attempts -> Integer,
+        response_status -> Nullable<Integer>,
+        error_message -> Nullable<Varchar>,
+        next_attempt_at -> Nullable<Timestamp>,
+        delivered_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    use crate::model::digest_subscription::EDigestFrequency;
+
+    digest_subscriptions (id) {
+        id -> Int8,
+        user_id -> Int8,
+        namespace_id -> Int8,
+        frequency -> EDigestFrequency,
+        last_sent_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    namespace_usages (id) {
+        id -> Int8,
+        namespace_id -> Int8,
+        date -> Date,
+        messages_count -> Int8,
+        bytes_count -> Int8,
+        dropped_count -> Int8,
+        notified_percent -> Int2,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    use crate::model::redaction_rule::ERedactionDetector;
+
+    redaction_rules (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        namespace_id -> Int8,
+        name -> Varchar,
+        detector -> ERedactionDetector,
+        pattern -> Nullable<Text>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    namespace_key_redirects (id) {
+        id -> Int8,
+        namespace_id -> Int8,
+        key -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use diesel::pg::types::sql_types::Uuid;
+
+    use crate::model::membership::EMembershipRole;
+
+    invitations (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        namespace_id -> Int8,
+        inviter_id -> Int8,
+        email -> Varchar,
+        role -> EMembershipRole,
+        token -> Nullable<Varchar>,
+        token_granted_at -> Nullable<Timestamp>,
+        token_expires_at -> Nullable<Timestamp>,
+        accepted_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    use crate::model::stream_grant::ERole;
+
+    stream_grants (id) {
+        id -> Int8,
+        stream_id -> Int8,
+        user_id -> Int8,
+        role -> ERole,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    audit_events (id) {
+        id -> Int8,
+        uuid -> Uuid,
+        actor_id -> Nullable<Int8>,
+        action -> Varchar,
+        target -> Nullable<Varchar>,
+        namespace_id -> Nullable<Int8>,
+        ip_address -> Nullable<Varchar>,
+        user_agent -> Nullable<Varchar>,
+        diff -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+
+    user_two_factors (id) {
+        id -> Int8,
+        user_id -> Int8,
+        secret -> Varchar,
+        recovery_codes -> Array<Text>,
+        last_used_step -> Nullable<Int8>,
+        enabled_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 joinable!(user_emails -> users (user_id));
+joinable!(audit_events -> users (actor_id));
 joinable!(streams -> namespaces (namespace_id));
 joinable!(messages -> streams (stream_id));
 joinable!(memberships -> namespaces (namespace_id));
 joinable!(memberships -> users (user_id));
+joinable!(alert_rules -> streams (stream_id));
+joinable!(alerts -> alert_rules (alert_rule_id));
+joinable!(webhooks -> namespaces (namespace_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
+joinable!(digest_subscriptions -> users (user_id));
+joinable!(digest_subscriptions -> namespaces (namespace_id));
+joinable!(namespace_usages -> namespaces (namespace_id));
+joinable!(redaction_rules -> namespaces (namespace_id));
+joinable!(namespace_key_redirects -> namespaces (namespace_id));
+joinable!(invitations -> namespaces (namespace_id));
+joinable!(invitations -> users (inviter_id));
+joinable!(stream_grants -> streams (stream_id));
+joinable!(stream_grants -> users (user_id));
+joinable!(user_two_factors -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(users, access_tokens);
 allow_tables_to_appear_in_same_query!(users, memberships);
 allow_tables_to_appear_in_same_query!(users, user_emails);
+allow_tables_to_appear_in_same_query!(users, stream_grants);
+allow_tables_to_appear_in_same_query!(users, audit_events);
+allow_tables_to_appear_in_same_query!(users, user_two_factors);
 
 allow_tables_to_appear_in_same_query!(namespaces, memberships);
 allow_tables_to_appear_in_same_query!(namespaces, streams);
 
+allow_tables_to_appear_in_same_query!(streams, memberships);
 allow_tables_to_appear_in_same_query!(streams, messages);
+allow_tables_to_appear_in_same_query!(streams, stream_grants);
+
+allow_tables_to_appear_in_same_query!(alert_rules, streams);
+allow_tables_to_appear_in_same_query!(alert_rules, alerts);
+
+allow_tables_to_appear_in_same_query!(webhooks, namespaces);
+allow_tables_to_appear_in_same_query!(webhooks, webhook_deliveries);
+
+allow_tables_to_appear_in_same_query!(digest_subscriptions, users);
+allow_tables_to_appear_in_same_query!(digest_subscriptions, namespaces);
+
+allow_tables_to_appear_in_same_query!(namespace_usages, namespaces);
+
+allow_tables_to_appear_in_same_query!(redaction_rules, namespaces);
+
+allow_tables_to_appear_in_same_query!(namespace_key_redirects, namespaces);
+
+allow_tables_to_appear_in_same_query!(invitations, namespaces);
+allow_tables_to_appear_in_same_query!(invitations, users);
@@ -0,0 +1,272 @@
+use chrono::{Duration, NaiveDateTime};
+use diesel::PgConnection;
+
+use crate::logger::Logger;
+use crate::model::alert::{Alert, NewAlert};
+use crate::model::alert_rule::AlertRule;
+use crate::model::message::Message;
+use crate::util::Clock;
+
+/// What to do with a rule after counting its messages.
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Fire,
+    Resolve,
+    Keep,
+}
+
+/// Decides the next step of the rule.
+///
+/// * fires if messages exceed the threshold and there is no firing alert,
+///   unless the last one has been fired within the cooldown.
+/// * resolves the firing alert once messages get back to the threshold.
+pub fn decide(
+    rule: &AlertRule,
+    messages_count: i64,
+    last: Option<&Alert>,
+    now: NaiveDateTime,
+) -> Decision {
+    let exceeded = messages_count > i64::from(rule.threshold);
+    match last {
+        Some(a) if a.is_firing() => {
+            if exceeded {
+                Decision::Keep
+            } else {
+                Decision::Resolve
+            }
+        },
+        Some(a) if exceeded => {
+            let cooldown =
+                Duration::minutes(i64::from(rule.cooldown_minutes));
+            if now < a.fired_at + cooldown {
+                Decision::Keep
+            } else {
+                Decision::Fire
+            }
+        },
+        None if exceeded => Decision::Fire,
+        _ => Decision::Keep,
+    }
+}
+
+pub struct AlertEvaluator<'a, C: Clock> {
+    conn: &'a PgConnection,
+    clock: &'a C,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Clock> AlertEvaluator<'a, C> {
+    pub fn new(
+        conn: &'a PgConnection,
+        clock: &'a C,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            logger,
+        }
+    }
+
+    /// Evaluates the rule and returns the alert if it has been fired or
+    /// resolved.
+    pub fn evaluate(
+        &self,
+        rule: &AlertRule,
+    ) -> Result<Option<Alert>, &'static str> {
+        let now = self.clock.now();
+        let since = now - Duration::minutes(i64::from(rule.window_minutes));
+
+        let messages_count = Message::count_by_stream_id_since(
+            rule.stream_id,
+            rule.level.and_above(),
+            since,
+            self.conn,
+            self.logger,
+        )
+        .ok_or("failed to count messages")?;
+
+        let last = Alert::latest_by_rule(rule, self.conn, self.logger);
+        match decide(rule, messages_count, last.as_ref(), now) {
+            Decision::Fire => {
+                let a = NewAlert {
+                    alert_rule_id: rule.id,
+                    messages_count,
+                    fired_at: now,
+                };
+                info!(self.logger, "{} fired ({})", rule, messages_count);
+                Alert::insert(&a, self.conn, self.logger)
+                    .map(Some)
+                    .ok_or("failed to fire alert")
+            },
+            Decision::Resolve => {
+                info!(self.logger, "{} resolved", rule);
+                last.unwrap()
+                    .resolve(now, self.conn, self.logger)
+                    .map(Some)
+            },
+            Decision::Keep => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::Utc;
+    use diesel::prelude::*;
+
+    use crate::model::alert::AlertState;
+    use crate::model::alert_rule::alert_rules;
+    use crate::model::alert_rule::data::ALERT_RULES;
+    use crate::model::message::{
+        AgentType, LogFormat, LogLevel, NewMessage, messages,
+    };
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::stream::{Stream, streams};
+    use crate::model::stream::data::STREAMS;
+    use crate::model::test::run;
+    use crate::util::FixedClock;
+
+    fn build_alert(state: AlertState, fired_at: NaiveDateTime) -> Alert {
+        Alert {
+            id: 1,
+            uuid: uuid::Uuid::new_v4(),
+            alert_rule_id: 1,
+            state,
+            messages_count: 3,
+            fired_at,
+            resolved_at: None,
+            created_at: fired_at,
+            updated_at: fired_at,
+        }
+    }
+
+    #[test]
+    fn test_decide_fire() {
+        let rule = ALERT_RULES.get("too many errors").unwrap();
+        let now = Utc::now().naive_utc();
+
+        assert_eq!(decide(rule, 4, None, now), Decision::Fire);
+        assert_eq!(decide(rule, 3, None, now), Decision::Keep);
+    }
+
+    #[test]
+    fn test_decide_firing() {
+        let rule = ALERT_RULES.get("too many errors").unwrap();
+        let now = Utc::now().naive_utc();
+        let last = build_alert(AlertState::Firing, now - Duration::hours(2));
+
+        assert_eq!(decide(rule, 4, Some(&last), now), Decision::Keep);
+        assert_eq!(decide(rule, 3, Some(&last), now), Decision::Resolve);
+    }
+
+    #[test]
+    fn test_decide_cooldown() {
+        let rule = ALERT_RULES.get("too many errors").unwrap();
+        let now = Utc::now().naive_utc();
+
+        // 30 minutes
+        let fired_at = now - Duration::minutes(29);
+        let last = build_alert(AlertState::Resolved, fired_at);
+        assert_eq!(decide(rule, 4, Some(&last), now), Decision::Keep);
+
+        let fired_at = now - Duration::minutes(30);
+        let last = build_alert(AlertState::Resolved, fired_at);
+        assert_eq!(decide(rule, 4, Some(&last), now), Decision::Fire);
+        assert_eq!(decide(rule, 3, Some(&last), now), Decision::Keep);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        run(|conn, _, logger| {
+            let ns = NAMESPACES.get("piano").unwrap();
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+            s.namespace_id = namespace.id;
+            let stream = diesel::insert_into(streams::table)
+                .values(&s)
+                .get_result::<Stream>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let mut r = ALERT_RULES.get("too many errors").unwrap().clone();
+            r.stream_id = stream.id;
+            let rule = diesel::insert_into(alert_rules::table)
+                .values(&r)
+                .get_result::<AlertRule>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let levels = [
+                LogLevel::Warning,
+                LogLevel::Error,
+                LogLevel::Error,
+                LogLevel::Error,
+            ];
+            for level in &levels {
+                let m = NewMessage {
+                    agent_id: 1,
+                    agent_type: AgentType::Person,
+                    stream_id: stream.id,
+                    level: level.clone(),
+                    format: LogFormat::TOML,
+                    title: Some("title".to_string()),
+
+                    ..Default::default()
+                };
+                let _ = diesel::insert_into(messages::table)
+                    .values(&m)
+                    .execute(conn)
+                    .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+            }
+
+            let now = Utc::now().naive_utc();
+
+            // only 3 errors (warning is below the level)
+            let clock = FixedClock(now);
+            let evaluator = AlertEvaluator::new(conn, &clock, logger);
+            assert_eq!(evaluator.evaluate(&rule), Ok(None));
+
+            let m = NewMessage {
+                agent_id: 1,
+                stream_id: stream.id,
+                level: LogLevel::Critical,
+                title: Some("title".to_string()),
+
+                ..Default::default()
+            };
+            let _ = diesel::insert_into(messages::table)
+                .values(&m)
+                .execute(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let result = evaluator.evaluate(&rule);
+            assert!(result.is_ok());
+
+            let alert = result.unwrap().unwrap();
+            assert_eq!(alert.state, AlertState::Firing);
+            assert_eq!(alert.messages_count, 4);
+
+            // still firing
+            assert_eq!(evaluator.evaluate(&rule), Ok(None));
+
+            // messages are out of the window
+            let clock = FixedClock(now + Duration::minutes(10));
+            let evaluator = AlertEvaluator::new(conn, &clock, logger);
+            let result = evaluator.evaluate(&rule);
+            assert!(result.is_ok());
+
+            let resolved = result.unwrap().unwrap();
+            assert_eq!(resolved.id, alert.id);
+            assert_eq!(resolved.state, AlertState::Resolved);
+
+            // already resolved
+            assert_eq!(evaluator.evaluate(&rule), Ok(None));
+        })
+    }
+}
@@ -0,0 +1,275 @@
+//! AttemptLimiter protects an action (e.g. login) against brute-force attacks
+//! using attempt counters on Redis.
+//!
+//! Counters are kept per account and per IP address. After a few attempts,
+//! the next one must wait for a delay which grows exponentially (progressive
+//! delay), and the subject is locked out temporarily once the attempts reach
+//! the threshold. A counter is reset if no attempt is made within its window.
+use std::fmt;
+
+use chrono::Duration;
+use openssl::sha::sha256;
+use redis::{Commands, Script};
+
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::request::client::ClientInfo;
+use crate::util::Clock;
+
+const KEY_PREFIX: &str = "attempt";
+
+// KEYS: subject keys
+// ARGV[1]: now
+//
+// Returns seconds to wait until the next attempt (0 if it's allowed).
+const CHECK_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local wait = 0
+for _, key in ipairs(KEYS) do
+  local s = redis.call('HMGET', key, 'next_at', 'locked_until')
+  local next_at = tonumber(s[1]) or 0
+  local locked_until = tonumber(s[2]) or 0
+  wait = math.max(wait, next_at - now, locked_until - now)
+end
+return wait
+"#;
+
+// KEYS[1]: subject key
+// ARGV[1]: now, ARGV[2]: window, ARGV[3]: free attempts, ARGV[4]: max delay,
+// ARGV[5]: threshold, ARGV[6]: lockout duration (in seconds)
+//
+// Returns {count, delay, locked}.
+const RECORD_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local free = tonumber(ARGV[3])
+
+local count = redis.call('HINCRBY', KEYS[1], 'count', 1)
+local delay = 0
+if count > free then
+  delay = math.min(math.floor(2 ^ (count - free - 1)), tonumber(ARGV[4]))
+end
+redis.call('HSET', KEYS[1], 'next_at', now + delay)
+
+if count >= tonumber(ARGV[5]) then
+  local lockout = tonumber(ARGV[6])
+  redis.call('HSET', KEYS[1], 'count', 0, 'locked_until', now + lockout)
+  redis.call('EXPIRE', KEYS[1], lockout)
+  return {count, lockout, 1}
+end
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return {count, delay, 0}
+"#;
+
+/// Scope is an action protected by the limiter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    Login,
+    PasswordReset,
+    TwoFactor,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Login => write!(f, "login"),
+            Self::PasswordReset => write!(f, "password_reset"),
+            Self::TwoFactor => write!(f, "two_factor"),
+        }
+    }
+}
+
+/// Attempt is a state of the counter after recording an attempt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attempt {
+    pub count: i64,
+    // seconds until the next attempt can be accepted
+    pub delay: i64,
+    // true if the subject has been locked out by this attempt
+    pub locked: bool,
+}
+
+impl Attempt {
+    fn from_reply(reply: &[i64]) -> Option<Self> {
+        if reply.len() != 3 {
+            return None;
+        }
+        Some(Self {
+            count: reply[0],
+            delay: reply[1].max(0),
+            locked: reply[2] == 1,
+        })
+    }
+}
+
+/// Returns a counter key for the account (username or email address).
+///
+/// The identifier itself is not stored, and the key is made regardless of
+/// whether the account exists.
+pub fn account_key(scope: Scope, identifier: &str) -> String {
+    let digest = sha256(identifier.trim().to_lowercase().as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}:{}:account:{}", KEY_PREFIX, scope, hex)
+}
+
+/// Returns a counter key for the IP address.
+pub fn ip_key(scope: Scope, ip_address: &str) -> String {
+    format!("{}:{}:ip:{}", KEY_PREFIX, scope, ip_address)
+}
+
+fn keys(scope: Scope, identifier: &str, client: &ClientInfo) -> Vec<String> {
+    let mut keys = vec![account_key(scope, identifier)];
+    if let Some(ref ip_address) = client.ip_address {
+        keys.push(ip_key(scope, ip_address));
+    }
+    keys
+}
+
+pub struct AttemptLimiter<'a, C: Clock> {
+    conn: &'a mut redis::Connection,
+    clock: &'a C,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Clock> AttemptLimiter<'a, C> {
+    pub fn new(
+        conn: &'a mut redis::Connection,
+        clock: &'a C,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            logger,
+        }
+    }
+
+    /// Returns seconds to wait until the next attempt on the account from the
+    /// client is accepted (0 if it's accepted now).
+    pub fn check(
+        &mut self,
+        scope: Scope,
+        identifier: &str,
+        client: &ClientInfo,
+    ) -> Result<i64, &'static str> {
+        let script = Script::new(CHECK_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        for key in keys(scope, identifier, client) {
+            invocation.key(key);
+        }
+        let wait: i64 = invocation
+            .arg(self.clock.now().timestamp())
+            .invoke(self.conn)
+            .map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to check attempts"
+            })?;
+        Ok(wait.max(0))
+    }
+
+    /// Records an attempt on the account from the client. Both of them are
+    /// locked out once the attempts reach their threshold within the window.
+    ///
+    /// Returns the state of the account.
+    pub fn record(
+        &mut self,
+        scope: Scope,
+        identifier: &str,
+        client: &ClientInfo,
+    ) -> Result<Attempt, &'static str> {
+        if let Some(ref ip_address) = client.ip_address {
+            let key = ip_key(scope, ip_address);
+            self.record_to(&key, Config::IP_ATTEMPT_THRESHOLD)?;
+        }
+        let key = account_key(scope, identifier);
+        self.record_to(&key, Config::ACCOUNT_ATTEMPT_THRESHOLD)
+    }
+
+    fn record_to(
+        &mut self,
+        key: &str,
+        threshold: i64,
+    ) -> Result<Attempt, &'static str> {
+        let window = Duration::minutes(Config::ATTEMPT_WINDOW);
+        let lockout = Duration::minutes(Config::ATTEMPT_LOCKOUT_DURATION);
+        let reply: Vec<i64> = Script::new(RECORD_SCRIPT)
+            .key(key)
+            .arg(self.clock.now().timestamp())
+            .arg(window.num_seconds())
+            .arg(Config::ATTEMPT_FREE_COUNT)
+            .arg(Config::ATTEMPT_MAX_DELAY)
+            .arg(threshold)
+            .arg(lockout.num_seconds())
+            .invoke(self.conn)
+            .map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to record an attempt"
+            })?;
+
+        Attempt::from_reply(&reply).ok_or("unexpected reply")
+    }
+
+    /// Resets the counter (and the lockout) of the account. The one of the
+    /// client (IP address) is kept as it is.
+    pub fn reset(
+        &mut self,
+        scope: Scope,
+        identifier: &str,
+    ) -> Result<(), &'static str> {
+        self.conn.del(account_key(scope, identifier)).map_err(|e| {
+            error!(self.logger, "err: {}", e);
+            "failed to reset attempts"
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_reply() {
+        assert_eq!(
+            Attempt::from_reply(&[4, 2, 0]),
+            Some(Attempt {
+                count: 4,
+                delay: 2,
+                locked: false,
+            })
+        );
+        assert!(Attempt::from_reply(&[10, 1800, 1]).unwrap().locked);
+        assert_eq!(Attempt::from_reply(&[1, 0]), None);
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(
+            ip_key(Scope::Login, "127.0.0.1"),
+            "attempt:login:ip:127.0.0.1"
+        );
+
+        let key = account_key(Scope::PasswordReset, "Oswald@example.org ");
+        assert!(key.starts_with("attempt:password_reset:account:"));
+        assert!(!key.contains("oswald"));
+        assert_eq!(
+            key,
+            account_key(Scope::PasswordReset, "oswald@example.org")
+        );
+        assert_ne!(key, account_key(Scope::Login, "oswald@example.org"));
+
+        let client = ClientInfo {
+            ip_address: Some("127.0.0.1".to_string()),
+            user_agent: None,
+        };
+        assert_eq!(
+            keys(Scope::Login, "oswald@example.org", &client),
+            vec![
+                account_key(Scope::Login, "oswald@example.org"),
+                ip_key(Scope::Login, "127.0.0.1"),
+            ]
+        );
+        assert_eq!(
+            keys(Scope::Login, "oswald@example.org", &Default::default()),
+            vec![account_key(Scope::Login, "oswald@example.org")]
+        );
+    }
+}
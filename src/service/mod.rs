@@ -1,2 +1,10 @@
 pub mod account_activator;
+pub mod alert_evaluator;
+pub mod attempt_limiter;
 pub mod password_updater;
+pub mod rate_limiter;
+pub mod redactor;
+pub mod session_manager;
+pub mod totp;
+pub mod usage_meter;
+pub mod webhook_sender;
@@ -52,7 +52,12 @@ where T: Authenticatable + Clone + Verifiable<T> + fmt::Display
     pub fn update(&self, new_password: &str) -> Result<(), &str> {
         if let Some(mut user) = self.target.clone() {
             return user
-                .update_password(new_password, self.db_conn, self.logger)
+                .update_password(
+                    new_password,
+                    self.config,
+                    self.db_conn,
+                    self.logger,
+                )
                 .map(|_| {
                     info!(
                         self.logger,
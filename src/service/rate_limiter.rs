@@ -0,0 +1,262 @@
+//! RateLimiter limits requests using token buckets on Redis.
+//!
+//! A bucket holds `limit` tokens at most and it's refilled at the rate of
+//! `limit` tokens per minute. Each request takes a token from the bucket,
+//! and it will be rejected if the bucket is empty.
+//!
+//! Buckets are updated atomically by a Lua script, so that it works across
+//! server processes.
+use openssl::sha::sha256;
+use redis::Script;
+
+use crate::logger::Logger;
+use crate::util::Clock;
+
+const KEY_PREFIX: &str = "rate_limit";
+
+// KEYS[1..n]: bucket keys
+// ARGV[1]: now in ms, ARGV[2i], ARGV[2i + 1]: capacity and tokens per
+// millisecond of KEYS[i]
+//
+// A token is taken from every bucket only if all of them have one, so a
+// rejected request doesn't drain the others.
+//
+// Returns {allowed, remaining, retry after in ms, reset after in ms} for each
+// bucket (flattened).
+const TAKE_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+
+local buckets = {}
+local allowed = 1
+for i, key in ipairs(KEYS) do
+  local capacity = tonumber(ARGV[i * 2])
+  local rate = tonumber(ARGV[i * 2 + 1])
+
+  local bucket = redis.call('HMGET', key, 'tokens', 'ts')
+  local tokens = tonumber(bucket[1]) or capacity
+  local ts = tonumber(bucket[2]) or now
+
+  tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate)
+  if tokens < 1 then
+    allowed = 0
+  end
+  buckets[i] = {capacity, rate, tokens}
+end
+
+local reply = {}
+for i, key in ipairs(KEYS) do
+  local capacity, rate, tokens = unpack(buckets[i])
+
+  local ok = 1
+  local retry_after = 0
+  if allowed == 1 then
+    tokens = tokens - 1
+  elseif tokens < 1 then
+    ok = 0
+    retry_after = math.ceil((1 - tokens) / rate)
+  end
+
+  redis.call('HMSET', key, 'tokens', tostring(tokens), 'ts', now)
+  redis.call('PEXPIRE', key, math.ceil(capacity / rate))
+
+  local reset_after = math.ceil((capacity - tokens) / rate)
+  for _, v in ipairs({ok, math.floor(tokens), retry_after, reset_after}) do
+    table.insert(reply, v)
+  end
+end
+return reply
+"#;
+
+/// RateLimit is a state of a bucket after taking a token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub limit: i64,
+    pub remaining: i64,
+    // seconds until the bucket is full again
+    pub reset: i64,
+    // seconds until the next request can be accepted (if rejected)
+    pub retry_after: Option<i64>,
+}
+
+impl RateLimit {
+    fn from_reply(limit: i64, reply: &[i64]) -> Option<Self> {
+        if reply.len() != 4 {
+            return None;
+        }
+        let retry_after = if reply[0] == 1 {
+            None
+        } else {
+            Some(to_seconds(reply[2]).max(1))
+        };
+        Some(Self {
+            limit,
+            remaining: reply[1].max(0),
+            reset: to_seconds(reply[3]),
+            retry_after,
+        })
+    }
+
+    pub fn is_exceeded(&self) -> bool {
+        self.retry_after.is_some()
+    }
+
+    /// Returns values for `X-RateLimit-*` (and `Retry-After`) headers.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("X-RateLimit-Limit", self.limit.to_string()),
+            ("X-RateLimit-Remaining", self.remaining.to_string()),
+            ("X-RateLimit-Reset", self.reset.to_string()),
+        ];
+        if let Some(retry_after) = self.retry_after {
+            headers.push(("Retry-After", retry_after.to_string()));
+        }
+        headers
+    }
+}
+
+fn to_seconds(ms: i64) -> i64 {
+    (ms + 999) / 1000
+}
+
+/// Returns a bucket key for the access token.
+///
+/// The token itself is not stored.
+pub fn token_key(token: &str) -> String {
+    let digest = sha256(token.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}:token:{}", KEY_PREFIX, hex)
+}
+
+/// Returns a bucket key for the browser session, which outlives its tokens.
+pub fn session_key(session_id: &str) -> String {
+    let digest = sha256(session_id.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}:session:{}", KEY_PREFIX, hex)
+}
+
+/// Returns a bucket key for the namespace.
+pub fn namespace_key(namespace_id: i64) -> String {
+    format!("{}:namespace:{}", KEY_PREFIX, namespace_id)
+}
+
+pub struct RateLimiter<'a, C: Clock> {
+    conn: &'a mut redis::Connection,
+    clock: &'a C,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Clock> RateLimiter<'a, C> {
+    pub fn new(
+        conn: &'a mut redis::Connection,
+        clock: &'a C,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            logger,
+        }
+    }
+
+    /// Takes a token from the bucket which allows `limit` requests per
+    /// minute.
+    pub fn take(
+        &mut self,
+        key: &str,
+        limit: i32,
+    ) -> Result<RateLimit, &'static str> {
+        self.take_all(&[(key, limit)]).map(|mut v| v.remove(0))
+    }
+
+    /// Takes a token from each bucket at once. Nothing is taken if any of
+    /// them is empty, and the limits are returned in the same order.
+    pub fn take_all(
+        &mut self,
+        buckets: &[(&str, i32)],
+    ) -> Result<Vec<RateLimit>, &'static str> {
+        if buckets.is_empty() || buckets.iter().any(|(_, l)| *l < 1) {
+            return Err("invalid limit");
+        }
+
+        let now = self.clock.now().timestamp_millis();
+
+        let script = Script::new(TAKE_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        for (key, _) in buckets {
+            invocation.key(*key);
+        }
+        invocation.arg(now);
+        for (_, limit) in buckets {
+            let capacity = i64::from(*limit);
+            let rate = capacity as f64 / 60_000.0; // per millisecond
+            invocation.arg(capacity).arg(rate);
+        }
+
+        let reply: Vec<i64> = invocation.invoke(self.conn).map_err(|e| {
+            error!(self.logger, "err: {}", e);
+            "failed to take a token"
+        })?;
+        if reply.len() != buckets.len() * 4 {
+            return Err("unexpected reply");
+        }
+
+        buckets
+            .iter()
+            .zip(reply.chunks(4))
+            .map(|((_, limit), r)| {
+                RateLimit::from_reply(i64::from(*limit), r)
+                    .ok_or("unexpected reply")
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_reply_allowed() {
+        let r = RateLimit::from_reply(60, &[1, 59, 0, 1000]).unwrap();
+        assert!(!r.is_exceeded());
+        assert_eq!(
+            r.headers(),
+            vec![
+                ("X-RateLimit-Limit", "60".to_string()),
+                ("X-RateLimit-Remaining", "59".to_string()),
+                ("X-RateLimit-Reset", "1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_reply_rejected() {
+        let r = RateLimit::from_reply(60, &[0, 0, 250, 60000]).unwrap();
+        assert!(r.is_exceeded());
+        assert_eq!(r.retry_after, Some(1));
+        assert_eq!(r.reset, 60);
+        assert_eq!(
+            r.headers().last(),
+            Some(&("Retry-After", "1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_reply_unexpected() {
+        assert_eq!(RateLimit::from_reply(60, &[1, 59]), None);
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(namespace_key(1), "rate_limit:namespace:1");
+
+        let key = token_key("token");
+        assert!(key.starts_with("rate_limit:token:"));
+        assert!(!key.contains("token:token"));
+        assert_eq!(key.len(), "rate_limit:token:".len() + 64);
+
+        let key = session_key("session");
+        assert!(key.starts_with("rate_limit:session:"));
+        assert_eq!(key.len(), "rate_limit:session:".len() + 64);
+    }
+}
@@ -0,0 +1,313 @@
+//! Redaction of personal data in messages.
+//!
+//! Rules of the namespace are applied in order to `title`, `content` and
+//! `code` of a message before it's saved. The content is rewritten as text,
+//! so that values in structured formats (TOML, JSON) are masked as well.
+//!
+//! Each match is replaced with a labelled placeholder like
+//! `[REDACTED:email]`. A placeholder may be longer than the value, so the
+//! redacted field is truncated to the maximum length of it. Other fields
+//! (agent, lang, level and format) aren't free text, and they are validated
+//! against known values.
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::logger::Logger;
+use crate::model::message::NewMessage;
+use crate::model::redaction_rule::{RedactionDetector, RedactionRule};
+
+// a limit for compiled custom patterns (bytes)
+const SIZE_LIMIT: usize = 1 << 20;
+
+// maximum lengths of fields (see validation/message.rs)
+const CODE_MAX_LENGTH: usize = 32;
+const CONTENT_MAX_LENGTH: usize = 8000;
+const TITLE_MAX_LENGTH: usize = 255;
+
+/// Compiles the pattern of a custom rule.
+pub fn compile(pattern: &str) -> Result<Regex, &'static str> {
+    RegexBuilder::new(pattern)
+        .size_limit(SIZE_LIMIT)
+        .build()
+        .map_err(|_| "invalid pattern")
+}
+
+/// Checks digits (with separators) by the Luhn algorithm.
+pub fn luhn(s: &str) -> bool {
+    let digits: Vec<u32> = s.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() < 13 {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| {
+            if i % 2 == 1 {
+                let n = d * 2;
+                if n > 9 {
+                    n - 9
+                } else {
+                    n
+                }
+            } else {
+                *d
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+/// Truncates the text to the number of characters.
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+/// Redaction counts a label has matched.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Redaction {
+    pub label: String,
+    pub count: usize,
+}
+
+struct Matcher {
+    label: String,
+    detector: RedactionDetector,
+    regex: Regex,
+}
+
+pub struct Redactor {
+    matchers: Vec<Matcher>,
+}
+
+impl Redactor {
+    /// Builds a redactor from rules.
+    ///
+    /// A rule which has an invalid pattern is skipped.
+    pub fn new(rules: &[RedactionRule], logger: &Logger) -> Self {
+        let matchers = rules
+            .iter()
+            .filter_map(|r| {
+                let pattern = match r.detector.pattern() {
+                    Some(p) => p,
+                    None => r.pattern.as_deref()?,
+                };
+                match compile(pattern) {
+                    Ok(regex) => {
+                        Some(Matcher {
+                            label: r.label(),
+                            detector: r.detector.clone(),
+                            regex,
+                        })
+                    },
+                    Err(e) => {
+                        error!(logger, "err: {} {}", r, e);
+                        None
+                    },
+                }
+            })
+            .collect();
+        Self { matchers }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    /// Returns the redacted text and counts of matches for each label.
+    pub fn redact(&self, text: &str) -> (String, Vec<Redaction>) {
+        let mut result = text.to_string();
+        let mut redactions: Vec<Redaction> = vec![];
+        for m in &self.matchers {
+            let mut count = 0;
+            let placeholder = format!("[REDACTED:{}]", m.label);
+            result = m
+                .regex
+                .replace_all(&result, |caps: &regex::Captures| {
+                    let v = &caps[0];
+                    if m.detector == RedactionDetector::CreditCard && !luhn(v)
+                    {
+                        return v.to_string();
+                    }
+                    count += 1;
+                    placeholder.clone()
+                })
+                .into_owned();
+
+            if count < 1 {
+                continue;
+            }
+            match redactions.iter_mut().find(|r| r.label == m.label) {
+                Some(r) => r.count += count,
+                None => {
+                    redactions.push(Redaction {
+                        label: m.label.to_string(),
+                        count,
+                    })
+                },
+            }
+        }
+        (result, redactions)
+    }
+
+    /// Rewrites fields of the message, and returns the number of matches.
+    pub fn apply(&self, message: &mut NewMessage) -> usize {
+        let mut count = 0;
+        for (field, max) in [
+            (&mut message.title, TITLE_MAX_LENGTH),
+            (&mut message.content, CONTENT_MAX_LENGTH),
+            (&mut message.code, CODE_MAX_LENGTH),
+        ] {
+            if let Some(v) = field.as_ref() {
+                let (text, redactions) = self.redact(v);
+                if !redactions.is_empty() {
+                    count += redactions.iter().map(|r| r.count).sum::<usize>();
+                    *field = Some(truncate(&text, max));
+                }
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::logger::get_logger;
+    use crate::model::redaction_rule::data::REDACTION_RULES;
+    use crate::model::test::CONFIG;
+
+    fn build_rule(detector: RedactionDetector) -> RedactionRule {
+        let mut rule =
+            REDACTION_RULES.get("piano's email rule").unwrap().clone();
+        rule.detector = detector;
+        rule
+    }
+
+    #[test]
+    fn test_luhn() {
+        assert!(luhn("4242424242424242"));
+        assert!(luhn("4242 4242 4242 4242"));
+        assert!(luhn("5555-5555-5555-4444"));
+        assert!(!luhn("4242424242424241"));
+        assert!(!luhn("1234"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("", 3), "");
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abcd", 3), "abc");
+        assert_eq!(truncate("日本語です", 3), "日本語");
+    }
+
+    #[test]
+    fn test_compile() {
+        assert!(compile(r"sid=[0-9a-f]+").is_ok());
+        assert!(compile(r"sid=[0-9a-f+").is_err());
+    }
+
+    #[test]
+    fn test_redact_email() {
+        let logger = get_logger(&CONFIG);
+        let redactor =
+            Redactor::new(&[build_rule(RedactionDetector::Email)], &logger);
+
+        let (text, redactions) =
+            redactor.redact("from oswald@example.org to piano@example.com");
+        assert_eq!(text, "from [REDACTED:email] to [REDACTED:email]");
+        assert_eq!(
+            redactions,
+            vec![Redaction {
+                label: "email".to_string(),
+                count: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_redact_credit_card() {
+        let logger = get_logger(&CONFIG);
+        let redactor = Redactor::new(
+            &[build_rule(RedactionDetector::CreditCard)],
+            &logger,
+        );
+
+        let (text, _) =
+            redactor.redact("card: 4242 4242 4242 4242, order: 1234567890123");
+        assert_eq!(text, "card: [REDACTED:credit_card], order: 1234567890123");
+    }
+
+    #[test]
+    fn test_redact_jwt_and_bearer_token() {
+        let logger = get_logger(&CONFIG);
+        let redactor = Redactor::new(
+            &[
+                build_rule(RedactionDetector::Jwt),
+                build_rule(RedactionDetector::BearerToken),
+            ],
+            &logger,
+        );
+
+        let (text, _) = redactor.redact("token=eyJhbGciOi.eyJzdWIiOi.c2lnbmF0");
+        assert_eq!(text, "token=[REDACTED:jwt]");
+
+        let (text, _) = redactor.redact("Authorization: Bearer abc.def-123");
+        assert_eq!(text, "Authorization: [REDACTED:bearer_token]");
+    }
+
+    #[test]
+    fn test_redact_custom() {
+        let logger = get_logger(&CONFIG);
+        let rule = REDACTION_RULES.get("piano's session rule").unwrap();
+        let redactor = Redactor::new(&[rule.clone()], &logger);
+
+        let (text, redactions) = redactor.redact("GET /?sid=deadbeef");
+        assert_eq!(text, "GET /?[REDACTED:session]");
+        assert_eq!(redactions[0].label, "session");
+
+        // invalid pattern is skipped
+        let mut rule = rule.clone();
+        rule.pattern = Some("sid=[".to_string());
+        let redactor = Redactor::new(&[rule], &logger);
+        assert!(redactor.is_empty());
+    }
+
+    #[test]
+    fn test_apply() {
+        let logger = get_logger(&CONFIG);
+        let redactor =
+            Redactor::new(&[build_rule(RedactionDetector::Email)], &logger);
+
+        let mut m = NewMessage {
+            title: Some("Signup by oswald@example.org".to_string()),
+            content: Some("email = \"oswald@example.org\"".to_string()),
+            code: None,
+            ..Default::default()
+        };
+        assert_eq!(redactor.apply(&mut m), 2);
+        assert_eq!(m.title, Some("Signup by [REDACTED:email]".to_string()));
+        assert_eq!(m.content, Some("email = \"[REDACTED:email]\"".to_string()));
+        assert_eq!(m.code, None);
+    }
+
+    #[test]
+    fn test_apply_truncates() {
+        let logger = get_logger(&CONFIG);
+        let redactor =
+            Redactor::new(&[build_rule(RedactionDetector::Email)], &logger);
+
+        let mut m = NewMessage {
+            title: Some("Signup".to_string()),
+            code: Some("a@b.co a@b.co a@b.co".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(redactor.apply(&mut m), 3);
+        assert_eq!(
+            m.code,
+            Some("[REDACTED:email] [REDACTED:email".to_string())
+        );
+        assert_eq!(m.title, Some("Signup".to_string()));
+    }
+}
@@ -0,0 +1,461 @@
+//! SessionManager manages browser sessions on the session store.
+//!
+//! A session is started at login and holds the current refresh token, which
+//! is rotated on every refresh. If a refresh token which has already been
+//! used is presented, the session is destroyed (reuse detection).
+//!
+//! The session expires if it's not refreshed within the idle timeout ("Are
+//! you there?"), or after its maximum lifetime. An authentication token is
+//! bound to the session by its id (`jti`), so it's no longer available once
+//! the session is destroyed (e.g. logout).
+use std::collections::HashMap;
+
+use chrono::Duration;
+use redis::{Commands, Script};
+use rocket::http::Cookies;
+
+use crate::config::Config;
+use crate::logger::Logger;
+use crate::model::token::{
+    AuthenticationClaims, BrowserCookieTokenClaims, Claims, TokenData,
+};
+use crate::model::user::User;
+use crate::request::client::ClientInfo;
+use crate::ss::user_sessions_key;
+use crate::util::{
+    Clock, generate_random_hash, make_cookie, make_refresh_cookie, split_token,
+};
+
+const KEY_PREFIX: &str = "as";
+
+// KEYS[1]: session key
+// ARGV[1]: presented refresh token, ARGV[2]: new refresh token,
+// ARGV[3]: now, ARGV[4]: max lifetime, ARGV[5]: idle timeout (in seconds)
+//
+// Returns {status, user_id, created_at}. The status is 1 (rotated), 0 (not
+// found), -1 (reused) or -2 (expired).
+const ROTATE_SCRIPT: &str = r#"
+local s = redis.call('HMGET', KEYS[1], 'user_id', 'refresh_token', 'created_at')
+if not s[1] then
+  return {0, 0, 0}
+end
+
+local user_id = tonumber(s[1])
+local created_at = tonumber(s[3])
+if s[2] ~= ARGV[1] then
+  redis.call('DEL', KEYS[1])
+  return {-1, user_id, created_at}
+end
+if created_at + tonumber(ARGV[4]) < tonumber(ARGV[3]) then
+  redis.call('DEL', KEYS[1])
+  return {-2, user_id, created_at}
+end
+
+redis.call('HSET', KEYS[1], 'refresh_token', ARGV[2])
+redis.call('EXPIRE', KEYS[1], ARGV[5])
+return {1, user_id, created_at}
+"#;
+
+// KEYS[1]: session key
+// ARGV[1]: now, ARGV[2]: ip address
+//
+// Returns 1 if the session exists, otherwise 0.
+const TOUCH_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+  return 0
+end
+redis.call('HSET', KEYS[1], 'last_seen_at', ARGV[1], 'ip_address', ARGV[2])
+return 1
+"#;
+
+/// Session is a browser session of the user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub user_id: i64,
+    pub refresh_token: String,
+    // timestamp
+    pub created_at: i64,
+}
+
+impl Session {
+    /// Returns a value for the refresh cookie.
+    pub fn to_cookie_value(&self) -> String {
+        format!("{}.{}", self.id, self.refresh_token)
+    }
+
+    /// Parses a value of the refresh cookie into a pair of the session id and
+    /// the refresh token.
+    pub fn parse_cookie_value(value: &str) -> Option<(String, String)> {
+        let parts: Vec<&str> = value.split('.').collect();
+        if parts.len() != 2 || parts.iter().any(|p| p.is_empty()) {
+            return None;
+        }
+        Some((parts[0].to_string(), parts[1].to_string()))
+    }
+}
+
+/// SessionInfo is a summary of the session (for listing).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SessionInfo {
+    pub id: String,
+    pub user_agent: String,
+    pub ip_address: String,
+    // timestamp
+    pub created_at: i64,
+    pub last_seen_at: i64,
+}
+
+impl SessionInfo {
+    fn from_hash(id: &str, h: &HashMap<String, String>) -> Option<Self> {
+        let get = |k: &str| h.get(k).cloned().unwrap_or_default();
+        let created_at = get("created_at").parse::<i64>().ok()?;
+        Some(Self {
+            id: id.to_string(),
+            user_agent: get("user_agent"),
+            ip_address: get("ip_address"),
+            created_at,
+            last_seen_at: get("last_seen_at")
+                .parse::<i64>()
+                .unwrap_or(created_at),
+        })
+    }
+}
+
+/// Returns the key of the session.
+pub fn session_key(id: &str) -> String {
+    format!("{}-{}", KEY_PREFIX, id)
+}
+
+/// Returns the session id (`jti`) of the browser token.
+pub fn session_id_of(token: &str, config: &Config) -> Option<String> {
+    BrowserCookieTokenClaims::decode(
+        token,
+        &config.authentication_token_issuer,
+        &config.authentication_token_secret,
+    )
+    .ok()
+    .and_then(|c| c.jti)
+}
+
+fn generate_hash() -> String {
+    generate_random_hash(Config::CSRF_HASH_SOURCE, Config::SESSION_HASH_LENGTH)
+}
+
+/// Issues a (short-lived) authentication token for the session, and sets its
+/// signature and the refresh token as private cookies. Returns the token
+/// without signature.
+pub fn issue_token(
+    user: &User,
+    session: &Session,
+    granted_at: i64,
+    config: &Config,
+    cookies: &mut Cookies,
+) -> Option<String> {
+    let duration = Duration::minutes(Config::AUTHENTICATION_TOKEN_DURATION);
+    let data = TokenData {
+        value: user.uuid.to_urn().to_string(),
+        granted_at,
+        expires_at: granted_at + duration.num_seconds(),
+    };
+    let authentication_token = AuthenticationClaims::encode_for_session(
+        data,
+        &session.id,
+        &config.authentication_token_issuer,
+        &config.authentication_token_key_id,
+        &config.authentication_token_secret,
+    );
+    let (token, sign) = split_token(authentication_token)?;
+
+    cookies.add_private(make_cookie(sign, config));
+    cookies.add_private(make_refresh_cookie(session.to_cookie_value(), config));
+    Some(token)
+}
+
+pub struct SessionManager<'a, C: Clock> {
+    conn: &'a mut redis::Connection,
+    clock: &'a C,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Clock> SessionManager<'a, C> {
+    pub fn new(
+        conn: &'a mut redis::Connection,
+        clock: &'a C,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            logger,
+        }
+    }
+
+    /// Starts a new session for the user on the client.
+    pub fn start(
+        &mut self,
+        user_id: i64,
+        client: &ClientInfo,
+    ) -> Result<Session, &'static str> {
+        let session = Session {
+            id: generate_hash(),
+            user_id,
+            refresh_token: generate_hash(),
+            created_at: self.clock.now().timestamp(),
+        };
+
+        let key = session_key(&session.id);
+        let idle_timeout = Duration::minutes(Config::SESSION_IDLE_TIMEOUT);
+        let duration = Duration::days(Config::SESSION_DURATION);
+        let sessions_key = user_sessions_key(user_id);
+        redis::pipe()
+            .atomic()
+            .hset_multiple(
+                &key,
+                &[
+                    ("user_id", user_id.to_string()),
+                    ("refresh_token", session.refresh_token.to_string()),
+                    ("created_at", session.created_at.to_string()),
+                    ("last_seen_at", session.created_at.to_string()),
+                    (
+                        "user_agent",
+                        client.user_agent.clone().unwrap_or_default(),
+                    ),
+                    (
+                        "ip_address",
+                        client.ip_address.clone().unwrap_or_default(),
+                    ),
+                ],
+            )
+            .ignore()
+            .expire(&key, idle_timeout.num_seconds() as usize)
+            .ignore()
+            .sadd(&sessions_key, &key)
+            .ignore()
+            .expire(&sessions_key, duration.num_seconds() as usize)
+            .ignore()
+            .query::<()>(self.conn)
+            .map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to start session"
+            })?;
+        Ok(session)
+    }
+
+    /// Rotates the refresh token of the session, and extends its idle
+    /// timeout.
+    pub fn rotate(
+        &mut self,
+        id: &str,
+        refresh_token: &str,
+    ) -> Result<Session, &'static str> {
+        let new_refresh_token = generate_hash();
+        let now = self.clock.now().timestamp();
+        let duration = Duration::days(Config::SESSION_DURATION);
+        let idle_timeout = Duration::minutes(Config::SESSION_IDLE_TIMEOUT);
+
+        let reply: Vec<i64> = Script::new(ROTATE_SCRIPT)
+            .key(session_key(id))
+            .arg(refresh_token)
+            .arg(&new_refresh_token)
+            .arg(now)
+            .arg(duration.num_seconds())
+            .arg(idle_timeout.num_seconds())
+            .invoke(self.conn)
+            .map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to rotate refresh token"
+            })?;
+
+        match reply.as_slice() {
+            [1, user_id, created_at] => {
+                Ok(Session {
+                    id: id.to_string(),
+                    user_id: *user_id,
+                    refresh_token: new_refresh_token,
+                    created_at: *created_at,
+                })
+            },
+            [-1, user_id, _] => {
+                warn!(
+                    self.logger,
+                    "refresh token has been reused: user_id {}", user_id
+                );
+                Err("refresh token has been reused")
+            },
+            [-2, ..] => Err("session has been expired"),
+            _ => Err("session not found"),
+        }
+    }
+
+    /// Records the access to the session. Returns false if the session
+    /// doesn't exist (expired or destroyed).
+    pub fn touch(
+        &mut self,
+        id: &str,
+        client: &ClientInfo,
+    ) -> Result<bool, &'static str> {
+        let reply: i64 = Script::new(TOUCH_SCRIPT)
+            .key(session_key(id))
+            .arg(self.clock.now().timestamp())
+            .arg(client.ip_address.clone().unwrap_or_default())
+            .invoke(self.conn)
+            .map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to touch session"
+            })?;
+        Ok(reply == 1)
+    }
+
+    /// Returns the active sessions of the user, the latest one first.
+    pub fn list(
+        &mut self,
+        user_id: i64,
+    ) -> Result<Vec<SessionInfo>, &'static str> {
+        let sessions_key = user_sessions_key(user_id);
+        let members: Vec<String> =
+            self.conn.smembers(&sessions_key).map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to load sessions"
+            })?;
+
+        let prefix = session_key("");
+        let mut sessions = vec![];
+        for key in members.iter().filter(|k| k.starts_with(&prefix)) {
+            let h: HashMap<String, String> =
+                self.conn.hgetall(key).map_err(|e| {
+                    error!(self.logger, "err: {}", e);
+                    "failed to load session"
+                })?;
+            match SessionInfo::from_hash(&key[prefix.len()..], &h) {
+                Some(s) => sessions.push(s),
+                None => {
+                    // expired
+                    let _: Result<i64, _> = self.conn.srem(&sessions_key, key);
+                },
+            }
+        }
+        sessions.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at));
+        Ok(sessions)
+    }
+
+    /// Destroys the session.
+    pub fn finish(&mut self, id: &str) -> Result<(), &'static str> {
+        self.conn.del(session_key(id)).map_err(|e| {
+            error!(self.logger, "err: {}", e);
+            "failed to finish session"
+        })
+    }
+
+    /// Destroys the session of the user. Returns false if the user has no
+    /// such session.
+    pub fn finish_of(
+        &mut self,
+        user_id: i64,
+        id: &str,
+    ) -> Result<bool, &'static str> {
+        let sessions_key = user_sessions_key(user_id);
+        let key = session_key(id);
+        let removed: i64 = self.conn.srem(&sessions_key, &key).map_err(|e| {
+            error!(self.logger, "err: {}", e);
+            "failed to load sessions"
+        })?;
+        if removed == 0 {
+            return Ok(false);
+        }
+        self.finish(id).map(|_| true)
+    }
+
+    /// Destroys all the sessions of the user except the given one.
+    pub fn finish_all(
+        &mut self,
+        user_id: i64,
+        except: Option<&str>,
+    ) -> Result<(), &'static str> {
+        let sessions_key = user_sessions_key(user_id);
+        let members: Vec<String> =
+            self.conn.smembers(&sessions_key).map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to load sessions"
+            })?;
+
+        let prefix = session_key("");
+        let except = except.map(session_key);
+        let keys: Vec<String> = members
+            .into_iter()
+            .filter(|k| k.starts_with(&prefix) && Some(k) != except.as_ref())
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        redis::pipe()
+            .atomic()
+            .del(&keys)
+            .ignore()
+            .srem(&sessions_key, &keys)
+            .ignore()
+            .query::<()>(self.conn)
+            .map_err(|e| {
+                error!(self.logger, "err: {}", e);
+                "failed to finish sessions"
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_session_cookie_value() {
+        let session = Session {
+            id: "123".to_string(),
+            user_id: 1,
+            refresh_token: "456".to_string(),
+            created_at: 0,
+        };
+        let value = session.to_cookie_value();
+        assert_eq!(value, "123.456");
+        assert_eq!(
+            Session::parse_cookie_value(&value),
+            Some(("123".to_string(), "456".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_cookie_value_invalid() {
+        assert_eq!(Session::parse_cookie_value(""), None);
+        assert_eq!(Session::parse_cookie_value("123"), None);
+        assert_eq!(Session::parse_cookie_value("123."), None);
+        assert_eq!(Session::parse_cookie_value(".456"), None);
+        assert_eq!(Session::parse_cookie_value("1.2.3"), None);
+    }
+
+    #[test]
+    fn test_session_info_from_hash() {
+        let mut h = HashMap::new();
+        assert_eq!(SessionInfo::from_hash("123", &h), None);
+
+        h.insert("created_at".to_string(), "1".to_string());
+        h.insert("user_agent".to_string(), "Firefox".to_string());
+        assert_eq!(
+            SessionInfo::from_hash("123", &h),
+            Some(SessionInfo {
+                id: "123".to_string(),
+                user_agent: "Firefox".to_string(),
+                ip_address: "".to_string(),
+                created_at: 1,
+                last_seen_at: 1,
+            })
+        );
+
+        h.insert("last_seen_at".to_string(), "2".to_string());
+        assert_eq!(SessionInfo::from_hash("123", &h).unwrap().last_seen_at, 2);
+    }
+
+    #[test]
+    fn test_session_key() {
+        assert_eq!(session_key("123"), "as-123");
+    }
+}
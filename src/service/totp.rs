@@ -0,0 +1,367 @@
+//! TOTP (RFC 6238) for two-factor authentication.
+//!
+//! A code is HOTP (RFC 4226) of the time step (30 seconds), and it's accepted
+//! within a window of a step before and after the current one, to allow clock
+//! drift of the authenticator app. The secret is shown to the user as Base32
+//! (RFC 4648) in a provisioning URI, and it's stored encrypted with AES-GCM.
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use openssl::sign::Signer;
+use openssl::symm::{Cipher, decrypt_aead, encrypt_aead};
+
+pub const DIGITS: u32 = 6;
+pub const PERIOD: i64 = 30;
+pub const SECRET_LENGTH: usize = 20;
+// steps accepted before and after the current one
+const WINDOW: i64 = 1;
+
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn digest(self) -> MessageDigest {
+        match self {
+            Self::Sha1 => MessageDigest::sha1(),
+            Self::Sha256 => MessageDigest::sha256(),
+            Self::Sha512 => MessageDigest::sha512(),
+        }
+    }
+}
+
+/// Returns a HOTP value of the counter (RFC 4226).
+pub fn hotp(
+    secret: &[u8],
+    counter: u64,
+    digits: u32,
+    algorithm: Algorithm,
+) -> Option<String> {
+    let key = PKey::hmac(secret).ok()?;
+    let mut signer = Signer::new(algorithm.digest(), &key).ok()?;
+    signer.update(&counter.to_be_bytes()).ok()?;
+    let hmac = signer.sign_to_vec().ok()?;
+
+    // dynamic truncation
+    let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+    let binary = (u32::from(hmac[offset] & 0x7f) << 24) |
+        (u32::from(hmac[offset + 1]) << 16) |
+        (u32::from(hmac[offset + 2]) << 8) |
+        u32::from(hmac[offset + 3]);
+    let value = u64::from(binary) % 10_u64.pow(digits);
+    Some(format!("{:0width$}", value, width = digits as usize))
+}
+
+/// Returns the time step at the time (UNIX time in seconds).
+pub fn step_at(time: i64) -> i64 {
+    time.div_euclid(PERIOD)
+}
+
+/// Returns a TOTP value at the time (RFC 6238).
+pub fn totp(
+    secret: &[u8],
+    time: i64,
+    digits: u32,
+    algorithm: Algorithm,
+) -> Option<String> {
+    let step = step_at(time);
+    if step < 0 {
+        return None;
+    }
+    hotp(secret, step as u64, digits, algorithm)
+}
+
+/// Returns the time step of the code if it's valid at the time.
+///
+/// The steps until `last_used_step` are not accepted, so that a code can't be
+/// replayed.
+pub fn verify(
+    secret: &[u8],
+    code: &str,
+    time: i64,
+    last_used_step: Option<i64>,
+) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize ||
+        !code.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let current = step_at(time);
+    ((current - WINDOW)..=(current + WINDOW))
+        .filter(|step| *step >= 0 && Some(*step) > last_used_step)
+        .find(|step| {
+            hotp(secret, *step as u64, DIGITS, Algorithm::Sha1)
+                .map(|v| memcmp::eq(v.as_bytes(), code.as_bytes()))
+                .unwrap_or(false)
+        })
+}
+
+/// Generates a new random secret.
+pub fn generate_secret() -> Option<Vec<u8>> {
+    let mut secret = vec![0; SECRET_LENGTH];
+    rand_bytes(&mut secret).ok()?;
+    Some(secret)
+}
+
+/// Returns a URI for authenticator apps (often shown as QR code).
+pub fn provisioning_uri(secret: &[u8], account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}\
+         &algorithm=SHA1&digits={digits}&period={period}",
+        issuer = encode_uri_component(issuer),
+        account = encode_uri_component(account),
+        secret = base32_encode(secret),
+        digits = DIGITS,
+        period = PERIOD,
+    )
+}
+
+fn encode_uri_component(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' |
+            b'a'..=b'z' |
+            b'0'..=b'9' |
+            b'-' |
+            b'.' |
+            b'_' |
+            b'~' |
+            b'@' => char::from(b).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Encodes bytes as Base32 (RFC 4648) without padding.
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut s = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for b in data {
+        buffer = (buffer << 8) | u32::from(*b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            let i = ((buffer >> bits) & 0x1f) as usize;
+            s.push(char::from(BASE32_ALPHABET[i]));
+        }
+    }
+    if bits > 0 {
+        let i = ((buffer << (5 - bits)) & 0x1f) as usize;
+        s.push(char::from(BASE32_ALPHABET[i]));
+    }
+    s
+}
+
+/// Decodes Base32 (RFC 4648) text. Padding and case are ignored.
+pub fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in s.trim_end_matches('=').bytes() {
+        let c = c.to_ascii_uppercase();
+        let i = BASE32_ALPHABET.iter().position(|a| *a == c)?;
+        buffer = (buffer << 5) | i as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+        }
+    }
+    Some(data)
+}
+
+/// Encrypts the secret with the key using AES-256-GCM.
+///
+/// Returns Base64 text of nonce, tag and cipher text.
+pub fn encrypt_secret(secret: &[u8], key: &str) -> Option<String> {
+    let mut nonce = [0; NONCE_LENGTH];
+    rand_bytes(&mut nonce).ok()?;
+    let mut tag = [0; TAG_LENGTH];
+    let encrypted = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        &sha256(key.as_bytes()),
+        Some(&nonce),
+        &[],
+        secret,
+        &mut tag,
+    )
+    .ok()?;
+
+    let mut value = nonce.to_vec();
+    value.extend_from_slice(&tag);
+    value.extend(encrypted);
+    Some(base64::encode(value))
+}
+
+/// Decrypts the value made by `encrypt_secret`.
+pub fn decrypt_secret(value: &str, key: &str) -> Option<Vec<u8>> {
+    let value = base64::decode(value).ok()?;
+    if value.len() <= NONCE_LENGTH + TAG_LENGTH {
+        return None;
+    }
+    let (nonce, rest) = value.split_at(NONCE_LENGTH);
+    let (tag, encrypted) = rest.split_at(TAG_LENGTH);
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        &sha256(key.as_bytes()),
+        Some(nonce),
+        &[],
+        encrypted,
+        tag,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SEED_SHA1: &[u8] = b"12345678901234567890";
+    const SEED_SHA256: &[u8] = b"12345678901234567890123456789012";
+    const SEED_SHA512: &[u8] =
+        b"1234567890123456789012345678901234567890123456789012345678901234";
+
+    #[test]
+    fn test_hotp() {
+        // RFC 4226 Appendix D
+        let values = [
+            "755224", "287082", "359152", "969429", "338314", "254676",
+            "287922", "162583", "399871", "520489",
+        ];
+        for (counter, value) in values.iter().enumerate() {
+            assert_eq!(
+                hotp(SEED_SHA1, counter as u64, 6, Algorithm::Sha1).unwrap(),
+                *value
+            );
+        }
+    }
+
+    #[test]
+    fn test_totp() {
+        // RFC 6238 Appendix B
+        let vectors: [(i64, &str, &str, &str); 6] = [
+            (59, "94287082", "46119246", "90693936"),
+            (1_111_111_109, "07081804", "68084774", "25091201"),
+            (1_111_111_111, "14050471", "67062674", "99943326"),
+            (1_234_567_890, "89005924", "91819424", "93441116"),
+            (2_000_000_000, "69279037", "90698825", "38618901"),
+            (20_000_000_000, "65353130", "77737706", "47863826"),
+        ];
+        for (time, v1, v256, v512) in vectors.iter() {
+            assert_eq!(
+                totp(SEED_SHA1, *time, 8, Algorithm::Sha1).unwrap(),
+                *v1
+            );
+            assert_eq!(
+                totp(SEED_SHA256, *time, 8, Algorithm::Sha256).unwrap(),
+                *v256
+            );
+            assert_eq!(
+                totp(SEED_SHA512, *time, 8, Algorithm::Sha512).unwrap(),
+                *v512
+            );
+        }
+
+        assert_eq!(totp(SEED_SHA1, -1, 8, Algorithm::Sha1), None);
+    }
+
+    #[test]
+    fn test_verify() {
+        let time = 1_111_111_111;
+        let step = step_at(time);
+        let code = totp(SEED_SHA1, time, 6, Algorithm::Sha1).unwrap();
+        assert_eq!(code, "050471");
+
+        assert_eq!(verify(SEED_SHA1, &code, time, None), Some(step));
+        assert_eq!(verify(SEED_SHA1, " 050471 ", time, None), Some(step));
+
+        // clock drift
+        assert_eq!(verify(SEED_SHA1, &code, time - PERIOD, None), Some(step));
+        assert_eq!(verify(SEED_SHA1, &code, time + PERIOD, None), Some(step));
+        assert_eq!(verify(SEED_SHA1, &code, time + PERIOD * 2, None), None);
+
+        // replay
+        assert_eq!(verify(SEED_SHA1, &code, time, Some(step - 1)), Some(step));
+        assert_eq!(verify(SEED_SHA1, &code, time, Some(step)), None);
+
+        assert_eq!(verify(SEED_SHA1, "", time, None), None);
+        assert_eq!(verify(SEED_SHA1, "05047", time, None), None);
+        assert_eq!(verify(SEED_SHA1, "14050471", time, None), None);
+        assert_eq!(verify(SEED_SHA1, "05o471", time, None), None);
+        assert_eq!(verify(SEED_SHA256, &code, time, None), None);
+    }
+
+    #[test]
+    fn test_generate_secret() {
+        let secret = generate_secret().unwrap();
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert_ne!(secret, generate_secret().unwrap());
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let account = "oswald+log@example.org";
+        assert_eq!(
+            provisioning_uri(b"foobar", account, "Eloquentlog"),
+            "otpauth://totp/Eloquentlog:oswald%2Blog@example.org\
+             ?secret=MZXW6YTBOI&issuer=Eloquentlog&algorithm=SHA1&digits=6\
+             &period=30"
+        );
+    }
+
+    #[test]
+    fn test_base32() {
+        // RFC 4648 Section 10 (without padding)
+        let vectors = [
+            ("", ""),
+            ("f", "MY"),
+            ("fo", "MZXQ"),
+            ("foo", "MZXW6"),
+            ("foob", "MZXW6YQ"),
+            ("fooba", "MZXW6YTB"),
+            ("foobar", "MZXW6YTBOI"),
+        ];
+        for (data, text) in vectors.iter() {
+            assert_eq!(base32_encode(data.as_bytes()), *text);
+            assert_eq!(base32_decode(text).unwrap(), data.as_bytes());
+        }
+
+        assert_eq!(base32_decode("MZXW6YQ=").unwrap(), b"foob");
+        assert_eq!(base32_decode("mzxw6ytboi").unwrap(), b"foobar");
+        assert_eq!(base32_decode("MZXW1"), None);
+
+        let secret = generate_secret().unwrap();
+        assert_eq!(base32_decode(&base32_encode(&secret)).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_encrypt_secret() {
+        let secret = generate_secret().unwrap();
+        let value = encrypt_secret(&secret, "key").unwrap();
+        assert!(!value.contains(&base64::encode(&secret)));
+        assert_ne!(value, encrypt_secret(&secret, "key").unwrap());
+
+        assert_eq!(decrypt_secret(&value, "key").unwrap(), secret);
+        assert_eq!(decrypt_secret(&value, "another key"), None);
+        assert_eq!(decrypt_secret("", "key"), None);
+        assert_eq!(decrypt_secret("invalid", "key"), None);
+
+        let mut tampered = base64::decode(&value).unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert_eq!(decrypt_secret(&base64::encode(tampered), "key"), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Usage metering for namespaces.
+//!
+//! Messages (and their bytes) ingested into a namespace are counted per day
+//! (UTC). Once the count reaches the daily quota, further messages are
+//! rejected, or sampled if `ingestion_over_quota_sampling` is set.
+//!
+//! Members are warned by email when the usage reaches 80% and 100% of the
+//! quota, once for each threshold a day.
+use chrono::{Duration, NaiveDateTime};
+use diesel::PgConnection;
+use rand::prelude::*;
+
+use crate::config::Config;
+use crate::job::{Job, JobKind};
+use crate::logger::Logger;
+use crate::model::namespace::Namespace;
+use crate::model::namespace_usage::NamespaceUsage;
+use crate::util::Clock;
+
+const THRESHOLDS: [i16; 2] = [100, 80];
+
+/// What to do with an incoming message.
+#[derive(Debug, PartialEq)]
+pub enum Admission {
+    Accept,
+    // sampled out
+    Drop,
+    Reject,
+}
+
+/// Returns the daily quota of the namespace.
+pub fn quota_for(namespace: &Namespace, config: &Config) -> i64 {
+    namespace
+        .ingestion_daily_quota
+        .unwrap_or(config.ingestion_daily_quota)
+}
+
+/// Returns the highest threshold (%) the count has reached, if any.
+pub fn threshold(messages_count: i64, quota: i64) -> Option<i16> {
+    if quota < 1 {
+        return None;
+    }
+    THRESHOLDS
+        .iter()
+        .find(|t| messages_count * 100 >= quota * i64::from(**t))
+        .copied()
+}
+
+/// Returns seconds until the quota is reset (at the next UTC midnight).
+pub fn seconds_until_reset(now: NaiveDateTime) -> i64 {
+    let tomorrow = (now.date() + Duration::days(1)).and_hms(0, 0, 0);
+    (tomorrow - now).num_seconds().max(1)
+}
+
+pub struct UsageMeter<'a, C: Clock> {
+    conn: &'a PgConnection,
+    clock: &'a C,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Clock> UsageMeter<'a, C> {
+    pub fn new(
+        conn: &'a PgConnection,
+        clock: &'a C,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            logger,
+        }
+    }
+
+    /// Decides whether a message into the namespace can be accepted.
+    ///
+    /// Messages which are not accepted are counted as dropped.
+    pub fn admit(&self, namespace: &Namespace, config: &Config) -> Admission {
+        let now = self.clock.now();
+        let quota = quota_for(namespace, config);
+        let messages_count = NamespaceUsage::find_by_namespace_id_and_date(
+            namespace.id,
+            now.date(),
+            self.conn,
+            self.logger,
+        )
+        .map_or(0, |u| u.messages_count);
+        if messages_count < quota {
+            return Admission::Accept;
+        }
+
+        let sampling = config.ingestion_over_quota_sampling;
+        let admission = if sampling < 1 {
+            Admission::Reject
+        } else if thread_rng().gen_ratio(1, sampling as u32) {
+            return Admission::Accept;
+        } else {
+            Admission::Drop
+        };
+
+        NamespaceUsage::increment(
+            namespace.id,
+            now.date(),
+            0,
+            0,
+            1,
+            now,
+            self.conn,
+            self.logger,
+        );
+        admission
+    }
+
+    /// Records an accepted message, and returns a job for the warning email
+    /// if the usage has newly reached a threshold.
+    pub fn record(
+        &self,
+        namespace: &Namespace,
+        bytes: i64,
+        config: &Config,
+    ) -> Option<Job<String>> {
+        let now = self.clock.now();
+        let usage = NamespaceUsage::increment(
+            namespace.id,
+            now.date(),
+            1,
+            bytes,
+            0,
+            now,
+            self.conn,
+            self.logger,
+        )?;
+
+        let quota = quota_for(namespace, config);
+        let percent = threshold(usage.messages_count, quota)?;
+        if usage.notified_percent >= percent {
+            return None;
+        }
+        // only one of concurrent requests can mark it
+        let usage = usage.notify(percent, self.conn, self.logger)?;
+        Some(Job::<String> {
+            kind: JobKind::SendQuotaWarningEmail,
+            args: vec![
+                usage.id.to_string(),
+                percent.to_string(),
+                quota.to_string(),
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chrono::{TimeZone, Utc};
+    use diesel::prelude::*;
+
+    use crate::model::namespace::namespaces;
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::util::FixedClock;
+
+    #[test]
+    fn test_threshold() {
+        assert_eq!(threshold(0, 10), None);
+        assert_eq!(threshold(7, 10), None);
+        assert_eq!(threshold(8, 10), Some(80));
+        assert_eq!(threshold(9, 10), Some(80));
+        assert_eq!(threshold(10, 10), Some(100));
+        assert_eq!(threshold(11, 10), Some(100));
+        assert_eq!(threshold(1, 0), None);
+    }
+
+    #[test]
+    fn test_seconds_until_reset() {
+        let now = Utc.ymd(2019, 7, 7).and_hms(23, 59, 0).naive_utc();
+        assert_eq!(seconds_until_reset(now), 60);
+
+        let now = Utc.ymd(2019, 7, 7).and_hms(0, 0, 0).naive_utc();
+        assert_eq!(seconds_until_reset(now), 86_400);
+    }
+
+    #[test]
+    fn test_quota_for() {
+        let config = Config::default();
+        let mut namespace = NAMESPACES.get("piano").unwrap().clone();
+        assert_eq!(quota_for(&namespace, &config), 100_000);
+
+        namespace.ingestion_daily_quota = Some(10);
+        assert_eq!(quota_for(&namespace, &config), 10);
+    }
+
+    #[test]
+    fn test_record_and_admit() {
+        run(|conn, config, logger| {
+            let mut ns = NAMESPACES.get("piano").unwrap().clone();
+            ns.ingestion_daily_quota = Some(5);
+            let namespace = diesel::insert_into(namespaces::table)
+                .values(ns)
+                .get_result::<Namespace>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let clock =
+                FixedClock(Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc());
+            let meter = UsageMeter::new(conn, &clock, logger);
+
+            let mut jobs = vec![];
+            for _ in 0..5 {
+                assert_eq!(meter.admit(&namespace, config), Admission::Accept);
+                jobs.push(meter.record(&namespace, 10, config));
+            }
+            let jobs: Vec<Job<String>> = jobs.into_iter().flatten().collect();
+            assert_eq!(jobs.len(), 2);
+            assert_eq!(jobs[0].args[1..], ["80".to_string(), "5".to_string()]);
+            assert_eq!(jobs[1].args[1..], ["100".to_string(), "5".to_string()]);
+
+            // ingestion_over_quota_sampling is 0
+            assert_eq!(meter.admit(&namespace, config), Admission::Reject);
+
+            let usage = NamespaceUsage::find_by_namespace_id_and_date(
+                namespace.id,
+                clock.0.date(),
+                conn,
+                logger,
+            )
+            .unwrap();
+            assert_eq!(usage.messages_count, 5);
+            assert_eq!(usage.bytes_count, 50);
+            assert_eq!(usage.dropped_count, 1);
+        })
+    }
+}
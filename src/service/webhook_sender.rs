@@ -0,0 +1,713 @@
+//! Webhook delivery.
+//!
+//! Events are stored as WebhookDelivery first, then sent by the worker. The
+//! payload is signed with the secret of the webhook using HMAC-SHA256, and
+//! its hex digest is set in `X-Eloquentlog-Signature` (`sha256=...`).
+//!
+//! A delivery is retried with backoff until MAX_ATTEMPTS. Note that it's
+//! at-least-once, the receiver may see the same delivery more than once.
+//!
+//! The host is resolved on each attempt, and it's refused if the address is
+//! a loopback, private, link-local or unspecified one (unless it's allowed by
+//! the config), so that a webhook can't reach internal services.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs,
+};
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, NaiveDateTime};
+use diesel::PgConnection;
+use native_tls::TlsConnector;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde_json::{Value, json};
+
+use crate::config::Config;
+use crate::job::{Job, JobKind};
+use crate::logger::Logger;
+use crate::model::webhook::{Webhook, WebhookEvent};
+use crate::model::webhook_delivery::{NewWebhookDelivery, WebhookDelivery};
+use crate::util::Clock;
+
+pub const MAX_ATTEMPTS: i32 = 5;
+
+const TIMEOUT: u64 = 10; // seconds
+const USER_AGENT: &str = "Eloquentlog-Webhook/1.0";
+
+/// Signs the payload with the secret and returns it as hex string.
+pub fn sign(secret: &str, payload: &str) -> Result<String, &'static str> {
+    let key =
+        PKey::hmac(secret.as_bytes()).map_err(|_| "invalid secret")?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)
+        .map_err(|_| "failed to sign")?;
+    signer
+        .update(payload.as_bytes())
+        .map_err(|_| "failed to sign")?;
+    let digest = signer.sign_to_vec().map_err(|_| "failed to sign")?;
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Returns when the next attempt should be, or None if it has been given up.
+///
+/// It waits 1, 2, 4 and 8 minutes after each failure.
+pub fn next_attempt_at(
+    attempts: i32,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    if attempts < 1 || attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    Some(now + Duration::minutes(1 << (attempts - 1)))
+}
+
+/// Creates deliveries for webhooks which subscribe the event in the
+/// namespace, and returns jobs to send them.
+pub fn dispatch(
+    namespace_id: i64,
+    event: &WebhookEvent,
+    data: Value,
+    conn: &PgConnection,
+    logger: &Logger,
+) -> Vec<Job<String>> {
+    let webhooks = Webhook::find_all_subscribing(
+        namespace_id,
+        event,
+        conn,
+        logger,
+    )
+    .unwrap_or_default();
+
+    webhooks
+        .iter()
+        .filter_map(|w| prepare(w, event, data.clone(), conn, logger))
+        .map(|d| job_for(&d))
+        .collect()
+}
+
+/// Creates a delivery of the event for the webhook (regardless of its
+/// subscription).
+pub fn prepare(
+    webhook: &Webhook,
+    event: &WebhookEvent,
+    data: Value,
+    conn: &PgConnection,
+    logger: &Logger,
+) -> Option<WebhookDelivery> {
+    let payload = json!({
+        "event": event.to_string(),
+        "data": data,
+    });
+    let d = NewWebhookDelivery {
+        webhook_id: webhook.id,
+        event: event.to_string(),
+        payload: payload.to_string(),
+    };
+    WebhookDelivery::insert(&d, conn, logger)
+}
+
+/// Returns a job which sends the delivery.
+pub fn job_for(delivery: &WebhookDelivery) -> Job<String> {
+    Job::<String> {
+        kind: JobKind::DeliverWebhook,
+        args: vec![delivery.id.to_string()],
+    }
+}
+
+// Returns false if the address is a loopback, private, link-local or
+// unspecified one.
+fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, _, _] = v4.octets();
+            // shared address space (100.64.0.0/10)
+            !(v4.is_loopback() ||
+                v4.is_private() ||
+                v4.is_link_local() ||
+                v4.is_unspecified() ||
+                v4.is_broadcast() ||
+                (a == 100 && (b & 0xc0) == 64))
+        },
+        IpAddr::V6(v6) => {
+            // e.g. ::ffff:127.0.0.1
+            let s = v6.segments();
+            if s[..5].iter().all(|v| *v == 0) && s[5] == 0xffff {
+                let [a, b] = s[6].to_be_bytes();
+                let [c, d] = s[7].to_be_bytes();
+                return is_public(&IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+            }
+            // unique local (fc00::/7) and link-local (fe80::/10)
+            !(v6.is_loopback() ||
+                v6.is_unspecified() ||
+                (s[0] & 0xfe00) == 0xfc00 ||
+                (s[0] & 0xffc0) == 0xfe80)
+        },
+    }
+}
+
+fn default_port(tls: bool) -> u16 {
+    if tls {
+        443
+    } else {
+        80
+    }
+}
+
+struct Endpoint {
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Option<Self> {
+        // the url is written into the request as it is
+        if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return None;
+        }
+
+        let (tls, rest) = if let Some(r) = url.strip_prefix("https://") {
+            (true, r)
+        } else if let Some(r) = url.strip_prefix("http://") {
+            (false, r)
+        } else {
+            return None;
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        if authority.contains('@') {
+            return None;
+        }
+
+        // e.g. [::1]:8080
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(r) => {
+                let i = r.find(']')?;
+                r[..i].parse::<Ipv6Addr>().ok()?;
+                (&r[..i], &r[i + 1..])
+            },
+            None => {
+                match authority.rfind(':') {
+                    Some(i) => (&authority[..i], &authority[i..]),
+                    None => (authority, ""),
+                }
+            },
+        };
+        let port = match port {
+            "" => default_port(tls),
+            p => p.strip_prefix(':')?.parse().ok()?,
+        };
+        if host.is_empty() ||
+            (host.contains(':') && !authority.starts_with('['))
+        {
+            return None;
+        }
+
+        Some(Self {
+            tls,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// Returns the value for Host header (the port is omitted if it's the
+    /// default one).
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.to_string()
+        };
+        if self.port == default_port(self.tls) {
+            host
+        } else {
+            format!("{}:{}", host, self.port)
+        }
+    }
+
+    /// Resolves the host, and returns an address to connect to.
+    fn resolve(&self, allow_private: bool) -> Result<SocketAddr, String> {
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or_else(|| "unknown host".to_string())?;
+        if !allow_private && !is_public(&addr.ip()) {
+            return Err(format!("forbidden address: {}", addr.ip()));
+        }
+        Ok(addr)
+    }
+}
+
+// Sends the request and returns the status code of the response.
+fn exchange<S: Read + Write>(
+    mut stream: S,
+    request: &[u8],
+) -> Result<u16, String> {
+    stream.write_all(request).map_err(|e| e.to_string())?;
+    stream.flush().map_err(|e| e.to_string())?;
+
+    // e.g. HTTP/1.1 200 OK
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    line.split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| format!("invalid response: {}", line.trim()))
+}
+
+fn post(
+    url: &str,
+    headers: &[(&str, String)],
+    body: &str,
+    allow_private: bool,
+) -> Result<u16, String> {
+    let endpoint =
+        Endpoint::parse(url).ok_or_else(|| "invalid url".to_string())?;
+
+    // connects to the address checked (not resolved again)
+    let addr = endpoint.resolve(allow_private)?;
+    let timeout = StdDuration::from_secs(TIMEOUT);
+    let stream = TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .map_err(|e| e.to_string())?;
+    stream
+        .set_write_timeout(Some(timeout))
+        .map_err(|e| e.to_string())?;
+
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         User-Agent: {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n",
+        endpoint.path,
+        endpoint.authority(),
+        USER_AGENT,
+        body.len(),
+    );
+    for (k, v) in headers {
+        request.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+
+    if endpoint.tls {
+        let connector = TlsConnector::new().map_err(|e| e.to_string())?;
+        let stream = connector
+            .connect(&endpoint.host, stream)
+            .map_err(|e| e.to_string())?;
+        exchange(stream, request.as_bytes())
+    } else {
+        exchange(stream, request.as_bytes())
+    }
+}
+
+pub struct WebhookSender<'a, C: Clock> {
+    conn: &'a PgConnection,
+    config: &'a Config,
+    clock: &'a C,
+    logger: &'a Logger,
+}
+
+impl<'a, C: Clock> WebhookSender<'a, C> {
+    pub fn new(
+        conn: &'a PgConnection,
+        config: &'a Config,
+        clock: &'a C,
+        logger: &'a Logger,
+    ) -> Self {
+        Self {
+            conn,
+            config,
+            clock,
+            logger,
+        }
+    }
+
+    /// Sends the delivery and records the result of the attempt.
+    pub fn deliver(
+        &self,
+        delivery: &WebhookDelivery,
+    ) -> Result<WebhookDelivery, &'static str> {
+        if !delivery.is_pending() {
+            return Err("already done");
+        }
+
+        let webhook =
+            Webhook::find_by_id(delivery.webhook_id, self.conn, self.logger)
+                .ok_or("webhook not found")?;
+        let signature = sign(&webhook.secret, &delivery.payload)?;
+        let headers = [
+            ("X-Eloquentlog-Event", delivery.event.to_string()),
+            ("X-Eloquentlog-Delivery", delivery.uuid.to_string()),
+            ("X-Eloquentlog-Signature", format!("sha256={}", signature)),
+        ];
+
+        let result = post(
+            &webhook.url,
+            &headers,
+            &delivery.payload,
+            self.config.webhook_allow_private_hosts,
+        );
+
+        let now = self.clock.now();
+        let next = next_attempt_at(delivery.attempts + 1, now);
+        match result {
+            Ok(status) if (200..300).contains(&status) => {
+                info!(self.logger, "{} delivered ({})", delivery, status);
+                let status = i32::from(status);
+                delivery.succeed(status, now, self.conn, self.logger)
+            },
+            Ok(status) => {
+                error!(self.logger, "{} responded ({})", delivery, status);
+                delivery.fail(
+                    Some(i32::from(status)),
+                    &format!("unexpected status: {}", status),
+                    next,
+                    now,
+                    self.conn,
+                    self.logger,
+                )
+            },
+            Err(e) => {
+                error!(self.logger, "{} err: {}", delivery, e);
+                delivery.fail(None, &e, next, now, self.conn, self.logger)
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::TcpListener;
+    use std::thread;
+
+    use chrono::{Timelike, Utc};
+    use diesel::prelude::*;
+
+    use crate::model::namespace::{Namespace, namespaces};
+    use crate::model::namespace::data::NAMESPACES;
+    use crate::model::test::run;
+    use crate::model::webhook::webhooks;
+    use crate::model::webhook::data::WEBHOOKS;
+    use crate::model::webhook_delivery::WebhookDeliveryState;
+    use crate::util::FixedClock;
+
+    // Accepts a request and responds with the status, then returns the
+    // received request (as text).
+    fn stand_in(status: u16) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(v) = line.strip_prefix("Content-Length: ") {
+                    length = v.trim().parse::<usize>().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+
+            let mut stream = reader.into_inner();
+            stream
+                .write_all(
+                    format!(
+                        "HTTP/1.1 {} Status\r\nContent-Length: 0\r\n\r\n",
+                        status
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+            request
+        });
+        (url, handle)
+    }
+
+    fn load_webhook(url: String, conn: &PgConnection) -> Webhook {
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace = diesel::insert_into(namespaces::table)
+            .values(ns)
+            .get_result::<Namespace>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+        let mut w = WEBHOOKS.get("piano's incident hook").unwrap().clone();
+        w.namespace_id = namespace.id;
+        w.url = url;
+        diesel::insert_into(webhooks::table)
+            .values(&w)
+            .get_result::<Webhook>(conn)
+            .unwrap_or_else(|e| panic!("Error at inserting: {}", e))
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 (Test Case 2)
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            Ok("5bdcc146bf60754e6a042426089575c7\
+                5a003f089d2739839dec58b964ec3843"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_next_attempt_at() {
+        let now = Utc::now().naive_utc();
+
+        assert_eq!(next_attempt_at(0, now), None);
+        assert_eq!(next_attempt_at(1, now), Some(now + Duration::minutes(1)));
+        assert_eq!(next_attempt_at(2, now), Some(now + Duration::minutes(2)));
+        assert_eq!(next_attempt_at(4, now), Some(now + Duration::minutes(8)));
+        assert_eq!(next_attempt_at(MAX_ATTEMPTS, now), None);
+    }
+
+    #[test]
+    fn test_endpoint_parse() {
+        let e = Endpoint::parse("https://example.org/hook?a=b").unwrap();
+        assert!(e.tls);
+        assert_eq!(e.host, "example.org");
+        assert_eq!(e.port, 443);
+        assert_eq!(e.path, "/hook?a=b");
+
+        let e = Endpoint::parse("http://127.0.0.1:8080").unwrap();
+        assert!(!e.tls);
+        assert_eq!(e.host, "127.0.0.1");
+        assert_eq!(e.port, 8080);
+        assert_eq!(e.path, "/");
+
+        let e = Endpoint::parse("http://[::1]:8080/hook").unwrap();
+        assert_eq!(e.host, "::1");
+        assert_eq!(e.port, 8080);
+        assert_eq!(e.path, "/hook");
+
+        let e = Endpoint::parse("https://[2001:db8::1]").unwrap();
+        assert_eq!(e.host, "2001:db8::1");
+        assert_eq!(e.port, 443);
+
+        assert!(Endpoint::parse("ftp://example.org/").is_none());
+        assert!(Endpoint::parse("http://:80/").is_none());
+        assert!(Endpoint::parse("http://example.org:port/").is_none());
+        assert!(Endpoint::parse("http://::1:8080/").is_none());
+        assert!(Endpoint::parse("http://[::1/").is_none());
+        assert!(Endpoint::parse("http://[::1]8080/").is_none());
+        assert!(Endpoint::parse("http://user@example.org/").is_none());
+        assert!(Endpoint::parse("http://example.org/a b").is_none());
+        assert!(Endpoint::parse(
+            "http://example.org/ HTTP/1.1\r\nHost: internal\r\n\r\n"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_endpoint_authority() {
+        let e = Endpoint::parse("https://example.org/hook").unwrap();
+        assert_eq!(e.authority(), "example.org");
+
+        let e = Endpoint::parse("http://example.org:443/hook").unwrap();
+        assert_eq!(e.authority(), "example.org:443");
+
+        let e = Endpoint::parse("http://[::1]:8080/hook").unwrap();
+        assert_eq!(e.authority(), "[::1]:8080");
+    }
+
+    #[test]
+    fn test_endpoint_resolve() {
+        let e = Endpoint::parse("http://127.0.0.1:8080/hook").unwrap();
+        assert!(e.resolve(true).is_ok());
+        assert_eq!(
+            e.resolve(false),
+            Err("forbidden address: 127.0.0.1".to_string())
+        );
+
+        let e = Endpoint::parse("http://[::1]/hook").unwrap();
+        assert!(e.resolve(false).is_err());
+
+        let e = Endpoint::parse("http://203.0.113.1/hook").unwrap();
+        assert!(e.resolve(false).is_ok());
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in &[
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.0.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(&ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in &["93.184.216.34", "100.128.0.1", "2606:4700::1111"] {
+            assert!(is_public(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_dispatch() {
+        run(|conn, _, logger| {
+            let webhook = load_webhook("http://127.0.0.1/".to_string(), conn);
+
+            let jobs = dispatch(
+                webhook.namespace_id,
+                &WebhookEvent::AlertResolved,
+                json!({}),
+                conn,
+                logger,
+            );
+            assert!(jobs.is_empty());
+
+            let jobs = dispatch(
+                webhook.namespace_id,
+                &WebhookEvent::AlertFired,
+                json!({"alert": {}}),
+                conn,
+                logger,
+            );
+            assert_eq!(jobs.len(), 1);
+            assert_eq!(jobs[0].kind, JobKind::DeliverWebhook);
+
+            let id = jobs[0].args[0].parse::<i64>().unwrap();
+            let delivery = WebhookDelivery::find_by_id(id, conn, logger);
+            assert_eq!(
+                delivery.unwrap().payload,
+                r#"{"data":{"alert":{}},"event":"alert.fired"}"#
+            );
+        })
+    }
+
+    #[test]
+    fn test_deliver() {
+        run(|conn, config, logger| {
+            let (url, handle) = stand_in(200);
+            let webhook = load_webhook(url, conn);
+
+            let delivery = prepare(
+                &webhook,
+                &WebhookEvent::Ping,
+                json!({}),
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            let clock = FixedClock(Utc::now().naive_utc());
+            let sender = WebhookSender::new(conn, config, &clock, logger);
+            let result = sender.deliver(&delivery);
+            assert!(result.is_ok());
+
+            let d = result.unwrap();
+            assert_eq!(d.state, WebhookDeliveryState::Succeeded);
+            assert_eq!(d.response_status, Some(200));
+
+            let request = handle.join().unwrap();
+            let signature = sign(&webhook.secret, &delivery.payload).unwrap();
+            assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+            assert!(request.contains("X-Eloquentlog-Event: ping\r\n"));
+            assert!(request.contains(&format!(
+                "X-Eloquentlog-Signature: sha256={}\r\n",
+                signature
+            )));
+            assert!(request.ends_with(&delivery.payload));
+
+            // never send it twice
+            assert!(sender.deliver(&d).is_err());
+        })
+    }
+
+    #[test]
+    fn test_deliver_failure() {
+        run(|conn, config, logger| {
+            let (url, handle) = stand_in(500);
+            let webhook = load_webhook(url, conn);
+
+            let delivery = prepare(
+                &webhook,
+                &WebhookEvent::Ping,
+                json!({}),
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            // timestamp column has microseconds precision
+            let now = Utc::now().naive_utc().with_nanosecond(0).unwrap();
+            let clock = FixedClock(now);
+            let sender = WebhookSender::new(conn, config, &clock, logger);
+            let result = sender.deliver(&delivery);
+            let _ = handle.join();
+            assert!(result.is_ok());
+
+            let d = result.unwrap();
+            assert_eq!(d.state, WebhookDeliveryState::Pending);
+            assert_eq!(d.attempts, 1);
+            assert_eq!(d.response_status, Some(500));
+            assert_eq!(d.next_attempt_at, Some(now + Duration::minutes(1)));
+        })
+    }
+
+    #[test]
+    fn test_deliver_to_private_host() {
+        run(|conn, config, logger| {
+            let webhook =
+                load_webhook("http://127.0.0.1:1/hook".to_string(), conn);
+
+            let delivery = prepare(
+                &webhook,
+                &WebhookEvent::Ping,
+                json!({}),
+                conn,
+                logger,
+            )
+            .unwrap();
+
+            let mut c = config.clone();
+            c.webhook_allow_private_hosts = false;
+            let clock = FixedClock(Utc::now().naive_utc());
+            let sender = WebhookSender::new(conn, &c, &clock, logger);
+            let result = sender.deliver(&delivery);
+            assert!(result.is_ok());
+
+            let d = result.unwrap();
+            assert_eq!(d.state, WebhookDeliveryState::Pending);
+            assert_eq!(d.response_status, None);
+            assert_eq!(
+                d.error_message,
+                Some("forbidden address: 127.0.0.1".to_string())
+            );
+        })
+    }
+}
@@ -51,6 +51,19 @@ impl DerefMut for SsConn {
     }
 }
 
+/// Returns the key of a set which holds the session keys issued for the user
+/// (e.g. `pr-...`), so that they can be cleared at once.
+pub fn user_sessions_key(user_id: i64) -> String {
+    format!("us-{}", user_id)
+}
+
+/// Returns the key which holds the timestamp when the browser sessions of
+/// the user have been revoked. Authentication tokens issued until then are
+/// rejected.
+pub fn user_sessions_revoked_at_key(user_id: i64) -> String {
+    format!("ur-{}", user_id)
+}
+
 // Initializes session store connection pool holder
 pub fn init_pool_holder(
     session_store_url: &str,
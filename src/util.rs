@@ -1,9 +1,38 @@
+use chrono::{NaiveDateTime, Utc};
 use rand::prelude::*;
-use rocket::http::{Cookie, SameSite};
+use rocket::http::{Cookie, Cookies, SameSite};
 use rocket::Request;
 
 use crate::config::Config;
 
+/// Clock tells the current time (in UTC).
+///
+/// This is for services which depend on time, so that they can be tested
+/// with a fixed one.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// SystemClock returns the actual current time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Utc::now().naive_utc()
+    }
+}
+
+/// FixedClock returns always the time given.
+#[cfg(test)]
+pub struct FixedClock(pub NaiveDateTime);
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveDateTime {
+        self.0
+    }
+}
+
 // Creates random hash based on source characters
 pub fn generate_random_hash(source: &[u8], length: i32) -> String {
     if length < 1 {
@@ -21,6 +50,34 @@ pub fn generate_random_hash(source: &[u8], length: i32) -> String {
         .collect()
 }
 
+/// Words which can't be used as a namespace key or a stream slug.
+pub const RESERVED_WORDS: &[&str] = &[
+    "admin", "api", "app", "assets", "console", "help", "login", "logout",
+    "new", "root", "settings", "signin", "signup", "static", "status",
+    "support", "system", "www",
+];
+
+pub fn is_reserved_word(s: &str) -> bool {
+    RESERVED_WORDS.contains(&s)
+}
+
+/// Makes a URL-safe slug from the text.
+///
+/// Alphanumeric characters are lowercased and others are replaced with a
+/// hyphen (e.g. `alice's default namespace` -> `alice-s-default-namespace`).
+pub fn slugify(s: &str, max: usize) -> String {
+    let mut slug = String::new();
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(max);
+    slug.trim_end_matches('-').to_string()
+}
+
 pub fn split_token(token: String) -> Option<(String, String)> {
     let parts: Vec<&str> = token.split('.').collect();
     // unexpected
@@ -51,11 +108,59 @@ pub fn make_cookie<'a>(sign: String, config: &Config) -> Cookie<'a> {
     sig
 }
 
+// Make a cookie for refresh token (refresh).
+//
+// This is also session cookie, and it's sent only to the web-console routes.
+pub fn make_refresh_cookie<'a>(value: String, config: &Config) -> Cookie<'a> {
+    let mut cookie = Cookie::new("refresh", value);
+    cookie.set_domain(config.cookie_domain.to_owned());
+    cookie.set_path("/_");
+    cookie.set_same_site(SameSite::Strict);
+    cookie.set_secure(config.cookie_secure);
+    cookie.set_http_only(true);
+    cookie
+}
+
+// Removes the cookies of the browser session (sign and refresh).
+pub fn remove_session_cookies(cookies: &mut Cookies) {
+    cookies.remove_private(Cookie::named("sign"));
+
+    // the path must match to remove it
+    let mut refresh = Cookie::named("refresh");
+    refresh.set_path("/_");
+    cookies.remove_private(refresh);
+}
+
+// Make a cookie for the challenge of two-factor authentication (two_factor).
+//
+// It's kept only until the second step of login on the web-console routes.
+pub fn make_two_factor_cookie<'a>(
+    value: String,
+    config: &Config,
+) -> Cookie<'a> {
+    let mut cookie = Cookie::new("two_factor", value);
+    cookie.set_domain(config.cookie_domain.to_owned());
+    cookie.set_path("/_");
+    cookie.set_same_site(SameSite::Strict);
+    cookie.set_secure(config.cookie_secure);
+    cookie.set_http_only(true);
+    cookie
+}
+
+// Removes the cookie of the challenge of two-factor authentication.
+pub fn remove_two_factor_cookie(cookies: &mut Cookies) {
+    let mut cookie = Cookie::named("two_factor");
+    cookie.set_path("/_");
+    cookies.remove_private(cookie);
+}
+
 /// Extract session key with a prefix from path
 ///
 /// The URI path should look like:
 /// * /_/password/reset/<...>
 /// * /_/activate/<...>
+/// * /_/email/change/<...>
+/// * /_/unlock/<...>
 pub fn extract_session_key(req: &Request<'_>) -> String {
     // NOTE: The part of `/_/` (empty segment) will be ignored in routed path
     // within Segments. See below:
@@ -68,6 +173,10 @@ pub fn extract_session_key(req: &Request<'_>) -> String {
         (2, "pr")
     } else if s0 == "activate" {
         (1, "ua")
+    } else if s0 == "email" {
+        (2, "ec")
+    } else if s0 == "unlock" {
+        (1, "ul")
     } else {
         return "".to_string();
     };
@@ -112,6 +221,26 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_is_reserved_word() {
+        assert!(is_reserved_word("api"));
+        assert!(!is_reserved_word("piano"));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("", 64), "");
+        assert_eq!(slugify("'", 64), "");
+        assert_eq!(slugify("main", 64), "main");
+        assert_eq!(
+            slugify("alice's default namespace", 64),
+            "alice-s-default-namespace"
+        );
+        assert_eq!(slugify("  Production (EU) ", 64), "production-eu");
+        assert_eq!(slugify("Café au lait", 64), "caf-au-lait");
+        assert_eq!(slugify("abc def", 4), "abc");
+    }
+
     #[test]
     fn test_extract_session_key() {
         let client = Client::new(rocket::ignite()).expect("valid rocket");
@@ -166,5 +295,21 @@ mod test {
         let uri = Origin::parse("/activate/456/789").unwrap();
         req.set_uri(uri);
         assert_eq!(extract_session_key(&req), "ua-456");
+
+        let uri = Origin::parse("/email/change").unwrap();
+        req.set_uri(uri);
+        assert_eq!(extract_session_key(&req), "");
+
+        let uri = Origin::parse("/email/change/789").unwrap();
+        req.set_uri(uri);
+        assert_eq!(extract_session_key(&req), "ec-789");
+
+        let uri = Origin::parse("/unlock").unwrap();
+        req.set_uri(uri);
+        assert_eq!(extract_session_key(&req), "");
+
+        let uri = Origin::parse("/unlock/012").unwrap();
+        req.set_uri(uri);
+        assert_eq!(extract_session_key(&req), "ul-012");
     }
 }
@@ -0,0 +1,183 @@
+use std::result::Result;
+
+use accord::{Invalid, ValidatorResult};
+use accord::validators::length;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::alert_rule::{LogLevel, NewAlertRule};
+use crate::request::alert_rule::AlertRule as RequestData;
+use crate::validation::*;
+
+// the default level is used if it's not given
+fn known_level_if_present(
+) -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        let v = match s {
+            Some(v) => v.to_ascii_lowercase(),
+            None => return Ok(()),
+        };
+        if LogLevel::iter().any(|l| l.to_string() == v) {
+            return Ok(());
+        }
+        let levels = LogLevel::iter()
+            .map(|l| l.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        Err(Invalid {
+            msg: "Must be one of %1".to_string(),
+            args: vec![levels.clone()],
+            human_readable: format!("Must be one of {}", levels),
+        })
+    })
+}
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let r = NewAlertRule::from(self.data.0.clone());
+        let result = rules! {
+            "name" => r.name => [length(1, 64)],
+            "level" => self.data.0.level => [known_level_if_present()],
+            "threshold" => r.threshold => [within(1, 100_000)],
+            "window_minutes" => r.window_minutes => [within(1, 1440)],
+            "cooldown_minutes" => r.cooldown_minutes => [within(0, 10080)]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+
+    #[test]
+    fn test_validate_name_is_none() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: None,
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("name", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain more than 1 characters"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_window_minutes_is_out_of_range() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("errors".to_string()),
+                window_minutes: Some(1441),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("window_minutes", errors[0].field);
+                assert_eq!(
+                    vec!["Must be in the range 1..1440"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_level_is_unknown() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("errors".to_string()),
+                level: Some("eror".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("level", errors[0].field);
+                assert_eq!(
+                    vec![
+                        "Must be one of debug, information, warning, error, \
+                         critical"
+                    ],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("errors".to_string()),
+                level: Some("warning".to_string()),
+                threshold: Some(10),
+                window_minutes: Some(15),
+                cooldown_minutes: Some(0),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
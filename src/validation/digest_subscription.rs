@@ -0,0 +1,121 @@
+use std::result::Result;
+
+use accord::{Invalid, ValidatorResult};
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::digest_subscription::DigestFrequency;
+use crate::request::digest_subscription::DigestSubscription as RequestData;
+use crate::validation::*;
+
+fn known_frequency() -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        let v = s.as_ref().map(|v| v.to_ascii_lowercase());
+        if DigestFrequency::iter().any(|f| Some(f.to_string()) == v) {
+            return Ok(());
+        }
+        let frequencies = DigestFrequency::iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        Err(Invalid {
+            msg: "Must be one of %1".to_string(),
+            args: vec![frequencies.clone()],
+            human_readable: format!("Must be one of {}", frequencies),
+        })
+    })
+}
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let frequency = self.data.0.frequency.clone();
+        let result = rules! {
+            "frequency" => frequency => [known_frequency()]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+
+    #[test]
+    fn test_validate_frequency_is_unknown() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                frequency: Some("monthly".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("frequency", errors[0].field);
+                assert_eq!(
+                    vec!["Must be one of daily, weekly"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_frequency_is_none() {
+        run(|_, _, logger| {
+            let data = Json(RequestData { frequency: None });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                frequency: Some("Daily".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
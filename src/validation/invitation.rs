@@ -0,0 +1,121 @@
+use std::result::Result;
+
+use accord::validators::{contains, length};
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::invitation::NewInvitation;
+use crate::request::invitation::Invitation as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let role = self.data.0.role.clone();
+        let i = NewInvitation::from(self.data.0.clone());
+        let result = rules! {
+            "email" => i.email => [
+                contains("@"),
+                contains("."),
+                length(6, 128)
+            ],
+            "role" => role => [assignable_role_if_present()]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+
+    #[test]
+    fn test_validate_email_is_none() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                email: None,
+                role: None,
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("email", errors[0].field);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_role_is_primary_owner() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                email: Some("weenie@example.org".to_string()),
+                role: Some("primary_owner".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("role", errors[0].field);
+                assert_eq!(
+                    vec!["Must be either owner or member"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                email: Some("weenie@example.org".to_string()),
+                role: Some("owner".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
@@ -1,12 +1,26 @@
+pub mod alert_rule;
+pub mod digest_subscription;
+pub mod invitation;
+pub mod membership;
 pub mod message;
 pub mod namespace;
+pub mod password;
 pub mod password_reset;
 pub mod password_reset_request;
+pub mod redaction_rule;
+pub mod stream;
+pub mod stream_grant;
 pub mod user;
+pub mod user_email;
+pub mod webhook;
+
+use std::fmt;
 
 use accord::{Invalid, ValidatorResult};
 use accord::validators::{alphanumeric, max as original_max};
 
+use crate::util::{is_reserved_word, slugify};
+
 type SV = Box<dyn Fn(&String) -> ValidatorResult>;
 
 const CHARS_LOWER: &[char] = &[
@@ -138,6 +152,63 @@ fn required() -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
     })
 }
 
+fn within(min: i32, max: i32) -> Box<dyn Fn(&i32) -> ValidatorResult> {
+    Box::new(move |n: &i32| {
+        if *n >= min && *n <= max {
+            return Ok(());
+        }
+        Err(Invalid {
+            msg: "Must be in the range %1..%2".to_string(),
+            args: vec![min.to_string(), max.to_string()],
+            human_readable: format!("Must be in the range {}..{}", min, max),
+        })
+    })
+}
+
+fn within_if_present<T>(
+    min: T,
+    max: T,
+) -> Box<dyn Fn(&Option<T>) -> ValidatorResult>
+where T: 'static + Copy + PartialOrd + fmt::Display {
+    Box::new(move |n: &Option<T>| {
+        match n {
+            Some(v) if *v < min || *v > max => {
+                Err(Invalid {
+                    msg: "Must be in the range %1..%2".to_string(),
+                    args: vec![min.to_string(), max.to_string()],
+                    human_readable: format!(
+                        "Must be in the range {}..{}",
+                        min, max
+                    ),
+                })
+            },
+            _ => Ok(()),
+        }
+    })
+}
+
+fn http_url() -> SV {
+    Box::new(move |s: &String| {
+        let rest = s
+            .strip_prefix("https://")
+            .or_else(|| s.strip_prefix("http://"))
+            .unwrap_or("");
+        // it's written into a request line as it is
+        let unsafe_char = |c: char| c.is_whitespace() || c.is_control();
+        if !rest.is_empty() &&
+            !rest.starts_with('/') &&
+            !s.contains(unsafe_char)
+        {
+            return Ok(());
+        }
+        Err(Invalid {
+            msg: "Must be a HTTP(S) URL".to_string(),
+            args: vec![],
+            human_readable: "Must be a HTTP(S) URL".to_string(),
+        })
+    })
+}
+
 fn max_if_present(
     max: usize,
 ) -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
@@ -149,6 +220,60 @@ fn max_if_present(
     })
 }
 
+// lowercase letters, digits and hyphens (not at the start or the end)
+fn slug_if_present() -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        match &s {
+            Some(v) if v.is_empty() || slugify(v, v.len()) != *v => {
+                Err(Invalid {
+                    msg: "Must contain only lowercase letters, digits and \
+                          hyphens"
+                        .to_string(),
+                    args: vec![],
+                    human_readable: "Must contain only lowercase letters, \
+                                     digits and hyphens"
+                        .to_string(),
+                })
+            },
+            _ => Ok(()),
+        }
+    })
+}
+
+fn not_reserved_if_present(
+) -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        match &s {
+            Some(v) if is_reserved_word(v) => {
+                Err(Invalid {
+                    msg: "Must not be a reserved word".to_string(),
+                    args: vec![],
+                    human_readable: "Must not be a reserved word".to_string(),
+                })
+            },
+            _ => Ok(()),
+        }
+    })
+}
+
+// the primary owner can be changed only by transfer
+fn assignable_role_if_present(
+) -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        match &s {
+            Some(v) if v != "owner" && v != "member" => {
+                Err(Invalid {
+                    msg: "Must be either owner or member".to_string(),
+                    args: vec![],
+                    human_readable: "Must be either owner or member"
+                        .to_string(),
+                })
+            },
+            _ => Ok(()),
+        }
+    })
+}
+
 #[rustfmt::skip::attributes(rstest)]
 #[cfg(test)]
 mod test {
@@ -291,6 +416,60 @@ mod test {
         assert_eq!(expected, f(s).is_ok());
     }
 
+    #[rstest(
+        min, max, n, expected,
+        case(1, 3, 0, false),
+        case(1, 3, 4, false),
+        case(1, 3, 1, true),
+        case(1, 3, 3, true),
+        case(0, 0, 0, true),
+        ::trace
+    )]
+    #[test]
+    fn test_within(min: i32, max: i32, n: i32, expected: bool) {
+        let f = within(min, max);
+
+        assert_eq!(expected, f(&n).is_ok());
+    }
+
+    #[rstest(
+        n, expected,
+        case(None, true),
+        case(Some(-1), false),
+        case(Some(4), false),
+        case(Some(0), true),
+        case(Some(3), true),
+        ::trace
+    )]
+    #[test]
+    fn test_within_if_present(n: Option<i64>, expected: bool) {
+        let f = within_if_present(0, 3);
+
+        assert_eq!(expected, f(&n).is_ok());
+    }
+
+    #[rstest(
+        raw_s, expected,
+        case("", false),
+        case("example.org", false),
+        case("ftp://example.org/", false),
+        case("https://", false),
+        case("http:///hook", false),
+        case("http://example.org/a b", false),
+        case("http://example.org/\r\nHost: internal", false),
+        case("http://example.org/\thook", false),
+        case("http://127.0.0.1:8080/hook", true),
+        case("https://example.org", true),
+        ::trace
+    )]
+    #[test]
+    fn test_http_url(raw_s: &'static str, expected: bool) {
+        let f = http_url();
+        let s = &raw_s.to_string();
+
+        assert_eq!(expected, f(s).is_ok());
+    }
+
     #[rstest(
         max, raw_s, expected,
         case(3, Some("1234".to_string()), false),
@@ -307,4 +486,57 @@ mod test {
 
         assert_eq!(expected, f(s).is_ok());
     }
+
+    #[rstest(
+        raw_s, expected,
+        case(Some("".to_string()), false),
+        case(Some("-main".to_string()), false),
+        case(Some("main-".to_string()), false),
+        case(Some("ma--in".to_string()), false),
+        case(Some("Main".to_string()), false),
+        case(Some("alice's".to_string()), false),
+        case(Some("alice-s-stream".to_string()), true),
+        case(Some("2020".to_string()), true),
+        case(None, true),
+        ::trace
+    )]
+    #[test]
+    fn test_slug_if_present(raw_s: Option<String>, expected: bool) {
+        let f = slug_if_present();
+        let s = &raw_s;
+
+        assert_eq!(expected, f(s).is_ok());
+    }
+
+    #[rstest(
+        raw_s, expected,
+        case(Some("api".to_string()), false),
+        case(Some("piano".to_string()), true),
+        case(None, true),
+        ::trace
+    )]
+    #[test]
+    fn test_not_reserved_if_present(raw_s: Option<String>, expected: bool) {
+        let f = not_reserved_if_present();
+        let s = &raw_s;
+
+        assert_eq!(expected, f(s).is_ok());
+    }
+
+    #[rstest(
+        raw_s, expected,
+        case(Some("primary_owner".to_string()), false),
+        case(Some("admin".to_string()), false),
+        case(Some("owner".to_string()), true),
+        case(Some("member".to_string()), true),
+        case(None, true),
+        ::trace
+    )]
+    #[test]
+    fn test_assignable_role_if_present(raw_s: Option<String>, expected: bool) {
+        let f = assignable_role_if_present();
+        let s = &raw_s;
+
+        assert_eq!(expected, f(s).is_ok());
+    }
 }
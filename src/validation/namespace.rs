@@ -6,6 +6,7 @@ use rocket_contrib::json::Json;
 use crate::logger::Logger;
 use crate::model::namespace::NewNamespace;
 use crate::request::namespace::Namespace as RequestData;
+use crate::validation::*;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ValidationError {
@@ -25,10 +26,22 @@ impl<'a> Validator<'a> {
 
     #[allow(clippy::redundant_closure)]
     pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let key = self.data.0.key.clone();
         let n = NewNamespace::from(self.data.0.clone());
         let result = rules! {
             "name" => n.name => [length(3, 255)],
-            "description" => n.description => [length_if_present(0, 3000)]
+            "key" => key => [
+                max_if_present(64),
+                slug_if_present(),
+                not_reserved_if_present()
+            ],
+            "description" => n.description => [length_if_present(0, 3000)],
+            "ingestion_rate_limit" => self.data.0.ingestion_rate_limit => [
+                within_if_present(0, 1_000_000)
+            ],
+            "ingestion_daily_quota" => self.data.0.ingestion_daily_quota => [
+                within_if_present(0, 1_000_000_000)
+            ]
         };
         if let Err(v) = result {
             // MultipleError to Vec<ValidationError>
@@ -173,6 +186,8 @@ mod test {
             let data = Json(RequestData {
                 description: Some("text".repeat(751)),
                 name: Some("name".to_string()),
+
+                ..Default::default()
             });
             let v = Validator::new(&data, logger);
 
@@ -198,6 +213,8 @@ mod test {
             let data = Json(RequestData {
                 description: None,
                 name: Some("name".to_string()),
+
+                ..Default::default()
             });
             let v = Validator::new(&data, logger);
 
@@ -212,6 +229,8 @@ mod test {
             let data = Json(RequestData {
                 description: Some("text".repeat(750)),
                 name: Some("name".to_string()),
+
+                ..Default::default()
             });
             let v = Validator::new(&data, logger);
 
@@ -220,6 +239,94 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_validate_key_is_invalid() {
+        run(|logger| {
+            let data = Json(RequestData {
+                name: Some("name".to_string()),
+                key: Some("alice's".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("key", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain only lowercase letters, digits and \
+                          hyphens"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_key_is_reserved() {
+        run(|logger| {
+            let data = Json(RequestData {
+                name: Some("name".to_string()),
+                key: Some("api".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("key", errors[0].field);
+                assert_eq!(
+                    vec!["Must not be a reserved word"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_ingestion_limits_are_out_of_range() {
+        run(|logger| {
+            let data = Json(RequestData {
+                name: Some("name".to_string()),
+                ingestion_rate_limit: Some(-1),
+                ingestion_daily_quota: Some(1_000_000_001),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(2, errors.len());
+                assert_eq!("ingestion_rate_limit", errors[0].field);
+                assert_eq!(
+                    vec!["Must be in the range 0..1000000"],
+                    errors[0].messages
+                );
+                assert_eq!("ingestion_daily_quota", errors[1].field);
+                assert_eq!(
+                    vec!["Must be in the range 0..1000000000"],
+                    errors[1].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
     #[test]
     fn test_validate_fields_are_default() {
         run(|logger| {
@@ -249,12 +356,15 @@ mod test {
         run(|logger| {
             let data = &Json(RequestData {
                 name: Some("changelog".to_string()),
+                key: Some("changelog".to_string()),
                 description: Some(
                     r#"
 This is a namespace for testing.
 "#
                     .to_string(),
                 ),
+                ingestion_rate_limit: Some(1200),
+                ingestion_daily_quota: Some(0),
             });
             let v = Validator::new(data, logger);
 
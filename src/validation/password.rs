@@ -0,0 +1,234 @@
+//! # PasswordPolicy
+//!
+//! PasswordPolicy holds the rules for new passwords. They are shared by user
+//! registration, password reset and password change.
+//!
+//! Breached passwords are looked up in a local list of SHA-1 hashes (e.g. a
+//! subset of Pwned Passwords), which is loaded at startup. Like k-anonymity
+//! range queries, the hashes are grouped by their first 5 characters, and only
+//! the suffixes within the prefix are compared.
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use accord::validators::length;
+use openssl::sha::sha1;
+
+use crate::config::Config;
+use crate::validation::*;
+
+const HASH_LENGTH: usize = 40;
+const PREFIX_LENGTH: usize = 5;
+
+// the local part of the email shorter than this isn't banned
+const MIN_BANNED_LENGTH: usize = 3;
+
+/// Returns the SHA-1 hash of the password in upper case hex.
+fn digest(password: &str) -> String {
+    sha1(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+pub struct PasswordPolicy {
+    min_length: usize,
+    // prefix -> suffixes (sorted)
+    breached: HashMap<String, Vec<String>>,
+}
+
+impl PasswordPolicy {
+    pub const MAX_LENGTH: usize = 1024;
+
+    pub fn new(min_length: usize) -> Self {
+        Self {
+            min_length,
+            breached: HashMap::new(),
+        }
+    }
+
+    /// Builds a policy by the config. The breached password list is read if
+    /// it's given.
+    pub fn from(config: &Config) -> Result<Self, String> {
+        let mut policy = Self::new(config.password_min_length);
+        let path = &config.password_breached_list;
+        if !path.is_empty() {
+            let file = File::open(path)
+                .map_err(|e| format!("failed to open {}: {}", path, e))?;
+            policy.load(BufReader::new(file))?;
+        }
+        Ok(policy)
+    }
+
+    /// Loads hashes from lines like `HASH` or `HASH:COUNT`. Blank lines and
+    /// comments (`#`) are skipped.
+    pub fn load<R: BufRead>(&mut self, reader: R) -> Result<(), String> {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let hash = line.split(':').next().unwrap_or("").to_uppercase();
+            if hash.len() != HASH_LENGTH ||
+                !hash.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return Err(format!("invalid hash at line {}", i + 1));
+            }
+            let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+            self.breached
+                .entry(prefix.to_string())
+                .or_insert_with(Vec::new)
+                .push(suffix.to_string());
+        }
+        for suffixes in self.breached.values_mut() {
+            suffixes.sort();
+            suffixes.dedup();
+        }
+        Ok(())
+    }
+
+    pub fn is_breached(&self, password: &str) -> bool {
+        let hash = digest(password);
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+        match self.breached.get(prefix) {
+            Some(suffixes) => {
+                suffixes.binary_search_by(|s| s.as_str().cmp(suffix)).is_ok()
+            },
+            None => false,
+        }
+    }
+
+    /// Validates the password of the user. The messages are returned as an
+    /// error of the `password` field.
+    pub fn validate(
+        &self,
+        password: &str,
+        username: &str,
+        email: &str,
+    ) -> Result<(), ValidationError> {
+        let mut rules: Vec<SV> = vec![
+            contain_any(CHARS_LOWER, "a-z"),
+            contain_any(CHARS_UPPER, "A-Z"),
+            contain_any(DIGITS, "0-9"),
+            not_overlap_with("username")(username.to_string()),
+        ];
+        let local_part = email.split('@').next().unwrap_or("");
+        if local_part.len() >= MIN_BANNED_LENGTH {
+            rules.push(not_overlap_with("email")(local_part.to_string()));
+        }
+        rules.push(length(self.min_length, Self::MAX_LENGTH));
+
+        let s = password.to_string();
+        let mut messages: Vec<String> = rules
+            .iter()
+            .filter_map(|f| f(&s).err())
+            .map(|i| i.human_readable)
+            .collect();
+
+        if self.is_breached(password) {
+            messages.push(
+                "Must not be a password exposed in a data breach".to_string(),
+            );
+        }
+
+        if !messages.is_empty() {
+            return Err(ValidationError {
+                field: "password".to_string(),
+                messages,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[rustfmt::skip::attributes(rstest)]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rstest::rstest;
+
+    // SHA-1 of "Passw0rd"
+    const BREACHED: &str = "\
+        # comment\n\
+        \n\
+        EBFC7910077770C8340F63CD2DCA2AC1F120444F:12\n\
+        ebfc700000000000000000000000000000000000\n";
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            "EBFC7910077770C8340F63CD2DCA2AC1F120444F",
+            digest("Passw0rd")
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let mut policy = PasswordPolicy::new(8);
+        assert!(policy.load(BREACHED.as_bytes()).is_ok());
+        assert_eq!(1, policy.breached.len());
+        assert_eq!(2, policy.breached["EBFC7"].len());
+
+        let mut policy = PasswordPolicy::new(8);
+        let result = policy.load("EBFC7:1\n".as_bytes());
+        assert_eq!(Err("invalid hash at line 1".to_string()), result);
+    }
+
+    #[test]
+    fn test_is_breached() {
+        let mut policy = PasswordPolicy::new(8);
+        assert!(!policy.is_breached("Passw0rd"));
+
+        policy.load(BREACHED.as_bytes()).unwrap();
+        assert!(policy.is_breached("Passw0rd"));
+        assert!(!policy.is_breached("passw0rd"));
+    }
+
+    #[rstest(
+        min_length, password, messages,
+        case(8, "Sh0rt", vec!["Must contain more than 8 characters"]),
+        case(12, "Passw0rd!", vec!["Must contain more than 12 characters"]),
+        case(8, "passw0rd", vec!["Must contain 'A-Z'"]),
+        case(8, "Myu$ern4mE", vec!["Must not overlap with username"]),
+        case(8, "Postmaster1", vec!["Must not overlap with email"]),
+        case(8, "Passw0rd", vec![
+            "Must not be a password exposed in a data breach"
+        ]),
+        ::trace
+    )]
+    #[test]
+    fn test_validate_failure(
+        min_length: usize,
+        password: &'static str,
+        messages: Vec<&'static str>,
+    ) {
+        let mut policy = PasswordPolicy::new(min_length);
+        policy.load(BREACHED.as_bytes()).unwrap();
+
+        let result =
+            policy.validate(password, "u$ern4mE", "Postmaster@example.org");
+        assert!(result.is_err());
+
+        if let Err(e) = result {
+            assert_eq!("password", e.field);
+            assert_eq!(messages, e.messages);
+        }
+    }
+
+    #[rstest(
+        password, email,
+        case("Str0ngPassword", "postmaster@example.org"),
+        // too short to be banned
+        case("Str0ngPassword", "wo@example.org"),
+        ::trace
+    )]
+    #[test]
+    fn test_validate(password: &'static str, email: &'static str) {
+        let mut policy = PasswordPolicy::new(8);
+        policy.load(BREACHED.as_bytes()).unwrap();
+
+        assert!(policy.validate(password, "username", email).is_ok());
+    }
+}
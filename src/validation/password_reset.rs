@@ -1,16 +1,17 @@
 use std::result::Result;
 
-use accord::validators::length;
 use diesel::PgConnection;
 use rocket_contrib::json::Json;
 
 use crate::logger::Logger;
 use crate::request::password_reset::PasswordReset as RequestData;
 use crate::validation::*;
+use crate::validation::password::PasswordPolicy;
 
 pub struct Validator<'a> {
     // conn: &'a PgConnection,
     data: &'a Json<RequestData>,
+    policy: &'a PasswordPolicy,
     logger: &'a Logger,
 }
 
@@ -18,41 +19,25 @@ impl<'a> Validator<'a> {
     pub fn new(
         _: &'a PgConnection,
         data: &'a Json<RequestData>,
+        policy: &'a PasswordPolicy,
         logger: &'a Logger,
     ) -> Self {
-        Self { data, logger }
+        Self {
+            data,
+            policy,
+            logger,
+        }
     }
 
-    #[allow(clippy::redundant_closure)]
     pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
-        let result = rules! {
-            // TODO: share this rule with a validation for user registration
-            "password" => self.data.0.password => [
-                contain_any(CHARS_LOWER, "a-z"),
-                contain_any(CHARS_UPPER, "A-Z"),
-                contain_any(DIGITS, "0-9"),
-                not_overlap_with("username")(self.data.0.username.to_string()),
-                length(8, 1024)
-            ]
-        };
-
         let mut errors: Vec<ValidationError> = vec![];
 
-        if let Err(v) = result {
-            // MultipleError to Vec<ValidationError>
-            errors =
-                v.0.iter()
-                    .map(|e| {
-                        ValidationError {
-                            field: e.tag.to_string(),
-                            messages: e
-                                .invalids
-                                .iter()
-                                .map(|i| i.human_readable.to_string())
-                                .collect(),
-                        }
-                    })
-                    .collect();
+        if let Err(e) = self.policy.validate(
+            &self.data.0.password,
+            &self.data.0.username,
+            &self.data.0.email,
+        ) {
+            errors.push(e);
         }
 
         if !errors.is_empty() {
@@ -80,14 +65,25 @@ mod test {
 
     use crate::model::test::run;
 
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy::new(8)
+    }
+
     #[test]
     fn test_validate_password_is_too_short() {
         run(|_, _, logger| {
             let data = &Json(RequestData {
                 username: "username".to_string(),
                 password: "Sh0rt".to_string(),
+
+                ..Default::default()
             });
-            let v = Validator { data, logger };
+            let policy = &policy();
+            let v = Validator {
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -111,8 +107,15 @@ mod test {
             let data = &Json(RequestData {
                 username: "username".to_string(),
                 password: "L0ng".repeat(257),
+
+                ..Default::default()
             });
-            let v = Validator { data, logger };
+            let policy = &policy();
+            let v = Validator {
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -136,8 +139,15 @@ mod test {
             let data = &Json(RequestData {
                 username: "Passw0rd".to_string(),
                 password: "Passw0rd".to_string(),
+
+                ..Default::default()
             });
-            let v = Validator { data, logger };
+            let policy = &policy();
+            let v = Validator {
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -172,8 +182,15 @@ mod test {
             let data = &Json(RequestData {
                 username: username.to_string(),
                 password: password.to_string(),
+
+                ..Default::default()
             });
-            let v = Validator { data, logger };
+            let policy = &policy();
+            let v = Validator {
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -208,8 +225,15 @@ mod test {
             let data = &Json(RequestData {
                 username: username.to_string(),
                 password: password.to_string(),
+
+                ..Default::default()
             });
-            let v = Validator { data, logger };
+            let policy = &policy();
+            let v = Validator {
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -243,8 +267,15 @@ mod test {
             let data = &Json(RequestData {
                 username: "username".to_string(),
                 password: password.to_string(),
+
+                ..Default::default()
             });
-            let v = Validator { data, logger };
+            let policy = &policy();
+            let v = Validator {
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
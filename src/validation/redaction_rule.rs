@@ -0,0 +1,191 @@
+use std::result::Result;
+
+use accord::{Invalid, ValidatorResult};
+use accord::validators::length;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::redaction_rule::{NewRedactionRule, RedactionDetector};
+use crate::request::redaction_rule::RedactionRule as RequestData;
+use crate::service::redactor;
+use crate::validation::*;
+
+fn known_detector() -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        let v = s.as_ref().map(|v| v.to_ascii_lowercase());
+        if RedactionDetector::iter().any(|d| Some(d.to_string()) == v) {
+            return Ok(());
+        }
+        let detectors = RedactionDetector::iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        Err(Invalid {
+            msg: "Must be one of %1".to_string(),
+            args: vec![detectors.clone()],
+            human_readable: format!("Must be one of {}", detectors),
+        })
+    })
+}
+
+// a custom detector requires a valid regular expression
+fn valid_pattern_for(
+    detector: RedactionDetector,
+) -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        if detector != RedactionDetector::Custom {
+            return Ok(());
+        }
+        match s {
+            Some(v) if !v.is_empty() && redactor::compile(v).is_ok() => Ok(()),
+            _ => {
+                Err(Invalid {
+                    msg: "Must be a valid regular expression".to_string(),
+                    args: vec![],
+                    human_readable: "Must be a valid regular expression"
+                        .to_string(),
+                })
+            },
+        }
+    })
+}
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let detector = self.data.0.detector.clone();
+        let r = NewRedactionRule::from(self.data.0.clone());
+        let result = rules! {
+            "name" => r.name => [length(1, 64)],
+            "detector" => detector => [known_detector()],
+            "pattern" => r.pattern => [valid_pattern_for(r.detector.clone())]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+
+    #[test]
+    fn test_validate_detector_is_unknown() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("phone".to_string()),
+                detector: Some("phone".to_string()),
+                pattern: None,
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("detector", errors[0].field);
+                assert_eq!(
+                    vec![
+                        "Must be one of email, credit_card, jwt, \
+                         bearer_token, custom"
+                    ],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_pattern_is_invalid() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("session".to_string()),
+                detector: Some("custom".to_string()),
+                pattern: Some("sid=[".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("pattern", errors[0].field);
+                assert_eq!(
+                    vec!["Must be a valid regular expression"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_pattern_is_none_for_custom() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("session".to_string()),
+                detector: Some("custom".to_string()),
+                pattern: None,
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                name: Some("email".to_string()),
+                detector: Some("email".to_string()),
+                pattern: None,
+            });
+            let v = Validator::new(&data, logger);
+            assert!(v.validate().is_ok());
+
+            let data = Json(RequestData {
+                name: Some("session".to_string()),
+                detector: Some("custom".to_string()),
+                pattern: Some(r"sid=[0-9a-f]+".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+            assert!(v.validate().is_ok());
+        })
+    }
+}
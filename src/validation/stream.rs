@@ -0,0 +1,198 @@
+use std::result::Result;
+
+use accord::validators::{length, length_if_present};
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::stream::NewStream;
+use crate::request::stream::Stream as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let slug = self.data.0.slug.clone();
+        let s = NewStream::from(self.data.0.clone());
+        let result = rules! {
+            "name" => s.name => [length(1, 64)],
+            "slug" => slug => [
+                max_if_present(64),
+                slug_if_present(),
+                not_reserved_if_present()
+            ],
+            "description" => s.description => [length_if_present(0, 128)]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    use dotenv::dotenv;
+    use rocket_contrib::json::Json;
+
+    use crate::config::Config;
+    use crate::logger::{Logger, get_logger};
+
+    pub fn run<T>(test: T)
+    where T: FnOnce(&Logger) + panic::UnwindSafe {
+        // TODO: remove dotenv from here
+        dotenv().ok();
+        let config = Config::from("testing").unwrap();
+        let logger = get_logger(&config);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| test(&logger)));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validate_name_is_none() {
+        run(|logger| {
+            let data = Json(RequestData {
+                name: None,
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("name", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain more than 1 characters"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_name_is_too_long() {
+        run(|logger| {
+            let data = Json(RequestData {
+                name: Some("name".repeat(17)),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("name", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain less than 64 characters"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_description_is_too_long() {
+        run(|logger| {
+            let data = Json(RequestData {
+                description: Some("text".repeat(33)),
+                name: Some("name".to_string()),
+                slug: None,
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("description", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain less than 128 characters"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_slug_is_invalid() {
+        run(|logger| {
+            let data = Json(RequestData {
+                name: Some("name".to_string()),
+                slug: Some("-main".to_string()),
+
+                ..Default::default()
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("slug", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain only lowercase letters, digits and \
+                          hyphens"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|logger| {
+            let data = Json(RequestData {
+                description: None,
+                name: Some("main".to_string()),
+                slug: Some("main".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
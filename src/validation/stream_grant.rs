@@ -0,0 +1,145 @@
+use std::result::Result;
+
+use accord::{Invalid, ValidatorResult};
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::stream_grant::Role;
+use crate::request::stream_grant::StreamGrant as RequestData;
+use crate::validation::*;
+
+fn known_role() -> Box<dyn Fn(&Option<String>) -> ValidatorResult> {
+    Box::new(move |s: &Option<String>| {
+        let v = s.as_ref().map(|v| v.to_ascii_lowercase());
+        if Role::iter().any(|r| Some(r.to_string()) == v) {
+            return Ok(());
+        }
+        let roles = Role::iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        Err(Invalid {
+            msg: "Must be one of %1".to_string(),
+            args: vec![roles.clone()],
+            human_readable: format!("Must be one of {}", roles),
+        })
+    })
+}
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let role = self.data.0.role.clone();
+        let result = rules! {
+            "role" => role => [known_role()]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+
+    #[test]
+    fn test_validate_role_is_none() {
+        run(|_, _, logger| {
+            let data = Json(RequestData { role: None });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("role", errors[0].field);
+                assert_eq!(
+                    vec!["Must be one of writer, member, maintainer, admin"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_role_is_owner() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                role: Some("owner".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+        })
+    }
+
+    #[test]
+    fn test_validate_role_is_unknown() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                role: Some("superuser".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("role", errors[0].field);
+                assert_eq!(
+                    vec!["Must be one of writer, member, maintainer, admin"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                role: Some("Writer".to_string()),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
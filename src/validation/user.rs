@@ -8,10 +8,12 @@ use crate::logger::Logger;
 use crate::model::user::{NewUser, User};
 use crate::request::user::registration::UserRegistration as RequestData;
 use crate::validation::*;
+use crate::validation::password::PasswordPolicy;
 
 pub struct Validator<'a> {
     conn: &'a PgConnection,
     data: &'a Json<RequestData>,
+    policy: &'a PasswordPolicy,
     logger: &'a Logger,
 }
 
@@ -19,9 +21,15 @@ impl<'a> Validator<'a> {
     pub fn new(
         conn: &'a PgConnection,
         data: &'a Json<RequestData>,
+        policy: &'a PasswordPolicy,
         logger: &'a Logger,
     ) -> Self {
-        Self { conn, data, logger }
+        Self {
+            conn,
+            data,
+            policy,
+            logger,
+        }
     }
 
     fn validate_email_uniqueness(&self) -> Result<(), ValidationError> {
@@ -73,13 +81,6 @@ impl<'a> Validator<'a> {
                 contains("@"),
                 contains("."),
                 length(6, 128)
-            ],
-            "password" => self.data.0.password => [
-                contain_any(CHARS_LOWER, "a-z"),
-                contain_any(CHARS_UPPER, "A-Z"),
-                contain_any(DIGITS, "0-9"),
-                not_overlap_with("username")(u.username),
-                length(8, 1024)
             ]
         };
 
@@ -102,6 +103,14 @@ impl<'a> Validator<'a> {
                     .collect();
         }
 
+        if let Err(e) = self.policy.validate(
+            &self.data.0.password,
+            &self.data.0.username,
+            &self.data.0.email,
+        ) {
+            errors.push(e);
+        }
+
         if !errors.iter().any(|e| "email" == e.field) {
             if let Err(e) = self.validate_email_uniqueness() {
                 errors.push(e);
@@ -131,6 +140,10 @@ mod test {
 
     use crate::model::test::run;
 
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy::new(8)
+    }
+
     #[test]
     fn test_validate_email_is_empty() {
         run(|conn, _, logger| {
@@ -141,7 +154,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -173,7 +192,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -201,7 +226,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -233,7 +264,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -261,7 +298,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -293,7 +336,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_ok());
@@ -308,8 +357,15 @@ mod test {
                 name: Some("long".repeat(26)),
                 username: "username".to_string(),
                 password: "Passw0rd".to_string(),
+                invitation_token: None,
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -335,8 +391,15 @@ mod test {
                 name: None,
                 username: "username".to_string(),
                 password: "Passw0rd".to_string(),
+                invitation_token: None,
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_ok());
@@ -351,8 +414,15 @@ mod test {
                 name: Some("Lorem ipsum".to_string()),
                 username: "username".to_string(),
                 password: "Passw0rd".to_string(),
+                invitation_token: None,
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_ok());
@@ -369,7 +439,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -397,7 +473,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -435,7 +517,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -488,7 +576,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -516,7 +610,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -555,7 +655,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_ok());
@@ -579,7 +685,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -607,7 +719,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -635,7 +753,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -663,7 +787,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -691,7 +821,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -709,6 +845,40 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_validate_password_contains_email() {
+        run(|conn, _, logger| {
+            let data = &Json(RequestData {
+                email: "postmaster@example.org".to_string(),
+                username: "username".to_string(),
+                password: "Mypostmaster1sAPartOfPassw0rd".to_string(),
+
+                ..Default::default()
+            });
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("password", errors[0].field);
+                assert_eq!(
+                    vec!["Must not overlap with email"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
     #[test]
     fn test_validate_password_is_included_in_username() {
         run(|conn, _, logger| {
@@ -719,7 +889,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -757,7 +933,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -774,7 +956,7 @@ mod test {
 
     #[test]
     fn test_validate_email_uniqueness() {
-        run(|conn, _, logger| {
+        run(|conn, config, logger| {
             let data = &Json(RequestData {
                 email: "postmaster@example.org".to_string(),
                 username: "username".to_string(),
@@ -784,7 +966,7 @@ mod test {
             });
 
             let mut u = NewUser::from(&data.0);
-            u.set_password(&data.password);
+            u.set_password(&data.password, config);
 
             let _id = User::insert(&u, conn, logger)
                 .unwrap_or_else(|| panic!("Error inserting: {}", u));
@@ -796,7 +978,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
@@ -813,7 +1001,7 @@ mod test {
 
     #[test]
     fn test_validate_username_uniqueness() {
-        run(|conn, _, logger| {
+        run(|conn, config, logger| {
             let data = &Json(RequestData {
                 email: "postmaster@example.org".to_string(),
                 username: "username".to_string(),
@@ -823,7 +1011,7 @@ mod test {
             });
 
             let mut u = NewUser::from(&data.0);
-            u.set_password(&data.password);
+            u.set_password(&data.password, config);
 
             let _id = User::insert(&u, conn, logger)
                 .unwrap_or_else(|| panic!("Error inserting: {}", u));
@@ -835,7 +1023,13 @@ mod test {
 
                 ..Default::default()
             });
-            let v = Validator { conn, data, logger };
+            let policy = &policy();
+            let v = Validator {
+                conn,
+                data,
+                policy,
+                logger,
+            };
 
             let result = v.validate();
             assert!(result.is_err());
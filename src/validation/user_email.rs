@@ -0,0 +1,141 @@
+use std::result::Result;
+
+use accord::validators::{contains, length};
+use diesel::PgConnection;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::user::User;
+use crate::request::user_email::UserEmail as RequestData;
+use crate::validation::*;
+
+pub struct Validator<'a> {
+    conn: &'a PgConnection,
+    data: &'a Json<RequestData>,
+    logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        conn: &'a PgConnection,
+        data: &'a Json<RequestData>,
+        logger: &'a Logger,
+    ) -> Self {
+        Self { conn, data, logger }
+    }
+
+    // the address must not be used by anyone (including the user)
+    fn validate_email_uniqueness(&self) -> Result<(), ValidationError> {
+        let email = self.data.0.email.as_deref().unwrap_or_default();
+        if !User::check_email_uniqueness(email, self.conn, self.logger) {
+            return Err(ValidationError {
+                field: "email".to_string(),
+                messages: vec!["Already exists".to_string()],
+            });
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let email = self.data.0.email.clone().unwrap_or_default();
+        let result = rules! {
+            "email" => email => [
+                contains("@"),
+                contains("."),
+                length(6, 64)
+            ]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+
+        if let Err(e) = self.validate_email_uniqueness() {
+            return Err(vec![e]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use diesel::{self, prelude::*};
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+    use crate::model::user::users;
+    use crate::model::user::data::USERS;
+
+    #[test]
+    fn test_validate_email_is_none() {
+        run(|conn, _, logger| {
+            let data = Json(RequestData { email: None });
+            let v = Validator::new(conn, &data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("email", errors[0].field);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_email_already_exists() {
+        run(|conn, _, logger| {
+            let u = USERS.get("oswald").unwrap();
+            let email = diesel::insert_into(users::table)
+                .values(u)
+                .returning(users::email)
+                .get_result::<String>(conn)
+                .unwrap_or_else(|e| panic!("Error at inserting: {}", e));
+
+            let data = Json(RequestData { email: Some(email) });
+            let v = Validator::new(conn, &data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("email", errors[0].field);
+                assert_eq!(vec!["Already exists"], errors[0].messages);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|conn, _, logger| {
+            let data = Json(RequestData {
+                email: Some("oswald@example.com".to_string()),
+            });
+            let v = Validator::new(conn, &data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
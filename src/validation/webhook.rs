@@ -0,0 +1,174 @@
+use std::result::Result;
+
+use accord::{Invalid, ValidatorResult};
+use accord::validators::length;
+use rocket_contrib::json::Json;
+
+use crate::logger::Logger;
+use crate::model::webhook::{NewWebhook, WebhookEvent};
+use crate::request::webhook::Webhook as RequestData;
+use crate::validation::*;
+
+fn subscribable_events() -> Box<dyn Fn(&Vec<String>) -> ValidatorResult> {
+    Box::new(move |v: &Vec<String>| {
+        if v.is_empty() {
+            return Err(Invalid {
+                msg: "Must contain at least an event".to_string(),
+                args: vec![],
+                human_readable: "Must contain at least an event".to_string(),
+            });
+        }
+        for s in v {
+            if WebhookEvent::find(s).is_none() {
+                return Err(Invalid {
+                    msg: "Must not contain unknown event %1".to_string(),
+                    args: vec![s.to_string()],
+                    human_readable: format!(
+                        "Must not contain unknown event '{}'",
+                        s
+                    ),
+                });
+            }
+        }
+        Ok(())
+    })
+}
+
+pub struct Validator<'a> {
+    data: &'a Json<RequestData>,
+    _logger: &'a Logger,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(data: &'a Json<RequestData>, _logger: &'a Logger) -> Self {
+        Self { data, _logger }
+    }
+
+    #[allow(clippy::redundant_closure)]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let w = NewWebhook::from(self.data.0.clone());
+        let result = rules! {
+            "url" => w.url => [length(1, 2048), http_url()],
+            "events" => w.events => [subscribable_events()]
+        };
+        if let Err(v) = result {
+            // MultipleError to Vec<ValidationError>
+            let errors =
+                v.0.iter()
+                    .map(|e| {
+                        ValidationError {
+                            field: e.tag.to_string(),
+                            messages: e
+                                .invalids
+                                .iter()
+                                .map(|i| i.human_readable.to_string())
+                                .collect(),
+                        }
+                    })
+                    .collect();
+            return Err(errors);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket_contrib::json::Json;
+
+    use crate::model::test::run;
+
+    #[test]
+    fn test_validate_url_is_not_http() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                url: Some("ftp://example.org/".to_string()),
+                events: Some(vec!["alert.fired".to_string()]),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("url", errors[0].field);
+                assert_eq!(vec!["Must be a HTTP(S) URL"], errors[0].messages);
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_events_contain_unknown_event() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                url: Some("https://example.org/hook".to_string()),
+                events: Some(vec![
+                    "alert.fired".to_string(),
+                    "ping".to_string(),
+                ]),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("events", errors[0].field);
+                assert_eq!(
+                    vec!["Must not contain unknown event 'ping'"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate_events_is_none() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                url: Some("https://example.org/hook".to_string()),
+                events: None,
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_err());
+
+            if let Err(errors) = &result {
+                assert_eq!(1, errors.len());
+                assert_eq!("events", errors[0].field);
+                assert_eq!(
+                    vec!["Must contain at least an event"],
+                    errors[0].messages
+                );
+            } else {
+                panic!("must fail");
+            }
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        run(|_, _, logger| {
+            let data = Json(RequestData {
+                url: Some("https://example.org/hook".to_string()),
+                events: Some(vec![
+                    "message.critical".to_string(),
+                    "alert.resolved".to_string(),
+                ]),
+            });
+            let v = Validator::new(&data, logger);
+
+            let result = v.validate();
+            assert!(result.is_ok());
+        })
+    }
+}
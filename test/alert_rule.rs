@@ -0,0 +1,178 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, make_raw_password, MEMBERSHIPS, NAMESPACES, STREAMS,
+    USERS,
+};
+
+#[test]
+fn test_hset_and_hgetall() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace_id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let stream = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .get_result::<model::stream::Stream>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let mut res = client
+            .post(format!("/v1/alert_rule/{}/hset", stream.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(
+                r#"{
+                    "name": "too many errors",
+                    "level": "error",
+                    "threshold": 3,
+                    "window_minutes": 5,
+                    "cooldown_minutes": 30
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let uuid = result["alert_rule"]["uuid"].as_str().unwrap();
+
+        let mut res = client
+            .get(format!("/v1/alert_rule/{}/hgetall", stream.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result[0]["alert_rule"]["uuid"].as_str().unwrap(), uuid);
+        assert_eq!(result[0]["alert_rule"]["threshold"].as_i64().unwrap(), 3);
+    });
+}
+
+#[test]
+fn test_hset_with_invalid_window() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace_id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let stream = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .get_result::<model::stream::Stream>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let res = client
+            .post(format!("/v1/alert_rule/{}/hset", stream.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(
+                r#"{
+                    "name": "too many errors",
+                    "window_minutes": 0
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
@@ -0,0 +1,130 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, login, make_raw_password, USERS};
+
+#[test]
+fn test_lrange() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let oswald = load_user(u, conn.db);
+        let oswald_token = login(client, &oswald, &password);
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let weenie = load_user(u, conn.db);
+        let weenie_token = login(client, &weenie, &password);
+
+        let mut res = client
+            .post("/v1/namespace/hset")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"name": "piano", "key": "piano"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let uuid = result["namespace"]["uuid"].as_str().unwrap().to_string();
+
+        let namespace_id = model::namespace::namespaces::table
+            .select(model::namespace::namespaces::id)
+            .filter(
+                model::namespace::namespaces::uuid
+                    .eq(Uuid::parse_str(&uuid).unwrap()),
+            )
+            .first::<i64>(conn.db)
+            .unwrap();
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values((
+                model::membership::memberships::namespace_id.eq(namespace_id),
+                model::membership::memberships::user_id.eq(weenie.id),
+                model::membership::memberships::role
+                    .eq(model::membership::MembershipRole::Member),
+            ))
+            .execute(conn.db)
+            .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+        let mut res = client
+            .get(format!("/v1/audit_event/{}/lrange/0/10", uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let events = result.as_array().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0]["audit_event"]["action"].as_str().unwrap(),
+            "namespace.create"
+        );
+        assert_eq!(
+            events[0]["audit_event"]["diff"]["name"][1].as_str().unwrap(),
+            "piano"
+        );
+        assert_eq!(
+            events[0]["actor"]["uuid"].as_str().unwrap(),
+            oswald.uuid.to_string()
+        );
+
+        let mut res = client
+            .get(format!(
+                "/v1/audit_event/{}/lrange/0/10?action=stream.archive",
+                uuid
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result.as_array().unwrap().is_empty());
+
+        let res = client
+            .get(format!("/v1/audit_event/{}/lrange/0/10?action=unknown", uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        // only owners can read
+        let res = client
+            .get(format!("/v1/audit_event/{}/lrange/0/10", uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+    });
+}
@@ -1,9 +1,15 @@
+use chrono::Utc;
+use diesel::prelude::*;
 use fourche::queue::Queue;
+use redis::Commands;
 use rocket::http::{ContentType, Header, Status};
+use rocket::local::{Client, LocalResponse};
+use serde_json::Value;
 
 use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
 
-use crate::run_test;
+use crate::{run_test, load_user, login, make_raw_password, Connection, USERS};
 
 #[test]
 fn test_login_with_wrong_username() {
@@ -218,3 +224,258 @@ fn test_login() {
         assert_eq!(res.status(), Status::Ok);
     });
 }
+
+#[test]
+fn test_refresh_without_session() {
+    run_test(|client, _, _, _| {
+        let res = client
+            .post("/_/refresh")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Unauthorized);
+    });
+}
+
+#[test]
+fn test_refresh() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let _ = login(client, &user, &password);
+
+        let mut res = client
+            .post("/_/refresh")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap().to_string();
+
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        // the rotated refresh token is available
+        let res = client
+            .post("/_/refresh")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_refresh_after_logout() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = client
+            .post("/_/logout")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .post("/_/refresh")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Unauthorized);
+    });
+}
+
+#[test]
+fn test_browser_token_as_access_token_after_logout() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = client
+            .post("/_/logout")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert!(
+            [Status::Unauthorized, Status::NotFound].contains(&res.status())
+        );
+
+        // the browser token isn't a personal access token
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Access-Token {}", token),
+            ))
+            .dispatch();
+
+        assert!(
+            [Status::Unauthorized, Status::NotFound].contains(&res.status())
+        );
+    });
+}
+
+fn try_login<'a>(
+    client: &'a Client,
+    username: &str,
+    password: &str,
+) -> LocalResponse<'a> {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+              "username": "{}",
+              "password": "{}"
+            }}"#,
+            username, password,
+        ))
+        .dispatch()
+}
+
+fn attempt_keys(conn: &mut Connection) -> Vec<String> {
+    conn.ss.keys("attempt:login:account:*").unwrap()
+}
+
+#[test]
+fn test_login_with_too_many_failed_attempts() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        // same for an unknown account
+        for username in &[user.email.as_str(), "unknown@example.org"] {
+            for _ in 0..3 {
+                let res = try_login(client, username, "wrong");
+                assert_eq!(res.status(), Status::Unauthorized);
+            }
+
+            let keys = attempt_keys(conn);
+            assert_eq!(keys.len(), 1);
+
+            // delayed
+            let now = Utc::now().timestamp();
+            let res = try_login(client, username, "wrong");
+            assert_eq!(res.status(), Status::Unauthorized);
+
+            let next_at: i64 = conn.ss.hget(&keys[0], "next_at").unwrap();
+            assert!(next_at > now);
+
+            let _: () = conn.ss.hset(&keys[0], "next_at", now + 60).unwrap();
+            let res = try_login(client, username, &password);
+            assert_eq!(res.status(), Status::TooManyRequests);
+            assert!(res.headers().get_one("Retry-After").is_some());
+
+            let _: () = conn.ss.del(&keys).unwrap();
+        }
+    });
+}
+
+#[test]
+fn test_login_lockout_and_unlock() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let res = try_login(client, &user.email, "wrong");
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        // the next failure reaches the threshold
+        let keys = attempt_keys(conn);
+        assert_eq!(keys.len(), 1);
+        let _: () = conn.ss.hset(&keys[0], "count", 9).unwrap();
+
+        let res = try_login(client, &user.email, "wrong");
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let res = try_login(client, &user.email, &password);
+        assert_eq!(res.status(), Status::TooManyRequests);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::SendAccountUnlockEmail);
+        assert_eq!(job.args[0], user.id.to_string());
+
+        let res = client
+            .patch(format!("/_/unlock/{}", job.args[1]))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", job.args[2]),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = try_login(client, &user.email, &password);
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_login_upgrades_password_hash() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        // a hash made by bcrypt
+        let hash = bcrypt::hash(&password, 4).unwrap().into_bytes();
+        let _ = diesel::update(model::user::users::table.find(user.id))
+            .set(model::user::users::password.eq(&hash))
+            .execute(conn.db)
+            .unwrap();
+
+        let res = try_login(client, &user.email, &password);
+        assert_eq!(res.status(), Status::Ok);
+
+        let u = model::user::User::find_by_id(user.id, conn.db, logger);
+        assert!(u.unwrap().password.starts_with(b"$argon2id$"));
+
+        let res = try_login(client, &user.email, &password);
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
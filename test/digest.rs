@@ -0,0 +1,187 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, make_raw_password, MEMBERSHIPS, NAMESPACES, USERS,
+};
+
+#[test]
+fn test_hset_and_del() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut res = client
+            .post(format!("/v1/digest/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"frequency": "daily"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["digest_subscription"]["frequency"].as_str().unwrap(),
+            "Daily"
+        );
+
+        // changes the frequency
+        let res = client
+            .post(format!("/v1/digest/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"frequency": "weekly"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut res = client
+            .get("/v1/digest/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+        assert_eq!(
+            result[0]["digest_subscription"]["frequency"]
+                .as_str()
+                .unwrap(),
+            "Weekly"
+        );
+        assert_eq!(
+            result[0]["namespace"]["uuid"].as_str().unwrap(),
+            namespace.uuid.to_string()
+        );
+
+        let res = client
+            .delete(format!("/v1/digest/{}/del", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .delete(format!("/v1/digest/{}/del", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
+
+#[test]
+fn test_hset_with_unknown_frequency() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let res = client
+            .post(format!("/v1/digest/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"frequency": "monthly"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
@@ -0,0 +1,162 @@
+use diesel::{self, prelude::*};
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, login, make_raw_password, USERS};
+
+#[test]
+fn test_request_and_confirm() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = client
+            .put("/_/email/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"email": "oswald@example.com", "password": "wrong"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = client
+            .put("/_/email/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{"email": "oswald@example.com", "password": "{}"}}"#,
+                password
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let verification = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(
+            verification.kind,
+            job::JobKind::SendEmailChangeVerificationEmail
+        );
+        assert_eq!(verification.args.len(), 3);
+
+        let notice = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(notice.kind, job::JobKind::SendEmailChangeNoticeEmail);
+        assert_eq!(notice.args, verification.args);
+
+        assert!(verification.invoke(conn.db, config, logger).is_empty());
+        assert!(notice.invoke(conn.db, config, logger).is_empty());
+
+        // not changed yet
+        let email = model::user::users::table
+            .select(model::user::users::email)
+            .filter(model::user::users::id.eq(user.id))
+            .first::<String>(conn.db)
+            .unwrap();
+        assert_eq!(email, user.email);
+
+        let session_id = verification.args[1].to_string();
+        let res = client
+            .patch(format!("/_/email/change/{}", session_id))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", verification.args[2]),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let email = model::user::users::table
+            .select(model::user::users::email)
+            .filter(model::user::users::id.eq(user.id))
+            .first::<String>(conn.db)
+            .unwrap();
+        assert_eq!(email, "oswald@example.com");
+
+        // the session has been revoked
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_ne!(res.status(), Status::Ok);
+
+        // already used
+        let res = client
+            .patch(format!("/_/email/change/{}", session_id))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", verification.args[2]),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
+
+#[test]
+fn test_request_and_cancel() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = client
+            .put("/_/email/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{"email": "oswald@example.com", "password": "{}"}}"#,
+                password
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let _ = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        let notice = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(notice.kind, job::JobKind::SendEmailChangeNoticeEmail);
+
+        let res = client
+            .delete(format!("/_/email/change/{}", notice.args[1]))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", notice.args[2]),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let count = model::user_email::user_emails::table
+            .filter(model::user_email::user_emails::user_id.eq(user.id))
+            .count()
+            .get_result::<i64>(conn.db)
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // the session is still available
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
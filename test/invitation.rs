@@ -0,0 +1,267 @@
+use diesel::{self, PgConnection, prelude::*};
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, login, make_raw_password, MEMBERSHIPS, NAMESPACES,
+    USERS,
+};
+
+fn load_namespace(conn: &PgConnection) -> model::namespace::Namespace {
+    let ns = NAMESPACES.get("piano").unwrap();
+    let namespace = diesel::insert_into(model::namespace::namespaces::table)
+        .values(ns)
+        .get_result::<model::namespace::Namespace>(conn)
+        .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+    let mut ms = MEMBERSHIPS
+        .get("oswald as a primary owner")
+        .unwrap()
+        .clone();
+    ms.namespace_id = namespace.id;
+    let _ = diesel::insert_into(model::membership::memberships::table)
+        .values(&ms)
+        .returning(model::membership::memberships::id)
+        .get_result::<i64>(conn)
+        .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+    namespace
+}
+
+#[test]
+fn test_hset_and_accept() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let oswald = load_user(u, conn.db);
+        let oswald_token = login(client, &oswald, &password);
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let weenie = load_user(u, conn.db);
+        let weenie_token = login(client, &weenie, &password);
+
+        let namespace = load_namespace(conn.db);
+
+        // only owners can invite
+        let res = client
+            .post(format!("/v1/invitation/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(r#"{"email": "weenie@example.org"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+
+        let mut res = client
+            .post(format!("/v1/invitation/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"email": "weenie@example.org", "role": "owner"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["invitation"]["email"].as_str().unwrap(),
+            "weenie@example.org"
+        );
+
+        // already invited
+        let res = client
+            .post(format!("/v1/invitation/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"email": "weenie@example.org"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::SendInvitationEmail);
+        assert_eq!(job.args.len(), 2);
+
+        let jobs = job.invoke(conn.db, config, logger);
+        assert!(jobs.is_empty());
+
+        let token = job.args[1].to_string();
+
+        let res = client
+            .post("/v1/invitation/accept")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(r#"{"token": "invalid-token"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::BadRequest);
+
+        // only for the invited email address
+        let res = client
+            .post("/v1/invitation/accept")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(format!(r#"{{"token": "{}"}}"#, token))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::BadRequest);
+
+        let mut res = client
+            .post("/v1/invitation/accept")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(format!(r#"{{"token": "{}"}}"#, token))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["namespace"]["uuid"].as_str().unwrap(),
+            namespace.uuid.to_string()
+        );
+
+        let role = model::membership::memberships::table
+            .select(model::membership::memberships::role)
+            .filter(model::membership::memberships::user_id.eq(weenie.id))
+            .first::<model::membership::MembershipRole>(conn.db)
+            .unwrap();
+        assert_eq!(role, model::membership::MembershipRole::Owner);
+
+        // can't be used twice
+        let res = client
+            .post("/v1/invitation/accept")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(format!(r#"{{"token": "{}"}}"#, token))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::BadRequest);
+
+        // already a member
+        let res = client
+            .post(format!("/v1/invitation/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"email": "weenie@example.org"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
+
+#[test]
+fn test_resend_and_del() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let namespace = load_namespace(conn.db);
+
+        let mut res = client
+            .post(format!("/v1/invitation/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"email": "hennry@example.org"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let uuid = result["invitation"]["uuid"].as_str().unwrap().to_string();
+
+        let res = client
+            .post(format!("/v1/invitation/{}/resend/{}", namespace.uuid, uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Accepted);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let first = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        let second = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(first.kind, job::JobKind::SendInvitationEmail);
+        assert_eq!(second.kind, job::JobKind::SendInvitationEmail);
+        assert_ne!(first.args[1], second.args[1]);
+
+        let mut res = client
+            .get(format!("/v1/invitation/{}/hgetall", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+
+        let res = client
+            .delete(format!("/v1/invitation/{}/del/{}", namespace.uuid, uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut res = client
+            .get(format!("/v1/invitation/{}/hgetall", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result.as_array().unwrap().is_empty());
+    });
+}
@@ -0,0 +1,221 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, login, make_raw_password, MEMBERSHIPS, NAMESPACES,
+    USERS,
+};
+
+#[test]
+fn test_hupdate_transfer_and_del() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let oswald = load_user(u, conn.db);
+        let oswald_token = login(client, &oswald, &password);
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let weenie = load_user(u, conn.db);
+        let weenie_token = login(client, &weenie, &password);
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .execute(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values((
+                model::membership::memberships::namespace_id.eq(namespace.id),
+                model::membership::memberships::user_id.eq(weenie.id),
+                model::membership::memberships::role
+                    .eq(model::membership::MembershipRole::Member),
+            ))
+            .execute(conn.db)
+            .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+        let mut res = client
+            .get(format!("/v1/membership/{}/hgetall", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 2);
+
+        // members can't change roles
+        let res = client
+            .patch(format!(
+                "/v1/membership/{}/hset/{}",
+                namespace.uuid, weenie.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(r#"{"role": "owner"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        // the primary owner can't be demoted
+        let res = client
+            .patch(format!(
+                "/v1/membership/{}/hset/{}",
+                namespace.uuid, oswald.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"role": "member"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .patch(format!(
+                "/v1/membership/{}/hset/{}",
+                namespace.uuid, weenie.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"role": "primary_owner"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = client
+            .patch(format!(
+                "/v1/membership/{}/hset/{}",
+                namespace.uuid, weenie.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"role": "owner"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        // only the primary owner can transfer
+        let res = client
+            .post(format!(
+                "/v1/membership/{}/transfer/{}",
+                namespace.uuid, oswald.uuid
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .post(format!(
+                "/v1/membership/{}/transfer/{}",
+                namespace.uuid, weenie.uuid
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        use model::membership::memberships;
+
+        let roles = memberships::table
+            .select(memberships::role)
+            .filter(memberships::namespace_id.eq(namespace.id))
+            .order(memberships::user_id.asc())
+            .load::<model::membership::MembershipRole>(conn.db)
+            .unwrap();
+        assert_eq!(
+            roles,
+            vec![
+                model::membership::MembershipRole::Owner,
+                model::membership::MembershipRole::PrimaryOwner,
+            ]
+        );
+
+        use model::audit_event::audit_events;
+
+        let actions = audit_events::table
+            .select(audit_events::action)
+            .filter(audit_events::namespace_id.eq(namespace.id))
+            .order(audit_events::id.asc())
+            .load::<String>(conn.db)
+            .unwrap();
+        assert_eq!(
+            actions,
+            vec!["membership.role_change", "membership.transfer"]
+        );
+
+        // the new primary owner revokes the former one
+        let res = client
+            .delete(format!(
+                "/v1/membership/{}/del/{}",
+                namespace.uuid, oswald.uuid
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let res = client
+            .get(format!("/v1/namespace/hget/{}", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
@@ -1,4 +1,4 @@
-use diesel::{self, prelude::*};
+use diesel::{self, PgConnection, prelude::*};
 use chrono::{Utc, TimeZone};
 use rocket::http::{ContentType, Header, Status};
 use serde_json::Value;
@@ -7,9 +7,23 @@ use uuid::Uuid;
 use eloquentlog_console_api::model;
 
 use crate::{
-    minify, run_test, load_user, make_raw_password, NAMESPACES, STREAMS, USERS,
+    minify, run_test, load_user, make_raw_password, MEMBERSHIPS, NAMESPACES,
+    STREAMS, USERS,
 };
 
+// Makes oswald a primary owner of the namespace.
+fn load_membership(namespace_id: i64, conn: &PgConnection) {
+    let mut ms = MEMBERSHIPS
+        .get("oswald as a primary owner")
+        .unwrap()
+        .clone();
+    ms.namespace_id = namespace_id;
+    let _ = diesel::insert_into(model::membership::memberships::table)
+        .values(&ms)
+        .execute(conn)
+        .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+}
+
 #[test]
 fn test_lrange_no_message() {
     run_test(|client, conn, _, _| {
@@ -41,8 +55,24 @@ fn test_lrange_no_message() {
         let result: Value = serde_json::from_str(&body).unwrap();
         let token = result["token"].as_str().unwrap();
 
-        let namespace_key = "key";
-        let stream_slug = "slug";
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+        load_membership(namespace_id, conn.db);
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let _ = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .execute(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let namespace_key = "piano";
+        let stream_slug = "oswald-s-stream";
 
         let mut res = client
             .get(format!(
@@ -96,6 +126,7 @@ fn test_lrange_messages() {
                 .returning(model::namespace::namespaces::id)
                 .get_result::<i64>(conn.db)
                 .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+        load_membership(namespace_id, conn.db);
 
         let mut s = STREAMS.get("oswald's stream").unwrap().clone();
         s.namespace_id = namespace_id;
@@ -128,8 +159,8 @@ fn test_lrange_messages() {
             .get_result::<i64>(conn.db)
             .unwrap_or_else(|_| panic!("Error inserting: {}", m));
 
-        let namespace_key = "key";
-        let stream_slug = "slug"; // FIXME
+        let namespace_key = "piano";
+        let stream_slug = "oswald-s-stream";
 
         let mut res = client
             .get(format!(
@@ -198,8 +229,24 @@ fn test_append_with_validation_errors() {
         let result: Value = serde_json::from_str(&body).unwrap();
         let token = result["token"].as_str().unwrap();
 
-        let namespace_key = "key";
-        let stream_slug = "slug";
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+        load_membership(namespace_id, conn.db);
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let _ = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .execute(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let namespace_key = "piano";
+        let stream_slug = "oswald-s-stream";
 
         let mut res = client
             .post(format!(
@@ -257,8 +304,8 @@ fn test_append() {
         let result: Value = serde_json::from_str(&body).unwrap();
         let token = result["token"].as_str().unwrap();
 
-        let namespace_key = "key";
-        let stream_slug = "slug";
+        let namespace_key = "piano";
+        let stream_slug = "oswald-s-stream";
 
         let ns = NAMESPACES.get("piano").unwrap();
         let namespace_id =
@@ -267,6 +314,7 @@ fn test_append() {
                 .returning(model::namespace::namespaces::id)
                 .get_result::<i64>(conn.db)
                 .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+        load_membership(namespace_id, conn.db);
 
         let mut s = STREAMS.get("oswald's stream").unwrap().clone();
         s.namespace_id = namespace_id;
@@ -303,3 +351,204 @@ fn test_append() {
         assert!(res.body_string().unwrap().contains("id"));
     });
 }
+
+#[test]
+fn test_append_over_rate_limit() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let namespace_key = "piano";
+        let stream_slug = "oswald-s-stream";
+
+        // allows only a request per minute
+        let mut ns = NAMESPACES.get("piano").unwrap().clone();
+        ns.ingestion_rate_limit = Some(1);
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(&ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+        load_membership(namespace_id, conn.db);
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let stream_uuid = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .returning(model::stream::streams::uuid)
+            .get_result::<Uuid>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let body = format!(
+            r#"{{
+                "agent_id": 1,
+                "agent_type": "person",
+                "stream_id": 1,
+                "format": "toml",
+                "stream": "{}",
+                "title": "New message"
+            }}"#,
+            stream_uuid
+        );
+
+        let res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace_key, stream_slug
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(&body)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+        assert_eq!(res.headers().get_one("X-RateLimit-Limit"), Some("1"));
+        assert_eq!(res.headers().get_one("X-RateLimit-Remaining"), Some("0"));
+
+        let res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace_key, stream_slug
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(&body)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::TooManyRequests);
+        assert!(res.headers().get_one("Retry-After").is_some());
+        assert_eq!(res.headers().get_one("X-RateLimit-Remaining"), Some("0"));
+    });
+}
+
+#[test]
+fn test_append_over_daily_quota() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let namespace_key = "piano";
+        let stream_slug = "oswald-s-stream";
+
+        // allows only a message per day
+        let mut ns = NAMESPACES.get("piano").unwrap().clone();
+        ns.ingestion_daily_quota = Some(1);
+        let namespace_id =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(&ns)
+                .returning(model::namespace::namespaces::id)
+                .get_result::<i64>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+        load_membership(namespace_id, conn.db);
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace_id;
+        let stream_uuid = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .returning(model::stream::streams::uuid)
+            .get_result::<Uuid>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let body = format!(
+            r#"{{
+                "agent_id": 1,
+                "agent_type": "person",
+                "stream_id": 1,
+                "format": "toml",
+                "stream": "{}",
+                "title": "New message"
+            }}"#,
+            stream_uuid
+        );
+
+        let res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace_key, stream_slug
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(&body)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut res = client
+            .post(format!(
+                "/v1/message/{}/append/{}",
+                namespace_key, stream_slug
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(&body)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::TooManyRequests);
+        assert!(res.headers().get_one("Retry-After").is_some());
+        assert_eq!(
+            res.body_string().unwrap(),
+            r#"{"message":"Daily quota exceeded"}"#
+        );
+
+        let count = model::message::messages::table
+            .count()
+            .get_result::<i64>(conn.db)
+            .unwrap();
+        assert_eq!(count, 1);
+    });
+}
@@ -1,7 +1,9 @@
 use diesel::{self, prelude::*};
+use fourche::queue::Queue;
 use rocket::http::{ContentType, Header, Status};
 use serde_json::Value;
 
+use eloquentlog_console_api::job;
 use eloquentlog_console_api::model;
 
 use crate::{
@@ -116,6 +118,9 @@ fn test_hget_namespace() {
   "archived_at": null,
   "created_at": "2019-07-07T07:20:15",
   "description": "description",
+  "ingestion_daily_quota": null,
+  "ingestion_rate_limit": null,
+  "key": "piano",
   "name": "piano",
   "streams_count": 0,
   "updated_at": "2019-07-07T07:20:15",
@@ -235,6 +240,9 @@ fn test_hgetall_namespaces() {
   "archived_at": null,
   "created_at": "2019-07-07T07:20:15",
   "description": "description",
+  "ingestion_daily_quota": null,
+  "ingestion_rate_limit": null,
+  "key": "piano",
   "name": "piano",
   "streams_count": 0,
   "updated_at": "2019-07-07T07:20:15",
@@ -246,3 +254,467 @@ fn test_hgetall_namespaces() {
         );
     });
 }
+
+#[test]
+fn test_archive_and_unarchive() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut res = client
+            .post(format!("/v1/namespace/archive/{}", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(!result["namespace"]["archived_at"].is_null());
+
+        let res = client
+            .get(format!("/v1/namespace/hget/{}", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+
+        let mut res = client
+            .get("/v1/namespace/archived/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+
+        let mut res = client
+            .post(format!("/v1/namespace/unarchive/{}", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result["namespace"]["archived_at"].is_null());
+    });
+}
+
+#[test]
+fn test_archive_by_member() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        ms.role = model::membership::MembershipRole::Member;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let res = client
+            .post(format!("/v1/namespace/archive/{}", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+    });
+}
+
+#[test]
+fn test_hupdate() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "o"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "organ", "description": "Pipes"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["namespace"]["name"].as_str().unwrap(), "organ");
+        assert_eq!(
+            result["namespace"]["description"].as_str().unwrap(),
+            "Pipes"
+        );
+        assert_eq!(result["namespace"]["key"].as_str().unwrap(), "piano");
+
+        let res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "organ", "ingestion_rate_limit": -1}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(
+                r#"{
+                    "name": "organ",
+                    "ingestion_rate_limit": 1200,
+                    "ingestion_daily_quota": 500000
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["namespace"]["ingestion_rate_limit"].as_i64().unwrap(),
+            1200
+        );
+        assert_eq!(
+            result["namespace"]["ingestion_daily_quota"].as_i64().unwrap(),
+            500_000
+        );
+
+        // 0 resets the override, and the other one is kept
+        let mut res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "organ", "ingestion_rate_limit": 0}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result["namespace"]["ingestion_rate_limit"].is_null());
+        assert_eq!(
+            result["namespace"]["ingestion_daily_quota"].as_i64().unwrap(),
+            500_000
+        );
+    });
+}
+
+#[test]
+fn test_hupdate_key() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        // reserved
+        let res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "piano", "key": "admin"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch(format!("/v1/namespace/hset/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "piano", "key": "organ"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["namespace"]["key"].as_str().unwrap(), "organ");
+
+        // old key
+        let res = client
+            .get("/v1/message/piano/lrange/main/0/2")
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::PermanentRedirect);
+        assert_eq!(
+            res.headers().get_one("Location"),
+            Some("/v1/message/organ/lrange/main/0/2")
+        );
+    });
+}
+
+#[test]
+fn test_del() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let res = client
+            .delete(format!("/v1/namespace/del/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "organ"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = client
+            .delete(format!("/v1/namespace/del/{}", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "piano"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Accepted);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::DeleteNamespace);
+
+        let jobs = job.invoke(conn.db, config, logger);
+        assert!(jobs.is_empty());
+
+        let rows_count: i64 = model::namespace::namespaces::table
+            .count()
+            .first(conn.db)
+            .expect("Failed to count rows");
+        assert_eq!(0, rows_count);
+    });
+}
@@ -0,0 +1,139 @@
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+
+use eloquentlog_console_api::job;
+
+use crate::{run_test, load_user, login, make_raw_password, USERS};
+
+#[test]
+fn test_change_with_invalid_passwords() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = client
+            .patch("/_/password/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"password": "wrong", "new_password": "n3w-Passw0rd"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .patch("/_/password/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{"password": "{}", "new_password": "short"}}"#,
+                password
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["errors"][0]["field"].as_str().unwrap(),
+            "new_password"
+        );
+    });
+}
+
+#[test]
+fn test_change() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = client
+            .patch("/_/password/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{"password": "{}", "new_password": "n3w-Passw0rd"}}"#,
+                password
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::SendPasswordChangeNoticeEmail);
+        assert_eq!(job.args, vec![user.id.to_string()]);
+        assert!(job.invoke(conn.db, config, logger).is_empty());
+
+        // the session is still available
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let token = login(client, &user, "n3w-Passw0rd");
+        assert!(!token.is_empty());
+    });
+}
+
+#[test]
+fn test_change_with_revoke_sessions() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let mut res = client
+            .patch("/_/password/change")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(
+                r#"{{
+                  "password": "{}",
+                  "new_password": "n3w-Passw0rd",
+                  "revoke_sessions": true
+                }}"#,
+                password
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let new_token = result["token"].as_str().unwrap().to_string();
+        assert_ne!(new_token, token);
+
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_ne!(res.status(), Status::Ok);
+
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", new_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
@@ -85,3 +85,43 @@ fn test_password_reset_request() {
         assert!(value.is_ok());
     });
 }
+
+#[test]
+fn test_password_reset_request_too_many() {
+    run_test(|client, conn, _, _| {
+        let _ = client
+            .head("/_/password/reset/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let request = || {
+            client
+                .put("/_/password/reset")
+                .header(ContentType::JSON)
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .body(r#"{"email": "unknown@example.org"}"#)
+                .dispatch()
+        };
+
+        // the response doesn't tell whether the account exists
+        for _ in 0..3 {
+            assert_eq!(request().status(), Status::Ok);
+        }
+        let mut queue = Queue::new("default", conn.mq);
+        assert!(queue.dequeue::<job::Job<String>>().is_err());
+
+        assert_eq!(request().status(), Status::Ok);
+
+        let keys: Vec<String> =
+            conn.ss.keys("attempt:password_reset:account:*").unwrap();
+        assert_eq!(keys.len(), 1);
+        let next_at: i64 = conn.ss.hget(&keys[0], "next_at").unwrap();
+        let _: () = conn.ss.hset(&keys[0], "next_at", next_at + 60).unwrap();
+
+        let res = request();
+        assert_eq!(res.status(), Status::TooManyRequests);
+        assert!(res.headers().get_one("Retry-After").is_some());
+    });
+}
@@ -0,0 +1,245 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, make_raw_password, MEMBERSHIPS, NAMESPACES, STREAMS,
+    USERS,
+};
+
+#[test]
+fn test_hset_and_append() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+        s.namespace_id = namespace.id;
+        let stream_uuid = diesel::insert_into(model::stream::streams::table)
+            .values(&s)
+            .returning(model::stream::streams::uuid)
+            .get_result::<Uuid>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+
+        let mut res = client
+            .post(format!("/v1/redaction_rule/{}/hset", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"name": "email", "detector": "email"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["redaction_rule"]["detector"].as_str().unwrap(),
+            "Email"
+        );
+
+        let mut res = client
+            .get(format!("/v1/redaction_rule/{}/hgetall", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+
+        let body = format!(
+            r#"{{
+                "agent_id": 1,
+                "agent_type": "person",
+                "stream_id": 1,
+                "format": "toml",
+                "stream": "{}",
+                "title": "Signup by oswald@example.org",
+                "content": "email = \"oswald@example.org\""
+            }}"#,
+            stream_uuid
+        );
+        let res = client
+            .post("/v1/message/piano/append/oswald-s-stream")
+            .header(ContentType::JSON)
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(&body)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let (title, content) = model::message::messages::table
+            .select((
+                model::message::messages::title,
+                model::message::messages::content,
+            ))
+            .first::<(Option<String>, Option<String>)>(conn.db)
+            .unwrap();
+        assert_eq!(title, Some("Signup by [REDACTED:email]".to_string()));
+        assert_eq!(content, Some("email = \"[REDACTED:email]\"".to_string()));
+    });
+}
+
+#[test]
+fn test_eval() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        // a candidate rule
+        let mut res = client
+            .post(format!("/v1/redaction_rule/{}/eval", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(
+                r#"{
+                    "text": "GET /?sid=deadbeef&sid=cafe",
+                    "rule": {
+                        "name": "session",
+                        "detector": "custom",
+                        "pattern": "sid=[0-9a-f]+"
+                    }
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["text"].as_str().unwrap(),
+            "GET /?[REDACTED:session]&[REDACTED:session]"
+        );
+        assert_eq!(result["redactions"][0]["count"].as_i64(), Some(2));
+
+        // an invalid pattern
+        let res = client
+            .post(format!("/v1/redaction_rule/{}/eval", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(
+                r#"{
+                    "text": "GET /?sid=deadbeef",
+                    "rule": {
+                        "name": "session",
+                        "detector": "custom",
+                        "pattern": "sid=["
+                    }
+                }"#,
+            )
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        // no rule in the namespace
+        let mut res = client
+            .post(format!("/v1/redaction_rule/{}/eval", namespace.uuid))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"text": "oswald@example.org"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["text"].as_str().unwrap(), "oswald@example.org");
+    });
+}
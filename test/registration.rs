@@ -1,11 +1,14 @@
+use diesel::{self, prelude::*};
 use fourche::queue::Queue;
 use rocket::http::{ContentType, Header, Status};
 use redis::{Commands, RedisError};
+use uuid::Uuid;
 
 use eloquentlog_console_api::model;
+use eloquentlog_console_api::model::membership::MembershipRole;
 use eloquentlog_console_api::job;
 
-use crate::run_test;
+use crate::{run_test, load_user, login, make_raw_password, NAMESPACES, USERS};
 
 #[test]
 fn test_register_with_validation_error() {
@@ -84,3 +87,124 @@ fn test_register() {
         assert!(result.is_ok());
     });
 }
+
+#[test]
+fn test_deregister() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let oswald = load_user(u, conn.db);
+        let token = login(client, &oswald, &password);
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let weenie = load_user(u, conn.db);
+
+        // piano (with another owner) and organ (without any other owner)
+        let mut namespaces = vec![];
+        for (i, name) in ["piano", "organ"].iter().enumerate() {
+            let mut ns = NAMESPACES.get("piano").unwrap().clone();
+            ns.id = i as i64 + 1;
+            ns.uuid = Uuid::new_v4();
+            ns.name = name.to_string();
+            ns.key = name.to_string();
+            let namespace =
+                diesel::insert_into(model::namespace::namespaces::table)
+                    .values(ns)
+                    .get_result::<model::namespace::Namespace>(conn.db)
+                    .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+            namespaces.push(namespace);
+        }
+
+        for (namespace, user, role) in &[
+            (&namespaces[0], &oswald, MembershipRole::PrimaryOwner),
+            (&namespaces[0], &weenie, MembershipRole::Owner),
+            (&namespaces[1], &oswald, MembershipRole::PrimaryOwner),
+        ] {
+            let _ = diesel::insert_into(model::membership::memberships::table)
+                .values((
+                    model::membership::memberships::namespace_id
+                        .eq(namespace.id),
+                    model::membership::memberships::user_id.eq(user.id),
+                    model::membership::memberships::role.eq(role),
+                ))
+                .execute(conn.db)
+                .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+        }
+
+        let res = client
+            .post("/_/deregister")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"password": "wrong"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = client
+            .post("/_/deregister")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(r#"{{"password": "{}"}}"#, password))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Accepted);
+
+        let user = model::user::User::find_by_id(oswald.id, conn.db, logger)
+            .unwrap();
+        assert_eq!(user.state, model::user::UserState::Deleted);
+
+        // transferred
+        let m = model::membership::Membership::find_by_namespace_id_and_user(
+            namespaces[0].id,
+            &weenie,
+            conn.db,
+            logger,
+        )
+        .unwrap();
+        assert_eq!(m.role, MembershipRole::PrimaryOwner);
+
+        // archived
+        let namespace = model::namespace::Namespace::find_by_id(
+            namespaces[1].id,
+            conn.db,
+            logger,
+        )
+        .unwrap();
+        assert!(namespace.archived_at.is_some());
+
+        let res = client
+            .get("/v1/namespace/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_ne!(res.status(), Status::Ok);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::DeleteNamespace);
+        assert_eq!(job.args, vec![namespaces[1].id.to_string()]);
+
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::DeleteUser);
+
+        let jobs = job.invoke(conn.db, config, logger);
+        assert!(jobs.is_empty());
+
+        assert!(model::user::User::find_by_id(oswald.id, conn.db, logger)
+            .is_none());
+        let rows_count: i64 = model::membership::memberships::table
+            .filter(model::membership::memberships::user_id.eq(oswald.id))
+            .count()
+            .first(conn.db)
+            .expect("Failed to count rows");
+        assert_eq!(0, rows_count);
+    });
+}
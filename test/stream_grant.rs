@@ -0,0 +1,228 @@
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, login, make_raw_password, MEMBERSHIPS, NAMESPACES,
+    STREAMS, USERS,
+};
+
+#[test]
+fn test_hset_and_del() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let oswald = load_user(u, conn.db);
+        let oswald_token = login(client, &oswald, &password);
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let weenie = load_user(u, conn.db);
+        let weenie_token = login(client, &weenie, &password);
+
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .execute(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values((
+                model::membership::memberships::namespace_id.eq(namespace.id),
+                model::membership::memberships::user_id.eq(weenie.id),
+                model::membership::memberships::role
+                    .eq(model::membership::MembershipRole::Member),
+            ))
+            .execute(conn.db)
+            .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+        let mut streams = vec![];
+        for (id, slug) in &[(1, "oswald-s-stream"), (2, "weenie-s-stream")] {
+            let mut s = STREAMS.get("oswald's stream").unwrap().clone();
+            s.id = *id;
+            s.uuid = Uuid::new_v4();
+            s.namespace_id = namespace.id;
+            s.slug = slug.to_string();
+            let stream = diesel::insert_into(model::stream::streams::table)
+                .values(&s)
+                .get_result::<model::stream::Stream>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", s));
+            streams.push(stream);
+        }
+
+        // members can't grant
+        let res = client
+            .post(format!(
+                "/v1/stream_grant/{}/hset/{}",
+                streams[0].uuid, weenie.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(r#"{"role": "admin"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .post(format!(
+                "/v1/stream_grant/{}/hset/{}",
+                streams[0].uuid, weenie.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"role": "owner"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = client
+            .post(format!(
+                "/v1/stream_grant/{}/hset/{}",
+                streams[0].uuid, weenie.uuid
+            ))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .body(r#"{"role": "writer"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut res = client
+            .get(format!("/v1/stream_grant/{}/hgetall", streams[0].uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result[0]["stream_grant"]["role"].as_str().unwrap(),
+            "writer"
+        );
+
+        // a writer can append messages only into the granted stream
+        let res = client
+            .post("/v1/message/piano/append/oswald-s-stream")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .body(format!(
+                r#"{{
+                    "agent_id": 1,
+                    "agent_type": "person",
+                    "stream_id": 1,
+                    "code": "200",
+                    "format": "toml",
+                    "stream": "{}",
+                    "title": "New message",
+                    "content": "Hello, world!"
+                }}"#,
+                streams[0].uuid
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let mut res = client
+            .get("/v1/message/piano/lrange/oswald-s-stream/0/2")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["data"]["permission"].as_str().unwrap(),
+            "messages:read"
+        );
+
+        let res = client
+            .get("/v1/message/piano/lrange/weenie-s-stream/0/2")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Forbidden);
+
+        let res = client
+            .delete(format!(
+                "/v1/stream_grant/{}/del/{}",
+                streams[0].uuid, weenie.uuid
+            ))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", oswald_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        // back to the role in the namespace
+        let res = client
+            .get("/v1/message/piano/lrange/weenie-s-stream/0/2")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new(
+                "Authorization",
+                format!("Bearer {}", weenie_token),
+            ))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        use model::audit_event::audit_events;
+
+        let actions = audit_events::table
+            .select(audit_events::action)
+            .filter(audit_events::namespace_id.eq(namespace.id))
+            .order(audit_events::id.asc())
+            .load::<String>(conn.db)
+            .unwrap();
+        assert_eq!(actions, vec!["stream_grant.change", "stream_grant.revoke"]);
+    });
+}
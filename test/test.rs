@@ -3,11 +3,13 @@ extern crate regex;
 #[macro_use]
 extern crate lazy_static;
 
+extern crate bcrypt;
 extern crate chrono;
 extern crate diesel;
 extern crate dotenv;
 extern crate fourche;
 extern crate fnv;
+extern crate openssl;
 extern crate parking_lot;
 extern crate redis;
 extern crate rocket;
@@ -20,15 +22,30 @@ extern crate eloquentlog_console_api;
 
 mod activation;
 mod authentication;
+mod email_change;
 mod error;
 mod health;
 mod registration;
+mod password_change;
 mod password_reset;
 mod password_reset_request;
 
 mod access_token;
+mod alert_rule;
+mod audit_event;
+mod digest;
+mod invitation;
+mod membership;
 mod message;
 mod namespace;
+mod redaction_rule;
+mod stream;
+mod stream_grant;
+mod two_factor;
+mod usage;
+mod user_email;
+mod user_session;
+mod webhook;
 
 use std::panic::{self, AssertUnwindSafe};
 use regex::Regex;
@@ -39,6 +56,7 @@ use dotenv::dotenv;
 use chrono::{Utc, TimeZone};
 use fnv::FnvHashMap;
 use parking_lot::Mutex;
+use rocket::http::{ContentType, Header};
 use rocket::local::Client;
 use rocket_slog::SlogFairing;
 use uuid::Uuid;
@@ -50,6 +68,7 @@ use eloquentlog_console_api::ss;
 use eloquentlog_console_api::config;
 use eloquentlog_console_api::logger;
 use eloquentlog_console_api::model;
+use eloquentlog_console_api::validation::password::PasswordPolicy;
 
 // NOTE:
 // For now, run tests sequencially :'(
@@ -123,7 +142,8 @@ where T: FnOnce(&Client, &mut Connection, &config::Config, &logger::Logger)
             .manage(DB_POOL_HOLDER.clone())
             .manage(MQ_POOL_HOLDER.clone())
             .manage(SS_POOL_HOLDER.clone())
-            .manage(CONFIG.clone());
+            .manage(CONFIG.clone())
+            .manage(PasswordPolicy::from(&CONFIG).unwrap());
         let client = Client::new(server).unwrap();
 
         test(&client, &mut conn, &CONFIG, &logger)
@@ -200,6 +220,7 @@ lazy_static! {
             uuid: Uuid::new_v4(),
             namespace_id: NAMESPACES.get("piano").unwrap().id,
             name: "oswald's stream".to_string(),
+            slug: "oswald-s-stream".to_string(),
             description: Some("description".to_string()),
             archived_at: None,
             created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -211,8 +232,11 @@ lazy_static! {
             id: 1,
             uuid: Uuid::new_v4(),
             name: "piano".to_string(),
+            key: "piano".to_string(),
             description: Some("description".to_string()),
             streams_count: 0,
+            ingestion_rate_limit: None,
+            ingestion_daily_quota: None,
             archived_at: None,
             created_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
             updated_at: Utc.ymd(2019, 7, 7).and_hms(7, 20, 15).naive_utc(),
@@ -254,7 +278,7 @@ fn load_user(
     mut user: model::user::User,
     db_conn: &PgConnection,
 ) -> model::user::User {
-    user.change_password(&make_raw_password(&user));
+    user.change_password(&make_raw_password(&user), &CONFIG);
 
     let result: Result<model::user::User, diesel::result::Error> = db_conn
         .build_transaction()
@@ -283,6 +307,33 @@ fn load_user(
     result.unwrap()
 }
 
+/// Signs in as the user, and returns an access token.
+fn login(client: &Client, user: &model::user::User, password: &str) -> String {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    let mut res = client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+                "username": "{}",
+                "password": "{}"
+            }}"#,
+            user.email, password,
+        ))
+        .dispatch();
+
+    let body = res.body_string().unwrap();
+    let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+    result["token"].as_str().unwrap().to_string()
+}
+
 /// Creates raw password string.
 ///
 /// It works only in test because USERS has `password` as dummy `Vec<u8>`.
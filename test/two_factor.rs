@@ -0,0 +1,440 @@
+use chrono::Utc;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use redis::Commands;
+use rocket::http::{ContentType, Header, Method, Status};
+use rocket::local::{Client, LocalResponse};
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, login, make_raw_password, USERS};
+
+/// Makes a TOTP code (SHA1, 6 digits, 30 seconds) of the Base32 secret.
+fn make_code(secret: &str, time: i64) -> String {
+    let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+    let mut key = vec![];
+    let (mut buffer, mut bits) = (0_u32, 0);
+    for c in secret.bytes() {
+        let i = alphabet.iter().position(|a| *a == c).unwrap();
+        buffer = (buffer << 5) | i as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            key.push((buffer >> bits) as u8);
+        }
+    }
+
+    let pkey = PKey::hmac(&key).unwrap();
+    let mut signer = Signer::new(MessageDigest::sha1(), &pkey).unwrap();
+    signer.update(&((time / 30) as u64).to_be_bytes()).unwrap();
+    let hmac = signer.sign_to_vec().unwrap();
+
+    let offset = (hmac[hmac.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([
+        hmac[offset] & 0x7f,
+        hmac[offset + 1],
+        hmac[offset + 2],
+        hmac[offset + 3],
+    ]);
+    format!("{:06}", value % 1_000_000)
+}
+
+fn request<'a>(
+    client: &'a Client,
+    method: Method,
+    path: &str,
+    token: &str,
+    body: String,
+) -> LocalResponse<'a> {
+    client
+        .req(method, path)
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .body(body)
+        .dispatch()
+}
+
+/// Enables two-factor authentication, and returns the secret and recovery
+/// codes.
+fn enable(
+    client: &Client,
+    token: &str,
+    password: &str,
+) -> (String, Vec<String>) {
+    let mut res = request(
+        client,
+        Method::Put,
+        "/_/two_factor/setup",
+        token,
+        format!(r#"{{"password": "{}"}}"#, password),
+    );
+    assert_eq!(res.status(), Status::Ok);
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    let secret = result["secret"].as_str().unwrap().to_string();
+
+    let code = make_code(&secret, Utc::now().timestamp());
+    let mut res = request(
+        client,
+        Method::Patch,
+        "/_/two_factor/enable",
+        token,
+        format!(r#"{{"code": "{}"}}"#, code),
+    );
+    assert_eq!(res.status(), Status::Ok);
+
+    let body = res.body_string().unwrap();
+    let result: Value = serde_json::from_str(&body).unwrap();
+    let recovery_codes = result["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|c| c.as_str().unwrap().to_string())
+        .collect();
+    (secret, recovery_codes)
+}
+
+fn login_first<'a>(
+    client: &'a Client,
+    user: &model::user::User,
+    password: &str,
+) -> LocalResponse<'a> {
+    let _ = client
+        .head("/_/login/")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body("{}")
+        .dispatch();
+
+    client
+        .post("/_/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(
+            r#"{{
+              "username": "{}",
+              "password": "{}"
+            }}"#,
+            user.email, password,
+        ))
+        .dispatch()
+}
+
+fn login_second<'a>(client: &'a Client, code: &str) -> LocalResponse<'a> {
+    client
+        .post("/_/login/two_factor")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .body(format!(r#"{{"code": "{}"}}"#, code))
+        .dispatch()
+}
+
+#[test]
+fn test_setup_with_wrong_password() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        let res = request(
+            client,
+            Method::Put,
+            "/_/two_factor/setup",
+            &token,
+            r#"{"password": "wrong"}"#.to_string(),
+        );
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+    });
+}
+
+#[test]
+fn test_setup_and_enable() {
+    run_test(|client, conn, _, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        // not set up yet
+        let res = request(
+            client,
+            Method::Patch,
+            "/_/two_factor/enable",
+            &token,
+            r#"{"code": "123456"}"#.to_string(),
+        );
+        assert_eq!(res.status(), Status::NotFound);
+
+        let mut res = request(
+            client,
+            Method::Put,
+            "/_/two_factor/setup",
+            &token,
+            format!(r#"{{"password": "{}"}}"#, password),
+        );
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let secret = result["secret"].as_str().unwrap();
+        assert_eq!(
+            result["uri"].as_str().unwrap(),
+            format!(
+                "otpauth://totp/Eloquentlog:oswald@example.org?secret={}\
+                 &issuer=Eloquentlog&algorithm=SHA1&digits=6&period=30",
+                secret
+            )
+        );
+
+        let res = request(
+            client,
+            Method::Patch,
+            "/_/two_factor/enable",
+            &token,
+            r#"{"code": "abcdef"}"#.to_string(),
+        );
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let code = make_code(secret, Utc::now().timestamp());
+        let mut res = request(
+            client,
+            Method::Patch,
+            "/_/two_factor/enable",
+            &token,
+            format!(r#"{{"code": "{}"}}"#, code),
+        );
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["recovery_codes"].as_array().unwrap().len(), 10);
+
+        // the secret is encrypted
+        let t = model::user_two_factor::UserTwoFactor::find_by_user_id(
+            user.id, conn.db, logger,
+        )
+        .unwrap();
+        assert!(t.is_enabled());
+        assert_ne!(t.secret, secret);
+        assert_eq!(t.recovery_codes.len(), 10);
+
+        // enabled already
+        let res = request(
+            client,
+            Method::Put,
+            "/_/two_factor/setup",
+            &token,
+            format!(r#"{{"password": "{}"}}"#, password),
+        );
+        assert_eq!(res.status(), Status::Conflict);
+    });
+}
+
+#[test]
+fn test_login_without_challenge() {
+    run_test(|client, _, _, _| {
+        let res = login_second(client, "123456");
+        assert_eq!(res.status(), Status::Unauthorized);
+    });
+}
+
+#[test]
+fn test_login_with_code() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+        let (secret, _) = enable(client, &token, &password);
+
+        let mut res = login_first(client, &user, &password);
+        assert_eq!(res.status(), Status::Accepted);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert!(result["two_factor"].as_bool().unwrap());
+        assert!(result["token"].is_null());
+
+        let res = login_second(client, "abcdef");
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        // the code of enable has been used, so the next one is required
+        let code = make_code(&secret, Utc::now().timestamp() + 30);
+        let mut res = login_second(client, &code);
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+
+        // the challenge has been consumed
+        let res = login_second(client, &code);
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        // the code can't be replayed
+        let res = login_first(client, &user, &password);
+        assert_eq!(res.status(), Status::Accepted);
+        let res = login_second(client, &code);
+        assert_eq!(res.status(), Status::Unauthorized);
+    });
+}
+
+#[test]
+fn test_login_with_recovery_code() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+        let (_, recovery_codes) = enable(client, &token, &password);
+
+        let res = login_first(client, &user, &password);
+        assert_eq!(res.status(), Status::Accepted);
+        let res = login_second(client, &recovery_codes[0]);
+        assert_eq!(res.status(), Status::Ok);
+
+        // once
+        let res = login_first(client, &user, &password);
+        assert_eq!(res.status(), Status::Accepted);
+        let res = login_second(client, &recovery_codes[0]);
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let res = login_second(client, &recovery_codes[1]);
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_recovery_codes_and_disable() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+        let (_, recovery_codes) = enable(client, &token, &password);
+
+        let res = request(
+            client,
+            Method::Put,
+            "/_/two_factor/recovery_codes",
+            &token,
+            format!(
+                r#"{{"password": "wrong", "code": "{}"}}"#,
+                recovery_codes[0]
+            ),
+        );
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = request(
+            client,
+            Method::Put,
+            "/_/two_factor/recovery_codes",
+            &token,
+            format!(
+                r#"{{"password": "{}", "code": "{}"}}"#,
+                password, recovery_codes[0]
+            ),
+        );
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let new_codes = result["recovery_codes"].as_array().unwrap();
+        assert_eq!(new_codes.len(), 10);
+
+        // the old codes are no longer available
+        let res = request(
+            client,
+            Method::Patch,
+            "/_/two_factor/disable",
+            &token,
+            format!(
+                r#"{{"password": "{}", "code": "{}"}}"#,
+                password, recovery_codes[1]
+            ),
+        );
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = request(
+            client,
+            Method::Patch,
+            "/_/two_factor/disable",
+            &token,
+            format!(
+                r#"{{"password": "{}", "code": "{}"}}"#,
+                password,
+                new_codes[0].as_str().unwrap()
+            ),
+        );
+        assert_eq!(res.status(), Status::Ok);
+
+        // password only
+        let res = login_first(client, &user, &password);
+        assert_eq!(res.status(), Status::Ok);
+    });
+}
+
+#[test]
+fn test_disable_and_recovery_codes_with_too_many_failed_attempts() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+        let (secret, _) = enable(client, &token, &password);
+
+        for _ in 0..4 {
+            let res = request(
+                client,
+                Method::Patch,
+                "/_/two_factor/disable",
+                &token,
+                format!(r#"{{"password": "{}", "code": "000000"}}"#, password),
+            );
+            assert_eq!(res.status(), Status::UnprocessableEntity);
+        }
+
+        let keys: Vec<String> =
+            conn.ss.keys("attempt:two_factor:account:*").unwrap();
+        assert_eq!(keys.len(), 1);
+
+        // delayed (even with the right password and code)
+        let now = Utc::now().timestamp();
+        let _: () = conn.ss.hset(&keys[0], "next_at", now + 60).unwrap();
+
+        let code = make_code(&secret, now);
+        for (method, path) in &[
+            (Method::Patch, "/_/two_factor/disable"),
+            (Method::Put, "/_/two_factor/recovery_codes"),
+        ] {
+            let res = request(
+                client,
+                *method,
+                path,
+                &token,
+                format!(
+                    r#"{{"password": "{}", "code": "{}"}}"#,
+                    password, code
+                ),
+            );
+            assert_eq!(res.status(), Status::TooManyRequests);
+            assert!(res.headers().get_one("Retry-After").is_some());
+        }
+
+        // still enabled
+        let res = login_first(client, &user, &password);
+        assert_eq!(res.status(), Status::Accepted);
+    });
+}
@@ -0,0 +1,167 @@
+use chrono::Utc;
+use diesel::{self, prelude::*};
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+
+use eloquentlog_console_api::model;
+
+use crate::{
+    run_test, load_user, make_raw_password, MEMBERSHIPS, NAMESPACES, USERS,
+};
+
+#[test]
+fn test_hget_and_lrange() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        let mut ns = NAMESPACES.get("piano").unwrap().clone();
+        ns.ingestion_daily_quota = Some(10);
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(&ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let mut ms = MEMBERSHIPS
+            .get("oswald as a primary owner")
+            .unwrap()
+            .clone();
+        ms.namespace_id = namespace.id;
+        let _ = diesel::insert_into(model::membership::memberships::table)
+            .values(&ms)
+            .returning(model::membership::memberships::id)
+            .get_result::<i64>(conn.db)
+            .unwrap_or_else(|_| panic!("Error inserting: {}", ms));
+
+        let mut res = client
+            .get(format!("/v1/usage/{}/hget", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["usage"]["messages_count"].as_i64(), Some(0));
+        assert_eq!(result["usage"]["quota"].as_i64(), Some(10));
+
+        let today = Utc::now().naive_utc().date();
+        let _ = diesel::insert_into(
+            model::namespace_usage::namespace_usages::table,
+        )
+        .values((
+            model::namespace_usage::namespace_usages::namespace_id
+                .eq(namespace.id),
+            model::namespace_usage::namespace_usages::date.eq(today),
+            model::namespace_usage::namespace_usages::messages_count.eq(3),
+            model::namespace_usage::namespace_usages::bytes_count.eq(128),
+        ))
+        .execute(conn.db)
+        .unwrap_or_else(|e| panic!("Error inserting: {}", e));
+
+        let mut res = client
+            .get(format!("/v1/usage/{}/hget", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["usage"]["messages_count"].as_i64(), Some(3));
+        assert_eq!(result["usage"]["bytes_count"].as_i64(), Some(128));
+
+        let mut res = client
+            .get(format!("/v1/usage/{}/lrange/0/9", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+        assert_eq!(
+            result[0]["usage"]["date"].as_str().unwrap(),
+            today.format("%Y-%m-%d").to_string()
+        );
+    });
+}
+
+#[test]
+fn test_hget_in_unknown_namespace() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+
+        let _ = client
+            .head("/_/login/")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body("{}")
+            .dispatch();
+
+        let mut res = client
+            .post("/_/login")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .body(format!(
+                r#"{{
+                    "username": "{}",
+                    "password": "{}"
+                }}"#,
+                user.email, password,
+            ))
+            .dispatch();
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let token = result["token"].as_str().unwrap();
+
+        // not a member
+        let ns = NAMESPACES.get("piano").unwrap();
+        let namespace =
+            diesel::insert_into(model::namespace::namespaces::table)
+                .values(ns)
+                .get_result::<model::namespace::Namespace>(conn.db)
+                .unwrap_or_else(|_| panic!("Error inserting: {}", ns));
+
+        let res = client
+            .get(format!("/v1/usage/{}/hget", namespace.uuid))
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+    });
+}
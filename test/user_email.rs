@@ -0,0 +1,229 @@
+use chrono::{Duration, Utc};
+use diesel::{self, prelude::*};
+use fourche::queue::Queue;
+use rocket::http::{ContentType, Header, Status};
+use serde_json::Value;
+use uuid::Uuid;
+
+use eloquentlog_console_api::job;
+use eloquentlog_console_api::model;
+
+use crate::{run_test, load_user, login, make_raw_password, USERS};
+
+#[test]
+fn test_append_verify_primary_and_del() {
+    run_test(|client, conn, config, logger| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        // already used as the primary address
+        let res = client
+            .post("/v1/user_email/append")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(r#"{{"email": "{}"}}"#, user.email))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut res = client
+            .post("/v1/user_email/append")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"email": "oswald@example.com"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(result["user_email"]["role"].as_str().unwrap(), "general");
+
+        // not verified yet
+        let res = client
+            .patch("/v1/user_email/primary")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"email": "oswald@example.com"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let mut queue = Queue::new("default", conn.mq);
+        let job = queue.dequeue::<job::Job<String>>().ok().unwrap();
+        assert_eq!(job.kind, job::JobKind::SendEmailVerificationEmail);
+        assert_eq!(job.args.len(), 2);
+
+        let jobs = job.invoke(conn.db, config, logger);
+        assert!(jobs.is_empty());
+
+        let res = client
+            .post("/v1/user_email/verify")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"token": "invalid-token"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::BadRequest);
+
+        let mut res = client
+            .post("/v1/user_email/verify")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(r#"{{"token": "{}"}}"#, job.args[1]))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            result["user_email"]["identification_state"].as_str().unwrap(),
+            "done"
+        );
+
+        let res = client
+            .patch("/v1/user_email/primary")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"email": "oswald@example.com"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let email = model::user::users::table
+            .select(model::user::users::email)
+            .filter(model::user::users::id.eq(user.id))
+            .first::<String>(conn.db)
+            .unwrap();
+        assert_eq!(email, "oswald@example.com");
+
+        let mut res = client
+            .get("/v1/user_email/hgetall")
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let body = res.body_string().unwrap();
+        let result: Value = serde_json::from_str(&body).unwrap();
+        let emails = result.as_array().unwrap();
+        assert_eq!(emails.len(), 2);
+        assert_eq!(
+            emails[0]["user_email"]["email"].as_str().unwrap(),
+            "oswald@example.com"
+        );
+        assert_eq!(
+            emails[0]["user_email"]["role"].as_str().unwrap(),
+            "primary"
+        );
+
+        // the primary one can't be deleted
+        let res = client
+            .delete("/v1/user_email/del")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(r#"{"email": "oswald@example.com"}"#)
+            .dispatch();
+
+        assert_eq!(res.status(), Status::UnprocessableEntity);
+
+        let res = client
+            .delete("/v1/user_email/del")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .body(format!(r#"{{"email": "{}"}}"#, user.email))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let count = model::user_email::user_emails::table
+            .filter(model::user_email::user_emails::user_id.eq(user.id))
+            .count()
+            .get_result::<i64>(conn.db)
+            .unwrap();
+        assert_eq!(count, 1);
+    });
+}
+
+#[test]
+fn test_append_to_squat_addresses() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let oswald = load_user(u, conn.db);
+        let oswald_token = login(client, &oswald, &password);
+
+        let mut u = USERS.get("oswald").unwrap().clone();
+        u.id = 2;
+        u.uuid = Uuid::new_v4();
+        u.username = "weenie".to_string();
+        u.email = "weenie@example.org".to_string();
+        let weenie = load_user(u, conn.db);
+        let weenie_token = login(client, &weenie, &password);
+
+        let append = |token: &str, email: &str| {
+            client
+                .post("/v1/user_email/append")
+                .header(ContentType::JSON)
+                .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+                .header(Header::new(
+                    "Authorization",
+                    format!("Bearer {}", token),
+                ))
+                .body(format!(r#"{{"email": "{}"}}"#, email))
+                .dispatch()
+                .status()
+        };
+
+        for i in 0..3 {
+            let email = format!("weenie+{}@example.com", i);
+            assert_eq!(append(&oswald_token, &email), Status::Ok);
+        }
+
+        // too many unverified addresses
+        assert_eq!(
+            append(&oswald_token, "oswald@example.com"),
+            Status::UnprocessableEntity
+        );
+
+        // the address is kept only until the token expires
+        assert_eq!(
+            append(&weenie_token, "weenie+0@example.com"),
+            Status::UnprocessableEntity
+        );
+
+        use model::user_email::user_emails;
+
+        diesel::update(
+            user_emails::table.filter(user_emails::user_id.eq(oswald.id)),
+        )
+        .set(
+            user_emails::identification_token_expires_at
+                .eq(Utc::now().naive_utc() - Duration::minutes(1)),
+        )
+        .execute(conn.db)
+        .unwrap_or_else(|e| panic!("Error updating: {}", e));
+
+        assert_eq!(append(&weenie_token, "weenie+0@example.com"), Status::Ok);
+        assert_eq!(append(&oswald_token, "oswald@example.com"), Status::Ok);
+
+        let user_id = user_emails::table
+            .select(user_emails::user_id)
+            .filter(user_emails::email.eq("weenie+0@example.com"))
+            .first::<i64>(conn.db)
+            .unwrap();
+        assert_eq!(user_id, weenie.id);
+    });
+}
@@ -0,0 +1,128 @@
+use rocket::http::{ContentType, Header, Status};
+use rocket::local::Client;
+use serde_json::Value;
+
+use crate::{run_test, load_user, login, make_raw_password, USERS};
+
+fn hgetall(client: &Client, token: &str) -> Option<Value> {
+    let mut res = client
+        .get("/v1/user_session/hgetall")
+        .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+        .header(Header::new("Authorization", format!("Bearer {}", token)))
+        .dispatch();
+
+    if res.status() != Status::Ok {
+        return None;
+    }
+    let body = res.body_string().unwrap();
+    serde_json::from_str(&body).ok()
+}
+
+#[test]
+fn test_hgetall_and_del() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let _ = login(client, &user, &password);
+        let token = login(client, &user, &password);
+
+        let result = hgetall(client, &token).unwrap();
+        let sessions = result.as_array().unwrap();
+        assert_eq!(sessions.len(), 2);
+
+        let find = |current: bool| {
+            let s = sessions
+                .iter()
+                .find(|s| s["current"].as_bool().unwrap() == current)
+                .unwrap();
+            s["user_session"]["id"].as_str().unwrap().to_string()
+        };
+        let id = find(false);
+        let current_id = find(true);
+
+        let res = client
+            .delete(format!("/v1/user_session/del/{}", id))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let result = hgetall(client, &token).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 1);
+
+        // already destroyed
+        let res = client
+            .delete(format!("/v1/user_session/del/{}", id))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::NotFound);
+
+        let res = client
+            .delete(format!("/v1/user_session/del/{}", current_id))
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        // the token bound to the session is no longer available
+        assert!(hgetall(client, &token).is_none());
+    });
+}
+
+#[test]
+fn test_clear() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let _ = login(client, &user, &password);
+        let token = login(client, &user, &password);
+        let result = hgetall(client, &token).unwrap();
+        assert_eq!(result.as_array().unwrap().len(), 2);
+
+        let res = client
+            .delete("/v1/user_session/clear")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        let result = hgetall(client, &token).unwrap();
+        let sessions = result.as_array().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0]["current"].as_bool().unwrap());
+    });
+}
+
+#[test]
+fn test_logout() {
+    run_test(|client, conn, _, _| {
+        let u = USERS.get("oswald").unwrap().clone();
+        let password = make_raw_password(&u);
+        let user = load_user(u, conn.db);
+        let token = login(client, &user, &password);
+
+        assert!(hgetall(client, &token).is_some());
+
+        let res = client
+            .post("/_/logout")
+            .header(ContentType::JSON)
+            .header(Header::new("X-Requested-With", "XMLHttpRequest"))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+
+        assert_eq!(res.status(), Status::Ok);
+
+        assert!(hgetall(client, &token).is_none());
+    });
+}